@@ -0,0 +1,18 @@
+-- ============================================================================
+-- TAGGED CHECKSUMS
+-- Description: Rename files.checksum_sha256 to files.checksum and store the
+--              hash algorithm in front of the digest ("sha256:<hex>",
+--              "blake3:<hex>") so BLAKE3 can be used alongside SHA-256
+-- ============================================================================
+
+ALTER TABLE files RENAME COLUMN checksum_sha256 TO checksum;
+
+ALTER TABLE files ALTER COLUMN checksum TYPE VARCHAR(80);
+
+-- Existing untagged values were all SHA-256
+UPDATE files
+SET checksum = 'sha256:' || lower(checksum)
+WHERE checksum IS NOT NULL
+  AND checksum NOT LIKE '%:%';
+
+COMMENT ON COLUMN files.checksum IS 'Integrity checksum of the original file as <algorithm>:<hex>, algorithm being sha256 or blake3';
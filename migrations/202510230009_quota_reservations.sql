@@ -0,0 +1,147 @@
+-- ============================================================================
+-- QUOTA RESERVATIONS
+-- Description: Optionally reserve storage quota when an upload is initialized
+--              so the final debit cannot fail after every chunk was sent
+-- ============================================================================
+
+ALTER TABLE users ADD COLUMN reserved_bytes BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE users ADD CONSTRAINT users_reserved_bytes_non_negative CHECK (reserved_bytes >= 0);
+
+COMMENT ON COLUMN users.reserved_bytes IS 'Quota held by in-progress uploads (QUOTA_RESERVATION_ENABLED); counted as unavailable until committed or released';
+
+-- ----------------------------------------------------------------------------
+-- ATOMIC STORAGE UPDATE FUNCTION (now honours reservations)
+-- ----------------------------------------------------------------------------
+
+CREATE OR REPLACE FUNCTION update_storage_with_quota_check(
+    p_user_id UUID,
+    p_file_size BIGINT
+)
+RETURNS TABLE(
+    success BOOLEAN,
+    available_bytes BIGINT,
+    new_storage_used BIGINT
+) AS $$
+DECLARE
+    v_storage_quota BIGINT;
+    v_storage_used BIGINT;
+    v_reserved BIGINT;
+    v_available BIGINT;
+    v_new_storage_used BIGINT;
+BEGIN
+    SELECT storage_quota_bytes, storage_used_bytes, reserved_bytes
+    INTO v_storage_quota, v_storage_used, v_reserved
+    FROM users
+    WHERE id = p_user_id
+    FOR UPDATE;
+
+    IF NOT FOUND THEN
+        RETURN QUERY SELECT FALSE, 0::BIGINT, 0::BIGINT;
+        RETURN;
+    END IF;
+
+    -- Space held by other uploads' reservations is not available
+    v_available := v_storage_quota - v_storage_used - v_reserved;
+
+    IF p_file_size > v_available THEN
+        RETURN QUERY SELECT FALSE, v_available, v_storage_used;
+        RETURN;
+    END IF;
+
+    UPDATE users
+    SET storage_used_bytes = storage_used_bytes + p_file_size
+    WHERE id = p_user_id
+    RETURNING storage_used_bytes INTO v_new_storage_used;
+
+    RETURN QUERY SELECT TRUE, v_available, v_new_storage_used;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ----------------------------------------------------------------------------
+-- RESERVE STORAGE FUNCTION (upload init)
+-- ----------------------------------------------------------------------------
+
+CREATE OR REPLACE FUNCTION reserve_storage(
+    p_user_id UUID,
+    p_bytes BIGINT
+)
+RETURNS TABLE(
+    success BOOLEAN,
+    available_bytes BIGINT
+) AS $$
+DECLARE
+    v_available BIGINT;
+BEGIN
+    SELECT storage_quota_bytes - storage_used_bytes - reserved_bytes
+    INTO v_available
+    FROM users
+    WHERE id = p_user_id
+    FOR UPDATE;
+
+    IF NOT FOUND THEN
+        RETURN QUERY SELECT FALSE, 0::BIGINT;
+        RETURN;
+    END IF;
+
+    IF p_bytes > v_available THEN
+        RETURN QUERY SELECT FALSE, v_available;
+        RETURN;
+    END IF;
+
+    UPDATE users
+    SET reserved_bytes = reserved_bytes + p_bytes
+    WHERE id = p_user_id;
+
+    RETURN QUERY SELECT TRUE, v_available;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMENT ON FUNCTION reserve_storage(UUID, BIGINT) IS
+'Atomically reserves quota for an upload; fails if quota minus usage and existing reservations is too small';
+
+-- ----------------------------------------------------------------------------
+-- COMMIT RESERVATION FUNCTION (upload finalize)
+-- ----------------------------------------------------------------------------
+
+CREATE OR REPLACE FUNCTION commit_storage_reservation(
+    p_user_id UUID,
+    p_bytes BIGINT
+)
+RETURNS BIGINT AS $$
+DECLARE
+    v_new_storage_used BIGINT;
+BEGIN
+    UPDATE users
+    SET reserved_bytes = GREATEST(0, reserved_bytes - p_bytes),
+        storage_used_bytes = storage_used_bytes + p_bytes
+    WHERE id = p_user_id
+    RETURNING storage_used_bytes INTO v_new_storage_used;
+
+    RETURN v_new_storage_used;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMENT ON FUNCTION commit_storage_reservation(UUID, BIGINT) IS
+'Turns a reservation into storage usage once the upload is finalized';
+
+-- ----------------------------------------------------------------------------
+-- RELEASE RESERVATION FUNCTION (upload cancel/expiry/failure)
+-- ----------------------------------------------------------------------------
+
+CREATE OR REPLACE FUNCTION release_storage_reservation(
+    p_user_id UUID,
+    p_bytes BIGINT
+)
+RETURNS BOOLEAN AS $$
+BEGIN
+    UPDATE users
+    SET reserved_bytes = GREATEST(0, reserved_bytes - p_bytes)
+    WHERE id = p_user_id;
+
+    RETURN FOUND;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMENT ON FUNCTION release_storage_reservation(UUID, BIGINT) IS
+'Releases a reservation held by a canceled, expired or failed upload. Uses GREATEST to prevent negative values';
@@ -0,0 +1,7 @@
+-- ============================================================================
+-- DELETED FILES REAPER
+-- Description: Index soft-deleted files by deletion time so the background
+--              reaper can find rows past the trash grace period cheaply
+-- ============================================================================
+
+CREATE INDEX IF NOT EXISTS idx_files_deleted_at ON files(deleted_at) WHERE is_deleted = true;
@@ -0,0 +1,10 @@
+-- ============================================================================
+-- DEK KEY DERIVATION PARAMETERS
+-- Description: Record the Argon2id cost of the password-derived key that wraps
+--              each user's DEK, so the cost can be raised without locking out
+--              users whose DEK was wrapped under an older one
+-- ============================================================================
+
+ALTER TABLE users ADD COLUMN dek_kdf_params TEXT;
+
+COMMENT ON COLUMN users.dek_kdf_params IS 'Argon2id cost of the key wrapping encrypted_dek, as m=<KiB>,t=<passes>,p=<lanes>; NULL means the argon2 crate defaults used before this column existed. Rewrapped at login when it differs from the configured cost';
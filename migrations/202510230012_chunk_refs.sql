@@ -0,0 +1,18 @@
+-- ============================================================================
+-- CHUNK DEDUPLICATION
+-- Description: Reference counts for content-addressed chunk files, used when
+--              CHUNK_DEDUP_ENABLED stores identical chunks of a user once
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS chunk_refs (
+    content_hash TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ref_count INTEGER NOT NULL CHECK (ref_count > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_chunk_refs_user_id ON chunk_refs(user_id);
+
+COMMENT ON TABLE chunk_refs IS 'One row per content-addressed chunk file in uploads/files; the file is unlinked when its last reference is released';
+COMMENT ON COLUMN chunk_refs.content_hash IS 'Hex SHA-256 of the user DEK followed by the chunk plaintext as stored; also the chunk file name';
+COMMENT ON COLUMN chunk_refs.ref_count IS 'Upload sessions and files (deleted or not) whose chunk lists name this chunk';
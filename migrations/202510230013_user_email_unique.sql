@@ -0,0 +1,14 @@
+-- ============================================================================
+-- UNIQUE USER EMAILS
+-- Description: Registration stores an optional, lowercased email address;
+--              each address may belong to one user only
+-- ============================================================================
+
+-- Users created before registration collected an email had their username
+-- written to this column; clear those so it only holds real addresses.
+UPDATE users SET email = NULL WHERE email = username;
+
+DROP INDEX IF EXISTS idx_users_email;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_unique ON users(email) WHERE email IS NOT NULL;
+
+COMMENT ON INDEX idx_users_email_unique IS 'One user per email address; the application stores addresses trimmed and lowercased';
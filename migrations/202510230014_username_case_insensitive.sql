@@ -0,0 +1,12 @@
+-- ============================================================================
+-- CASE-INSENSITIVE USERNAMES
+-- Description: Usernames are matched ignoring case, so "Alice" and "alice"
+--              can no longer be registered as two accounts
+-- ============================================================================
+
+-- New usernames are stored lowercased; older mixed-case ones keep their
+-- spelling and are found through LOWER(username). Creating the index fails if
+-- two existing usernames differ only by case, which must be resolved first.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username_lower ON users(LOWER(username));
+
+COMMENT ON INDEX idx_users_username_lower IS 'One user per username regardless of case; also serves login lookups by LOWER(username)';
@@ -0,0 +1,26 @@
+-- ============================================================================
+-- TWO-FACTOR AUTHENTICATION
+-- Description: Optional TOTP second factor with single-use recovery codes
+-- ============================================================================
+
+ALTER TABLE users
+    ADD COLUMN two_factor_enabled BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN totp_secret_encrypted BYTEA,
+    ADD COLUMN totp_kek_version INTEGER,
+    ADD COLUMN totp_last_step BIGINT;
+
+COMMENT ON COLUMN users.two_factor_enabled IS 'Logins require a TOTP or recovery code; set once an enrollment is confirmed with a valid code';
+COMMENT ON COLUMN users.totp_secret_encrypted IS 'TOTP secret encrypted with KEK totp_kek_version, as ciphertext || nonce; set at enrollment, before it is confirmed';
+COMMENT ON COLUMN users.totp_last_step IS 'The last 30-second TOTP step accepted, so a code cannot be replayed';
+
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, code_hash)
+);
+
+COMMENT ON TABLE user_recovery_codes IS 'Single-use codes that stand in for a TOTP code; replaced whenever two-factor enrollment is confirmed';
+COMMENT ON COLUMN user_recovery_codes.code_hash IS 'Hex SHA-256 of the normalized code; codes carry 80 random bits, so a fast hash is enough';
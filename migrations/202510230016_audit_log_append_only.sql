@@ -0,0 +1,26 @@
+-- ============================================================================
+-- APPEND-ONLY AUDIT LOG
+-- Description: Attempted usernames for failed logins, and a trigger that
+--              rejects any change to recorded audit entries
+-- ============================================================================
+
+ALTER TABLE audit_logs
+    ADD COLUMN username VARCHAR(255);
+
+COMMENT ON COLUMN audit_logs.username IS 'The username the action was attempted with, e.g. for failed logins where no user_id is known';
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_user_created ON audit_logs(user_id, created_at DESC);
+
+CREATE OR REPLACE FUNCTION reject_audit_log_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_logs_append_only
+    BEFORE UPDATE OR DELETE ON audit_logs
+    FOR EACH ROW EXECUTE FUNCTION reject_audit_log_changes();
+
+COMMENT ON TRIGGER audit_logs_append_only ON audit_logs IS
+'Keeps recorded audit entries immutable; retention has to drop the trigger or partition the table deliberately';
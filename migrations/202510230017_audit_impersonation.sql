@@ -0,0 +1,10 @@
+-- ============================================================================
+-- IMPERSONATED AUDIT ENTRIES
+-- Description: Records the admin behind actions taken through an
+--              impersonation session
+-- ============================================================================
+
+ALTER TABLE audit_logs
+    ADD COLUMN impersonated_by UUID;
+
+COMMENT ON COLUMN audit_logs.impersonated_by IS 'The admin who acted through an impersonation session for user_id; NULL for the user acting themselves';
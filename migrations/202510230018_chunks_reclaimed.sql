@@ -0,0 +1,11 @@
+-- ============================================================================
+-- RECLAIMED CHUNKS
+-- Description: Flag deleted files whose chunk files were removed on delete
+--              (HARD_DELETE_ON_DELETE), so they are neither listed in the
+--              trash, restored, nor reclaimed a second time when purged
+-- ============================================================================
+
+ALTER TABLE files
+    ADD COLUMN chunks_reclaimed BOOLEAN NOT NULL DEFAULT false;
+
+COMMENT ON COLUMN files.chunks_reclaimed IS 'Whether the chunk files of this deleted file were already removed; such rows only wait to be purged';
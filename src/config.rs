@@ -1,10 +1,24 @@
 use std::env;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use http::{HeaderValue, Uri};
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 use zeroize::{Zeroize, Zeroizing};
 
+use crate::{
+    crypto::checksum::ChecksumAlgorithm,
+    models::{file::ConflictPolicy, user::QuotaThresholds},
+    tenant::{is_valid_tenant_id, TenantMode},
+    validation::auth::PasswordPolicy,
+};
+
 /// The application's configuration.
 #[derive(Clone)]
 pub struct Config {
+    /// The deployment environment (`APP_ENV`), e.g. `development` or `production`.
+    pub app_env: String,
     /// The URL of the PostgreSQL database.
     pub database_url: String,
     /// The URL of the Redis server.
@@ -13,6 +27,148 @@ pub struct Config {
     pub session_duration_days: i64,
     /// The master key used for encryption.
     pub master_key: Zeroizing<Vec<u8>>,
+    /// The lifetime of an admin-issued impersonation session in minutes.
+    pub impersonation_session_minutes: i64,
+    /// How often open progress sockets and event streams re-check their session, in seconds.
+    pub stream_session_check_secs: u64,
+    /// The maximum number of multipart fields accepted in a chunk upload.
+    pub max_multipart_fields: usize,
+    /// The maximum time in seconds allowed to read a single multipart field.
+    pub multipart_field_timeout_secs: u64,
+    /// Whether changing the password signs out every other session of the user.
+    pub invalidate_sessions_on_password_change: bool,
+    /// The default policy for uploads finalized under an existing file name.
+    pub upload_conflict_policy: ConflictPolicy,
+    /// The maximum number of cached folder listings; zero disables the cache.
+    pub folder_cache_capacity: usize,
+    /// How long a cached folder listing stays valid, in seconds.
+    pub folder_cache_ttl_secs: u64,
+    /// The origins allowed to make credentialed cross-origin requests.
+    pub cors_allowed_origins: Vec<HeaderValue>,
+    /// The networks allowed to reach the admin routes; empty allows all.
+    pub admin_ip_allowlist: Vec<IpNet>,
+    /// The networks denied from the admin routes, checked before the allowlist.
+    pub admin_ip_denylist: Vec<IpNet>,
+    /// Whether to take the client IP from `X-Real-IP`/`X-Forwarded-For`.
+    pub trust_proxy_headers: bool,
+    /// Where the tenant of a request is read from; `Off` serves a single tenant.
+    pub tenant_mode: TenantMode,
+    /// The header naming the tenant in `TenantMode::Header`.
+    pub tenant_header: http::HeaderName,
+    /// The domain whose subdomains name tenants in `TenantMode::Subdomain`.
+    pub tenant_base_domain: String,
+    /// The tenants requests may be addressed to; any other is rejected.
+    pub tenants: Vec<String>,
+    /// Whether deleting a file also removes its chunk files from disk.
+    pub hard_delete_on_delete: bool,
+    /// How long deleted files stay recoverable before they are purged, in days.
+    pub trash_retention_days: i64,
+    /// How long a share link stays valid when the request names no expiry, in seconds.
+    pub share_link_ttl_secs: u64,
+    /// The longest expiry a share link may be created with, in seconds.
+    pub share_link_max_ttl_secs: u64,
+    /// Whether chunk decryption for downloads runs on the blocking thread pool.
+    pub blocking_decrypt_enabled: bool,
+    /// The minimum file size, in bytes, for decryption to use the blocking pool.
+    pub blocking_decrypt_min_bytes: i64,
+    /// Whether finalize decrypts and hashes the upload to check `expected_hash`.
+    pub verify_checksum_on_finalize: bool,
+    /// Whether zero-byte files may be uploaded.
+    pub allow_empty_files: bool,
+    /// The maximum length of an uploaded filename, in characters.
+    pub max_filename_length: usize,
+    /// Whether passwords must contain an uppercase letter.
+    pub password_require_uppercase: bool,
+    /// Whether passwords must contain a lowercase letter.
+    pub password_require_lowercase: bool,
+    /// Whether passwords must contain a digit.
+    pub password_require_digit: bool,
+    /// Whether passwords must contain a character that is not a letter or digit.
+    pub password_require_symbol: bool,
+    /// Failed logins from one IP for one username within the throttle window
+    /// after which further attempts are refused; zero disables the throttle.
+    pub login_throttle_attempts: u32,
+    /// The window in which failed logins count towards the throttle, in seconds.
+    pub login_throttle_window_secs: u64,
+    /// Failed logins within the lockout window that lock an account; zero disables lockout.
+    pub login_lockout_threshold: u32,
+    /// The window in which failed logins count towards a lockout, in seconds.
+    pub login_lockout_window_secs: u64,
+    /// How long a locked account rejects every login, in seconds.
+    pub login_lockout_duration_secs: u64,
+    /// The Argon2id cost of new password hashes, from `ARGON2_MEMORY_MB`,
+    /// `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`.
+    pub argon2_params: argon2::Params,
+    /// The maximum number of Argon2 computations allowed to run at once.
+    pub max_concurrent_password_hashes: usize,
+    /// How long a request may wait for an Argon2 slot, in milliseconds.
+    pub password_hash_queue_timeout_ms: u64,
+    /// Whether finalized uploads are scanned with clamd.
+    pub antivirus_enabled: bool,
+    /// The clamd address: `host:port`, or a Unix socket path starting with `/`.
+    pub clamd_address: String,
+    /// The maximum time allowed for one antivirus scan, in seconds.
+    pub antivirus_timeout_secs: u64,
+    /// Whether to accept uploads when clamd is unavailable or errors.
+    pub antivirus_fail_open: bool,
+    /// How long a request waits for a free database connection, in milliseconds.
+    pub db_acquire_timeout_ms: u64,
+    /// The algorithm assumed for untagged upload checksums and used by verification.
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Whether upload init reserves the file's size against the quota.
+    pub quota_reservation_enabled: bool,
+    /// The largest file that may be uploaded, in bytes.
+    pub max_file_size_bytes: i64,
+    /// The plaintext size of every upload chunk but the last, in bytes.
+    pub chunk_size_bytes: usize,
+    /// How long an upload session may run before it expires, in seconds.
+    pub upload_expiration_secs: u64,
+    /// The maximum number of upload sessions in progress across all users.
+    pub max_active_upload_sessions: usize,
+    /// The maximum number of upload sessions one user may have in progress.
+    pub max_active_uploads_per_user: usize,
+    /// The maximum number of upload progress sockets one user may have open.
+    pub max_progress_sockets_per_user: usize,
+    /// The maximum number of account event streams one user may have open.
+    pub max_event_streams_per_user: usize,
+    /// The slowest upload rate a chunk request may sustain, in bytes per second.
+    pub upload_min_bytes_per_sec: u64,
+    /// The lower bound of a chunk request's read timeout, in seconds.
+    pub upload_chunk_timeout_min_secs: u64,
+    /// The upper bound of a chunk request's read timeout, in seconds.
+    pub upload_chunk_timeout_max_secs: u64,
+    /// Whether startup aborts when `MASTER_KEY` cannot decrypt the stored KEKs.
+    pub master_key_mismatch_fatal: bool,
+    /// The maximum number of decrypted KEKs kept in memory.
+    pub kek_cache_capacity: usize,
+    /// How long a decrypted KEK stays cached before it is dropped, in seconds.
+    pub kek_cache_ttl_secs: u64,
+    /// The `Content-Security-Policy` sent with every response; empty disables it.
+    pub content_security_policy: String,
+    /// The `Strict-Transport-Security` max-age in production, in seconds; zero disables it.
+    pub hsts_max_age_secs: u64,
+    /// How long a shutdown waits for in-flight requests to finish, in seconds.
+    pub shutdown_timeout_secs: u64,
+    /// Whether identical chunks of a user are stored once, content-addressed.
+    pub chunk_dedup_enabled: bool,
+    /// The directory encrypted chunks are stored in.
+    pub upload_dir: PathBuf,
+    /// The directory of static files served for unmatched routes.
+    pub public_dir: PathBuf,
+    /// The free space on the upload filesystem that uploads may not use, in bytes.
+    pub disk_reserve_bytes: u64,
+    /// How long a password reset token stays valid, in seconds.
+    pub password_reset_ttl_secs: u64,
+    /// The most files `POST /api/files/batch-delete` accepts at once.
+    pub max_batch_delete_files: usize,
+    /// How long an item must have been in the trash before `POST /api/folders/trash/empty` purges it.
+    pub trash_empty_grace_secs: i64,
+    /// The storage usage percentage at which `storage_info` reports `warning`.
+    pub quota_warning_percent: f64,
+    /// The storage usage percentage at which `storage_info` reports `critical`.
+    pub quota_critical_percent: f64,
+    /// The storage usage percentage at which `storage_info` reports `full`.
+    pub quota_full_percent: f64,
 }
 
 impl Config {
@@ -22,7 +178,22 @@ impl Config {
     ///
     /// A `Result` containing the `Config`.
     pub fn from_env() -> Result<Self> {
-        let mut master_key_hex = env::var("MASTER_KEY")
+        Self::from_lookup(|key| env::var(key))
+    }
+
+    /// Creates a new `Config` reading each variable through `var`.
+    ///
+    /// Tests use this to point the application at test backends without
+    /// mutating the process environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `var` - Looks up a variable by name, like `std::env::var`.
+    pub fn from_lookup<F>(var: F) -> Result<Self>
+    where
+        F: Fn(&str) -> std::result::Result<String, env::VarError>,
+    {
+        let mut master_key_hex = var("MASTER_KEY")
             .context("MASTER_KEY must be set (generate with: openssl rand -hex 32)")?;
         
         let master_key_bytes = hex::decode(&master_key_hex)
@@ -34,16 +205,470 @@ impl Config {
             anyhow::bail!("MASTER_KEY must be exactly 32 bytes (64 hex characters)");
         }
         
+        let content_security_policy = var("CONTENT_SECURITY_POLICY").unwrap_or_else(|_| {
+            "default-src 'self'; object-src 'none'; frame-ancestors 'none'; base-uri 'self'"
+                .to_string()
+        });
+        http::HeaderValue::from_str(&content_security_policy)
+            .context("Invalid CONTENT_SECURITY_POLICY")?;
+
+        let tenant_mode: TenantMode = var("TENANT_MODE")
+            .unwrap_or_else(|_| "off".to_string())
+            .parse()
+            .context("Invalid TENANT_MODE")?;
+        let tenants: Vec<String> = var("TENANTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|tenant| tenant.trim().to_ascii_lowercase())
+            .filter(|tenant| !tenant.is_empty())
+            .collect();
+        if let Some(tenant) = tenants.iter().find(|tenant| !is_valid_tenant_id(tenant)) {
+            anyhow::bail!("Invalid tenant '{}' in TENANTS: use 1 to 63 lowercase letters, digits or hyphens", tenant);
+        }
+        if tenant_mode != TenantMode::Off && tenants.is_empty() {
+            anyhow::bail!("TENANTS must list at least one tenant when TENANT_MODE is enabled");
+        }
+        let tenant_base_domain = var("TENANT_BASE_DOMAIN")
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('.')
+            .to_ascii_lowercase();
+        if tenant_mode == TenantMode::Subdomain && tenant_base_domain.is_empty() {
+            anyhow::bail!("TENANT_BASE_DOMAIN must be set when TENANT_MODE is subdomain");
+        }
+
+        let argon2_memory_mb: u32 = var("ARGON2_MEMORY_MB")
+            .unwrap_or_else(|_| "19".to_string())
+            .parse()
+            .context("Invalid ARGON2_MEMORY_MB")?;
+        let argon2_iterations: u32 = var("ARGON2_ITERATIONS")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .context("Invalid ARGON2_ITERATIONS")?;
+        let argon2_parallelism: u32 = var("ARGON2_PARALLELISM")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse()
+            .context("Invalid ARGON2_PARALLELISM")?;
+        let argon2_params = argon2::ParamsBuilder::new()
+            .m_cost(argon2_memory_mb.saturating_mul(1024))
+            .t_cost(argon2_iterations)
+            .p_cost(argon2_parallelism)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+
+        let max_concurrent_password_hashes: usize = var("MAX_CONCURRENT_PASSWORD_HASHES")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse()
+            .context("Invalid MAX_CONCURRENT_PASSWORD_HASHES")?;
+        if max_concurrent_password_hashes == 0 {
+            bail!("MAX_CONCURRENT_PASSWORD_HASHES must be greater than 0");
+        }
+
+        let chunk_size_bytes: usize = var("CHUNK_SIZE_BYTES")
+            .unwrap_or_else(|_| "6291456".to_string())
+            .parse()
+            .context("Invalid CHUNK_SIZE_BYTES")?;
+        if chunk_size_bytes == 0 {
+            bail!("CHUNK_SIZE_BYTES must be greater than 0");
+        }
+
+        let impersonation_session_minutes: i64 = var("IMPERSONATION_SESSION_MINUTES")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .context("Invalid IMPERSONATION_SESSION_MINUTES")?;
+        if impersonation_session_minutes <= 0 {
+            bail!("IMPERSONATION_SESSION_MINUTES must be greater than 0");
+        }
+
+        let stream_session_check_secs: u64 = var("STREAM_SESSION_CHECK_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .context("Invalid STREAM_SESSION_CHECK_SECS")?;
+        if stream_session_check_secs == 0 {
+            bail!("STREAM_SESSION_CHECK_SECS must be greater than 0");
+        }
+
+        let quota_warning_percent: f64 = var("QUOTA_WARNING_PERCENT")
+            .unwrap_or_else(|_| "80".to_string())
+            .parse()
+            .context("Invalid QUOTA_WARNING_PERCENT")?;
+        let quota_critical_percent: f64 = var("QUOTA_CRITICAL_PERCENT")
+            .unwrap_or_else(|_| "95".to_string())
+            .parse()
+            .context("Invalid QUOTA_CRITICAL_PERCENT")?;
+        let quota_full_percent: f64 = var("QUOTA_FULL_PERCENT")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .context("Invalid QUOTA_FULL_PERCENT")?;
+        if !(0.0 < quota_warning_percent
+            && quota_warning_percent <= quota_critical_percent
+            && quota_critical_percent <= quota_full_percent)
+        {
+            bail!(
+                "Quota thresholds must satisfy 0 < QUOTA_WARNING_PERCENT <= QUOTA_CRITICAL_PERCENT <= QUOTA_FULL_PERCENT"
+            );
+        }
+
         Ok(Self {
-            database_url: env::var("DATABASE_URL")
+            app_env: var("APP_ENV").unwrap_or_else(|_| "development".to_string()),
+            database_url: var("DATABASE_URL")
                 .context("DATABASE_URL must be set")?,
-            redis_url: env::var("REDIS_URL")
+            redis_url: var("REDIS_URL")
                 .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string()),
-            session_duration_days: env::var("SESSION_DURATION_DAYS")
+            session_duration_days: var("SESSION_DURATION_DAYS")
                 .unwrap_or_else(|_| "7".to_string())
                 .parse()
                 .context("Invalid SESSION_DURATION_DAYS")?,
             master_key: Zeroizing::new(master_key_bytes),
+            impersonation_session_minutes,
+            stream_session_check_secs,
+            max_multipart_fields: var("MAX_MULTIPART_FIELDS")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .context("Invalid MAX_MULTIPART_FIELDS")?,
+            multipart_field_timeout_secs: var("MULTIPART_FIELD_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()
+                .context("Invalid MULTIPART_FIELD_TIMEOUT_SECS")?,
+            invalidate_sessions_on_password_change: var("INVALIDATE_SESSIONS_ON_PASSWORD_CHANGE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid INVALIDATE_SESSIONS_ON_PASSWORD_CHANGE")?,
+            upload_conflict_policy: var("UPLOAD_CONFLICT_POLICY")
+                .unwrap_or_else(|_| "keep-both".to_string())
+                .parse()
+                .context("Invalid UPLOAD_CONFLICT_POLICY")?,
+            folder_cache_capacity: var("FOLDER_CACHE_CAPACITY")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("Invalid FOLDER_CACHE_CAPACITY")?,
+            folder_cache_ttl_secs: var("FOLDER_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid FOLDER_CACHE_TTL_SECS")?,
+            cors_allowed_origins: parse_origin_list(&var, "CORS_ALLOWED_ORIGINS")?,
+            admin_ip_allowlist: parse_ip_list(&var, "ADMIN_IP_ALLOWLIST")?,
+            admin_ip_denylist: parse_ip_list(&var, "ADMIN_IP_DENYLIST")?,
+            trust_proxy_headers: var("TRUST_PROXY_HEADERS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid TRUST_PROXY_HEADERS")?,
+            tenant_mode,
+            tenant_header: var("TENANT_HEADER")
+                .unwrap_or_else(|_| "x-tenant-id".to_string())
+                .parse()
+                .context("Invalid TENANT_HEADER")?,
+            tenant_base_domain,
+            tenants,
+            hard_delete_on_delete: var("HARD_DELETE_ON_DELETE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid HARD_DELETE_ON_DELETE")?,
+            trash_retention_days: var("TRASH_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid TRASH_RETENTION_DAYS")?,
+            share_link_ttl_secs: var("SHARE_LINK_TTL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .context("Invalid SHARE_LINK_TTL_SECS")?,
+            share_link_max_ttl_secs: var("SHARE_LINK_MAX_TTL_SECS")
+                .unwrap_or_else(|_| "2592000".to_string())
+                .parse()
+                .context("Invalid SHARE_LINK_MAX_TTL_SECS")?,
+            blocking_decrypt_enabled: var("BLOCKING_DECRYPT_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid BLOCKING_DECRYPT_ENABLED")?,
+            blocking_decrypt_min_bytes: var("BLOCKING_DECRYPT_MIN_BYTES")
+                .unwrap_or_else(|_| "1048576".to_string())
+                .parse()
+                .context("Invalid BLOCKING_DECRYPT_MIN_BYTES")?,
+            verify_checksum_on_finalize: var("VERIFY_CHECKSUM_ON_FINALIZE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid VERIFY_CHECKSUM_ON_FINALIZE")?,
+            allow_empty_files: var("ALLOW_EMPTY_FILES")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid ALLOW_EMPTY_FILES")?,
+            max_filename_length: var("MAX_FILENAME_LENGTH")
+                .unwrap_or_else(|_| "255".to_string())
+                .parse()
+                .context("Invalid MAX_FILENAME_LENGTH")?,
+            password_require_uppercase: var("PASSWORD_REQUIRE_UPPERCASE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid PASSWORD_REQUIRE_UPPERCASE")?,
+            password_require_lowercase: var("PASSWORD_REQUIRE_LOWERCASE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid PASSWORD_REQUIRE_LOWERCASE")?,
+            password_require_digit: var("PASSWORD_REQUIRE_DIGIT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid PASSWORD_REQUIRE_DIGIT")?,
+            password_require_symbol: var("PASSWORD_REQUIRE_SYMBOL")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid PASSWORD_REQUIRE_SYMBOL")?,
+            login_throttle_attempts: var("LOGIN_THROTTLE_ATTEMPTS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid LOGIN_THROTTLE_ATTEMPTS")?,
+            login_throttle_window_secs: var("LOGIN_THROTTLE_WINDOW_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .context("Invalid LOGIN_THROTTLE_WINDOW_SECS")?,
+            login_lockout_threshold: var("LOGIN_LOCKOUT_THRESHOLD")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid LOGIN_LOCKOUT_THRESHOLD")?,
+            login_lockout_window_secs: var("LOGIN_LOCKOUT_WINDOW_SECS")
+                .unwrap_or_else(|_| "604800".to_string())
+                .parse()
+                .context("Invalid LOGIN_LOCKOUT_WINDOW_SECS")?,
+            login_lockout_duration_secs: var("LOGIN_LOCKOUT_DURATION_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .context("Invalid LOGIN_LOCKOUT_DURATION_SECS")?,
+            argon2_params,
+            max_concurrent_password_hashes,
+            password_hash_queue_timeout_ms: var("PASSWORD_HASH_QUEUE_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .context("Invalid PASSWORD_HASH_QUEUE_TIMEOUT_MS")?,
+            antivirus_enabled: var("ANTIVIRUS_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid ANTIVIRUS_ENABLED")?,
+            clamd_address: var("CLAMD_ADDRESS")
+                .unwrap_or_else(|_| "127.0.0.1:3310".to_string()),
+            antivirus_timeout_secs: var("ANTIVIRUS_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()
+                .context("Invalid ANTIVIRUS_TIMEOUT_SECS")?,
+            antivirus_fail_open: var("ANTIVIRUS_FAIL_OPEN")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid ANTIVIRUS_FAIL_OPEN")?,
+            db_acquire_timeout_ms: var("DB_ACQUIRE_TIMEOUT_MS")
+                .unwrap_or_else(|_| "3000".to_string())
+                .parse()
+                .context("Invalid DB_ACQUIRE_TIMEOUT_MS")?,
+            checksum_algorithm: var("CHECKSUM_ALGORITHM")
+                .unwrap_or_else(|_| "sha256".to_string())
+                .parse()
+                .context("Invalid CHECKSUM_ALGORITHM")?,
+            quota_reservation_enabled: var("QUOTA_RESERVATION_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid QUOTA_RESERVATION_ENABLED")?,
+            max_file_size_bytes: var("MAX_FILE_SIZE_BYTES")
+                .unwrap_or_else(|_| "53687091200".to_string())
+                .parse()
+                .context("Invalid MAX_FILE_SIZE_BYTES")?,
+            chunk_size_bytes,
+            upload_expiration_secs: var("UPLOAD_EXPIRATION_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .context("Invalid UPLOAD_EXPIRATION_SECS")?,
+            max_active_upload_sessions: var("MAX_ACTIVE_UPLOAD_SESSIONS")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .context("Invalid MAX_ACTIVE_UPLOAD_SESSIONS")?,
+            max_active_uploads_per_user: var("MAX_ACTIVE_UPLOADS_PER_USER")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid MAX_ACTIVE_UPLOADS_PER_USER")?,
+            max_progress_sockets_per_user: var("MAX_PROGRESS_SOCKETS_PER_USER")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("Invalid MAX_PROGRESS_SOCKETS_PER_USER")?,
+            max_event_streams_per_user: var("MAX_EVENT_STREAMS_PER_USER")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .context("Invalid MAX_EVENT_STREAMS_PER_USER")?,
+            upload_min_bytes_per_sec: var("UPLOAD_MIN_BYTES_PER_SEC")
+                .unwrap_or_else(|_| "16384".to_string())
+                .parse()
+                .context("Invalid UPLOAD_MIN_BYTES_PER_SEC")?,
+            upload_chunk_timeout_min_secs: var("UPLOAD_CHUNK_TIMEOUT_MIN_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid UPLOAD_CHUNK_TIMEOUT_MIN_SECS")?,
+            upload_chunk_timeout_max_secs: var("UPLOAD_CHUNK_TIMEOUT_MAX_SECS")
+                .unwrap_or_else(|_| "1800".to_string())
+                .parse()
+                .context("Invalid UPLOAD_CHUNK_TIMEOUT_MAX_SECS")?,
+            master_key_mismatch_fatal: var("MASTER_KEY_MISMATCH_FATAL")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("Invalid MASTER_KEY_MISMATCH_FATAL")?,
+            kek_cache_capacity: var("KEK_CACHE_CAPACITY")
+                .unwrap_or_else(|_| "16".to_string())
+                .parse()
+                .context("Invalid KEK_CACHE_CAPACITY")?,
+            kek_cache_ttl_secs: var("KEK_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .context("Invalid KEK_CACHE_TTL_SECS")?,
+            content_security_policy,
+            hsts_max_age_secs: var("HSTS_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "31536000".to_string())
+                .parse()
+                .context("Invalid HSTS_MAX_AGE_SECS")?,
+            shutdown_timeout_secs: var("SHUTDOWN_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("Invalid SHUTDOWN_TIMEOUT_SECS")?,
+            chunk_dedup_enabled: var("CHUNK_DEDUP_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("Invalid CHUNK_DEDUP_ENABLED")?,
+            upload_dir: PathBuf::from(var("UPLOAD_DIR").unwrap_or_else(|_| "uploads/files".to_string())),
+            public_dir: PathBuf::from(var("PUBLIC_DIR").unwrap_or_else(|_| "files/public".to_string())),
+            disk_reserve_bytes: var("DISK_RESERVE_BYTES")
+                .unwrap_or_else(|_| "1073741824".to_string())
+                .parse()
+                .context("Invalid DISK_RESERVE_BYTES")?,
+            password_reset_ttl_secs: var("PASSWORD_RESET_TTL_SECS")
+                .unwrap_or_else(|_| "1800".to_string())
+                .parse()
+                .context("Invalid PASSWORD_RESET_TTL_SECS")?,
+            max_batch_delete_files: var("MAX_BATCH_DELETE_FILES")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .context("Invalid MAX_BATCH_DELETE_FILES")?,
+            trash_empty_grace_secs: var("TRASH_EMPTY_GRACE_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .context("Invalid TRASH_EMPTY_GRACE_SECS")?,
+            quota_warning_percent,
+            quota_critical_percent,
+            quota_full_percent,
         })
     }
 }
+
+impl Config {
+    /// Whether the application runs in production (`APP_ENV=production`).
+    ///
+    /// Production marks cookies `Secure` and sends `Strict-Transport-Security`.
+    pub fn is_production(&self) -> bool {
+        self.app_env == "production"
+    }
+
+    /// Returns how long a chunk request of `bytes` may take to arrive.
+    ///
+    /// The timeout is the time needed at `upload_min_bytes_per_sec`, clamped to
+    /// the configured bounds, so slow but steady clients finish while clients
+    /// trickling bytes (slowloris) are cut off.
+    pub fn chunk_read_timeout(&self, bytes: u64) -> Duration {
+        let secs = bytes.div_ceil(self.upload_min_bytes_per_sec.max(1));
+        Duration::from_secs(secs.clamp(
+            self.upload_chunk_timeout_min_secs,
+            self.upload_chunk_timeout_max_secs.max(self.upload_chunk_timeout_min_secs),
+        ))
+    }
+
+    /// Returns whether chunks of a file of `file_size` bytes should be
+    /// decrypted on the blocking pool rather than on the async workers.
+    pub fn decrypt_on_blocking_pool(&self, file_size: i64) -> bool {
+        self.blocking_decrypt_enabled && file_size >= self.blocking_decrypt_min_bytes
+    }
+
+    /// Returns the character classes new passwords must contain.
+    pub fn password_policy(&self) -> PasswordPolicy {
+        PasswordPolicy {
+            require_uppercase: self.password_require_uppercase,
+            require_lowercase: self.password_require_lowercase,
+            require_digit: self.password_require_digit,
+            require_symbol: self.password_require_symbol,
+        }
+    }
+
+    /// Returns the usage percentages at which storage is reported as running out.
+    pub fn quota_thresholds(&self) -> QuotaThresholds {
+        QuotaThresholds {
+            warning_percent: self.quota_warning_percent,
+            critical_percent: self.quota_critical_percent,
+            full_percent: self.quota_full_percent,
+        }
+    }
+
+    /// Returns how long a deleted file stays restorable, in seconds.
+    pub fn trash_retention_secs(&self) -> i64 {
+        self.trash_retention_days.saturating_mul(86400)
+    }
+}
+
+/// Parses a comma-separated list of CIDR networks or bare IP addresses.
+fn parse_ip_list<F>(var: &F, name: &str) -> Result<Vec<IpNet>>
+where
+    F: Fn(&str) -> std::result::Result<String, env::VarError>,
+{
+    let raw = var(name).unwrap_or_default();
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .with_context(|| format!("Invalid {} entry: {}", name, entry))
+        })
+        .collect()
+}
+
+/// The origins allowed when `CORS_ALLOWED_ORIGINS` is unset: a frontend dev
+/// server on port 3000.
+const DEFAULT_CORS_ORIGINS: &str = "http://localhost:3000,http://127.0.0.1:3000,http://[::1]:3000";
+
+/// Parses a comma-separated list of CORS origins such as `https://app.example.com`.
+///
+/// Credentialed CORS cannot use a wildcard, so `*` is rejected, as is
+/// anything with a path: browsers send the bare origin, which would never
+/// match `https://app.example.com/`.
+fn parse_origin_list<F>(var: &F, name: &str) -> Result<Vec<HeaderValue>>
+where
+    F: Fn(&str) -> std::result::Result<String, env::VarError>,
+{
+    let raw = var(name).unwrap_or_else(|_| DEFAULT_CORS_ORIGINS.to_string());
+
+    let origins = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            if entry.contains('*') {
+                bail!(
+                    "Invalid {} entry: {} (wildcards cannot be used with credentialed CORS; list each origin)",
+                    name,
+                    entry
+                );
+            }
+
+            let uri: Uri = entry
+                .parse()
+                .with_context(|| format!("Invalid {} entry: {}", name, entry))?;
+            let has_path = uri.path_and_query().is_some_and(|p| p.as_str() != "/") || entry.ends_with('/');
+            if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.authority().is_none() || has_path {
+                bail!(
+                    "Invalid {} entry: {} (expected scheme://host[:port] with no path)",
+                    name,
+                    entry
+                );
+            }
+
+            HeaderValue::from_str(entry).with_context(|| format!("Invalid {} entry: {}", name, entry))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if origins.is_empty() {
+        bail!("{} must list at least one origin", name);
+    }
+
+    Ok(origins)
+}
@@ -1,6 +1,6 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
-    Aes256Gcm, Nonce,
+    aead::{Aead, AeadInPlace, KeyInit, OsRng},
+    Aes256Gcm, Nonce, Tag,
 };
 use aes_gcm::aead::rand_core::RngCore;
 use zeroize::{Zeroize, ZeroizeOnDrop};
@@ -10,6 +10,8 @@ use crate::error::{AppError, Result};
 pub const KEY_SIZE: usize = 32;
 /// The size of the AES-GCM nonce in bytes.
 pub const NONCE_SIZE: usize = 12;
+/// The size of the AES-GCM authentication tag appended to each ciphertext.
+pub const TAG_SIZE: usize = 16;
 
 /// A secure key wrapper that ensures the key is zeroized on drop.
 #[derive(Zeroize, ZeroizeOnDrop)]
@@ -69,18 +71,35 @@ pub fn generate_nonce() -> [u8; NONCE_SIZE] {
 ///
 /// A tuple containing the ciphertext and the nonce used for encryption.
 pub fn encrypt(key: &[u8; KEY_SIZE], plaintext: &[u8]) -> Result<(Vec<u8>, [u8; NONCE_SIZE])> {
-    let cipher = Aes256Gcm::new(key.into());
-
     let nonce_bytes = generate_nonce();
-    let nonce = Nonce::from(nonce_bytes);
-
-    let ciphertext = cipher
-        .encrypt(&nonce, plaintext)
-        .map_err(|e| AppError::Encryption(format!("Encryption failed: {}", e)))?;
+    let ciphertext = encrypt_with_nonce(key, plaintext, &nonce_bytes)?;
 
     Ok((ciphertext, nonce_bytes))
 }
 
+/// Encrypts a plaintext using AES-256-GCM with a caller-chosen nonce.
+///
+/// A nonce must never be reused with a different plaintext under the same
+/// key; only use this with a nonce derived from the plaintext itself.
+///
+/// # Arguments
+///
+/// * `key` - The AES-256 key.
+/// * `plaintext` - The data to encrypt.
+/// * `nonce` - The nonce to encrypt with.
+///
+/// # Returns
+///
+/// The ciphertext.
+pub fn encrypt_with_nonce(key: &[u8; KEY_SIZE], plaintext: &[u8], nonce: &[u8; NONCE_SIZE]) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Nonce::from(*nonce);
+
+    cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| AppError::Encryption(format!("Encryption failed: {}", e)))
+}
+
 /// Decrypts a ciphertext using AES-256-GCM.
 ///
 /// # Arguments
@@ -100,3 +119,31 @@ pub fn decrypt(key: &[u8; KEY_SIZE], ciphertext: &[u8], nonce: &[u8; NONCE_SIZE]
         .decrypt(&nonce, ciphertext)
         .map_err(|e| AppError::Encryption(format!("Decryption failed: {}", e)))
 }
+
+/// Decrypts a ciphertext in place using AES-256-GCM, without allocating.
+///
+/// # Arguments
+///
+/// * `key` - The AES-256 key.
+/// * `buffer` - The ciphertext followed by its tag; overwritten with the plaintext.
+/// * `nonce` - The nonce used for encryption.
+///
+/// # Returns
+///
+/// The length of the plaintext, which starts at the beginning of `buffer`.
+pub fn decrypt_in_place(key: &[u8; KEY_SIZE], buffer: &mut [u8], nonce: &[u8; NONCE_SIZE]) -> Result<usize> {
+    let plaintext_len = buffer
+        .len()
+        .checked_sub(TAG_SIZE)
+        .ok_or_else(|| AppError::Encryption("Ciphertext shorter than its tag".to_string()))?;
+
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Nonce::from(*nonce);
+    let (ciphertext, tag) = buffer.split_at_mut(plaintext_len);
+
+    cipher
+        .decrypt_in_place_detached(&nonce, b"", ciphertext, Tag::from_slice(tag))
+        .map_err(|e| AppError::Encryption(format!("Decryption failed: {}", e)))?;
+
+    Ok(plaintext_len)
+}
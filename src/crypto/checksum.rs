@@ -0,0 +1,129 @@
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr};
+
+use crate::error::{AppError, Result};
+
+/// The hash algorithm of a file's integrity checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumAlgorithm {
+    /// SHA-256, the default, which most clients can pre-compute.
+    #[default]
+    Sha256,
+    /// BLAKE3, several times faster on large files.
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    /// Returns the tag stored in front of the hex digest.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        }
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Self::Sha256),
+            "blake3" => Ok(Self::Blake3),
+            other => anyhow::bail!(
+                "unknown checksum algorithm '{}' (expected sha256 or blake3)",
+                other
+            ),
+        }
+    }
+}
+
+/// A checksum tagged with its algorithm, stored as `<algorithm>:<hex>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub hex: String,
+}
+
+impl Checksum {
+    /// Parses a client-supplied checksum.
+    ///
+    /// A bare hex digest is taken to use `default_algorithm`.
+    pub fn parse(value: &str, default_algorithm: ChecksumAlgorithm) -> Result<Self> {
+        let (algorithm, hex) = match value.split_once(':') {
+            Some((tag, hex)) => (
+                tag.parse()
+                    .map_err(|e: anyhow::Error| AppError::Validation(e.to_string()))?,
+                hex,
+            ),
+            None => (default_algorithm, value),
+        };
+
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::Validation(format!(
+                "Invalid {} checksum: expected 64 hex characters",
+                algorithm.tag()
+            )));
+        }
+
+        Ok(Self {
+            algorithm,
+            hex: hex.to_ascii_lowercase(),
+        })
+    }
+
+    /// Parses a checksum read from the database.
+    ///
+    /// Values written before checksums were tagged are SHA-256.
+    pub fn parse_stored(value: &str) -> Result<Self> {
+        Self::parse(value, ChecksumAlgorithm::Sha256)
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm.tag(), self.hex)
+    }
+}
+
+/// An incremental hasher for either checksum algorithm.
+pub enum ChecksumHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl ChecksumHasher {
+    /// Creates a new hasher for the given algorithm.
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// Feeds data into the hasher.
+    ///
+    /// Large BLAKE3 inputs are hashed across threads with rayon.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Blake3(hasher) => {
+                hasher.update_rayon(data);
+            }
+        }
+    }
+
+    /// Finishes hashing and returns the tagged checksum.
+    pub fn finalize(self) -> Checksum {
+        match self {
+            Self::Sha256(hasher) => Checksum {
+                algorithm: ChecksumAlgorithm::Sha256,
+                hex: hex::encode(hasher.finalize()),
+            },
+            Self::Blake3(hasher) => Checksum {
+                algorithm: ChecksumAlgorithm::Blake3,
+                hex: hasher.finalize().to_hex().to_string(),
+            },
+        }
+    }
+}
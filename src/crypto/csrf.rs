@@ -2,6 +2,7 @@ use crate::error::{AppError, Result};
 use rand::RngCore;
 use rand::rngs::OsRng;
 use base64::{Engine as _, engine::general_purpose};
+use subtle::ConstantTimeEq;
 
 /// The size of the CSRF token in bytes.
 const CSRF_TOKEN_SIZE: usize = 32;
@@ -18,3 +19,22 @@ pub fn generate_csrf_token() -> Result<String> {
     
     Ok(general_purpose::URL_SAFE_NO_PAD.encode(token))
 }
+
+/// Compares a CSRF cookie and header value in constant time.
+///
+/// Both are decoded first; a value that is not a well-formed token of
+/// `CSRF_TOKEN_SIZE` bytes is rejected before comparing, which only reveals
+/// that it was malformed, never how much of a valid token it matched.
+pub fn csrf_tokens_match(cookie_token: &str, header_token: &str) -> bool {
+    let decode = |token: &str| {
+        general_purpose::URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .filter(|bytes| bytes.len() == CSRF_TOKEN_SIZE)
+    };
+
+    match (decode(cookie_token), decode(header_token)) {
+        (Some(cookie), Some(header)) => cookie.ct_eq(&header).into(),
+        _ => false,
+    }
+}
@@ -1,26 +1,101 @@
-use argon2::Argon2;
+use std::{fmt, str::FromStr};
+
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::{rngs::OsRng, RngCore};
 use crate::error::{AppError, Result};
 
-/// Derives a key from a password and salt using Argon2.
-fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32]> {
+/// The Argon2id cost of the key that wraps a user's DEK.
+///
+/// It is stored with the user (`users.dek_kdf_params`), so the key can still
+/// be derived after the configured cost changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// The memory cost in KiB.
+    pub m_cost: u32,
+    /// The number of passes over memory.
+    pub t_cost: u32,
+    /// The number of lanes.
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    /// The cost of DEKs wrapped before it was recorded: `Argon2::default()`.
+    pub const LEGACY: Self = Self {
+        m_cost: Params::DEFAULT_M_COST,
+        t_cost: Params::DEFAULT_T_COST,
+        p_cost: Params::DEFAULT_P_COST,
+    };
+
+    /// Parses the stored cost of a user's DEK, where `None` means [`LEGACY`](Self::LEGACY).
+    pub fn from_stored(stored: Option<&str>) -> Result<Self> {
+        stored.map_or(Ok(Self::LEGACY), str::parse)
+    }
+}
+
+impl From<&Params> for KdfParams {
+    fn from(params: &Params) -> Self {
+        Self {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+        }
+    }
+}
+
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m={},t={},p={}", self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+impl FromStr for KdfParams {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || AppError::Encryption(format!("Invalid DEK KDF parameters: {}", s));
+
+        let mut params = [None; 3];
+        for pair in s.split(',') {
+            let (name, value) = pair.split_once('=').ok_or_else(invalid)?;
+            let slot = match name.trim() {
+                "m" => 0,
+                "t" => 1,
+                "p" => 2,
+                _ => return Err(invalid()),
+            };
+            params[slot] = Some(value.trim().parse::<u32>().map_err(|_| invalid())?);
+        }
+
+        match params {
+            [Some(m_cost), Some(t_cost), Some(p_cost)] => Ok(Self { m_cost, t_cost, p_cost }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Derives a key from a password and salt using Argon2id at the given cost.
+fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<[u8; 32]> {
+    let argon2_params = Params::new(params.m_cost, params.t_cost, params.p_cost, None)
+        .map_err(|e| AppError::Encryption(format!("Argon2 params: {}", e)))?;
+
     let mut key = [0u8; 32];
-    Argon2::default()
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params)
         .hash_password_into(password.as_bytes(), salt, &mut key)
         .map_err(|e| AppError::Encryption(format!("Argon2 key derivation error: {}", e)))?;
     Ok(key)
 }
 
-/// Creates a new user data encryption key (DEK).
-pub fn create_user_dek(password: &str) -> Result<(Vec<u8>, Vec<u8>)> {
-    let mut dek = [0u8; 32];
-    OsRng.fill_bytes(&mut dek);
-
+/// Wraps a DEK under a key derived from `password` with a fresh salt.
+///
+/// # Returns
+///
+/// The wrapped DEK (`ciphertext || nonce`) and the salt.
+pub fn wrap_dek(dek: &[u8], password: &str, params: &KdfParams) -> Result<(Vec<u8>, Vec<u8>)> {
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
 
-    let key = derive_key(password, &salt)?;
-    let (encrypted_dek, nonce) = crate::crypto::aes::encrypt(&key, &dek)?;
+    let key = derive_key(password, &salt, params)?;
+    let (encrypted_dek, nonce) = crate::crypto::aes::encrypt(&key, dek)?;
 
     let mut result = Vec::with_capacity(encrypted_dek.len() + nonce.len());
     result.extend_from_slice(&encrypted_dek);
@@ -29,43 +104,59 @@ pub fn create_user_dek(password: &str) -> Result<(Vec<u8>, Vec<u8>)> {
     Ok((result, salt.to_vec()))
 }
 
-/// Changes a user's password and re-encrypts the DEK.
-pub fn change_user_password_dek(
+/// Unwraps a DEK wrapped by [`wrap_dek`].
+fn unwrap_dek(
     encrypted_dek_with_nonce: &[u8],
     salt: &[u8],
-    old_password: &str,
-    new_password: &str,
-) -> Result<(Vec<u8>, Vec<u8>)> {
-    let old_key = derive_key(old_password, salt)?;
+    params: &KdfParams,
+    password: &str,
+) -> Result<Vec<u8>> {
+    if encrypted_dek_with_nonce.len() < 12 {
+        return Err(AppError::Encryption("Encrypted DEK is truncated".to_string()));
+    }
+
+    let key = derive_key(password, salt, params)?;
     let (encrypted_dek, nonce) = encrypted_dek_with_nonce.split_at(encrypted_dek_with_nonce.len() - 12);
     let nonce_arr: [u8; 12] = nonce.try_into().unwrap();
 
-    let dek = crate::crypto::aes::decrypt(&old_key, encrypted_dek, &nonce_arr)?;
+    crate::crypto::aes::decrypt(&key, encrypted_dek, &nonce_arr)
+}
 
-    let mut new_salt = [0u8; 16];
-    OsRng.fill_bytes(&mut new_salt);
+/// Creates a new user data encryption key (DEK), wrapped under a key derived
+/// from `password` at `params`.
+pub fn create_user_dek(password: &str, params: &KdfParams) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut dek = [0u8; 32];
+    OsRng.fill_bytes(&mut dek);
 
-    let new_key = derive_key(new_password, &new_salt)?;
-    let (new_encrypted_dek, new_nonce) = crate::crypto::aes::encrypt(&new_key, &dek)?;
+    wrap_dek(&dek, password, params)
+}
 
-    let mut result = Vec::with_capacity(new_encrypted_dek.len() + new_nonce.len());
-    result.extend_from_slice(&new_encrypted_dek);
-    result.extend_from_slice(&new_nonce);
+/// Changes a user's password and re-encrypts the DEK.
+///
+/// The DEK is unwrapped with the cost it was stored with (`old_params`) and
+/// rewrapped at `new_params`; passing the same password twice only migrates
+/// the DEK to the new cost.
+pub fn change_user_password_dek(
+    encrypted_dek_with_nonce: &[u8],
+    salt: &[u8],
+    old_params: &KdfParams,
+    old_password: &str,
+    new_password: &str,
+    new_params: &KdfParams,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let dek = zeroize::Zeroizing::new(unwrap_dek(encrypted_dek_with_nonce, salt, old_params, old_password)?);
 
-    Ok((result, new_salt.to_vec()))
+    wrap_dek(&dek, new_password, new_params)
 }
 
 /// Decrypts a user's data encryption key (DEK).
 pub fn decrypt_user_dek(
     encrypted_dek_with_nonce: &[u8],
     salt: &[u8],
+    params: &KdfParams,
     password: &str,
 ) -> Result<zeroize::Zeroizing<String>> {
-    let key = derive_key(password, salt)?;
-    let (encrypted_dek, nonce) = encrypted_dek_with_nonce.split_at(encrypted_dek_with_nonce.len() - 12);
-    let nonce_arr: [u8; 12] = nonce.try_into().unwrap();
-
-    let dek = crate::crypto::aes::decrypt(&key, encrypted_dek, &nonce_arr)?;
+    let dek = zeroize::Zeroizing::new(unwrap_dek(encrypted_dek_with_nonce, salt, params, password)?);
 
-    Ok(zeroize::Zeroizing::new(hex::encode(dek)))
+    Ok(zeroize::Zeroizing::new(hex::encode(&*dek)))
 }
@@ -1,32 +1,59 @@
 use deadpool_postgres::Pool;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 use crate::crypto::aes;
 use crate::error::{AppError, Result};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// A cached Key Encryption Key (KEK).
-#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+///
+/// The key data is zeroized when the entry is dropped, which happens on
+/// eviction, expiry, [`KekCache::remove`] and [`KekCache::clear`].
+#[derive(Zeroize, ZeroizeOnDrop)]
 pub struct CachedKek {
     /// The version of the KEK.
     #[zeroize(skip)]
     pub version: i32,
     /// The key data.
     pub keydata: Vec<u8>,
+    /// When the KEK was loaded into the cache.
+    #[zeroize(skip)]
+    inserted_at: Instant,
+    /// The access tick of the last lookup, used for LRU eviction.
+    #[zeroize(skip)]
+    last_used: u64,
 }
 
-/// A cache for Key Encryption Keys (KEKs).
+struct KekCacheInner {
+    entries: HashMap<i32, CachedKek>,
+    tick: u64,
+}
+
+/// A bounded cache for decrypted Key Encryption Keys (KEKs).
+///
+/// Entries expire after the configured TTL and the least recently used entry
+/// is evicted once `capacity` is reached, so decrypted key material does not
+/// stay resident indefinitely. Evicted KEKs are reloaded from the database on
+/// demand by [`load_kek`].
 #[derive(Clone)]
 pub struct KekCache {
-    cache: Arc<RwLock<HashMap<i32, CachedKek>>>,
+    inner: Arc<Mutex<KekCacheInner>>,
+    capacity: usize,
+    ttl: Duration,
 }
 
 impl KekCache {
-    /// Creates a new `KekCache`.
-    pub fn new() -> Self {
+    /// Creates a new `KekCache` holding at most `capacity` KEKs for `ttl` each.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
         Self {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            inner: Arc::new(Mutex::new(KekCacheInner {
+                entries: HashMap::new(),
+                tick: 0,
+            })),
+            capacity: capacity.max(1),
+            ttl,
         }
     }
 
@@ -38,59 +65,227 @@ impl KekCache {
     ///
     /// # Returns
     ///
-    /// An `Option` containing the key data if the KEK is found in the cache.
-    pub async fn get(&self, version: i32) -> Option<Vec<u8>> {
-        let cache = self.cache.read().await;
-        cache.get(&version).map(|kek| kek.keydata.clone())
+    /// An `Option` containing the key data if the KEK is cached and has not
+    /// expired. An expired entry is dropped (and zeroized) on lookup.
+    pub async fn get(&self, version: i32) -> Option<Zeroizing<Vec<u8>>> {
+        let mut inner = self.inner.lock().await;
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let entry = inner.entries.get_mut(&version)?;
+        if entry.inserted_at.elapsed() < self.ttl {
+            entry.last_used = tick;
+            return Some(Zeroizing::new(entry.keydata.clone()));
+        }
+
+        inner.entries.remove(&version);
+        None
     }
 
-    /// Inserts a KEK into the cache.
+    /// Inserts a KEK into the cache, evicting expired entries and then the
+    /// least recently used one if the cache is full.
     ///
     /// # Arguments
     ///
     /// * `version` - The version of the KEK.
     /// * `keydata` - The key data to insert.
     pub async fn insert(&self, version: i32, keydata: Vec<u8>) {
-        let mut cache = self.cache.write().await;
-        cache.insert(version, CachedKek { version, keydata });
+        let mut inner = self.inner.lock().await;
+
+        if !inner.entries.contains_key(&version) && inner.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+
+            if inner.entries.len() >= self.capacity
+                && let Some(lru_version) = inner
+                    .entries
+                    .values()
+                    .min_by_key(|entry| entry.last_used)
+                    .map(|entry| entry.version)
+            {
+                inner.entries.remove(&lru_version);
+            }
+        }
+
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.entries.insert(
+            version,
+            CachedKek {
+                version,
+                keydata,
+                inserted_at: Instant::now(),
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Drops every expired KEK from the cache.
+    ///
+    /// # Returns
+    ///
+    /// The number of KEKs evicted.
+    pub async fn trim(&self) -> usize {
+        let mut inner = self.inner.lock().await;
+        let before = inner.entries.len();
+        let ttl = self.ttl;
+        inner.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+        before - inner.entries.len()
+    }
+
+    /// Removes a single KEK version from the cache, e.g. after it is rotated out.
+    pub async fn remove(&self, version: i32) {
+        let mut inner = self.inner.lock().await;
+        inner.entries.remove(&version);
+    }
+
+    /// Removes every KEK older than `version` from the cache, e.g. once a
+    /// rotation has moved all DEKs onto `version`.
+    ///
+    /// # Returns
+    ///
+    /// The number of KEKs evicted.
+    pub async fn evict_older_than(&self, version: i32) -> usize {
+        let mut inner = self.inner.lock().await;
+        let before = inner.entries.len();
+        inner.entries.retain(|&cached, _| cached >= version);
+        before - inner.entries.len()
+    }
+
+    /// Returns the number of KEKs currently cached, including expired ones
+    /// that have not been trimmed yet.
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.entries.len()
+    }
+
+    /// Returns whether the cache holds no KEKs.
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
     }
 
     /// Clears the KEK cache.
+    ///
+    /// Call this whenever KEKs are rotated or rewrapped so that no rotated-out
+    /// key material lingers in memory.
     pub async fn clear(&self) {
-        let mut cache = self.cache.write().await;
-        cache.clear();
+        let mut inner = self.inner.lock().await;
+        inner.entries.clear();
     }
 }
 
-/// Ensures that a KEK with version 1 exists in the database.
-pub async fn ensure_kek_exists(
+/// Gets a KEK by version, decrypting it from the database with the master key
+/// if it is not cached (or was evicted).
+pub async fn load_kek(
     pool: &Pool,
     master_key: &[u8],
     kek_cache: &KekCache,
-) -> Result<i32> {
+    version: i32,
+) -> Result<Zeroizing<Vec<u8>>> {
+    if let Some(keydata) = kek_cache.get(version).await {
+        return Ok(keydata);
+    }
+
+    let master_key_array = master_key_array(master_key)?;
+
+    let client = pool.get().await?;
+    let stmt = client
+        .prepare("SELECT encrypted_keydata, nonce FROM keks WHERE version = $1 AND is_active = true")
+        .await?;
+    let row = client
+        .query_opt(&stmt, &[&version])
+        .await?
+        .ok_or_else(|| AppError::Encryption(format!("KEK version {} not found or inactive", version)))?;
+
+    let encrypted_keydata: Vec<u8> = row.get("encrypted_keydata");
+    let nonce: [u8; 12] = row
+        .get::<_, Vec<u8>>("nonce")
+        .as_slice()
+        .try_into()
+        .map_err(|_| AppError::Encryption(format!("KEK version {} has an invalid nonce", version)))?;
+
+    let keydata = Zeroizing::new(aes::decrypt(&master_key_array, &encrypted_keydata, &nonce)?);
+    kek_cache.insert(version, keydata.to_vec()).await;
+
+    Ok(keydata)
+}
+
+/// Like [`load_kek`], but returns the KEK as a fixed-size AES key.
+pub async fn load_kek_key(
+    pool: &Pool,
+    master_key: &[u8],
+    kek_cache: &KekCache,
+    version: i32,
+) -> Result<Zeroizing<[u8; 32]>> {
+    let keydata = load_kek(pool, master_key, kek_cache, version).await?;
+    let key: [u8; 32] = keydata
+        .as_slice()
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid KEK size".to_string()))?;
+    Ok(Zeroizing::new(key))
+}
+
+/// The lifecycle flags of a stored KEK.
+#[derive(Debug, Clone, Copy)]
+pub struct KekStatus {
+    /// Whether the KEK may still be used to unwrap DEKs.
+    pub is_active: bool,
+    /// Whether the KEK has been superseded by a newer version.
+    pub is_deprecated: bool,
+}
+
+/// Looks up the status of a KEK version, or `None` if it does not exist.
+pub async fn kek_status(pool: &Pool, version: i32) -> Result<Option<KekStatus>> {
+    let client = pool.get().await?;
+    let stmt = client
+        .prepare("SELECT is_active, is_deprecated FROM keks WHERE version = $1")
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&version]).await?;
+
+    Ok(row.map(|r| KekStatus {
+        is_active: r.get("is_active"),
+        is_deprecated: r.get("is_deprecated"),
+    }))
+}
+
+/// Returns the KEK version new DEKs are wrapped under: the newest version
+/// that is active and not deprecated.
+pub async fn active_kek_version(pool: &Pool) -> Result<i32> {
     let client = pool.get().await?;
     let stmt = client
         .prepare(
-            "SELECT version FROM keks WHERE version = 1 AND is_active = true AND is_deprecated = false",
+            "SELECT MAX(version) AS version FROM keks WHERE is_active = true AND is_deprecated = false",
         )
         .await?;
 
-    let existing = client.query_opt(&stmt, &[]).await?;
+    let row = client.query_one(&stmt, &[]).await?;
+    row.get::<_, Option<i32>>("version")
+        .ok_or_else(|| AppError::Encryption("No active KEK available".to_string()))
+}
 
-    if existing.is_some() {
-        tracing::info!("✅ KEK version 1 already exists and is active");
-        return Ok(1);
+/// Ensures that an active KEK exists, creating version 1 on first startup.
+///
+/// # Returns
+///
+/// The active KEK version.
+pub async fn ensure_kek_exists(
+    pool: &Pool,
+    master_key: &[u8],
+    kek_cache: &KekCache,
+) -> Result<i32> {
+    if let Ok(version) = active_kek_version(pool).await {
+        tracing::info!("✅ KEK version {} already exists and is active", version);
+        return Ok(version);
     }
 
     tracing::warn!("⚠️  KEK version 1 not found, creating...");
 
+    let client = pool.get().await?;
     let version = 1i32;
     let kek = aes::generate_key();
     let keydata = kek.as_bytes().to_vec();
 
-    let master_key_array: [u8; 32] = master_key
-        .try_into()
-        .map_err(|_| AppError::Encryption("Invalid master key size".to_string()))?;
+    let master_key_array = master_key_array(master_key)?;
 
     let (encrypted_keydata, nonce) = aes::encrypt(&master_key_array, &keydata)?;
 
@@ -117,3 +312,185 @@ pub async fn ensure_kek_exists(
     tracing::info!("✅ KEK version 1 created successfully and cached");
     Ok(version)
 }
+
+/// Generates a new KEK version and deprecates every older one.
+///
+/// Deprecated KEKs stay active so that DEKs still wrapped under them can be
+/// unwrapped until they are rewrapped under the new version. The `keks` table
+/// is locked for the duration of the transaction, so concurrent rotations get
+/// consecutive versions instead of colliding.
+///
+/// # Returns
+///
+/// The new KEK version.
+pub async fn rotate_kek(pool: &Pool, master_key: &[u8], kek_cache: &KekCache) -> Result<i32> {
+    let master_key_array = master_key_array(master_key)?;
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    transaction
+        .execute("LOCK TABLE keks IN SHARE ROW EXCLUSIVE MODE", &[])
+        .await?;
+
+    let row = transaction
+        .query_one("SELECT COALESCE(MAX(version), 0) + 1 AS version FROM keks", &[])
+        .await?;
+    let version: i32 = row.get("version");
+
+    let kek = aes::generate_key();
+    let keydata = kek.as_bytes().to_vec();
+    let (encrypted_keydata, nonce) = aes::encrypt(&master_key_array, &keydata)?;
+
+    transaction
+        .execute(
+            r#"
+        UPDATE keks
+        SET is_deprecated = true, deprecated_at = NOW()
+        WHERE is_deprecated = false
+        "#,
+            &[],
+        )
+        .await?;
+
+    transaction
+        .execute(
+            r#"
+        INSERT INTO keks (version, encrypted_keydata, nonce, is_active, is_deprecated, created_at)
+        VALUES ($1, $2, $3, true, false, NOW())
+        "#,
+            &[&version, &encrypted_keydata, &nonce.to_vec()],
+        )
+        .await?;
+
+    transaction.commit().await?;
+    kek_cache.insert(version, keydata).await;
+
+    Ok(version)
+}
+
+/// Converts a master key slice into the fixed-size AES key.
+fn master_key_array(master_key: &[u8]) -> Result<[u8; 32]> {
+    master_key
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid master key size".to_string()))
+}
+
+/// Checks that the configured master key can decrypt every stored KEK.
+///
+/// Run at startup so that a changed `MASTER_KEY` is reported as such instead
+/// of surfacing later as opaque "Decryption failed" errors on every request.
+///
+/// # Returns
+///
+/// The number of KEKs checked, or an `Encryption` error naming the versions
+/// the master key could not decrypt.
+pub async fn verify_master_key(pool: &Pool, master_key: &[u8]) -> Result<usize> {
+    let master_key_array = master_key_array(master_key)?;
+
+    let client = pool.get().await?;
+    let stmt = client
+        .prepare("SELECT version, encrypted_keydata, nonce FROM keks ORDER BY version")
+        .await?;
+    let rows = client.query(&stmt, &[]).await?;
+
+    let mut failed_versions = Vec::new();
+    for row in &rows {
+        let version: i32 = row.get("version");
+        let encrypted_keydata: Vec<u8> = row.get("encrypted_keydata");
+        let nonce: Vec<u8> = row.get("nonce");
+
+        let decrypted = nonce
+            .as_slice()
+            .try_into()
+            .ok()
+            .and_then(|nonce: [u8; 12]| aes::decrypt(&master_key_array, &encrypted_keydata, &nonce).ok());
+
+        match decrypted {
+            Some(mut keydata) => keydata.zeroize(),
+            None => failed_versions.push(version),
+        }
+    }
+
+    if !failed_versions.is_empty() {
+        return Err(AppError::Encryption(format!(
+            "MASTER_KEY does not match the stored KEKs (versions {:?} failed to decrypt). \
+             If the master key was rotated, restore the previous MASTER_KEY or run \
+             `rocket rewrap-keks` with OLD_MASTER_KEY set to re-encrypt the KEKs under the new key",
+            failed_versions
+        )));
+    }
+
+    Ok(rows.len())
+}
+
+/// Re-encrypts every KEK from `old_master_key` to `new_master_key`.
+///
+/// Runs in a single transaction: either every KEK is rewrapped or none is.
+/// KEKs that already decrypt with the new key are left untouched, so an
+/// interrupted or repeated run is safe. The KEK cache is cleared once the
+/// transaction commits.
+///
+/// # Returns
+///
+/// The number of KEKs rewrapped.
+pub async fn rewrap_keks(
+    pool: &Pool,
+    old_master_key: &[u8],
+    new_master_key: &[u8],
+    kek_cache: &KekCache,
+) -> Result<usize> {
+    let old_key = master_key_array(old_master_key)?;
+    let new_key = master_key_array(new_master_key)?;
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    let rows = transaction
+        .query(
+            "SELECT version, encrypted_keydata, nonce FROM keks ORDER BY version FOR UPDATE",
+            &[],
+        )
+        .await?;
+
+    let update_stmt = transaction
+        .prepare("UPDATE keks SET encrypted_keydata = $2, nonce = $3 WHERE version = $1")
+        .await?;
+
+    let mut rewrapped = 0usize;
+    for row in &rows {
+        let version: i32 = row.get("version");
+        let encrypted_keydata: Vec<u8> = row.get("encrypted_keydata");
+        let nonce: [u8; 12] = row
+            .get::<_, Vec<u8>>("nonce")
+            .as_slice()
+            .try_into()
+            .map_err(|_| AppError::Encryption(format!("KEK version {} has an invalid nonce", version)))?;
+
+        if aes::decrypt(&new_key, &encrypted_keydata, &nonce).is_ok() {
+            tracing::info!("KEK version {} already uses the new master key, skipping", version);
+            continue;
+        }
+
+        let mut keydata = aes::decrypt(&old_key, &encrypted_keydata, &nonce).map_err(|_| {
+            AppError::Encryption(format!(
+                "KEK version {} cannot be decrypted with OLD_MASTER_KEY",
+                version
+            ))
+        })?;
+
+        let encrypted = aes::encrypt(&new_key, &keydata);
+        keydata.zeroize();
+        let (new_encrypted_keydata, new_nonce) = encrypted?;
+
+        transaction
+            .execute(&update_stmt, &[&version, &new_encrypted_keydata, &new_nonce.to_vec()])
+            .await?;
+        rewrapped += 1;
+    }
+
+    transaction.commit().await?;
+    kek_cache.clear().await;
+
+    Ok(rewrapped)
+}
@@ -2,8 +2,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use deadpool::managed::CreatePoolError;
-use deadpool_postgres::ConfigError;
+use deadpool_postgres::CreatePoolError;
 use thiserror::Error;
 
 /// The application's error type.
@@ -15,11 +14,11 @@ pub enum AppError {
 
     /// A deadpool-postgres pool error.
     #[error("Database pool error: {0}")]
-    Pool(#[from] deadpool_postgres::PoolError),
+    Pool(deadpool_postgres::PoolError),
 
     /// A deadpool-postgres build error.
     #[error("Database pool build error: {0}")]
-    PoolBuild(#[from] CreatePoolError<ConfigError>),
+    PoolBuild(#[from] CreatePoolError),
 
     /// A Redis error.
     #[error("Redis error: {0}")]
@@ -57,9 +56,43 @@ pub enum AppError {
     #[error("Internal server error: {0}")]
     Internal(String),
 
+    /// A request body larger than the endpoint accepts.
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     /// A rate limit exceeded error.
     #[error("Rate limit exceeded: {0}")]
     RateLimitExceeded(String),
+
+    /// A temporary overload; clients should retry after the given seconds.
+    #[error("Service unavailable: {message}")]
+    ServiceUnavailable { message: String, retry_after_secs: u64 },
+
+    /// The user's storage quota cannot hold the requested bytes.
+    #[error("Storage quota exceeded: {required} bytes required, {available} available")]
+    QuotaExceeded { required: i64, available: i64 },
+
+    /// A `Range` header that is malformed or lies outside a resource of `size` bytes.
+    #[error("Range not satisfiable for {size} bytes")]
+    RangeNotSatisfiable { size: u64 },
+}
+
+/// How long clients are told to wait before retrying a saturated service.
+const RETRY_AFTER_SECS: u64 = 2;
+
+impl From<deadpool_postgres::PoolError> for AppError {
+    fn from(e: deadpool_postgres::PoolError) -> Self {
+        match e {
+            deadpool_postgres::PoolError::Timeout(_) => {
+                crate::metrics::record_db_pool_exhausted();
+                AppError::ServiceUnavailable {
+                    message: "Database is saturated, please retry shortly".to_string(),
+                    retry_after_secs: RETRY_AFTER_SECS,
+                }
+            }
+            e => AppError::Pool(e),
+        }
+    }
 }
 
 /// A `Result` type that uses `AppError` as the error type.
@@ -67,6 +100,10 @@ pub type Result<T> = std::result::Result<T, AppError>;
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let mut retry_after = None;
+        let mut quota = None;
+        let mut content_range = None;
+
         let (status, message) = match self {
             AppError::Postgres(ref e) => {
                 tracing::error!("Postgres error: {}", e);
@@ -128,17 +165,61 @@ impl IntoResponse for AppError {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
 
+            AppError::PayloadTooLarge(ref msg) => {
+                tracing::debug!("Payload too large: {}", msg);
+                (StatusCode::PAYLOAD_TOO_LARGE, msg.clone())
+            }
+
             AppError::RateLimitExceeded(ref msg) => {
                 tracing::warn!("Rate limit exceeded: {}", msg);
                 (StatusCode::TOO_MANY_REQUESTS, msg.clone())
             }
+
+            AppError::ServiceUnavailable { ref message, retry_after_secs } => {
+                tracing::warn!("Service unavailable: {}", message);
+                retry_after = Some(retry_after_secs);
+                (StatusCode::SERVICE_UNAVAILABLE, message.clone())
+            }
+
+            AppError::QuotaExceeded { required, available } => {
+                tracing::debug!("Quota exceeded: {} required, {} available", required, available);
+                quota = Some((required, available));
+                (StatusCode::INSUFFICIENT_STORAGE, "Storage quota exceeded".to_string())
+            }
+
+            AppError::RangeNotSatisfiable { size } => {
+                tracing::debug!("Range not satisfiable for {} bytes", size);
+                content_range = Some(format!("bytes */{}", size));
+                (StatusCode::RANGE_NOT_SATISFIABLE, "Range not satisfiable".to_string())
+            }
         };
 
-        let body = sonic_rs::to_string(&sonic_rs::json!({
-            "error": message
-        }))
+        crate::metrics::record_error(status);
+
+        let body = match quota {
+            Some((required, available)) => sonic_rs::to_string(&sonic_rs::json!({
+                "error": message,
+                "code": "quota_exceeded",
+                "required_bytes": required,
+                "available_bytes": available.max(0)
+            })),
+            None => sonic_rs::to_string(&sonic_rs::json!({
+                "error": message
+            })),
+        }
         .unwrap_or_else(|_| r#"{"error":"Internal server error"}"#.to_string());
 
-        (status, body).into_response()
+        let mut response = crate::response::json_response(status, body);
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(axum::http::header::RETRY_AFTER, secs.into());
+        }
+        if let Some(value) = content_range.and_then(|value| value.parse().ok()) {
+            response
+                .headers_mut()
+                .insert(axum::http::header::CONTENT_RANGE, value);
+        }
+        response
     }
 }
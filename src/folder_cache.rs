@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::models::{file::File, folder::Folder};
+
+/// The cached contents of one folder listing.
+pub type FolderListing = (Vec<Folder>, Vec<File>);
+
+type ListingKey = (Uuid, Option<Uuid>);
+
+struct CachedListing {
+    /// The user's collection version when the listing was read.
+    version: u64,
+    inserted_at: Instant,
+    last_used: u64,
+    listing: FolderListing,
+}
+
+struct CacheInner {
+    entries: HashMap<ListingKey, CachedListing>,
+    /// Per-user collection versions. Bumping a user's version invalidates all of
+    /// their cached listings at once without walking the entries.
+    versions: HashMap<Uuid, u64>,
+    tick: u64,
+}
+
+/// A bounded, short-TTL, in-process LRU cache for folder listings.
+///
+/// A capacity of zero disables the cache entirely.
+#[derive(Clone)]
+pub struct FolderListingCache {
+    inner: Arc<Mutex<CacheInner>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl FolderListingCache {
+    /// Creates a new `FolderListingCache`.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                versions: HashMap::new(),
+                tick: 0,
+            })),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// Returns whether the cache is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the current collection version for a user.
+    ///
+    /// Read this before querying the database and pass it to [`Self::insert`], so
+    /// a listing that raced with a write is stored as already stale.
+    pub async fn version(&self, user_id: Uuid) -> u64 {
+        let inner = self.inner.lock().await;
+        inner.versions.get(&user_id).copied().unwrap_or(0)
+    }
+
+    /// Gets a cached listing if it is still current and within its TTL.
+    pub async fn get(&self, user_id: Uuid, folder_id: Option<Uuid>) -> Option<FolderListing> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let mut inner = self.inner.lock().await;
+        let current_version = inner.versions.get(&user_id).copied().unwrap_or(0);
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let key = (user_id, folder_id);
+        let entry = inner.entries.get_mut(&key)?;
+        if entry.version == current_version && entry.inserted_at.elapsed() < self.ttl {
+            entry.last_used = tick;
+            return Some(entry.listing.clone());
+        }
+
+        inner.entries.remove(&key);
+        None
+    }
+
+    /// Inserts a listing read under the given collection version, evicting the
+    /// least recently used entry if the cache is full.
+    pub async fn insert(
+        &self,
+        user_id: Uuid,
+        folder_id: Option<Uuid>,
+        version: u64,
+        listing: FolderListing,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().await;
+        let key = (user_id, folder_id);
+
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+
+            if inner.entries.len() >= self.capacity
+                && let Some(lru_key) = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| *key)
+            {
+                inner.entries.remove(&lru_key);
+            }
+        }
+
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.entries.insert(
+            key,
+            CachedListing {
+                version,
+                inserted_at: Instant::now(),
+                last_used: tick,
+                listing,
+            },
+        );
+    }
+
+    /// Invalidates every cached listing of a user by bumping their version.
+    ///
+    /// Call this after any change to the user's files or folders.
+    pub async fn invalidate_user(&self, user_id: Uuid) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().await;
+        *inner.versions.entry(user_id).or_insert(0) += 1;
+    }
+}
@@ -0,0 +1,680 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use std::{net::SocketAddr, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    crypto,
+    error::{AppError, Result},
+    models::{
+        audit::AuditEvent,
+        file::ChunkInfo,
+        pagination::{default_limit, PageQuery, Pagination},
+        session::Session,
+    },
+    repositories,
+    response::json_response,
+    services::{
+        audit as audit_service, chunk_gc, kek_rotation, sessions as session_service,
+        shares as share_service,
+    },
+    state::AppState,
+};
+
+/// The query parameters for force-logging-out a user.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForceLogoutQuery {
+    /// Also disable the account so the user cannot log back in.
+    #[serde(default)]
+    pub deactivate: bool,
+}
+
+/// The query parameters for listing audit log entries.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Only entries of this user.
+    pub user_id: Option<Uuid>,
+    /// Only entries of this action, e.g. `login`.
+    pub action: Option<String>,
+    /// The maximum number of entries to return (1 to 1000, default 50).
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// The number of entries to skip.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// The request payload for setting a user's storage quota.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct SetQuotaRequest {
+    pub storage_quota_bytes: i64,
+}
+
+/// The request payload for enabling or disabling a user's account.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct SetActiveRequest {
+    pub is_active: bool,
+}
+
+/// Issues a time-limited session for another user, for support workflows.
+///
+/// The admin never learns the user's password, so the issued session carries
+/// no DEK: it can list and manage metadata (files, folders, quota) but cannot
+/// upload or download file contents. Impersonated sessions are also barred from
+/// changing the password and from the admin routes themselves. The session
+/// token and a CSRF token are returned in the body instead of being set as
+/// cookies, so the admin's own session is left untouched.
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/impersonate",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "The user to impersonate")),
+    responses(
+        (status = 201, description = "Impersonation session issued"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found")
+    )
+)]
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    tracing::warn!("🕵️ Admin {} impersonating user {}", admin_id, user_id);
+
+    let client = state.db.get().await?;
+    let target = repositories::user::find_by_id(&client, &user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !target.is_active {
+        return Err(AppError::Validation(
+            "Cannot impersonate an inactive user".to_string(),
+        ));
+    }
+
+    let expires_at = Utc::now() + chrono::Duration::minutes(state.config.impersonation_session_minutes);
+
+    let impersonated = Session {
+        user_id: target.id,
+        dek: Vec::new(),
+        dek_kek_version: None,
+        created_at: Utc::now(),
+        expires_at,
+        impersonated_by: Some(admin_id),
+        csrf_token: None,
+        user_agent: None,
+    };
+
+    let expiration_seconds: u64 = (state.config.impersonation_session_minutes * 60) as u64;
+    let (session_id, csrf_token) =
+        session_service::create_session(&state, impersonated, expiration_seconds).await?;
+
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("user"),
+            resource_id: Some(target.id),
+            ..audit_service::session_event(&state, "admin_impersonate", &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    tracing::info!(
+        "✅ Impersonation session issued for user {} by admin {} (expires {})",
+        target.id,
+        admin_id,
+        expires_at
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "session_id": session_id.to_string(),
+        "csrf_token": csrf_token,
+        "user_id": target.id.to_string(),
+        "expires_at": expires_at.to_rfc3339(),
+        "impersonated": true,
+        "can_decrypt_files": false,
+        "message": "Impersonation session issued. File contents cannot be accessed with this session."
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::CREATED, response))
+}
+
+/// Reports how a file is laid out in storage, without decrypting anything.
+///
+/// Intended for turning "decryption failed" reports into a diagnosis: it shows
+/// whether `chunks_metadata` decodes, which chunk files are missing or have an
+/// unexpected size on disk, and whether the KEK that wraps the file's DEK is
+/// still present and active. Expected chunk sizes assume the chunk size the
+/// file was uploaded with plus the AES-GCM tag.
+#[utoipa::path(
+    get,
+    path = "/api/admin/files/{file_id}/diagnostics",
+    tag = "admin",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    responses(
+        (status = 200, description = "Storage-layout diagnostics for the file"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "File not found")
+    )
+)]
+pub async fn file_diagnostics(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Response> {
+    tracing::info!("🩺 Admin {} requested diagnostics for file {}", session.user_id, file_id);
+
+    let client = state.db.get().await?;
+    let file = repositories::file::find_by_id_any_owner(&client, file_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let kek = crypto::kek::kek_status(&state.db, file.dek_version).await?;
+
+    let decoded = match file.chunks_metadata.as_deref() {
+        Some(raw) => ChunkInfo::decode_list(raw).map(Some),
+        None => Ok(None),
+    };
+    let (chunks, metadata_error) = match decoded {
+        Ok(chunks) => (chunks.unwrap_or_default(), None),
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+
+    let upload_dir = &state.config.upload_dir;
+    let tag_size = crypto::aes::TAG_SIZE as i64;
+    let chunk_size = ChunkInfo::layout_chunk_size(&chunks).unwrap_or(1) as i64;
+    let mut chunks_present = 0usize;
+    let mut chunk_reports = Vec::with_capacity(chunks.len());
+
+    for chunk in &chunks {
+        let plaintext_offset = chunk.index as i64 * chunk_size;
+        // A compressed chunk's size on disk depends on its contents.
+        let size_expected = (!chunk.compressed)
+            .then(|| (file.file_size - plaintext_offset).clamp(0, chunk_size) + tag_size);
+
+        let (filename, size_on_disk) = match chunk.get_filename() {
+            Ok(filename) => {
+                let size = tokio::fs::metadata(upload_dir.join(&filename))
+                    .await
+                    .ok()
+                    .map(|m| m.len() as i64);
+                (Some(filename), size)
+            }
+            Err(_) => (None, None),
+        };
+
+        if size_on_disk.is_some() {
+            chunks_present += 1;
+        }
+        let size_matches = match size_expected {
+            Some(expected) => size_on_disk == Some(expected),
+            None => size_on_disk.is_some(),
+        };
+
+        chunk_reports.push(sonic_rs::json!({
+            "index": chunk.index,
+            "filename": filename,
+            "present": size_on_disk.is_some(),
+            "size_on_disk": size_on_disk,
+            "size_expected": size_expected,
+            "size_recorded": chunk.size_encrypted,
+            "compressed": chunk.compressed,
+            "size_matches": size_matches
+        }));
+    }
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "file_id": file.id.to_string(),
+        "user_id": file.user_id.to_string(),
+        "file_size": file.file_size,
+        "upload_status": file.upload_status,
+        "is_deleted": file.is_deleted,
+        "total_chunks": file.total_chunks,
+        "chunks_metadata_present": file.chunks_metadata.is_some(),
+        "chunks_metadata_decodes": file.chunks_metadata.is_some() && metadata_error.is_none(),
+        "chunks_metadata_error": metadata_error,
+        "chunks_listed": chunks.len(),
+        "chunks_present": chunks_present,
+        "chunks": chunk_reports,
+        "dek_version": file.dek_version,
+        "kek_exists": kek.is_some(),
+        "kek_active": kek.is_some_and(|k| k.is_active),
+        "kek_deprecated": kek.is_some_and(|k| k.is_deprecated),
+        "kek_cached": state.kek_cache.get(file.dek_version).await.is_some()
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Revokes every session of a user, for incident response.
+///
+/// All sessions indexed under `user_sessions:{user_id}` are deleted together
+/// with their CSRF tokens, and every share link of the user is revoked,
+/// cutting access immediately without touching any data. With `deactivate=true` the account is also disabled, so a stolen
+/// password cannot be used to log back in until an admin re-enables it.
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/logout-all",
+    tag = "admin",
+    params(
+        ("user_id" = Uuid, Path, description = "The user to log out"),
+        ForceLogoutQuery
+    ),
+    responses(
+        (status = 200, description = "All sessions of the user revoked"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found")
+    )
+)]
+pub async fn force_logout_user(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<ForceLogoutQuery>,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    tracing::warn!(
+        "🚪 Admin {} forcing logout of user {} (deactivate: {})",
+        admin_id,
+        user_id,
+        query.deactivate
+    );
+
+    let client = state.db.get().await?;
+    repositories::user::find_by_id(&client, &user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if query.deactivate {
+        repositories::user::set_user_active(&client, &user_id, false, &state.stmt_cache).await?;
+    }
+
+    let revoked = session_service::revoke_all_sessions(&state, &user_id, None).await?;
+    share_service::revoke_all_shares(&state, &user_id).await?;
+
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("user"),
+            resource_id: Some(user_id),
+            ..audit_service::session_event(&state, if query.deactivate { "admin_logout_all_deactivate" } else { "admin_logout_all" }, &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "user_id": user_id.to_string(),
+        "sessions_revoked": revoked,
+        "deactivated": query.deactivate,
+        "message": "All sessions of the user have been revoked"
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Rotates the KEK and rewraps every file DEK, TOTP secret and session DEK
+/// under the new version.
+///
+/// A new KEK version is generated and the previous ones are deprecated; new
+/// uploads use the new version immediately. Existing keys are rewrapped by a
+/// background task whose progress is reported by
+/// `GET /api/admin/kek/rotation`. Only one rotation runs at a time.
+#[utoipa::path(
+    post,
+    path = "/api/admin/kek/rotate",
+    tag = "admin",
+    responses(
+        (status = 202, description = "KEK rotated, keys are being rewrapped"),
+        (status = 400, description = "A rotation is already in progress"),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn rotate_kek(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    tracing::warn!("🔑 Admin {} rotating the KEK", admin_id);
+
+    let version = kek_rotation::start_rotation(&state).await?;
+
+    let client = state.db.get().await?;
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("kek"),
+            ..audit_service::session_event(&state, "admin_kek_rotate", &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "version": version,
+        "message": "KEK rotated, keys are being rewrapped in the background"
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::ACCEPTED, response))
+}
+
+/// Reports the progress of the current or last KEK rotation.
+#[utoipa::path(
+    get,
+    path = "/api/admin/kek/rotation",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Rotation progress", body = kek_rotation::RotationStatus),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No rotation has been run")
+    )
+)]
+pub async fn kek_rotation_status(State(state): State<AppState>) -> Result<Response> {
+    let status = kek_rotation::rotation_status(&state)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let response = sonic_rs::to_string(&status)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize rotation status: {}", e)))?;
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Removes chunk files on disk that no upload session or file references.
+///
+/// Only chunks untouched for at least `UPLOAD_EXPIRATION_SECS` are considered,
+/// so chunks of uploads in progress are never collected. The same collection
+/// runs daily in the background.
+#[utoipa::path(
+    post,
+    path = "/api/admin/gc/chunks",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Orphaned chunks collected", body = chunk_gc::ChunkGcReport),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn collect_orphaned_chunks(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    tracing::info!("🧹 Admin {} collecting orphaned chunks", admin_id);
+
+    let min_age = Duration::from_secs(state.config.upload_expiration_secs);
+    let report = chunk_gc::collect_orphaned_chunks(&state, min_age).await?;
+
+    let client = state.db.get().await?;
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("chunks"),
+            ..audit_service::session_event(&state, "admin_gc_chunks", &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    let response = sonic_rs::to_string(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize chunk GC report: {}", e)))?;
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Lists every user with their roles, status and storage usage.
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    tag = "admin",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Page of users, oldest first"),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_users(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Response> {
+    let params = params.validate()?;
+
+    let client = state.db.get().await?;
+    let users =
+        repositories::user::list_users(&client, params.limit, params.offset, &state.stmt_cache).await?;
+    let total = repositories::user::count_users(&client, &state.stmt_cache).await?;
+    let pagination = Pagination::new(params, users.len(), total);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "users": users.iter().map(|u| sonic_rs::json!({
+            "id": u.id.to_string(),
+            "name": u.name,
+            "username": u.username,
+            "email": u.email,
+            "roles": u.roles,
+            "is_active": u.is_active,
+            "two_factor_enabled": u.two_factor_enabled,
+            "storage_quota_bytes": u.storage_quota_bytes,
+            "storage_used_bytes": u.storage_used_bytes,
+            "created_at": u.created_at.to_rfc3339()
+        })).collect::<Vec<_>>(),
+        "count": users.len(),
+        "pagination": pagination
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Sets a user's storage quota.
+///
+/// A quota below what the user already stores is accepted: their files are
+/// kept, but uploads fail until they free enough space.
+#[utoipa::path(
+    patch,
+    path = "/api/admin/users/{user_id}/quota",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "The user whose quota to set")),
+    request_body = SetQuotaRequest,
+    responses(
+        (status = 200, description = "Quota updated"),
+        (status = 400, description = "Negative quota"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found")
+    )
+)]
+pub async fn set_user_quota(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<SetQuotaRequest>,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    if payload.storage_quota_bytes < 0 {
+        return Err(AppError::Validation("storage_quota_bytes must not be negative".to_string()));
+    }
+
+    tracing::info!(
+        "📦 Admin {} setting quota of user {} to {} bytes",
+        admin_id,
+        user_id,
+        payload.storage_quota_bytes
+    );
+
+    let client = state.db.get().await?;
+    if !repositories::user::set_storage_quota(&client, &user_id, payload.storage_quota_bytes, &state.stmt_cache)
+        .await?
+    {
+        return Err(AppError::NotFound);
+    }
+
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("user"),
+            resource_id: Some(user_id),
+            ..audit_service::session_event(&state, "admin_set_quota", &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "user_id": user_id.to_string(),
+        "storage_quota_bytes": payload.storage_quota_bytes
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Enables or disables a user's account.
+///
+/// A disabled user cannot log in, and every session and share link they hold
+/// is revoked.
+/// Admins cannot disable their own account, so the last admin cannot lock
+/// everyone out by accident.
+#[utoipa::path(
+    patch,
+    path = "/api/admin/users/{user_id}/active",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "The user to enable or disable")),
+    request_body = SetActiveRequest,
+    responses(
+        (status = 200, description = "Account status updated"),
+        (status = 400, description = "Tried to disable the caller's own account"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found")
+    )
+)]
+pub async fn set_user_active(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<SetActiveRequest>,
+) -> Result<Response> {
+    let admin_id = session.user_id;
+
+    if user_id == admin_id && !payload.is_active {
+        return Err(AppError::Validation("You cannot disable your own account".to_string()));
+    }
+
+    tracing::warn!(
+        "🔒 Admin {} setting user {} active: {}",
+        admin_id,
+        user_id,
+        payload.is_active
+    );
+
+    let client = state.db.get().await?;
+    if !repositories::user::set_user_active(&client, &user_id, payload.is_active, &state.stmt_cache).await? {
+        return Err(AppError::NotFound);
+    }
+
+    let revoked = if payload.is_active {
+        0
+    } else {
+        share_service::revoke_all_shares(&state, &user_id).await?;
+        session_service::revoke_all_sessions(&state, &user_id, None).await?
+    };
+
+    repositories::audit::insert_event(
+        &client,
+        &AuditEvent {
+            resource_type: Some("user"),
+            resource_id: Some(user_id),
+            ..audit_service::session_event(&state, if payload.is_active { "admin_activate_user" } else { "admin_deactivate_user" }, &session, &headers, addr)
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "user_id": user_id.to_string(),
+        "is_active": payload.is_active,
+        "sessions_revoked": revoked
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Lists audit log entries, newest first.
+///
+/// Entries are written in the background as actions happen, so an action
+/// may take a moment to appear. The table is append-only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Page of audit log entries"),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_audit_logs(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response> {
+    let page = PageQuery {
+        limit: query.limit,
+        offset: query.offset,
+    }
+    .validate()?;
+    let action = query.action.as_deref().filter(|a| !a.is_empty());
+
+    let client = state.db.get().await?;
+    let entries = repositories::audit::list_audit_logs(
+        &client,
+        query.user_id,
+        action,
+        page.limit,
+        page.offset,
+        &state.stmt_cache,
+    )
+    .await?;
+    let total = repositories::audit::count_audit_logs(&client, query.user_id, action, &state.stmt_cache).await?;
+    let pagination = Pagination::new(page, entries.len(), total);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "entries": entries,
+        "count": entries.len(),
+        "pagination": pagination
+    }))
+    .map_err(|e| AppError::Internal(format!("Failed to serialize audit log: {}", e)))?;
+
+    Ok(json_response(StatusCode::OK, response))
+}
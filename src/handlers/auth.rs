@@ -1,6 +1,6 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Extension, Json,
 };
@@ -8,54 +8,208 @@ use tower_cookies::{Cookies, Cookie};
 use tower_cookies::cookie::time::Duration;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use std::net::SocketAddr;
+use utoipa::ToSchema;
 
 use crate::{
     error::{AppError, Result},
-    models::session::Session,
+    middleware_layer::auth::SessionToken,
+    models::{audit::AuditEvent, session::Session},
+    repositories,
+    response::json_response,
+    services::audit as audit_service,
     services::auth as auth_service,
+    services::password_reset as password_reset_service,
+    services::sessions as session_service,
+    services::shares as share_service,
+    services::two_factor as two_factor_service,
     state::AppState,
     validation::auth::*,
 };
 
 use redis::AsyncCommands;
 
+/// Stands in for secrets in the `Debug` output of request payloads, so a
+/// logged payload never carries a password or code.
+const REDACTED: &str = "<redacted>";
+
+/// Serializes a response body with `sonic_rs` and sends it as JSON.
+fn auth_json_response<T: Serialize>(status: StatusCode, body: &T) -> Result<Response> {
+    let body = sonic_rs::to_string(body)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok(json_response(status, body))
+}
+
 /// The request payload for user registration.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub name: String,
     pub username: String,
     pub password: String,
+    /// An email address not registered to any other user.
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+impl std::fmt::Debug for RegisterRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisterRequest")
+            .field("name", &self.name)
+            .field("username", &self.username)
+            .field("password", &REDACTED)
+            .field("email", &self.email)
+            .finish()
+    }
 }
 
 /// The request payload for user login.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// A current TOTP code or an unused recovery code; required when
+    /// two-factor authentication is enabled.
+    #[serde(default)]
+    pub totp_code: Option<String>,
+}
+
+impl std::fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("username", &self.username)
+            .field("password", &REDACTED)
+            .field("totp_code", &self.totp_code.as_ref().map(|_| REDACTED))
+            .finish()
+    }
 }
 
 /// The request payload for changing a user's password.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, ToSchema)]
 pub struct ChangePasswordRequest {
     pub old_password: String,
     pub new_password: String,
 }
 
+impl std::fmt::Debug for ChangePasswordRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangePasswordRequest")
+            .field("old_password", &REDACTED)
+            .field("new_password", &REDACTED)
+            .finish()
+    }
+}
+
+/// The request payload for requesting a password reset token.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+/// The request payload for resetting a password with an emailed token.
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// The token from the password reset email.
+    pub token: String,
+    pub new_password: String,
+}
+
+impl std::fmt::Debug for ResetPasswordRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResetPasswordRequest")
+            .field("token", &REDACTED)
+            .field("new_password", &REDACTED)
+            .finish()
+    }
+}
+
+/// The response payload for starting two-factor enrollment.
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorEnrollResponse {
+    /// The base32 TOTP secret, for entering into an authenticator app by hand.
+    pub secret: String,
+    /// The `otpauth://` URI carrying the secret, to render as a QR code.
+    pub otpauth_uri: String,
+}
+
+/// The request payload for confirming two-factor enrollment.
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFactorVerifyRequest {
+    /// A current code from the authenticator app.
+    pub code: String,
+}
+
+impl std::fmt::Debug for TwoFactorVerifyRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TwoFactorVerifyRequest")
+            .field("code", &REDACTED)
+            .finish()
+    }
+}
+
+/// The response payload for confirming two-factor enrollment.
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorVerifyResponse {
+    pub success: bool,
+    /// Single-use codes that stand in for a TOTP code at login. They are not
+    /// stored in readable form and cannot be shown again.
+    pub recovery_codes: Vec<String>,
+}
+
 /// The response payload for authentication-related requests.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AuthResponse {
     pub success: bool,
     pub message: String,
 }
 
+/// One of the caller's sessions, as listed by `GET /api/auth/sessions`.
+#[derive(Serialize, ToSchema)]
+pub struct SessionInfo {
+    /// The first characters of the session ID; pass it to
+    /// `DELETE /api/auth/sessions/{session_id}` to revoke the session.
+    pub id: String,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = DateTime)]
+    pub expires_at: DateTime<Utc>,
+    /// The `User-Agent` of the client that opened the session, if it sent one.
+    pub user_agent: Option<String>,
+    /// Whether this is the session making the request.
+    pub current: bool,
+    /// Whether an admin opened the session on the user's behalf.
+    pub impersonated: bool,
+}
+
+/// The response payload for listing the caller's sessions.
+#[derive(Serialize, ToSchema)]
+pub struct SessionListResponse {
+    pub sessions: Vec<SessionInfo>,
+}
+
+/// The longest `User-Agent` kept with a session, in characters.
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Returns the request's `User-Agent`, truncated for storage in the session.
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect())
+}
+
 /// Creates a secure cookie with the given name, value, and max age.
-fn create_secure_cookie(name: String, value: String, max_age_days: i64) -> Cookie<'static> {
+///
+/// The cookie is marked `Secure` when `is_production` is set.
+fn create_secure_cookie(
+    name: String,
+    value: String,
+    max_age_days: i64,
+    is_production: bool,
+) -> Cookie<'static> {
     let mut cookie = Cookie::new(name.clone(), value);
 
-    let is_production = std::env::var("APP_ENV")
-        .unwrap_or_else(|_| "development".to_string()) == "production";
-
     if name != "csrf_token" {
         cookie.set_http_only(true);
     }
@@ -72,65 +226,94 @@ fn create_secure_cookie(name: String, value: String, max_age_days: i64) -> Cooki
     cookie
 }
 
+/// Expires the session and CSRF cookies in the browser.
+fn clear_auth_cookies(cookies: &Cookies) {
+    for name in ["session_id", "csrf_token"] {
+        let mut cookie = Cookie::new(name, "");
+        cookie.set_max_age(Duration::seconds(0));
+        cookie.set_path("/");
+        cookies.remove(cookie);
+    }
+}
+
 /// Handles user registration.
+#[utoipa::path(
+    post,
+    path = "/api/auth/register",
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "User registered and logged in", body = AuthResponse),
+        (status = 400, description = "Invalid registration data, or the username or email is already registered")
+    )
+)]
 pub async fn register(
     State(state): State<AppState>,
     cookies: Cookies,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(payload): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse> {
-    tracing::info!("📝 Register attempt - Payload: {:?}", payload);
-    validate_username(&payload.username)?;
-    validate_password(&payload.password)?;
+    let username = normalize_username(&payload.username);
+    tracing::info!("📝 Register attempt for {:?}", username);
+    validate_username(&username)?;
+    validate_password(&payload.password, &state.config.password_policy())?;
     
     if payload.name.trim().is_empty() {
         return Err(AppError::Validation("Name cannot be empty".to_string()));
     }
 
-    tracing::info!("✅ Validations passed for: {}", payload.username);
+    let email = payload
+        .email
+        .as_deref()
+        .filter(|email| !email.trim().is_empty())
+        .map(normalize_email)
+        .transpose()?;
+
+    tracing::info!("✅ Validations passed for: {}", username);
     
     let user = auth_service::create_user(
         &state,
         payload.name.clone(),
-        payload.username.clone(),
+        username.clone(),
+        email,
         payload.password.clone(),
-    ).await?;
+    )
+    .await
+    .inspect_err(|e| {
+        crate::metrics::record_registration(false);
+        audit_service::record(&state, AuditEvent {
+            username: Some(username.clone()),
+            success: false,
+            error_message: Some(e.to_string()),
+            ..audit_service::event(&state, "register", &headers, addr)
+        });
+    })?;
 
     tracing::info!("✅ User registered: {}", user.id);
+    audit_service::record(&state, AuditEvent {
+        user_id: Some(user.id),
+        username: Some(user.username.clone()),
+        ..audit_service::event(&state, "register", &headers, addr)
+    });
 
-    let session_id = Uuid::new_v4();
-    tracing::debug!("🔑 Generated session_id: {}", session_id);
-
-    let enc_dek = user
-        .encrypted_dek
-        .clone()
-        .ok_or_else(|| AppError::Encryption("Missing encrypted DEK".to_string()))?;
-    let dek_salt = user
-        .dek_salt
-        .clone()
-        .ok_or_else(|| AppError::Encryption("Missing DEK salt".to_string()))?;
-    let dek_secure = crate::crypto::dek::decrypt_user_dek(&enc_dek, &dek_salt, &payload.password)?;
-    let session_dek: Vec<u8> = dek_secure.as_bytes().to_vec();
+    let dek_secure = auth_service::unlock_user_dek(&state, &user, payload.password.clone()).await?;
+    let (session_dek, dek_kek_version) = session_service::seal_session_dek(&state, &dek_secure).await?;
 
     let session = Session {
         user_id: user.id,
         dek: session_dek,
+        dek_kek_version: Some(dek_kek_version),
         created_at: Utc::now(),
         expires_at: Utc::now() + chrono::Duration::days(state.config.session_duration_days),
+        impersonated_by: None,
+        csrf_token: None,
+        user_agent: user_agent(&headers),
     };
 
-    let session_json = sonic_rs::to_string(&session)
-        .map_err(|e| AppError::Internal(format!("Session serialization failed: {}", e)))?;
-
     let expiration_seconds: u64 = (state.config.session_duration_days * 86400) as u64;
-    let _: () = state
-        .redis
-        .clone()
-        .set_ex(
-            format!("session:{}", session_id),
-            &session_json,
-            expiration_seconds,
-        )
-        .await?;
+    let (session_id, csrf_token) =
+        session_service::create_session(&state, session, expiration_seconds).await?;
 
     tracing::info!("✅ Session saved to Redis: session:{}", session_id);
 
@@ -138,91 +321,91 @@ pub async fn register(
         "session_id".to_string(),
         session_id.to_string(),
         state.config.session_duration_days,
+        state.config.is_production(),
     );
     cookies.add(session_cookie);
     tracing::info!("✅ Session cookie added: session_id={}", session_id);
 
-    let csrf_token = crate::crypto::csrf::generate_csrf_token()?;
-    tracing::debug!("🔐 Generated CSRF token: {}", &csrf_token[..20.min(csrf_token.len())]);
-
-    let _: () = state
-        .redis
-        .clone()
-        .set_ex(
-            format!("csrf:{}", csrf_token),
-            "valid",
-            3600,
-        )
-        .await?;
-
     let csrf_cookie = create_secure_cookie(
         "csrf_token".to_string(),
         csrf_token,
-        1,
+        state.config.session_duration_days,
+        state.config.is_production(),
     );
     cookies.add(csrf_cookie);
     tracing::info!("✅ CSRF cookie added");
 
+    crate::metrics::record_registration(true);
+
     let response = AuthResponse {
         success: true,
         message: "Registration successful. Welcome!".to_string(),
     };
 
-    Ok((StatusCode::CREATED, Json(response)).into_response())
+    auth_json_response(StatusCode::CREATED, &response)
 }
 
 /// Handles user login.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in; session and CSRF cookies set", body = AuthResponse),
+        (status = 401, description = "Invalid username or password, a missing or invalid two-factor code, or too many failed attempts"),
+        (status = 413, description = "Request body larger than 4 KiB")
+    )
+)]
 pub async fn login(
     State(state): State<AppState>,
     cookies: Cookies,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Response> {
-    tracing::info!("🔐 Login attempt - Payload: {:?}", payload);
-    validate_username(&payload.username)?;
+    let username = normalize_username(&payload.username);
+    tracing::info!("🔐 Login attempt for {:?}", username);
+    validate_username(&username)?;
 
     let password_plain = payload.password.clone();
 
-    let user = auth_service::authenticate_user(
-        &state,
-        payload.username.clone(),
-        payload.password,
-    )
-    .await?;
+    let authenticated = async {
+        let user = auth_service::authenticate_user(&state, username.clone(), payload.password).await?;
+        if user.two_factor_enabled {
+            two_factor_service::verify_login_code(&state, &user, payload.totp_code.as_deref()).await?;
+        }
+        Ok(user)
+    }
+    .await;
 
-    let session_id = Uuid::new_v4();
-    tracing::debug!("🔑 Generated session_id: {}", session_id);
+    let user = authenticated.inspect_err(|e: &AppError| {
+        crate::metrics::record_login(false);
+        audit_service::record(&state, AuditEvent {
+            username: Some(username.clone()),
+            success: false,
+            error_message: Some(e.to_string()),
+            ..audit_service::event(&state, "login", &headers, addr)
+        });
+    })?;
 
-    let enc_dek = user
-        .encrypted_dek
-        .clone()
-        .ok_or_else(|| AppError::Encryption("Missing encrypted DEK".to_string()))?;
-    let dek_salt = user
-        .dek_salt
-        .clone()
-        .ok_or_else(|| AppError::Encryption("Missing DEK salt".to_string()))?;
-    let dek_secure = crate::crypto::dek::decrypt_user_dek(&enc_dek, &dek_salt, &password_plain)?;
-    let session_dek: Vec<u8> = dek_secure.as_bytes().to_vec();
+    let dek_secure = auth_service::unlock_user_dek(&state, &user, password_plain).await?;
+    let (session_dek, dek_kek_version) = session_service::seal_session_dek(&state, &dek_secure).await?;
 
     let session = Session {
         user_id: user.id,
         dek: session_dek,
+        dek_kek_version: Some(dek_kek_version),
         created_at: Utc::now(),
         expires_at: Utc::now() + chrono::Duration::days(state.config.session_duration_days),
+        impersonated_by: None,
+        csrf_token: None,
+        user_agent: user_agent(&headers),
     };
 
-    let session_json = sonic_rs::to_string(&session)
-        .map_err(|e| AppError::Internal(format!("Session serialization failed: {}", e)))?;
-
     let expiration_seconds: u64 = (state.config.session_duration_days * 86400) as u64;
-    let _: () = state
-        .redis
-        .clone()
-        .set_ex(
-            format!("session:{}", session_id),
-            &session_json,
-            expiration_seconds,
-        )
-        .await?;
+    let (session_id, csrf_token) =
+        session_service::create_session(&state, session, expiration_seconds).await?;
 
     tracing::info!("✅ Session saved to Redis: session:{}", session_id);
 
@@ -230,62 +413,59 @@ pub async fn login(
         "session_id".to_string(),
         session_id.to_string(),
         state.config.session_duration_days,
+        state.config.is_production(),
     );
     cookies.add(session_cookie);
 
     tracing::info!("✅ Session cookie added: session_id={}", session_id);
 
-    let csrf_token = crate::crypto::csrf::generate_csrf_token()?;
-    tracing::debug!("🔐 Generated CSRF token: {}", &csrf_token[..20.min(csrf_token.len())]);
-
-    let _: () = state
-        .redis
-        .clone()
-        .set_ex(
-            format!("csrf:{}", csrf_token),
-            "valid",
-            3600,
-        )
-        .await?;
-
-    tracing::info!("✅ CSRF token saved to Redis");
-
     let csrf_cookie = create_secure_cookie(
         "csrf_token".to_string(),
         csrf_token,
-        1,
+        state.config.session_duration_days,
+        state.config.is_production(),
     );
     cookies.add(csrf_cookie);
 
     tracing::info!("✅ CSRF cookie added");
     tracing::info!("✅ User logged in: {}", user.id);
 
+    crate::metrics::record_login(true);
+    audit_service::record(&state, AuditEvent {
+        user_id: Some(user.id),
+        username: Some(user.username.clone()),
+        ..audit_service::event(&state, "login", &headers, addr)
+    });
+
     let response = AuthResponse {
         success: true,
         message: "Login successful".to_string(),
     };
 
-    Ok((StatusCode::OK, Json(response)).into_response())
+    auth_json_response(StatusCode::OK, &response)
 }
 
 /// Handles user logout.
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Logged out", body = AuthResponse),
+        (status = 403, description = "Not authenticated")
+    )
+)]
 pub async fn logout(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
     cookies: Cookies,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<Response> {
     tracing::info!("👋 Logout for user: {}", session.user_id);
 
-    let session_id = cookies
-        .get("session_id")
-        .map(|c| c.value().to_string())
-        .ok_or_else(|| AppError::Unauthorized)?;
-
-    let _: () = state
-        .redis
-        .clone()
-        .del(format!("session:{}", session_id))
-        .await?;
+    session_service::revoke_session(&state, &session.user_id, &token.id).await?;
 
     tracing::info!("✅ Session deleted from Redis");
 
@@ -294,56 +474,385 @@ pub async fn logout(
         let _: () = state
             .redis
             .clone()
-            .del(format!("csrf:{}", csrf_token))
+            .del(session_service::csrf_key(&state, csrf_token))
             .await
             .unwrap_or(());
         tracing::info!("✅ CSRF token deleted from Redis");
     }
 
-    let mut session_cookie = Cookie::new("session_id", "");
-    session_cookie.set_max_age(Duration::seconds(0));
-    session_cookie.set_path("/");
-    cookies.remove(session_cookie);
-
-    let mut csrf_cookie = Cookie::new("csrf_token", "");
-    csrf_cookie.set_max_age(Duration::seconds(0));
-    csrf_cookie.set_path("/");
-    cookies.remove(csrf_cookie);
+    clear_auth_cookies(&cookies);
 
     tracing::info!("✅ User logged out: {}", session.user_id);
+    audit_service::record(&state, audit_service::session_event(&state, "logout", &session, &headers, addr));
 
     let response = AuthResponse {
         success: true,
         message: "Logout successful".to_string(),
     };
 
-    Ok((StatusCode::OK, Json(response)).into_response())
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Signs the caller out everywhere: revokes every session and CSRF token of
+/// the user, including the current one, and expires the caller's cookies.
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout-all",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Every session revoked", body = AuthResponse),
+        (status = 403, description = "Not authenticated or impersonated session")
+    )
+)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
+    cookies: Cookies,
+) -> Result<Response> {
+    if session.is_impersonated() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let revoked = session_service::revoke_all_sessions(&state, &session.user_id, None).await?;
+    share_service::revoke_all_shares(&state, &session.user_id).await?;
+
+    // The current session may predate the index; make sure it goes too.
+    session_service::revoke_session(&state, &session.user_id, &token.id).await?;
+    let _: () = state
+        .redis
+        .clone()
+        .del(session_service::user_sessions_key(&state, &session.user_id))
+        .await?;
+
+    clear_auth_cookies(&cookies);
+
+    tracing::info!("✅ User {} logged out of {} sessions", session.user_id, revoked);
+
+    let response = AuthResponse {
+        success: true,
+        message: "Logged out of all sessions".to_string(),
+    };
+
+    auth_json_response(StatusCode::OK, &response)
 }
 
 /// Handles changing a user's password.
+#[utoipa::path(
+    post,
+    path = "/api/auth/change-password",
+    tag = "auth",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed", body = AuthResponse),
+        (status = 401, description = "Invalid current password"),
+        (status = 403, description = "Not authenticated or impersonated session")
+    )
+)]
 pub async fn change_password(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(payload): Json<ChangePasswordRequest>,
 ) -> Result<Response> {
     tracing::info!("🔑 Change password for user: {}", session.user_id);
 
-    validate_password(&payload.new_password)?;
+    if session.is_impersonated() {
+        tracing::warn!(
+            "❌ Impersonated session (admin {:?}) tried to change password for user {}",
+            session.impersonated_by,
+            session.user_id
+        );
+        return Err(AppError::Unauthorized);
+    }
 
-    auth_service::change_password(
+    validate_password(&payload.new_password, &state.config.password_policy())?;
+
+    let changed = auth_service::change_password(
         &state,
         session.user_id,
         payload.old_password,
         payload.new_password,
     )
-    .await?;
+    .await;
+    audit_service::record(&state, AuditEvent {
+        success: changed.is_ok(),
+        error_message: changed.as_ref().err().map(|e| e.to_string()),
+        ..audit_service::session_event(&state, "change_password", &session, &headers, addr)
+    });
+    changed?;
 
     tracing::info!("✅ Password changed for user: {}", session.user_id);
 
+    // A stolen session would otherwise outlive the password change that was
+    // meant to lock the attacker out, so other devices are signed out unless
+    // the operator explicitly opts out.
+    if state.config.invalidate_sessions_on_password_change {
+        let revoked =
+            session_service::revoke_all_sessions(&state, &session.user_id, Some(token.id)).await?;
+        share_service::revoke_all_shares(&state, &session.user_id).await?;
+
+        tracing::info!(
+            "✅ Invalidated {} other sessions after password change for user: {}",
+            revoked,
+            session.user_id
+        );
+    }
+
     let response = AuthResponse {
         success: true,
         message: "Password changed successfully".to_string(),
     };
 
-    Ok((StatusCode::OK, Json(response)).into_response())
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Emails a password reset token to the user registered with an email.
+///
+/// The response is the same whether or not the email is registered, and the
+/// lookup and email run in the background so response times do not tell
+/// either.
+#[utoipa::path(
+    post,
+    path = "/api/auth/forgot-password",
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Reset token sent if the email is registered", body = AuthResponse),
+        (status = 400, description = "Invalid email address")
+    )
+)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<Response> {
+    let email = normalize_email(&payload.email)?;
+
+    tokio::spawn(async move {
+        if let Err(e) = password_reset_service::request_reset(&state, &email).await {
+            tracing::error!("❌ Failed to send password reset token: {}", e);
+        }
+    });
+
+    let response = AuthResponse {
+        success: true,
+        message: "If that email is registered, a reset token has been sent to it".to_string(),
+    };
+
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Sets a new password with a token from `POST /api/auth/forgot-password`.
+///
+/// The old password is needed to unwrap the user's DEK, so a reset generates
+/// a new one for future uploads; see [`auth_service::reset_password`]. Every
+/// session of the user is revoked, and two-factor authentication, if enabled,
+/// stays enabled.
+#[utoipa::path(
+    post,
+    path = "/api/auth/reset-password",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset", body = AuthResponse),
+        (status = 400, description = "Invalid or expired token, or weak password")
+    )
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<Response> {
+    validate_password(&payload.new_password, &state.config.password_policy())?;
+
+    let user_id = password_reset_service::consume_token(&state, &payload.token).await?;
+    auth_service::reset_password(&state, user_id, payload.new_password).await?;
+    audit_service::record(&state, AuditEvent {
+        user_id: Some(user_id),
+        ..audit_service::event(&state, "reset_password", &headers, addr)
+    });
+
+    let revoked = session_service::revoke_all_sessions(&state, &user_id, None).await?;
+    share_service::revoke_all_shares(&state, &user_id).await?;
+    tracing::info!(
+        "✅ Invalidated {} sessions after password reset for user: {}",
+        revoked,
+        user_id
+    );
+
+    let response = AuthResponse {
+        success: true,
+        message: "Password reset successfully".to_string(),
+    };
+
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Starts two-factor enrollment with a new TOTP secret.
+///
+/// Two-factor authentication stays off until the secret is confirmed with
+/// `POST /api/auth/2fa/verify`; starting again replaces an unconfirmed secret.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/enroll",
+    tag = "auth",
+    responses(
+        (status = 200, description = "TOTP secret generated", body = TwoFactorEnrollResponse),
+        (status = 400, description = "Two-factor authentication is already enabled"),
+        (status = 403, description = "Not authenticated or impersonated session")
+    )
+)]
+pub async fn enroll_two_factor(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<Response> {
+    if session.is_impersonated() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let client = state.db.get().await?;
+    let user = repositories::user::find_by_id(&client, &session.user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    drop(client);
+
+    let enrollment = two_factor_service::start_enrollment(&state, &user).await?;
+
+    let response = TwoFactorEnrollResponse {
+        secret: enrollment.secret,
+        otpauth_uri: enrollment.otpauth_uri,
+    };
+
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Confirms two-factor enrollment with a code from the authenticator app and
+/// enables two-factor authentication.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/verify",
+    tag = "auth",
+    request_body = TwoFactorVerifyRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled", body = TwoFactorVerifyResponse),
+        (status = 400, description = "Invalid code, no enrollment started, or already enabled"),
+        (status = 403, description = "Not authenticated or impersonated session")
+    )
+)]
+pub async fn verify_two_factor(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Json(payload): Json<TwoFactorVerifyRequest>,
+) -> Result<Response> {
+    if session.is_impersonated() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let client = state.db.get().await?;
+    let user = repositories::user::find_by_id(&client, &session.user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    drop(client);
+
+    let recovery_codes = two_factor_service::confirm_enrollment(&state, &user, &payload.code).await?;
+
+    let response = TwoFactorVerifyResponse {
+        success: true,
+        recovery_codes,
+    };
+
+    auth_json_response(StatusCode::OK, &response)
+}
+
+/// Lists the caller's active sessions, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The caller's sessions", body = SessionListResponse),
+        (status = 403, description = "Not authenticated")
+    )
+)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
+) -> Result<Response> {
+    let sessions = session_service::list_sessions(&state, &session.user_id)
+        .await?
+        .into_iter()
+        .map(|(session_id, listed)| SessionInfo {
+            id: session_service::mask_session_id(&session_id),
+            created_at: listed.created_at,
+            expires_at: listed.expires_at,
+            user_agent: listed.user_agent,
+            current: session_id == token.id,
+            impersonated: listed.impersonated_by.is_some(),
+        })
+        .collect();
+
+    auth_json_response(StatusCode::OK, &SessionListResponse { sessions })
+}
+
+/// Revokes one of the caller's sessions, e.g. on a lost or stolen device.
+///
+/// `session_id` is the masked ID from the session list, or the full session
+/// ID. Only the caller's own sessions are considered.
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{session_id}",
+    tag = "auth",
+    params(("session_id" = String, Path, description = "The masked or full session ID")),
+    responses(
+        (status = 200, description = "Session revoked", body = AuthResponse),
+        (status = 400, description = "The ID matches more than one session"),
+        (status = 403, description = "Not authenticated or impersonated session"),
+        (status = 404, description = "No session of the caller has this ID")
+    )
+)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(session_id): Path<String>,
+) -> Result<Response> {
+    if session.is_impersonated() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let session_id = session_id.to_ascii_lowercase();
+    if session_id.len() < session_service::MASKED_SESSION_ID_LEN {
+        return Err(AppError::NotFound);
+    }
+
+    let matches: Vec<Uuid> = session_service::list_sessions(&state, &session.user_id)
+        .await?
+        .into_iter()
+        .map(|(id, _)| id)
+        .filter(|id| id.to_string().starts_with(&session_id))
+        .collect();
+
+    let target = match matches.as_slice() {
+        [] => return Err(AppError::NotFound),
+        [id] => *id,
+        _ => {
+            return Err(AppError::Validation(
+                "Session ID is ambiguous; pass the full ID".to_string(),
+            ))
+        }
+    };
+
+    session_service::revoke_session(&state, &session.user_id, &target).await?;
+
+    tracing::info!("✅ User {} revoked session {}", session.user_id, session_service::mask_session_id(&target));
+
+    let response = AuthResponse {
+        success: true,
+        message: "Session revoked".to_string(),
+    };
+
+    auth_json_response(StatusCode::OK, &response)
 }
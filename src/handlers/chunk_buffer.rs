@@ -0,0 +1,160 @@
+use bytes::Bytes;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    crypto::aes,
+    error::{AppError, Result},
+    models::file::ChunkInfo,
+};
+
+/// The most memory a download stream with `slots` buffer slots holds for
+/// chunks of `chunk_size` plaintext bytes.
+///
+/// Each slot holds one encrypted chunk, which is decrypted in place, so the
+/// ceiling is `slots × (chunk_size + TAG_SIZE)`. Compressed chunks add their
+/// decompressed copy on top while they are in flight.
+pub fn memory_ceiling(slots: usize, chunk_size: u64) -> u64 {
+    slots as u64 * (chunk_size + aes::TAG_SIZE as u64)
+}
+
+/// A pool of chunk buffers shared by the chunks of one download stream.
+///
+/// A buffer is read into, decrypted in place and handed to the response body
+/// as a [`Bytes`] that owns it. Once the body has written and dropped that
+/// `Bytes`, the buffer goes back to the pool, and the next chunk read into it
+/// reuses its allocation instead of making a new one.
+pub struct ChunkBufferPool {
+    upload_dir: PathBuf,
+    buffers: Mutex<Vec<Vec<u8>>>,
+    slots: usize,
+    allocated: AtomicUsize,
+}
+
+/// A decrypted chunk held in a pooled buffer, returned to its pool on drop.
+struct PooledChunk {
+    buffer: Vec<u8>,
+    len: usize,
+    pool: Arc<ChunkBufferPool>,
+}
+
+impl AsRef<[u8]> for PooledChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+impl Drop for PooledChunk {
+    fn drop(&mut self) {
+        self.pool.give_back(std::mem::take(&mut self.buffer));
+    }
+}
+
+impl ChunkBufferPool {
+    /// Creates a pool reading chunks from `upload_dir` that keeps up to
+    /// `slots` buffers for reuse.
+    pub fn new(upload_dir: PathBuf, slots: usize) -> Self {
+        Self {
+            upload_dir,
+            buffers: Mutex::new(Vec::with_capacity(slots)),
+            slots,
+            allocated: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of times this pool has allocated chunk memory,
+    /// either for a new buffer or to grow a reused one too small for a chunk.
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    fn take(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    fn give_back(&self, buffer: Vec<u8>) {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.slots {
+            buffers.push(buffer);
+        }
+    }
+
+    /// Reads one encrypted chunk from disk into a pooled buffer and decrypts
+    /// it in place, checking its GCM tag.
+    ///
+    /// With `blocking` set, the AES-GCM work runs on Tokio's blocking pool.
+    ///
+    /// # Returns
+    ///
+    /// The chunk's plaintext, owning the pooled buffer until it is dropped
+    /// unless the chunk was stored compressed.
+    pub async fn read_decrypted_chunk(
+        self: &Arc<Self>,
+        dek: &[u8; 32],
+        chunk_info: &ChunkInfo,
+        blocking: bool,
+    ) -> Result<Bytes> {
+        let chunk_filename = chunk_info.get_filename()?;
+        let chunk_path = self.upload_dir.join(&chunk_filename);
+
+        let mut buffer = self.take();
+        let read = async {
+            let mut file = tokio::fs::File::open(&chunk_path).await?;
+            let len = file.metadata().await?.len() as usize;
+            if buffer.capacity() < len {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+            }
+            buffer.clear();
+            buffer.resize(len, 0);
+            file.read_exact(&mut buffer).await?;
+            Ok::<(), std::io::Error>(())
+        };
+        if let Err(e) = read.await {
+            tracing::error!("Failed to read chunk {}: {}", chunk_filename, e);
+            self.give_back(buffer);
+            return Err(AppError::Io(e));
+        }
+
+        let nonce = chunk_info.nonce;
+        let decrypted = if blocking {
+            let dek = *dek;
+            tokio::task::spawn_blocking(move || {
+                let result = aes::decrypt_in_place(&dek, &mut buffer, &nonce);
+                (buffer, result)
+            })
+            .await
+            .map_err(|e| AppError::Internal(format!("Chunk decryption task failed: {}", e)))?
+        } else {
+            let result = aes::decrypt_in_place(dek, &mut buffer, &nonce);
+            (buffer, result)
+        };
+
+        let (buffer, result) = decrypted;
+        let plaintext_len = match result {
+            Ok(len) => len,
+            Err(e) => {
+                tracing::error!("Failed to decrypt chunk {}: {}", chunk_info.index, e);
+                self.give_back(buffer);
+                return Err(e);
+            }
+        };
+
+        let chunk = if chunk_info.compressed {
+            let plaintext = chunk_info.decompress_slice(&buffer[..plaintext_len]);
+            self.give_back(buffer);
+            Bytes::from(plaintext?)
+        } else {
+            Bytes::from_owner(PooledChunk {
+                buffer,
+                len: plaintext_len,
+                pool: Arc::clone(self),
+            })
+        };
+
+        tracing::debug!("✅ Chunk {} decrypted: {} bytes", chunk_info.index, chunk.len());
+
+        Ok(chunk)
+    }
+}
@@ -0,0 +1,139 @@
+use axum::extract::Multipart;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+
+/// A parsed chunk upload request.
+#[derive(Debug)]
+pub struct ChunkUpload {
+    /// The upload session the chunk belongs to.
+    pub session_id: Uuid,
+    /// The zero-based position of the chunk in the file.
+    pub chunk_index: usize,
+    /// The plaintext chunk data.
+    pub data: Vec<u8>,
+}
+
+/// The bounds applied while reading a chunk upload form.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkFormLimits {
+    /// The maximum number of multipart fields, including unknown ones.
+    pub max_fields: usize,
+    /// The maximum time to read a small text field.
+    pub field_timeout: Duration,
+    /// The maximum time to wait for the next field or to read the chunk data.
+    pub read_timeout: Duration,
+    /// The maximum size of the chunk data in bytes.
+    pub max_chunk_bytes: usize,
+}
+
+/// Reads the `upload_session_id`, `chunk_index` and `chunk` fields of a chunk
+/// upload form.
+///
+/// Parsing is kept apart from encryption and storage so it can be exercised
+/// with an in-memory request. The chunk data is read incrementally and
+/// rejected as soon as it grows past `max_chunk_bytes`, and repeated fields
+/// are rejected rather than silently overwriting the earlier value.
+///
+/// # Arguments
+///
+/// * `multipart` - The multipart body of the request.
+/// * `limits` - The field count, timeout and size bounds to enforce.
+pub async fn read_chunk_form(multipart: &mut Multipart, limits: &ChunkFormLimits) -> Result<ChunkUpload> {
+    let mut session_id: Option<Uuid> = None;
+    let mut chunk_index: Option<usize> = None;
+    let mut data: Option<Vec<u8>> = None;
+    let mut fields_seen = 0usize;
+
+    loop {
+        let field = match timeout(limits.read_timeout, multipart.next_field()).await {
+            Ok(Ok(Some(field))) => field,
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => return Err(AppError::Multipart(format!("Parse error: {}", e))),
+            Err(_) => return Err(AppError::Multipart("Upload timeout exceeded".into())),
+        };
+
+        fields_seen += 1;
+        if fields_seen > limits.max_fields {
+            return Err(AppError::Multipart(format!(
+                "Too many multipart fields (max {})",
+                limits.max_fields
+            )));
+        }
+
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "upload_session_id" => {
+                if session_id.is_some() {
+                    return Err(AppError::Validation("Duplicate upload_session_id field".into()));
+                }
+                let text = timeout(limits.field_timeout, field.text())
+                    .await
+                    .map_err(|_| AppError::Multipart("upload_session_id: field read timeout exceeded".into()))?
+                    .map_err(|e| AppError::Multipart(format!("upload_session_id: {}", e)))?;
+                session_id = Some(
+                    Uuid::parse_str(text.trim())
+                        .map_err(|_| AppError::Validation("Invalid session ID format".into()))?,
+                );
+            }
+            "chunk_index" => {
+                if chunk_index.is_some() {
+                    return Err(AppError::Validation("Duplicate chunk_index field".into()));
+                }
+                let text = timeout(limits.field_timeout, field.text())
+                    .await
+                    .map_err(|_| AppError::Multipart("chunk_index: field read timeout exceeded".into()))?
+                    .map_err(|e| AppError::Multipart(format!("chunk_index: {}", e)))?;
+                chunk_index = Some(
+                    text.trim()
+                        .parse()
+                        .map_err(|_| AppError::Validation("Invalid chunk_index".into()))?,
+                );
+            }
+            "chunk" => {
+                if data.is_some() {
+                    return Err(AppError::Validation("Duplicate chunk field".into()));
+                }
+                data = Some(
+                    timeout(limits.read_timeout, read_bounded(field, limits.max_chunk_bytes))
+                        .await
+                        .map_err(|_| AppError::Multipart("chunk data: field read timeout exceeded".into()))??,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let data = data.ok_or(AppError::Validation("Missing chunk data".into()))?;
+    if data.is_empty() {
+        return Err(AppError::Validation("Chunk data is empty".into()));
+    }
+
+    Ok(ChunkUpload {
+        session_id: session_id.ok_or(AppError::Validation("Missing upload_session_id".into()))?,
+        chunk_index: chunk_index.ok_or(AppError::Validation("Missing chunk_index".into()))?,
+        data,
+    })
+}
+
+/// Reads a field's bytes, failing once they exceed `max_bytes`.
+async fn read_bounded(mut field: axum::extract::multipart::Field<'_>, max_bytes: usize) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    while let Some(piece) = field
+        .chunk()
+        .await
+        .map_err(|e| AppError::Multipart(format!("chunk data: {}", e)))?
+    {
+        if buffer.len() + piece.len() > max_bytes {
+            return Err(AppError::Validation(format!(
+                "Chunk exceeds the maximum size of {} bytes",
+                max_bytes
+            )));
+        }
+        buffer.extend_from_slice(&piece);
+    }
+
+    Ok(buffer)
+}
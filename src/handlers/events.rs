@@ -0,0 +1,46 @@
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use futures::{Stream, StreamExt};
+use std::{convert::Infallible, time::Duration};
+
+use crate::{
+    error::Result,
+    middleware_layer::auth::SessionToken,
+    models::session::Session,
+    services::events,
+    state::AppState,
+};
+
+/// How often a keep-alive comment is sent on an idle event stream, so proxies
+/// do not time the connection out.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Streams the caller's account activity as Server-Sent Events.
+///
+/// Events are `upload_completed`, `download_started` and `quota_warning`,
+/// each with a JSON payload. The Redis subscription behind the stream is
+/// dropped as soon as the client disconnects, and the stream ends when the
+/// caller's session is revoked or expires.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "auth",
+    responses(
+        (status = 200, description = "A `text/event-stream` of the caller's account events", content_type = "text/event-stream"),
+        (status = 429, description = "The user already has MAX_EVENT_STREAMS_PER_USER streams open")
+    )
+)]
+pub async fn event_stream(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let user_events = events::subscribe(&state, session.user_id, token.id).await?;
+
+    let stream = user_events.map(|event| Ok(Event::default().event(event.kind).data(event.data)));
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL)))
+}
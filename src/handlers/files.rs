@@ -1,8 +1,8 @@
 use axum::{
-    extract::{Multipart, Path, Query, State},
+    extract::{ConnectInfo, Multipart, Path, Query, State},
     body::{Body, Bytes},
     http::StatusCode,
-    http::HeaderMap,
+    http::{HeaderMap, HeaderValue},
     response::{IntoResponse, Response},
     Extension,
 };
@@ -13,104 +13,346 @@ use futures::{
 use bincode::{Encode, Decode};
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use tokio::{
     io::{AsyncWriteExt, BufWriter},
-    time::{timeout, Duration}
+    time::Duration
 };
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use chrono::Utc;
 use crate::{
+    crypto::checksum::{Checksum, ChecksumHasher},
+    handlers::chunk_buffer::ChunkBufferPool,
+    handlers::chunk_form::{read_chunk_form, ChunkFormLimits, ChunkUpload},
+    handlers::range::ByteRange,
     error::{AppError, Result},
-    models::session::Session,
+    models::{
+        file::{
+            ChunkInfo, ConflictPolicy, FileCursor, FileFilter, FileOrder, FileSortKey, FolderScope,
+            NewFile, PageStart, SortDirection,
+        },
+        pagination::{default_limit, PageQuery, Pagination},
+        session::Session,
+        user::QuotaStatus,
+    },
     state::AppState,
     state::{UPLOAD_BUFFER_SLOTS, DOWNLOAD_BUFFER_SLOTS},
     repositories,
+    response::json_response,
+    services::{antivirus::ScanVerdict, chunk_store, events, sessions as session_service, upload_progress},
+    validation::files::{normalize_filename, normalize_mime_type},
 };
 use redis::AsyncCommands;
 
-const MAX_FILE_SIZE: usize = 50 * 1024 * 1024 * 1024;
-const CHUNK_SIZE: usize = 6 * 1024 * 1024;
-const UPLOAD_TIMEOUT: u64 = 300;
-const UPLOAD_EXPIRATION_SECS: u64 = 86400;
+/// Extra Redis lifetime for sessions holding a quota reservation, so the
+/// hourly sweeper always sees them expire and releases the reservation.
+const RESERVATION_GRACE_SECS: u64 = 7200;
 const DOWNLOAD_EXPIRATION_SECS: u64 = 3600;
+/// The MIME type of files whose type was neither declared nor recognized.
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
 const CLEANUP_BATCH_SIZE: usize = 50;
-
-#[derive(Debug, Clone, Encode, Decode)]
-struct ChunkInfo {
-    index: usize,
-    nonce: [u8; 12],
-    filename: Vec<u8>,
-    size_encrypted: i64,
-}
-
-impl ChunkInfo {
-    fn new(index: usize, nonce: [u8; 12], filename: String, size_encrypted: i64) -> Self {
-        Self {
-            index,
-            nonce,
-            filename: filename.into_bytes(),
-            size_encrypted,
-        }
-    }
-
-    fn get_filename(&self) -> Result<String> {
-        String::from_utf8(self.filename.clone())
-            .map_err(|_| AppError::Internal("Invalid filename encoding".to_string()))
-    }
-}
-
-#[derive(Deserialize)]
-pub struct ListFilesQuery {
-    #[serde(default = "default_limit")]
-    pub limit: i64,
-    #[serde(default)]
-    pub offset: i64,
-}
-
-fn default_limit() -> i64 {
-    50
-}
+/// How many expired upload sessions the sweeper releases at once.
+const SWEEP_CONCURRENCY: usize = 8;
+/// Sorted set of every active upload session, scored by expiry timestamp.
+const ACTIVE_UPLOADS_KEY: &str = "upload_sessions:active";
+/// How long clients are told to wait when the global session cap is reached.
+const UPLOAD_CAP_RETRY_AFTER_SECS: u64 = 60;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Encode, Decode)]
-struct UploadMetadata {
+pub(crate) struct UploadMetadata {
     pub upload_session_id: String,
     #[bincode(with_serde)]
     pub user_id: Uuid,
     pub filename: String,
     pub total_size: i64,
     pub total_chunks: usize,
+    /// The plaintext size of every chunk but the last, fixed at init.
+    pub chunk_size: usize,
     pub chunks_received_count: usize,
     pub expected_hash: Option<String>,
     pub created_at: i64,
     pub chunks_written_bytes: i64,
     pub chunk_nonces: Vec<[u8; 12]>,
+    pub quota_reserved: bool,
+    /// The MIME type declared at init; sniffed at finalize when `None`.
+    pub mime_type: Option<String>,
+    /// Whether chunks are compressed with zstd before encryption.
+    pub compress: bool,
+    /// Whether each stored chunk was kept compressed; a chunk that did not
+    /// shrink is stored as-is.
+    pub chunk_compressed: Vec<bool>,
+    /// The content hash of each chunk stored content-addressed under
+    /// `CHUNK_DEDUP_ENABLED`; `None` for session-named chunk files.
+    pub chunk_hashes: Vec<Option<String>>,
+}
+
+impl UploadMetadata {
+    /// Returns whether chunk `index` has been stored; a chunk's nonce stays
+    /// all zeros until it is written.
+    fn has_chunk(&self, index: usize) -> bool {
+        self.chunk_nonces
+            .get(index)
+            .is_some_and(|nonce| *nonce != [0u8; 12])
+    }
+
+    /// Returns the indices of the chunks stored so far, in order.
+    fn received_chunks(&self) -> Vec<usize> {
+        (0..self.total_chunks).filter(|&idx| self.has_chunk(idx)).collect()
+    }
+
+    /// Returns the name of chunk `index`'s file in the upload directory.
+    fn chunk_filename(&self, index: usize) -> String {
+        match self.chunk_hashes.get(index).cloned().flatten() {
+            Some(hash) => chunk_store::chunk_filename(&hash),
+            None => format!("{}_{}.encrypted_chunk", self.upload_session_id, index),
+        }
+    }
+
+    /// Returns the plaintext size chunk `index` must have.
+    fn expected_chunk_len(&self, index: usize) -> usize {
+        let offset = index as i64 * self.chunk_size as i64;
+        (self.total_size - offset).clamp(0, self.chunk_size as i64) as usize
+    }
+
+    /// How long the session's Redis key lives after each write, for sessions
+    /// that expire after `expiration_secs`.
+    fn ttl_secs(&self, expiration_secs: u64) -> u64 {
+        if self.quota_reserved {
+            expiration_secs + RESERVATION_GRACE_SECS
+        } else {
+            expiration_secs
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct InitUploadRequest {
     pub filename: String,
+    /// The plaintext size in bytes; `0` uploads an empty file.
     pub file_size: i64,
+    /// The number of chunks that will be sent: `file_size` divided by
+    /// `CHUNK_SIZE_BYTES`, rounded up, so `0` for an empty file.
     pub total_chunks: usize,
     pub expected_hash: Option<String>,
+    /// The file's MIME type, served as its `Content-Type` on download. Sniffed
+    /// from the file's first bytes when omitted. Also accepted as `content_type`.
+    #[serde(default, alias = "content_type")]
+    pub mime_type: Option<String>,
+    /// Compress each chunk with zstd before encrypting it. Worth it for text,
+    /// logs or CSV; leave it off for already compressed media.
+    #[serde(default)]
+    pub compress: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct FinalizeUploadRequest {
     pub upload_session_id: String,
     pub folder_id: Option<Uuid>,
+    /// Overrides the server's default `UPLOAD_CONFLICT_POLICY` for this upload.
+    pub conflict: Option<ConflictPolicy>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CancelUploadRequest {
     pub upload_session_id: String,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, ToSchema)]
+pub struct BatchDeleteRequest {
+    /// The files to delete, at most `MAX_BATCH_DELETE_FILES`.
+    pub file_ids: Vec<Uuid>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RenameFileRequest {
+    /// The new filename, normalized like an uploaded filename.
+    pub filename: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MoveFileRequest {
+    /// The destination folder; `null` moves the file to the root.
+    pub folder_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct UploadStatusQuery {
+    /// The upload session to report on.
+    pub upload_session_id: String,
+}
+
+/// The query parameters for listing files.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListFilesQuery {
+    /// Only list files in this folder, or `root` for files outside any folder.
+    /// All folders are listed when omitted.
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Only list files with exactly this MIME type.
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// Only list files whose name contains this text, case-insensitively.
+    #[serde(default)]
+    pub search: Option<String>,
+    /// The column to sort by (default `uploaded_at`).
+    #[serde(default)]
+    pub sort: Option<FileSortKey>,
+    /// `asc` or `desc`; defaults to `asc` for `name` and `desc` otherwise.
+    #[serde(default)]
+    pub order: Option<SortDirection>,
+    /// The `next_cursor` of the previous page. Seeks instead of skipping rows,
+    /// so it stays fast on large listings; only valid with the default sort
+    /// and without `offset`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// The maximum number of files to return (1 to 1000, default 50).
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// The number of files to skip.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// The longest `search` text accepted by `list_files`.
+const MAX_SEARCH_LEN: usize = 255;
+
+impl ListFilesQuery {
+    /// Validates the filters; empty values are treated as omitted.
+    fn filter(&self) -> Result<FileFilter> {
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let folder = non_empty(&self.folder_id)
+            .map(|folder_id| folder_id.parse::<FolderScope>())
+            .transpose()
+            .map_err(|e| AppError::Validation(e.to_string()))?;
+
+        let search = non_empty(&self.search);
+        if search.as_ref().is_some_and(|s| s.chars().count() > MAX_SEARCH_LEN) {
+            return Err(AppError::Validation(format!(
+                "search must be at most {} characters",
+                MAX_SEARCH_LEN
+            )));
+        }
+
+        Ok(FileFilter {
+            folder,
+            mime_type: non_empty(&self.mime_type),
+            search,
+        })
+    }
+
+    /// Returns the requested sort order, newest first by default.
+    fn order(&self) -> FileOrder {
+        FileOrder::new(self.sort.unwrap_or_default(), self.order)
+    }
+
+    /// Returns the validated page window.
+    fn page(&self) -> Result<PageQuery> {
+        PageQuery {
+            limit: self.limit,
+            offset: self.offset,
+        }
+        .validate()
+    }
+
+    /// Returns where the page starts: after the `cursor` if one is given,
+    /// otherwise at `offset`.
+    fn start(&self) -> Result<PageStart> {
+        let Some(cursor) = self.cursor.as_deref().filter(|c| !c.is_empty()) else {
+            return Ok(PageStart::Offset(self.offset));
+        };
+
+        if self.offset != 0 {
+            return Err(AppError::Validation("cursor cannot be combined with offset".to_string()));
+        }
+        if self.order() != FileOrder::default() {
+            return Err(AppError::Validation(
+                "cursor is only supported with the default sort (uploaded_at desc)".to_string(),
+            ));
+        }
+
+        FileCursor::decode(cursor).map(PageStart::After)
+    }
+}
+
+/// The multipart form fields accepted by `upload_chunk`.
+///
+/// Only used to document the endpoint; the handler parses the fields manually.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct UploadChunkForm {
+    pub upload_session_id: String,
+    pub chunk_index: usize,
+    #[schema(value_type = String, format = Binary)]
+    pub chunk: Vec<u8>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct StorageInfoResponse {
     pub storage_quota_bytes: i64,
     pub storage_used_bytes: i64,
+    pub reserved_bytes: i64,
     pub available_bytes: i64,
     pub usage_percentage: f64,
+    /// How close usage is to the quota, from the `QUOTA_*_PERCENT` thresholds.
+    pub status: QuotaStatus,
+    /// A description of `status` to show the user.
+    pub message: String,
+}
+
+/// Releases what an unfinished upload holds on the server: its quota
+/// reservation, then its chunk files.
+///
+/// The reservation goes first, so a failure leaves the chunks in place for the
+/// next attempt rather than releasing content-addressed chunks twice.
+///
+/// # Returns
+///
+/// The number of chunk files removed.
+async fn release_upload_storage(state: &AppState, metadata: &UploadMetadata) -> Result<usize> {
+    if metadata.quota_reserved {
+        let client = state.db.get().await?;
+        repositories::user::release_storage_reservation(
+            &client,
+            &metadata.user_id,
+            metadata.total_size,
+            &state.stmt_cache,
+        )
+        .await?;
+        tracing::debug!("✅ Released {} reserved bytes", metadata.total_size);
+    }
+
+    let upload_dir = &state.config.upload_dir;
+    let mut deleted_count = 0;
+
+    // Chunks may arrive in any order, so remove the ones actually received
+    // rather than the first `chunks_received_count` indices.
+    for chunk_batch in metadata.received_chunks().chunks(CLEANUP_BATCH_SIZE) {
+        for &chunk_idx in chunk_batch {
+            // A content-addressed chunk may be shared; only drop this upload's reference.
+            if let Some(hash) = &metadata.chunk_hashes[chunk_idx] {
+                if let Err(e) = chunk_store::release_chunk(state, hash).await {
+                    tracing::warn!("⚠️ Failed to release chunk {}: {}", hash, e);
+                }
+                continue;
+            }
+
+            let chunk_path = upload_dir.join(metadata.chunk_filename(chunk_idx));
+            if tokio::fs::remove_file(&chunk_path).await.is_ok() {
+                deleted_count += 1;
+            }
+        }
+    }
+
+    Ok(deleted_count)
 }
 
 async fn cleanup_failed_upload(
@@ -125,28 +367,19 @@ async fn cleanup_failed_upload(
         user_id
     );
 
-    let upload_dir = PathBuf::from("uploads/files");
-    let mut deleted_count = 0;
-
-    for chunk_batch_start in (0..metadata.chunks_received_count).step_by(CLEANUP_BATCH_SIZE) {
-        let batch_end = (chunk_batch_start + CLEANUP_BATCH_SIZE).min(metadata.chunks_received_count);
-        for chunk_idx in chunk_batch_start..batch_end {
-            let chunk_filename = format!("{}_{}.encrypted_chunk", upload_session_id, chunk_idx);
-            let chunk_path = upload_dir.join(&chunk_filename);
-            if tokio::fs::remove_file(&chunk_path).await.is_ok() {
-                deleted_count += 1;
-            }
-        }
-    }
-
+    let deleted_count = release_upload_storage(state, metadata).await?;
     tracing::debug!("✅ Removed {} chunk files from disk", deleted_count);
 
     let mut redis = state.redis.clone();
     let redis_key = format!("upload:{}:{}", user_id, upload_session_id);
     let _ = redis.del::<_, ()>(&redis_key).await.ok();
-
-    let lock_key = format!("user_uploading:{}", user_id);
-    let _ = redis.del::<_, ()>(&lock_key).await.ok();
+    unregister_upload_session(&mut redis, user_id, upload_session_id).await;
+    upload_progress::publish(
+        state,
+        upload_session_id,
+        upload_progress::closed_message(upload_session_id, "cancelled", None),
+    )
+    .await;
 
     tracing::info!(
         "✅ Upload cleanup completed for session: {}",
@@ -156,6 +389,184 @@ async fn cleanup_failed_upload(
     Ok(())
 }
 
+/// Rejects an upload of `required` bytes that the filesystem backing
+/// `UPLOAD_DIR` has no room for once `DISK_RESERVE_BYTES` is kept free.
+///
+/// The free space is only a snapshot, so concurrent uploads can still fill
+/// the disk; the reserve is what absorbs them. If the free space cannot be
+/// read the upload is let through, as it was before this check existed.
+async fn check_disk_space(state: &AppState, required: i64) -> Result<()> {
+    let upload_dir = state.config.upload_dir.clone();
+    let available = tokio::task::spawn_blocking(move || fs2::available_space(&upload_dir))
+        .await
+        .map_err(|e| AppError::Internal(format!("Disk space check task failed: {}", e)))?;
+
+    let available = match available {
+        Ok(available) => available,
+        Err(e) => {
+            tracing::warn!(
+                "⚠️ Could not read free space of {}: {}",
+                state.config.upload_dir.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    let required = required.max(0) as u64;
+    let reserve = state.config.disk_reserve_bytes;
+    if required.saturating_add(reserve) > available {
+        tracing::warn!(
+            "💽 Insufficient server storage: {} bytes required, {} free, {} reserved",
+            required,
+            available,
+            reserve
+        );
+        return Err(AppError::Validation("Insufficient server storage".into()));
+    }
+
+    tracing::debug!("💽 {} bytes required, {} free, {} reserved", required, available, reserve);
+
+    Ok(())
+}
+
+/// Records a new upload session against the global and per-user session caps.
+///
+/// The session is added first and the caps are checked afterwards, so two
+/// concurrent inits can both be rejected near the limit but never both admitted
+/// past it. Entries are scored by expiry, so sessions whose Redis keys lapsed
+/// without cleanup stop counting on their own.
+async fn register_upload_session(
+    state: &AppState,
+    redis: &mut redis::aio::ConnectionManager,
+    user_id: Uuid,
+    upload_session_id: &str,
+    expires_at: i64,
+) -> Result<()> {
+    let now = Utc::now().timestamp();
+    let user_key = format!("upload_sessions:{}", user_id);
+
+    let (global_active, user_active): (usize, usize) = redis::pipe()
+        .atomic()
+        .zrembyscore(ACTIVE_UPLOADS_KEY, "-inf", now)
+        .ignore()
+        .zadd(ACTIVE_UPLOADS_KEY, upload_session_id, expires_at)
+        .ignore()
+        .zcard(ACTIVE_UPLOADS_KEY)
+        .zrembyscore(&user_key, "-inf", now)
+        .ignore()
+        .zadd(&user_key, upload_session_id, expires_at)
+        .ignore()
+        .expire_at(&user_key, expires_at)
+        .ignore()
+        .zcard(&user_key)
+        .query_async(redis)
+        .await
+        .map_err(AppError::Redis)?;
+
+    if global_active > state.config.max_active_upload_sessions {
+        unregister_upload_session(redis, user_id, upload_session_id).await;
+        tracing::warn!(
+            "🚦 Active upload session cap reached ({}), rejecting init for user {}",
+            state.config.max_active_upload_sessions,
+            user_id
+        );
+        return Err(AppError::ServiceUnavailable {
+            message: "Too many uploads in progress, please retry later".to_string(),
+            retry_after_secs: UPLOAD_CAP_RETRY_AFTER_SECS,
+        });
+    }
+
+    if user_active > state.config.max_active_uploads_per_user {
+        unregister_upload_session(redis, user_id, upload_session_id).await;
+        return Err(AppError::RateLimitExceeded(format!(
+            "At most {} uploads may be in progress at once",
+            state.config.max_active_uploads_per_user
+        )));
+    }
+
+    Ok(())
+}
+
+/// Pushes back the expiry of an upload session that just received a chunk, so
+/// long-running uploads keep counting against the caps.
+async fn touch_upload_session(
+    redis: &mut redis::aio::ConnectionManager,
+    user_id: Uuid,
+    upload_session_id: &str,
+    expiration_secs: u64,
+) {
+    let user_key = format!("upload_sessions:{}", user_id);
+    let expires_at = Utc::now().timestamp() + expiration_secs as i64;
+    let _ = redis::pipe()
+        .zadd(ACTIVE_UPLOADS_KEY, upload_session_id, expires_at)
+        .ignore()
+        .zadd(&user_key, upload_session_id, expires_at)
+        .ignore()
+        .expire_at(&user_key, expires_at)
+        .ignore()
+        .query_async::<()>(redis)
+        .await
+        .ok();
+}
+
+/// Removes an upload session from the session caps. Failures are ignored,
+/// since stale entries age out by their expiry score.
+async fn unregister_upload_session(
+    redis: &mut redis::aio::ConnectionManager,
+    user_id: Uuid,
+    upload_session_id: &str,
+) {
+    let user_key = format!("upload_sessions:{}", user_id);
+    let _ = redis::pipe()
+        .zrem(ACTIVE_UPLOADS_KEY, upload_session_id)
+        .ignore()
+        .zrem(&user_key, upload_session_id)
+        .ignore()
+        .query_async::<()>(redis)
+        .await
+        .ok();
+}
+
+pub(crate) async fn load_upload_metadata(
+    redis: &mut redis::aio::ConnectionManager,
+    redis_key: &str,
+) -> Result<UploadMetadata> {
+    let metadata_bytes: Option<Vec<u8>> = redis
+        .get(redis_key)
+        .await
+        .map_err(|e| {
+            tracing::error!("Redis GET error: {}", e);
+            AppError::Redis(e)
+        })?;
+
+    let metadata_bytes = metadata_bytes.ok_or_else(|| {
+        tracing::warn!("❌ Upload session not found or expired: {}", redis_key);
+        AppError::Validation("Upload session not found or expired".to_string())
+    })?;
+
+    let (metadata, _): (UploadMetadata, usize) =
+        bincode::decode_from_slice(&metadata_bytes, bincode::config::standard()).map_err(|e| {
+            tracing::error!("Bincode decode failed: {}", e);
+            AppError::Internal(format!("Bincode decode failed: {}", e))
+        })?;
+
+    Ok(metadata)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/files/upload/init",
+    tag = "files",
+    request_body = InitUploadRequest,
+    responses(
+        (status = 200, description = "Upload session created"),
+        (status = 400, description = "Invalid upload parameters, or insufficient server storage"),
+        (status = 429, description = "The user already has MAX_ACTIVE_UPLOADS_PER_USER uploads in progress"),
+        (status = 503, description = "The server-wide upload session cap is reached"),
+        (status = 507, description = "Storage quota exceeded")
+    )
+)]
 pub async fn init_upload(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -171,46 +582,130 @@ pub async fn init_upload(
         req.total_chunks
     );
 
+    if session.is_impersonated() {
+        return Err(AppError::Validation(
+            "Impersonated sessions cannot upload files".to_string(),
+        ));
+    }
+
     let mut redis = state.redis.clone();
 
-    let lock_key = format!("user_uploading:{}", user_id);
-    match redis.get::<_, Option<String>>(&lock_key).await {
-        Ok(Some(_)) => {
-            return Err(AppError::Validation(
-                "Já há um upload ativo para este usuário. Aguarde a conclusão.".to_string(),
-            ));
-        }
-        Err(e) => return Err(AppError::Redis(e)),
-        _ => {}
+    if req.file_size < 0 {
+        return Err(AppError::Validation("File size must not be negative".into()));
     }
 
-    if req.file_size <= 0 {
-        return Err(AppError::Validation("File size must be positive".into()));
+    if req.file_size == 0 && !state.config.allow_empty_files {
+        return Err(AppError::Validation("Empty files are not accepted".into()));
     }
 
-    if req.file_size > MAX_FILE_SIZE as i64 {
+    if req.file_size > state.config.max_file_size_bytes {
         return Err(AppError::Validation(format!(
-            "File size exceeds maximum allowed ({}GB)",
-            MAX_FILE_SIZE / (1024 * 1024 * 1024)
+            "File size exceeds maximum allowed ({} bytes)",
+            state.config.max_file_size_bytes
         )));
     }
 
-    if req.total_chunks == 0 {
+    if req.file_size == 0 && req.total_chunks != 0 {
+        return Err(AppError::Validation(
+            "total_chunks must be 0 for an empty file".into(),
+        ));
+    }
+
+    if req.file_size > 0 && req.total_chunks == 0 {
         return Err(AppError::Validation(
             "total_chunks must be greater than 0".into(),
         ));
     }
 
+    let chunk_size = state.config.chunk_size_bytes;
+    let expected_chunks = (req.file_size as u64).div_ceil(chunk_size as u64);
+    if req.total_chunks as u64 != expected_chunks {
+        return Err(AppError::Validation(format!(
+            "total_chunks must be {} for a file of {} bytes in chunks of {} bytes",
+            expected_chunks, req.file_size, chunk_size
+        )));
+    }
+
+    let filename = normalize_filename(&req.filename, state.config.max_filename_length)?;
+    let mime_type = req.mime_type.as_deref().map(normalize_mime_type).transpose()?;
+
+    let mut expected_hash = req
+        .expected_hash
+        .as_deref()
+        .map(|hash| Checksum::parse(hash, state.config.checksum_algorithm))
+        .transpose()?;
+
+    // An empty file has no chunks to hash at finalize, so its checksum is
+    // known up front: check the client's against it, or record it.
+    if req.file_size == 0 {
+        let algorithm = expected_hash
+            .as_ref()
+            .map_or(state.config.checksum_algorithm, |checksum| checksum.algorithm);
+        let empty_checksum = ChecksumHasher::new(algorithm).finalize();
+
+        if expected_hash.as_ref().is_some_and(|checksum| *checksum != empty_checksum) {
+            return Err(AppError::Validation(
+                "expected_hash does not match an empty file".into(),
+            ));
+        }
+        expected_hash = Some(empty_checksum);
+    }
+
+    let expected_hash = expected_hash.map(|checksum| checksum.to_string());
+
     let client = state.db.get().await?;
     let (storage_quota_bytes, storage_used_bytes) =
         repositories::user::get_user_storage_info(&client, &user_id, &state.stmt_cache).await?;
 
-    let available_space = storage_quota_bytes - storage_used_bytes;
+    let mut available_space = storage_quota_bytes - storage_used_bytes;
     if req.file_size > available_space {
-        return Err(AppError::Validation(format!(
-            "Insufficient storage quota. Required: {} bytes, Available: {} bytes",
-            req.file_size, available_space
-        )));
+        return Err(AppError::QuotaExceeded {
+            required: req.file_size,
+            available: available_space,
+        });
+    }
+
+    if req.file_size > 0 {
+        check_disk_space(&state, req.file_size).await?;
+    }
+
+    let upload_session_id = Uuid::new_v4();
+    register_upload_session(
+        &state,
+        &mut redis,
+        user_id,
+        &upload_session_id.to_string(),
+        Utc::now().timestamp() + state.config.upload_expiration_secs as i64,
+    )
+    .await?;
+
+    let quota_reserved = state.config.quota_reservation_enabled;
+    if quota_reserved {
+        let reservation = repositories::user::reserve_storage(
+            &client,
+            &user_id,
+            req.file_size,
+            &state.stmt_cache,
+        )
+        .await;
+
+        let reservation = match reservation {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                unregister_upload_session(&mut redis, user_id, &upload_session_id.to_string()).await;
+                return Err(e);
+            }
+        };
+
+        if !reservation.success {
+            unregister_upload_session(&mut redis, user_id, &upload_session_id.to_string()).await;
+            return Err(AppError::QuotaExceeded {
+                required: req.file_size,
+                available: reservation.available_bytes,
+            });
+        }
+
+        available_space = reservation.available_bytes;
     }
 
     tracing::info!(
@@ -219,18 +714,23 @@ pub async fn init_upload(
         user_id
     );
 
-    let upload_session_id = Uuid::new_v4();
     let metadata = UploadMetadata {
         upload_session_id: upload_session_id.to_string(),
         user_id,
-        filename: req.filename.clone(),
+        filename,
         total_size: req.file_size,
         total_chunks: req.total_chunks,
+        chunk_size,
         chunks_received_count: 0,
-        expected_hash: req.expected_hash.clone(),
+        expected_hash,
         created_at: Utc::now().timestamp(),
         chunks_written_bytes: 0,
         chunk_nonces: vec![[0u8; 12]; req.total_chunks],
+        quota_reserved,
+        mime_type,
+        compress: req.compress,
+        chunk_compressed: vec![false; req.total_chunks],
+        chunk_hashes: vec![None; req.total_chunks],
     };
 
     let redis_key = format!("upload:{}:{}", user_id, upload_session_id);
@@ -238,38 +738,58 @@ pub async fn init_upload(
     let metadata_bytes = bincode::encode_to_vec(&metadata, config)
         .map_err(|e| AppError::Internal(format!("Bincode encode failed: {}", e)))?;
 
-    let _: () = redis
-        .set_ex(&redis_key, &metadata_bytes, UPLOAD_EXPIRATION_SECS)
-        .await
-        .map_err(|e| AppError::Redis(e))?;
-
-    let _: () = redis
-        .set_ex(&lock_key, "locked", UPLOAD_EXPIRATION_SECS)
-        .await
-        .map_err(|e| AppError::Redis(e))?;
+    let stored: redis::RedisResult<()> = redis
+        .set_ex(&redis_key, &metadata_bytes, metadata.ttl_secs(state.config.upload_expiration_secs))
+        .await;
+
+    if let Err(e) = stored {
+        unregister_upload_session(&mut redis, user_id, &upload_session_id.to_string()).await;
+        if quota_reserved {
+            repositories::user::release_storage_reservation(
+                &client,
+                &user_id,
+                req.file_size,
+                &state.stmt_cache,
+            )
+            .await?;
+        }
+        return Err(AppError::Redis(e));
+    }
 
     tracing::info!(
-        "✅ Upload session created: {} (expires in 24h)",
-        upload_session_id
+        "✅ Upload session created: {} (expires in {}s)",
+        upload_session_id,
+        state.config.upload_expiration_secs
     );
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "upload_session_id": upload_session_id.to_string(),
         "message": "Upload session initialized. Ready to receive chunks.",
-        "quota_reserved": 0,
+        "quota_reserved": if quota_reserved { req.file_size } else { 0 },
         "available_space_before": available_space,
         "chunks_to_send": req.total_chunks,
-        "chunk_size_bytes": CHUNK_SIZE,
-        "upload_timeout_seconds": UPLOAD_TIMEOUT
+        "chunk_size_bytes": chunk_size,
+        "upload_timeout_seconds": state.config.chunk_read_timeout(chunk_size as u64).as_secs()
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/files/upload/chunk",
+    tag = "files",
+    request_body(content = UploadChunkForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Chunk encrypted and stored"),
+        (status = 400, description = "Invalid chunk, unknown or expired upload session")
+    )
+)]
 pub async fn upload_chunk(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse> {
     let user_id = session.user_id;
@@ -293,61 +813,30 @@ pub async fn upload_chunk(
         available
     );
 
-    let mut upload_session_id: Option<String> = None;
-    let mut chunk_index: Option<usize> = None;
-    let mut chunk_data: Option<Vec<u8>> = None;
-
-    let timeout_duration = Duration::from_secs(UPLOAD_TIMEOUT);
-
-    loop {
-        match timeout(timeout_duration, multipart.next_field()).await {
-            Ok(Ok(Some(field))) => {
-                let field_name = field.name().unwrap_or("").to_string();
-                match field_name.as_str() {
-                    "upload_session_id" => {
-                        upload_session_id = Some(
-                            field
-                                .text()
-                                .await
-                                .map_err(|e| AppError::Multipart(format!("upload_session_id: {}", e)))?,
-                        );
-                    }
-                    "chunk_index" => {
-                        let text = field
-                            .text()
-                            .await
-                            .map_err(|e| AppError::Multipart(format!("chunk_index: {}", e)))?;
-                        chunk_index = Some(text.parse().map_err(|_| {
-                            AppError::Validation("Invalid chunk_index".into())
-                        })?);
-                    }
-                    "chunk" => {
-                        chunk_data = Some(
-                            field
-                                .bytes()
-                                .await
-                                .map_err(|e| AppError::Multipart(format!("chunk data: {}", e)))?
-                                .to_vec(),
-                        );
-                    }
-                    _ => {}
-                }
-            }
-            Ok(Ok(None)) => break,
-            Ok(Err(e)) => {
-                return Err(AppError::Multipart(format!("Parse error: {}", e)));
-            }
-            Err(_) => return Err(AppError::Multipart("Upload timeout exceeded".into())),
-        }
-    }
+    // Scale the read timeout to the request size; without a Content-Length,
+    // assume a full chunk.
+    let request_bytes = headers
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(state.config.chunk_size_bytes as u64);
+
+    let limits = ChunkFormLimits {
+        max_fields: state.config.max_multipart_fields,
+        field_timeout: Duration::from_secs(state.config.multipart_field_timeout_secs),
+        read_timeout: state.config.chunk_read_timeout(request_bytes),
+        max_chunk_bytes: state.config.chunk_size_bytes,
+    };
 
-    let session_id = upload_session_id
-        .ok_or(AppError::Validation("Missing upload_session_id".into()))?;
-    let chunk_idx = chunk_index
-        .ok_or(AppError::Validation("Missing chunk_index".into()))?;
-    let data = chunk_data.ok_or(AppError::Validation("Missing chunk data".into()))?;
-    let _session_uuid = Uuid::parse_str(&session_id)
-        .map_err(|_| AppError::Validation("Invalid session ID format".into()))?;
+    let ChunkUpload {
+        session_id,
+        chunk_index: chunk_idx,
+        data,
+    } = read_chunk_form(&mut multipart, &limits).await.map_err(|e| {
+        tracing::warn!("❌ Rejected chunk form from user {}: {}", user_id, e);
+        e
+    })?;
+    let session_id = session_id.to_string();
 
     tracing::debug!(
         "📋 Parsed multipart - session: {}, chunk_idx: {}, data_size: {} bytes",
@@ -358,14 +847,13 @@ pub async fn upload_chunk(
 
     let redis_key = format!("upload:{}:{}", user_id, session_id);
     let config = bincode::config::standard();
-    let metadata_bytes: Vec<u8> = redis
-        .get(&redis_key)
-        .await
-        .map_err(|e| AppError::Redis(e))?;
+    let mut metadata = load_upload_metadata(&mut redis, &redis_key).await?;
 
-    let (mut metadata, _): (UploadMetadata, usize) =
-        bincode::decode_from_slice(&metadata_bytes, config)
-            .map_err(|e| AppError::Internal(format!("Bincode decode failed: {}", e)))?;
+    if metadata.total_chunks == 0 {
+        return Err(AppError::Validation(
+            "This upload is an empty file and takes no chunks; finalize it directly".into(),
+        ));
+    }
 
     if chunk_idx >= metadata.total_chunks {
         return Err(AppError::Validation(format!(
@@ -375,6 +863,16 @@ pub async fn upload_chunk(
         )));
     }
 
+    let expected_len = metadata.expected_chunk_len(chunk_idx);
+    if data.len() != expected_len {
+        return Err(AppError::Validation(format!(
+            "Chunk {} must be {} bytes, got {}",
+            chunk_idx,
+            expected_len,
+            data.len()
+        )));
+    }
+
     tracing::debug!(
         "✅ Upload metadata loaded - total_chunks: {}, received: {}",
         metadata.total_chunks,
@@ -383,95 +881,92 @@ pub async fn upload_chunk(
 
     tracing::debug!("🔐 Using session DEK to encrypt chunk...");
 
-    if session.dek.len() != 32 {
-        tracing::error!(
-            "❌ Invalid DEK in session: {} bytes (expected 32)",
-            session.dek.len()
-        );
-        return Err(AppError::Encryption(
-            "Invalid DEK in session".to_string(),
-        ));
-    }
+    let dek_array = session_service::open_session_dek(&state, &session).await.map_err(|e| {
+        tracing::error!("❌ Cannot open the session DEK of user {}: {}", user_id, e);
+        e
+    })?;
 
-    let dek_array: [u8; 32] = session
-        .dek
-        .as_slice()
-        .try_into()
-        .map_err(|_| AppError::Encryption("Invalid DEK in session".to_string()))?;
+    let compressed = if metadata.compress {
+        compress_chunk(&data, chunk_idx)
+    } else {
+        None
+    };
+    let chunk_plaintext = compressed.as_deref().unwrap_or(&data);
+
+    let upload_dir = &state.config.upload_dir;
+    tokio::fs::create_dir_all(upload_dir).await.ok();
+
+    // A re-sent chunk (e.g. after a resume) replaces the earlier copy and
+    // must not be counted twice.
+    let resent = metadata.has_chunk(chunk_idx);
+    let previous_hash = metadata.chunk_hashes[chunk_idx].clone();
+    let session_chunk_filename = format!("{}_{}.encrypted_chunk", session_id, chunk_idx);
+    let previous_bytes = if resent {
+        let previous_filename = match &previous_hash {
+            Some(hash) => chunk_store::chunk_filename(hash),
+            None => session_chunk_filename.clone(),
+        };
+        tokio::fs::metadata(upload_dir.join(previous_filename))
+            .await
+            .map(|m| m.len() as i64)
+            .unwrap_or(0)
+    } else {
+        0
+    };
 
-    tracing::debug!(
-        "🔐 Encrypting chunk {} ({} bytes) with DEK...",
-        chunk_idx,
-        data.len()
-    );
-    let (chunk_encrypted, actual_nonce) =
-        crate::crypto::aes::encrypt(&dek_array, &data).map_err(|e| {
-            tracing::error!(
-                "❌ Failed to encrypt chunk {}: {}",
-                chunk_idx,
+    let (actual_nonce, size_encrypted, content_hash) = if state.config.chunk_dedup_enabled {
+        let stored = chunk_store::store_chunk(&state, user_id, &dek_array, chunk_plaintext)
+            .await
+            .map_err(|e| {
+                tracing::error!("❌ Failed to store chunk {}: {}", chunk_idx, e);
                 e
-            );
-            e
-        })?;
-
-    tracing::debug!(
-        "✅ Chunk {} encrypted: {} bytes → {} bytes (nonce: {:?})",
-        chunk_idx,
-        data.len(),
-        chunk_encrypted.len(),
-        &actual_nonce[..4]
-    );
-
-    tracing::debug!("💾 Saving encrypted chunk {} to disk...", chunk_idx);
-
-    let upload_dir = PathBuf::from("uploads/files");
-    tokio::fs::create_dir_all(&upload_dir).await.ok();
+            })?;
 
-    let chunk_filename = format!("{}_{}.encrypted_chunk", session_id, chunk_idx);
-    let chunk_path = upload_dir.join(&chunk_filename);
-
-    let file = tokio::fs::File::create(&chunk_path).await.map_err(|e| {
-        tracing::error!(
-            "❌ Failed to create chunk file {}: {}",
-            chunk_filename,
-            e
+        tracing::debug!(
+            "✅ Chunk {} stored as {} ({})",
+            chunk_idx,
+            stored.content_hash,
+            if stored.reused { "deduplicated" } else { "new" }
         );
-        AppError::Io(e)
-    })?;
-
-    let mut writer = BufWriter::with_capacity(dynamic_buffer, file);
-
-    writer.write_all(&chunk_encrypted).await.map_err(|e| {
-        tracing::error!(
-            "❌ Failed to write chunk {}: {}",
-            chunk_filename,
-            e
-        );
-        AppError::Io(e)
-    })?;
-
-    writer.flush().await.map_err(|e| {
-        tracing::error!(
-            "❌ Failed to flush chunk {}: {}",
-            chunk_filename,
-            e
+        (stored.nonce, stored.size_encrypted, Some(stored.content_hash))
+    } else {
+        let chunk_path = upload_dir.join(&session_chunk_filename);
+        let (size_encrypted, actual_nonce) =
+            write_session_chunk(&dek_array, chunk_plaintext, &chunk_path, dynamic_buffer).await?;
+
+        tracing::debug!(
+            "✅ Chunk {} saved to disk: {}",
+            chunk_idx,
+            session_chunk_filename
         );
-        AppError::Io(e)
-    })?;
-
-    drop(writer);
+        (actual_nonce, size_encrypted, None)
+    };
 
-    tracing::debug!(
-        "✅ Chunk {} saved to disk: {}",
-        chunk_idx,
-        chunk_filename
-    );
+    if resent {
+        match &previous_hash {
+            Some(hash) => {
+                if let Err(e) = chunk_store::release_chunk(&state, hash).await {
+                    tracing::warn!("⚠️ Failed to release replaced chunk {}: {}", hash, e);
+                }
+            }
+            None if content_hash.is_some() => {
+                tokio::fs::remove_file(upload_dir.join(&session_chunk_filename)).await.ok();
+            }
+            None => {}
+        }
+    }
 
     tracing::debug!("📝 Updating metadata in Redis...");
 
     metadata.chunk_nonces[chunk_idx] = actual_nonce;
-    metadata.chunks_received_count += 1;
-    metadata.chunks_written_bytes += chunk_encrypted.len() as i64;
+    metadata.chunk_compressed[chunk_idx] = compressed.is_some();
+    metadata.chunk_hashes[chunk_idx] = content_hash;
+    if resent {
+        tracing::debug!("♻️ Chunk {} re-sent, replacing the stored copy", chunk_idx);
+    } else {
+        metadata.chunks_received_count += 1;
+    }
+    metadata.chunks_written_bytes += size_encrypted - previous_bytes;
 
     let updated_bytes = bincode::encode_to_vec(&metadata, config).map_err(|e| {
         tracing::error!(
@@ -482,7 +977,7 @@ pub async fn upload_chunk(
     })?;
 
     let _: () = redis
-        .set_ex(&redis_key, &updated_bytes, UPLOAD_EXPIRATION_SECS)
+        .set_ex(&redis_key, &updated_bytes, metadata.ttl_secs(state.config.upload_expiration_secs))
         .await
         .map_err(|e| {
             tracing::error!(
@@ -492,6 +987,20 @@ pub async fn upload_chunk(
             AppError::Redis(e)
         })?;
 
+    touch_upload_session(&mut redis, user_id, &session_id, state.config.upload_expiration_secs).await;
+    crate::metrics::record_upload_chunk(data.len());
+    upload_progress::publish(
+        &state,
+        &session_id,
+        upload_progress::progress_message(
+            &session_id,
+            metadata.chunks_received_count,
+            metadata.total_chunks,
+            metadata.chunks_written_bytes,
+        ),
+    )
+    .await;
+
     tracing::debug!(
         "✅ Metadata updated: {}/{}",
         metadata.chunks_received_count,
@@ -504,9 +1013,11 @@ pub async fn upload_chunk(
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "chunk_index": chunk_idx,
         "chunk_size_plaintext": data.len(),
-        "chunk_size_encrypted": chunk_encrypted.len(),
+        "chunk_size_encrypted": size_encrypted,
+        "compressed": compressed.is_some(),
         "chunks_received": metadata.chunks_received_count,
         "total_chunks": metadata.total_chunks,
+        "replaced_existing": resent,
         "progress_percentage": format!("{:.2}", progress_percentage)
     }))
     .map_err(|e| {
@@ -525,9 +1036,199 @@ pub async fn upload_chunk(
         progress_percentage
     );
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Encrypts a chunk with a fresh nonce and writes it to its session-named
+/// file, overwriting any earlier copy.
+///
+/// # Returns
+///
+/// The size of the encrypted chunk and its nonce.
+async fn write_session_chunk(
+    dek: &[u8; 32],
+    chunk_plaintext: &[u8],
+    chunk_path: &std::path::Path,
+    buffer_size: usize,
+) -> Result<(i64, [u8; 12])> {
+    tracing::debug!("🔐 Encrypting chunk ({} bytes) with DEK...", chunk_plaintext.len());
+    let (chunk_encrypted, nonce) = crate::crypto::aes::encrypt(dek, chunk_plaintext).map_err(|e| {
+        tracing::error!("❌ Failed to encrypt chunk {:?}: {}", chunk_path, e);
+        e
+    })?;
+
+    tracing::debug!("💾 Saving encrypted chunk to {:?}...", chunk_path);
+
+    let file = tokio::fs::File::create(chunk_path).await.map_err(|e| {
+        tracing::error!("❌ Failed to create chunk file {:?}: {}", chunk_path, e);
+        AppError::Io(e)
+    })?;
+
+    let mut writer = BufWriter::with_capacity(buffer_size, file);
+
+    writer.write_all(&chunk_encrypted).await.map_err(|e| {
+        tracing::error!("❌ Failed to write chunk {:?}: {}", chunk_path, e);
+        AppError::Io(e)
+    })?;
+
+    writer.flush().await.map_err(|e| {
+        tracing::error!("❌ Failed to flush chunk {:?}: {}", chunk_path, e);
+        AppError::Io(e)
+    })?;
+
+    Ok((chunk_encrypted.len() as i64, nonce))
+}
+
+/// Compresses a chunk's plaintext with zstd.
+///
+/// # Returns
+///
+/// The compressed bytes, or `None` if compression failed or did not shrink
+/// the chunk, in which case it is stored as-is.
+fn compress_chunk(data: &[u8], chunk_idx: usize) -> Option<Vec<u8>> {
+    match zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL) {
+        Ok(compressed) if compressed.len() < data.len() => {
+            tracing::debug!(
+                "🗜️ Chunk {} compressed: {} bytes → {} bytes",
+                chunk_idx,
+                data.len(),
+                compressed.len()
+            );
+            Some(compressed)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("⚠️ Failed to compress chunk {}, storing it as-is: {}", chunk_idx, e);
+            None
+        }
+    }
+}
+
+/// Scans a completed upload with clamd before it becomes a file.
+///
+/// An infected upload is cleaned up and rejected. When clamd cannot be reached
+/// or errors, the upload is rejected (chunks kept, so finalize can be retried)
+/// unless `ANTIVIRUS_FAIL_OPEN` is set.
+async fn scan_upload_for_malware(
+    state: &AppState,
+    dek: &[u8; 32],
+    user_id: Uuid,
+    upload_session_id: &str,
+    metadata: &UploadMetadata,
+    chunks: &[ChunkInfo],
+) -> Result<()> {
+    tracing::info!("🦠 Scanning upload {} with clamd", upload_session_id);
+
+    let upload_dir = &state.config.upload_dir;
+    match crate::services::antivirus::scan_encrypted_chunks(&state.config, dek, upload_dir, chunks).await {
+        Ok(ScanVerdict::Clean) => {
+            tracing::info!("✅ Upload {} is clean", upload_session_id);
+            Ok(())
+        }
+        Ok(ScanVerdict::Infected(signature)) => {
+            tracing::warn!(
+                "🦠 Upload {} from user {} rejected: {}",
+                upload_session_id,
+                user_id,
+                signature
+            );
+            cleanup_failed_upload(state, user_id, upload_session_id, metadata).await?;
+            Err(AppError::Validation(format!(
+                "Upload rejected: malware detected ({})",
+                signature
+            )))
+        }
+        Err(e) if state.config.antivirus_fail_open => {
+            tracing::warn!(
+                "⚠️ Antivirus scan of {} failed, accepting upload (fail-open): {}",
+                upload_session_id,
+                e
+            );
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("❌ Antivirus scan of {} failed: {}", upload_session_id, e);
+            Err(AppError::Internal(format!("Antivirus scan unavailable: {}", e)))
+        }
+    }
+}
+
+/// Decrypts an upload's chunks with the session DEK and hashes the plaintext.
+///
+/// The algorithm is the one of the client's `expected_hash`, or the configured
+/// default when none was given. Fails if the digest differs from
+/// `expected_hash` or the plaintext size differs from the declared size.
+///
+/// # Returns
+///
+/// The tagged checksum to store with the file.
+async fn compute_upload_checksum(
+    state: &AppState,
+    dek: &[u8; 32],
+    metadata: &UploadMetadata,
+    chunks: &[ChunkInfo],
+) -> Result<String> {
+    let expected = metadata
+        .expected_hash
+        .as_deref()
+        .map(Checksum::parse_stored)
+        .transpose()?;
+    let algorithm = expected
+        .as_ref()
+        .map_or(state.config.checksum_algorithm, |checksum| checksum.algorithm);
+
+    let blocking_decrypt = state.config.decrypt_on_blocking_pool(metadata.total_size);
+    let mut hasher = ChecksumHasher::new(algorithm);
+    let mut plaintext_bytes = 0i64;
+
+    for chunk_info in chunks {
+        let chunk_plaintext = read_decrypted_chunk(&state.config.upload_dir, dek, chunk_info, blocking_decrypt).await?;
+        plaintext_bytes += chunk_plaintext.len() as i64;
+        hasher.update(&chunk_plaintext);
+    }
+
+    if plaintext_bytes != metadata.total_size {
+        tracing::warn!(
+            "❌ Upload {} holds {} bytes, {} declared",
+            metadata.upload_session_id,
+            plaintext_bytes,
+            metadata.total_size
+        );
+        return Err(AppError::Validation(format!(
+            "Uploaded data is {} bytes but file_size was {}",
+            plaintext_bytes, metadata.total_size
+        )));
+    }
+
+    let actual = hasher.finalize();
+    if let Some(expected) = expected
+        && expected != actual
+    {
+        tracing::warn!(
+            "❌ Checksum mismatch for upload {}: expected {}, got {}",
+            metadata.upload_session_id,
+            expected,
+            actual
+        );
+        return Err(AppError::Validation(
+            "Uploaded data does not match expected_hash".to_string(),
+        ));
+    }
+
+    Ok(actual.to_string())
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/files/upload/finalize",
+    tag = "files",
+    request_body = FinalizeUploadRequest,
+    responses(
+        (status = 200, description = "Upload finalized and quota debited"),
+        (status = 400, description = "Incomplete upload, missing chunk files, size or checksum mismatch, or unknown session"),
+        (status = 507, description = "Storage quota exceeded")
+    )
+)]
 pub async fn finalize_upload(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -543,21 +1244,7 @@ pub async fn finalize_upload(
 
     let mut redis = state.redis.clone();
     let redis_key = format!("upload:{}:{}", user_id, req.upload_session_id);
-    let config = bincode::config::standard();
-
-    let metadata_bytes: Vec<u8> = redis
-        .get(&redis_key)
-        .await
-        .map_err(|e| {
-            tracing::error!("Redis GET error: {}", e);
-            AppError::Redis(e)
-        })?;
-
-    let (metadata, _): (UploadMetadata, usize) =
-        bincode::decode_from_slice(&metadata_bytes, config).map_err(|e| {
-            tracing::error!("Bincode decode failed: {}", e);
-            AppError::Internal(format!("Bincode decode failed: {}", e))
-        })?;
+    let metadata = load_upload_metadata(&mut redis, &redis_key).await?;
 
     if metadata.chunks_received_count != metadata.total_chunks {
         tracing::error!(
@@ -572,19 +1259,60 @@ pub async fn finalize_upload(
         )));
     }
 
-    let client = state.db.get().await?;
+    // The count alone does not prove the chunk files survived on disk; check
+    // them before any quota is debited or a files row is written.
+    let upload_dir = &state.config.upload_dir;
+    let mut missing_chunks = Vec::new();
+    for idx in 0..metadata.total_chunks {
+        let chunk_path = upload_dir.join(metadata.chunk_filename(idx));
+        let present = tokio::fs::metadata(&chunk_path)
+            .await
+            .is_ok_and(|m| m.is_file());
+        if !present || !metadata.has_chunk(idx) {
+            missing_chunks.push(idx);
+        }
+    }
+
+    if !missing_chunks.is_empty() {
+        tracing::error!(
+            "❌ Upload {} is missing chunk files {:?}",
+            req.upload_session_id,
+            missing_chunks
+        );
+        cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
+        return Err(AppError::Validation(format!(
+            "Upload is missing chunks {:?}; the upload was discarded",
+            missing_chunks
+        )));
+    }
+
+    let mut client = state.db.get().await?;
     let (storage_quota_bytes, storage_used_bytes) =
         repositories::user::get_user_storage_info(&client, &user_id, &state.stmt_cache).await?;
 
+    let conflict = req.conflict.unwrap_or(state.config.upload_conflict_policy);
+
     let available_space = storage_quota_bytes - storage_used_bytes;
-    if metadata.total_size > available_space {
+    if !metadata.quota_reserved && metadata.total_size > available_space {
         cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
-        return Err(AppError::Validation(format!(
-            "Insufficient storage quota at finalization. Required: {} bytes, Available: {} bytes",
-            metadata.total_size, available_space
-        )));
+        return Err(AppError::QuotaExceeded {
+            required: metadata.total_size,
+            available: available_space,
+        });
     }
 
+    let user_dek = match session_service::open_session_dek(&state, &session).await {
+        Ok(dek) => dek,
+        Err(e) => {
+            tracing::error!("User DEK not available in session for user {}: {}", user_id, e);
+            // An outdated session only needs a fresh login; keep the chunks for the retry.
+            if !matches!(e, AppError::Authentication(_)) {
+                cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
+            }
+            return Err(e);
+        }
+    };
+
     let file_id = Uuid::new_v4();
     let mut chunks_data: Vec<ChunkInfo> = Vec::new();
 
@@ -592,28 +1320,54 @@ pub async fn finalize_upload(
         chunks_data.push(ChunkInfo::new(
             idx,
             *nonce,
-            format!("{}_{}.encrypted_chunk", req.upload_session_id, idx),
-            CHUNK_SIZE as i64,
+            metadata.chunk_filename(idx),
+            metadata.chunk_size as i64,
+            metadata.chunk_compressed.get(idx).copied().unwrap_or(false),
+            metadata.expected_chunk_len(idx) as i64,
         ));
     }
 
-    let chunks_bytes = bincode::encode_to_vec(&chunks_data, bincode::config::standard())
-        .map_err(|e| AppError::Internal(format!("Bincode encode failed: {}", e)))?;
+    if state.config.antivirus_enabled {
+        scan_upload_for_malware(&state, &user_dek, user_id, &req.upload_session_id, &metadata, &chunks_data)
+            .await?;
+    }
+
+    let chunks_bytes = ChunkInfo::encode_list(&chunks_data)?;
 
     tracing::info!("✅ Chunks metadata encoded: {} bytes", chunks_bytes.len());
 
-    let user_dek = session.dek.clone();
+    let mime_type = match &metadata.mime_type {
+        Some(mime_type) => mime_type.clone(),
+        None => sniff_mime_type(&state.config.upload_dir, &user_dek, chunks_data.first()).await,
+    };
 
-    if user_dek.is_empty() {
-        tracing::error!("User DEK not available in session for user: {}", user_id);
-        cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
-        return Err(AppError::Encryption(
-            "User DEK not available in session".to_string(),
-        ));
-    }
+    let checksum = if state.config.verify_checksum_on_finalize {
+        match compute_upload_checksum(&state, &user_dek, &metadata, &chunks_data).await {
+            Ok(checksum) => Some(checksum),
+            Err(e) => {
+                cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
+                return Err(e);
+            }
+        }
+    } else {
+        metadata.expected_hash.clone()
+    };
 
-    let (kek_version, kek_bytes) = state.kek_cache.get(1).await.map(|k| (1, k)).ok_or_else(|| {
-        tracing::error!("Failed to get active KEK");
+    let kek_version = crate::crypto::kek::active_kek_version(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get active KEK version: {}", e);
+            AppError::Encryption("Failed to get active KEK".to_string())
+        })?;
+    let kek_bytes = crate::crypto::kek::load_kek(
+        &state.db,
+        state.config.master_key.as_ref(),
+        &state.kek_cache,
+        kek_version,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to get active KEK: {}", e);
         AppError::Encryption("Failed to get active KEK".to_string())
     })?;
 
@@ -625,7 +1379,7 @@ pub async fn finalize_upload(
             AppError::Encryption("Invalid KEK size".to_string())
         })?;
 
-    let (encrypted_dek, dek_nonce) = crate::crypto::aes::encrypt(&kek_array, &user_dek)
+    let (encrypted_dek, dek_nonce) = crate::crypto::aes::encrypt(&kek_array, user_dek.as_slice())
         .map_err(|e| {
             tracing::error!("Failed to encrypt user DEK: {}", e);
             e
@@ -633,30 +1387,56 @@ pub async fn finalize_upload(
 
     tracing::debug!("DEK encrypted successfully with KEK version {}", kek_version);
 
-    repositories::file::create_file(
-        &client,
-        file_id,
+    // Debits the quota in the same transaction; another finalize may have
+    // used the space since the check above.
+    let new_file = NewFile {
+        id: file_id,
         user_id,
-        metadata.filename.clone(),
-        metadata.total_chunks as i32,
-        chunks_bytes,
+        folder_id: req.folder_id,
+        original_filename: metadata.filename.clone(),
+        total_chunks: metadata.total_chunks as i32,
+        chunks_metadata: chunks_bytes,
         encrypted_dek,
-        dek_nonce.to_vec(),
-        kek_version,
-        metadata.total_size,
-        Some("application/octet-stream".to_string()),
-        metadata.expected_hash.clone(),
+        nonce: dek_nonce.to_vec(),
+        dek_version: kek_version,
+        file_size: metadata.total_size,
+        mime_type: Some(mime_type),
+        checksum,
+    };
+    let created = repositories::file::create_file(
+        &mut client,
+        new_file,
+        conflict,
+        metadata.quota_reserved,
         &state.stmt_cache,
     )
-    .await?;
+    .await;
+    let (file, replaced_files) = match created {
+        Err(e @ AppError::QuotaExceeded { .. }) => {
+            cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
+            return Err(e);
+        }
+        created => created?,
+    };
 
-    repositories::user::update_storage_with_quota_check(
-        &client,
-        &user_id,
-        metadata.total_size,
-        &state.stmt_cache,
-    )
-    .await?;
+    state.folder_cache.invalidate_user(user_id).await;
+
+    if !replaced_files.is_empty() {
+        tracing::info!(
+            "♻️ Upload {} replaced {} existing file(s) named {:?}",
+            file_id,
+            replaced_files.len(),
+            metadata.filename
+        );
+    }
+
+    if state.config.hard_delete_on_delete {
+        for (replaced_id, chunks_metadata) in &replaced_files {
+            if let Some(chunks_metadata) = chunks_metadata {
+                crate::services::files::spawn_chunk_reclaim(state.clone(), *replaced_id, chunks_metadata.clone());
+            }
+        }
+    }
 
     tracing::info!(
         "⚡ Upload finalized successfully: File {} with {} chunks (quota debited)",
@@ -665,13 +1445,25 @@ pub async fn finalize_upload(
     );
 
     let _ = redis.del::<_, ()>(&redis_key).await.ok();
-    let lock_key = format!("user_uploading:{}", user_id);
-    let _ = redis.del::<_, ()>(&lock_key).await.ok();
+    unregister_upload_session(&mut redis, user_id, &req.upload_session_id).await;
+    upload_progress::publish(
+        &state,
+        &req.upload_session_id,
+        upload_progress::closed_message(&req.upload_session_id, "finalized", Some(file_id)),
+    )
+    .await;
+    events::publish(&state, user_id, "upload_completed", sonic_rs::json!({
+        "file_id": file_id.to_string(),
+        "filename": file.original_filename,
+        "size_bytes": metadata.total_size
+    }));
+    events::check_quota(&state, user_id, metadata.total_size);
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "message": "Upload finalized successfully",
         "file_id": file_id.to_string(),
-        "filename": metadata.filename,
+        "filename": file.original_filename,
+        "replaced_file_ids": replaced_files.iter().map(|(id, _)| id.to_string()).collect::<Vec<_>>(),
         "total_chunks": metadata.total_chunks,
         "size_bytes": metadata.total_size,
         "ready_for_download": true,
@@ -682,9 +1474,19 @@ pub async fn finalize_upload(
         AppError::Internal(format!("Response serialization failed: {}", e))
     })?;
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/files/upload/cancel",
+    tag = "files",
+    request_body = CancelUploadRequest,
+    responses(
+        (status = 200, description = "Upload canceled and chunks removed"),
+        (status = 400, description = "Unknown or expired upload session")
+    )
+)]
 pub async fn cancel_upload(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -700,46 +1502,216 @@ pub async fn cancel_upload(
 
     let mut redis = state.redis.clone();
     let redis_key = format!("upload:{}:{}", user_id, req.upload_session_id);
-    let config = bincode::config::standard();
-
-    let metadata_bytes: Vec<u8> = redis
-        .get(&redis_key)
-        .await
-        .map_err(|e| AppError::Redis(e))?;
-
-    let (metadata, _): (UploadMetadata, usize) =
-        bincode::decode_from_slice(&metadata_bytes, config)
-            .map_err(|e| AppError::Internal(format!("Bincode decode failed: {}", e)))?;
+    let metadata = load_upload_metadata(&mut redis, &redis_key).await?;
 
     cleanup_failed_upload(&state, user_id, &req.upload_session_id, &metadata).await?;
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "message": "Upload canceled successfully",
-        "quota_released": 0
+        "quota_released": if metadata.quota_reserved { metadata.total_size } else { 0 }
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Reads the upload sessions of `user_id` still held in Redis, with their keys.
+///
+/// Sessions that cannot be decoded are skipped.
+async fn user_upload_sessions(state: &AppState, user_id: Uuid) -> Result<Vec<(String, UploadMetadata)>> {
+    let mut redis = state.redis.clone();
+    let pattern = format!("upload:{}:*", user_id);
+
+    let mut cursor = 0u64;
+    let mut sessions = Vec::new();
+
+    loop {
+        let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(100)
+            .query_async(&mut redis)
+            .await?;
+
+        for key in keys {
+            let Some(metadata_bytes) = redis.get::<_, Option<Vec<u8>>>(&key).await? else {
+                continue;
+            };
+
+            let Ok((metadata, _)) = bincode::decode_from_slice::<UploadMetadata, _>(
+                &metadata_bytes,
+                bincode::config::standard(),
+            ) else {
+                tracing::warn!("⚠️ Skipping undecodable upload session {}", key);
+                continue;
+            };
+
+            sessions.push((key, metadata));
+        }
+
+        cursor = new_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    Ok(sessions)
+}
+
+/// Lists the caller's in-progress upload sessions so a restarted client can
+/// resume or cancel them.
+#[utoipa::path(
+    get,
+    path = "/api/files/upload/active",
+    tag = "files",
+    responses(
+        (status = 200, description = "The caller's in-progress upload sessions")
+    )
+)]
+pub async fn list_active_uploads(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let mut redis = state.redis.clone();
+    let mut uploads = Vec::new();
+
+    for (key, metadata) in user_upload_sessions(&state, user_id).await? {
+        let expires_in_secs: i64 = redis.ttl(&key).await?;
+        let progress_percent = if metadata.total_chunks > 0 {
+            metadata.chunks_received_count as f64 * 100.0 / metadata.total_chunks as f64
+        } else {
+            0.0
+        };
+
+        uploads.push(sonic_rs::json!({
+            "upload_session_id": metadata.upload_session_id,
+            "filename": metadata.filename,
+            "total_size": metadata.total_size,
+            "total_chunks": metadata.total_chunks,
+            "chunks_received": metadata.chunks_received_count,
+            "bytes_received": metadata.chunks_written_bytes,
+            "progress_percent": progress_percent,
+            "created_at": chrono::DateTime::from_timestamp(metadata.created_at, 0)
+                .map(|t| t.to_rfc3339()),
+            "expires_in_secs": expires_in_secs
+        }));
+    }
+
+    tracing::info!("📋 {} active uploads for user {}", uploads.len(), user_id);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "count": uploads.len(),
+        "uploads": uploads
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Reports which chunks of an in-progress upload have arrived, so an
+/// interrupted client can resume by sending only the missing indices.
+#[utoipa::path(
+    get,
+    path = "/api/files/upload/status",
+    tag = "files",
+    params(UploadStatusQuery),
+    responses(
+        (status = 200, description = "Received and missing chunk indices of the upload session"),
+        (status = 400, description = "Unknown or expired upload session")
+    )
+)]
+pub async fn upload_status(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Query(query): Query<UploadStatusQuery>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let mut redis = state.redis.clone();
+    let redis_key = format!("upload:{}:{}", user_id, query.upload_session_id);
+    let metadata = load_upload_metadata(&mut redis, &redis_key).await?;
+
+    let received_chunks = metadata.received_chunks();
+    let missing_chunks: Vec<usize> = (0..metadata.total_chunks)
+        .filter(|&idx| !metadata.has_chunk(idx))
+        .collect();
+    let expires_in_secs: i64 = redis.ttl(&redis_key).await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "upload_session_id": metadata.upload_session_id,
+        "filename": metadata.filename,
+        "total_size": metadata.total_size,
+        "total_chunks": metadata.total_chunks,
+        "chunks_received_count": metadata.chunks_received_count,
+        "received_chunks": received_chunks,
+        "missing_chunks": missing_chunks,
+        "chunk_size_bytes": metadata.chunk_size,
+        "expires_in_secs": expires_in_secs
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/files",
+    tag = "files",
+    params(ListFilesQuery),
+    responses(
+        (status = 200, description = "Page of the user's files matching the filters"),
+        (status = 400, description = "Invalid folder_id, search or page parameters")
+    )
+)]
 pub async fn list_files(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
-    Query(params): Query<ListFilesQuery>,
+    Query(query): Query<ListFilesQuery>,
 ) -> Result<impl IntoResponse> {
     let user_id = session.user_id;
+    let params = query.page()?;
+    let filter = query.filter()?;
+    let order = query.order();
+    let start = query.start()?;
 
-    tracing::debug!("📂 Listing files - limit: {}, offset: {}", params.limit, params.offset);
+    tracing::debug!(
+        "📂 Listing files - limit: {}, offset: {}, filter: {:?}, order: {:?}",
+        params.limit,
+        params.offset,
+        filter,
+        order
+    );
 
     let client = state.db.get().await?;
-    let files = repositories::file::list_user_files(
+    // One extra row tells whether another page follows without a second query.
+    let mut files = repositories::file::list_user_files(
         &client,
         user_id,
-        params.limit,
-        params.offset,
+        &filter,
+        order,
+        params.limit + 1,
+        start,
         &state.stmt_cache,
     )
     .await?;
+    let has_next = files.len() as i64 > params.limit;
+    files.truncate(params.limit as usize);
+
+    let total = repositories::file::count_user_files(&client, user_id, &filter, &state.stmt_cache).await?;
+    let pagination = match start {
+        PageStart::Offset(_) => Pagination::new(params, files.len(), total),
+        PageStart::After(_) => Pagination {
+            limit: params.limit,
+            offset: 0,
+            total,
+            has_more: has_next,
+        },
+    };
+    let next_cursor = (has_next && order == FileOrder::default())
+        .then(|| files.last().map(|f| FileCursor::after(f).encode()))
+        .flatten();
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "files": files.iter().map(|f| sonic_rs::json!({
@@ -750,13 +1722,37 @@ pub async fn list_files(
             "uploaded_at": f.uploaded_at.to_rfc3339(),
             "access_count": f.access_count.unwrap_or(0)
         })).collect::<Vec<_>>(),
-        "count": files.len()
+        "count": files.len(),
+        "pagination": pagination,
+        "next_cursor": next_cursor
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Guesses a file's MIME type from the magic bytes of its first chunk.
+///
+/// Falls back to [`DEFAULT_MIME_TYPE`] for empty files, unknown formats or a
+/// chunk that cannot be read; finalizing never fails because of sniffing.
+async fn sniff_mime_type(upload_dir: &std::path::Path, dek: &[u8; 32], first_chunk: Option<&ChunkInfo>) -> String {
+    let Some(chunk_info) = first_chunk else {
+        return DEFAULT_MIME_TYPE.to_string();
+    };
+
+    match read_decrypted_chunk(upload_dir, dek, chunk_info, false).await {
+        Ok(plaintext) => infer::get(&plaintext)
+            .map_or(DEFAULT_MIME_TYPE, |kind| kind.mime_type())
+            .to_string(),
+        Err(e) => {
+            tracing::warn!("⚠️ Could not read the first chunk to sniff its MIME type: {}", e);
+            DEFAULT_MIME_TYPE.to_string()
+        }
+    }
 }
 
+/// Sanitizes a stored filename for use inside a quoted `Content-Disposition`
+/// parameter.
 fn sanitize_filename(filename: &str) -> String {
     filename
         .chars()
@@ -769,59 +1765,57 @@ fn sanitize_filename(filename: &str) -> String {
         .collect()
 }
 
-pub async fn download_file(
-    State(state): State<AppState>,
-    Extension(session): Extension<Session>,
-    Path(file_id): Path<Uuid>,
-) -> Result<impl IntoResponse> {
-    let user_id = session.user_id;
-
-    tracing::info!("📥 Download file {} (STREAMING MODE)", file_id);
-
-    let mut redis = state.redis.clone();
-
-    let lock_key = format!("user_downloading:{}", user_id);
-    let exists_count: i64 = redis.exists(&lock_key).await.map_err(|e| AppError::Redis(e))?;
-    if exists_count > 0 {
-        return Err(AppError::Validation(
-            "Já há um download ativo para este usuário. Aguarde a conclusão.".to_string(),
-        ));
+/// Builds an `attachment` `Content-Disposition` header for a stored filename.
+///
+/// Emits an ASCII-only `filename` fallback for old clients plus an RFC 5987
+/// `filename*` parameter carrying the exact UTF-8 name.
+pub(crate) fn attachment_disposition(filename: &str) -> axum::http::HeaderValue {
+    let ascii_fallback: String = sanitize_filename(filename)
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+
+    let mut encoded = String::with_capacity(filename.len() * 3);
+    for byte in filename.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+            | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded.push(*byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
     }
 
-    let _: () = redis
-        .set_ex(&lock_key, "locked", DOWNLOAD_EXPIRATION_SECS)
-        .await
-        .map_err(|e| AppError::Redis(e))?;
+    format!(
+        r#"attachment; filename="{}"; filename*=UTF-8''{}"#,
+        ascii_fallback, encoded
+    )
+    .parse()
+    .unwrap_or_else(|_| axum::http::HeaderValue::from_static("attachment"))
+}
 
-    let _permit = state.download_limiter.acquire().await;
+/// Decodes the bincode-encoded chunk list stored alongside a finalized file.
+pub(crate) fn decode_chunks_metadata(file: &crate::models::file::File) -> Result<Vec<ChunkInfo>> {
+    let chunks_metadata_raw = file
+        .chunks_metadata
+        .as_ref()
+        .ok_or(AppError::Internal("Missing chunks_metadata".into()))?;
 
-    let available = state.download_limiter.available_permits();
-    let total_slots = DOWNLOAD_BUFFER_SLOTS;
-    let concurrent_downloads = total_slots.saturating_sub(available);
-    let buffer_chunks = std::cmp::max(1usize, total_slots / (concurrent_downloads.max(1) + 1));
-
-    tracing::info!("⏳ Download buffer: {} chunks (concurrent: {}, available: {})", buffer_chunks, concurrent_downloads, available);
-
-    let client = state.db.get().await?;
-    let file = repositories::file::find_by_id(&client, file_id, user_id, &state.stmt_cache)
-        .await?
-        .ok_or(AppError::NotFound)?;
-
-    let chunks_metadata_raw = file
-        .chunks_metadata
-        .ok_or(AppError::Internal("Missing chunks_metadata".into()))?;
-
-    let (chunks_data, _): (Vec<ChunkInfo>, usize) =
-        bincode::decode_from_slice(&chunks_metadata_raw, bincode::config::standard())
-            .map_err(|e| AppError::Internal(format!("Bincode decode failed: {}", e)))?;
-
-    let chunks_count = chunks_data.len();
-
-    tracing::info!("✅ Decoded {} chunks from metadata", chunks_count);
+    ChunkInfo::decode_list(chunks_metadata_raw)
+}
 
+/// Unwraps a file's DEK with the KEK version it was encrypted under.
+pub(crate) async fn decrypt_file_dek(state: &AppState, file: &crate::models::file::File) -> Result<[u8; 32]> {
     let kek_version = file.dek_version;
-    let kek_bytes = state.kek_cache.get(kek_version).await.ok_or_else(|| {
-        tracing::error!("Failed to get KEK: {}", kek_version);
+    let kek_bytes = crate::crypto::kek::load_kek(
+        &state.db,
+        state.config.master_key.as_ref(),
+        &state.kek_cache,
+        kek_version,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to get KEK {}: {}", kek_version, e);
         AppError::Encryption("Failed to get KEK".to_string())
     })?;
 
@@ -836,82 +1830,522 @@ pub async fn download_file(
         .try_into()
         .map_err(|_| AppError::Encryption("Invalid nonce size".into()))?;
 
-    let dek_encrypted = file.encrypted_dek;
-    let dek = crate::crypto::aes::decrypt(&kek_array, &dek_encrypted, &dek_nonce)
+    let dek = crate::crypto::aes::decrypt(&kek_array, &file.encrypted_dek, &dek_nonce)
         .map_err(|e| {
             tracing::error!("Failed to decrypt DEK: {}", e);
             e
         })?;
 
-    let dek_array: [u8; 32] = dek
-        .as_slice()
+    dek.as_slice()
         .try_into()
-        .map_err(|_| AppError::Encryption("Invalid DEK size".into()))?;
+        .map_err(|_| AppError::Encryption("Invalid DEK size".into()))
+}
+
+/// Deletes a lock key only if it still holds the caller's token, so a lock
+/// that expired and was taken by another download is left alone.
+const RELEASE_LOCK_SCRIPT: &str =
+    "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+/// Holds a user's `user_downloading` lock and releases it when dropped.
+///
+/// Moved into the download body stream, so the lock is released when the
+/// transfer completes, fails mid-stream or the client disconnects.
+pub(crate) struct DownloadLockGuard {
+    redis: redis::aio::ConnectionManager,
+    key: String,
+    token: String,
+}
+
+/// Takes the user's `user_downloading` lock, allowing one download at a time.
+///
+/// The lock is taken with a single `SET NX`, so two concurrent downloads
+/// cannot both acquire it, and stores a random token that the guard checks
+/// before releasing it.
+///
+/// # Returns
+///
+/// A guard that releases the lock when dropped, or `Validation` if another
+/// download of the user is still running.
+pub(crate) async fn acquire_download_lock(state: &AppState, user_id: Uuid) -> Result<DownloadLockGuard> {
+    let mut redis = state.redis.clone();
+
+    let lock_key = format!("user_downloading:{}", user_id);
+    let token = Uuid::new_v4().to_string();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(&lock_key)
+        .arg(&token)
+        .arg("NX")
+        .arg("EX")
+        .arg(DOWNLOAD_EXPIRATION_SECS)
+        .query_async(&mut redis)
+        .await
+        .map_err(AppError::Redis)?;
+
+    if acquired.is_none() {
+        return Err(AppError::Validation(
+            "A download is already in progress for this user. Wait for it to finish.".to_string(),
+        ));
+    }
+
+    Ok(DownloadLockGuard { redis, key: lock_key, token })
+}
+
+impl Drop for DownloadLockGuard {
+    fn drop(&mut self) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let mut redis = self.redis.clone();
+        let key = std::mem::take(&mut self.key);
+        let token = std::mem::take(&mut self.token);
+        handle.spawn(async move {
+            let released: redis::RedisResult<i64> = redis::cmd("EVAL")
+                .arg(RELEASE_LOCK_SCRIPT)
+                .arg(1)
+                .arg(&key)
+                .arg(&token)
+                .query_async(&mut redis)
+                .await;
+            if let Err(e) = released {
+                tracing::warn!("Failed to release download lock {}: {}", key, e);
+            }
+        });
+    }
+}
+
+/// Decrypts a chunk read from disk and decompresses it if it was stored
+/// compressed.
+pub(crate) fn open_chunk(dek: &[u8; 32], chunk_info: &ChunkInfo, chunk_encrypted: &[u8]) -> Result<Vec<u8>> {
+    let decrypted = crate::crypto::aes::decrypt(dek, chunk_encrypted, &chunk_info.nonce)?;
+    chunk_info.decompress(decrypted)
+}
+
+/// Reads one encrypted chunk from `upload_dir` and decrypts it, checking its
+/// GCM tag.
+///
+/// With `blocking` set, the AES-GCM work and any decompression run on Tokio's
+/// blocking pool so that many concurrent large downloads don't starve the
+/// async workers.
+pub(crate) async fn read_decrypted_chunk(
+    upload_dir: &std::path::Path,
+    dek: &[u8; 32],
+    chunk_info: &ChunkInfo,
+    blocking: bool,
+) -> Result<Vec<u8>> {
+    let chunk_filename = chunk_info.get_filename()?;
+    let chunk_path = upload_dir.join(&chunk_filename);
+
+    let chunk_encrypted = tokio::fs::read(&chunk_path).await.map_err(|e| {
+        tracing::error!("Failed to read chunk {}: {}", chunk_filename, e);
+        AppError::Io(e)
+    })?;
+
+    let decrypted = if blocking {
+        let dek = *dek;
+        let chunk_info = chunk_info.clone();
+        tokio::task::spawn_blocking(move || open_chunk(&dek, &chunk_info, &chunk_encrypted))
+            .await
+            .map_err(|e| AppError::Internal(format!("Chunk decryption task failed: {}", e)))?
+    } else {
+        open_chunk(dek, chunk_info, &chunk_encrypted)
+    };
+
+    let chunk_plaintext = decrypted.map_err(|e| {
+        tracing::error!("Failed to decrypt chunk {}: {}", chunk_info.index, e);
+        e
+    })?;
+
+    tracing::debug!(
+        "✅ Chunk {} decrypted: {} bytes",
+        chunk_info.index,
+        chunk_plaintext.len()
+    );
+
+    Ok(chunk_plaintext)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/files/{file_id}",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    responses(
+        (status = 200, description = "Decrypted file contents", content_type = "application/octet-stream",
+            headers(
+                ("Content-Length" = i64, description = "The plaintext size of the file in bytes"),
+                ("Accept-Ranges" = String, description = "Always `bytes`"),
+                ("X-Total-Chunks" = usize, description = "The number of chunks the body is streamed in, one body frame per chunk")
+            )),
+        (status = 206, description = "The byte range requested with a single-range `Range` header", content_type = "application/octet-stream",
+            headers(
+                ("Content-Range" = String, description = "The bytes served, as `bytes start-end/size`"),
+                ("Content-Length" = i64, description = "The size of the range in bytes"),
+                ("X-Total-Chunks" = usize, description = "The number of chunks the range is streamed in")
+            )),
+        (status = 404, description = "File not found"),
+        (status = 416, description = "Malformed `Range` header or range outside the file")
+    )
+)]
+pub async fn download_file(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+
+    tracing::info!("📥 Download file {} (STREAMING MODE)", file_id);
+
+    if session.is_impersonated() {
+        tracing::warn!(
+            "❌ Impersonated session (admin {:?}) tried to download file {}",
+            session.impersonated_by,
+            file_id
+        );
+        return Err(AppError::Unauthorized);
+    }
+
+    // Released on any early return below, or once the body stream is dropped.
+    let download_lock = acquire_download_lock(&state, user_id).await?;
+
+    let client = state.db.get().await?;
+    let file = repositories::file::find_by_id(&client, file_id, user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    drop(client);
+
+    crate::services::audit::record(&state, crate::models::audit::AuditEvent {
+        resource_type: Some("file"),
+        resource_id: Some(file_id),
+        ..crate::services::audit::session_event(&state, "download_file", &session, &headers, addr)
+    });
+    events::publish(&state, user_id, "download_started", sonic_rs::json!({
+        "file_id": file_id.to_string(),
+        "filename": file.original_filename
+    }));
+
+    stream_file(&state, file, &headers, Some(download_lock)).await
+}
+
+/// Streams a file's decrypted contents, or the byte range requested in
+/// `headers`, as a download response.
+///
+/// The download limiter permit and `download_lock` are held until the body
+/// stream ends; share links pass `None` as they are not tied to the owner's
+/// one-download-at-a-time lock.
+pub(crate) async fn stream_file(
+    state: &AppState,
+    file: crate::models::file::File,
+    headers: &HeaderMap,
+    download_lock: Option<DownloadLockGuard>,
+) -> Result<Response> {
+    let file_id = file.id;
+    let permit = state.download_limiter.acquire_owned().await;
+
+    let available = state.download_limiter.available_permits();
+    let total_slots = DOWNLOAD_BUFFER_SLOTS;
+    let concurrent_downloads = total_slots.saturating_sub(available);
+    let buffer_chunks = std::cmp::max(1usize, total_slots / (concurrent_downloads.max(1) + 1));
+
+    tracing::info!("⏳ Download buffer: {} chunks (concurrent: {}, available: {})", buffer_chunks, concurrent_downloads, available);
+
+    let mut chunks_data = decode_chunks_metadata(&file)?;
+
+    tracing::info!("✅ Decoded {} chunks from metadata", chunks_data.len());
+
+    let file_size = file.file_size.max(0) as u64;
+    let range = match headers.get(axum::http::header::RANGE) {
+        Some(value) => {
+            let value = value
+                .to_str()
+                .map_err(|_| AppError::RangeNotSatisfiable { size: file_size })?;
+            ByteRange::parse(value, file_size)?
+        }
+        None => None,
+    };
+
+    // Chunks hold `chunk_size` plaintext bytes each except the last, so the
+    // chunks outside the range are skipped without being read or decrypted.
+    let chunk_size = ChunkInfo::layout_chunk_size(&chunks_data).unwrap_or(file_size.max(1));
+    if let Some(range) = range {
+        let (first_chunk, last_chunk) = range.chunk_span(chunk_size);
+        chunks_data.retain(|chunk| (first_chunk..=last_chunk).contains(&chunk.index));
+        tracing::info!(
+            "📐 Range {}-{} of {} bytes -> chunks {}..={}",
+            range.start,
+            range.end,
+            file_size,
+            first_chunk,
+            last_chunk
+        );
+    }
+    let chunks_count = chunks_data.len();
+
+    let dek_array = decrypt_file_dek(state, &file).await?;
 
     tracing::info!("🔓 DEK decrypted successfully");
 
+    let blocking_decrypt = state.config.decrypt_on_blocking_pool(file.file_size);
+
+    // `buffered` keeps up to `buffer_chunks` chunks in flight while the body
+    // writes one more, so that many buffers are enough to never allocate per
+    // chunk; see `chunk_buffer::memory_ceiling` for the resulting bound.
+    let buffer_pool = Arc::new(ChunkBufferPool::new(state.config.upload_dir.clone(), buffer_chunks + 1));
+
     let chunk_stream = stream::iter(chunks_data)
         .map(move |chunk_info| {
             let dek = dek_array;
+            let buffer_pool = buffer_pool.clone();
             async move {
-                let chunk_filename = chunk_info.get_filename()
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-
-                let chunk_path = PathBuf::from("uploads/files").join(&chunk_filename);
-
-                let chunk_encrypted = tokio::fs::read(&chunk_path).await.map_err(|e| {
-                    tracing::error!("Failed to read chunk {}: {}", chunk_filename, e);
-                    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-                })?;
-
-                let chunk_plaintext = crate::crypto::aes::decrypt(
-                    &dek,
-                    &chunk_encrypted,
-                    &chunk_info.nonce,
-                ).map_err(|e| {
-                    tracing::error!("Failed to decrypt chunk {}: {}", chunk_info.index, e);
-                    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-                })?;
-
-                tracing::debug!(
-                    "✅ Chunk {} decrypted: {} bytes",
-                    chunk_info.index,
-                    chunk_plaintext.len()
-                );
-
-                Ok::<Bytes, std::io::Error>(Bytes::from(chunk_plaintext))
+                let mut chunk = buffer_pool
+                    .read_decrypted_chunk(&dek, &chunk_info, blocking_decrypt)
+                    .await
+                    .map_err(std::io::Error::other)?;
+
+                if let Some(range) = range {
+                    chunk = chunk.slice(range.slice_of_chunk(chunk_info.index, chunk_size, chunk.len()));
+                }
+                crate::metrics::record_download_bytes(chunk.len());
+
+                Ok::<Bytes, std::io::Error>(chunk)
             }
         })
-        .buffered(buffer_chunks);
-
+        .buffered(buffer_chunks)
+        .inspect(move |_| {
+            let _ = (&permit, &download_lock);
+        });
+
+    // `buffered` only prefetches; each decrypted chunk is still yielded as its
+    // own item, and hyper writes every item as a separate body frame, so the
+    // client sees a flush at each chunk boundary.
     let body = Body::from_stream(chunk_stream);
 
     let mut response_headers = HeaderMap::new();
-    response_headers.insert(
-        axum::http::header::CONTENT_TYPE,
-        "application/octet-stream".parse().unwrap(),
-    );
-
-    let safe_filename = sanitize_filename(&file.original_filename);
-    let disposition = format!(r#"attachment; filename="{}""#, safe_filename)
-        .parse()
-        .unwrap_or_else(|_| "attachment".parse().unwrap());
+    let content_type = file
+        .mime_type
+        .as_deref()
+        .and_then(|mime_type| HeaderValue::from_str(mime_type).ok())
+        .unwrap_or(HeaderValue::from_static(DEFAULT_MIME_TYPE));
+    response_headers.insert(axum::http::header::CONTENT_TYPE, content_type);
+    response_headers.insert(axum::http::header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert("x-total-chunks", HeaderValue::from(chunks_count));
+
+    let status = match range {
+        Some(range) => {
+            response_headers.insert(axum::http::header::CONTENT_LENGTH, HeaderValue::from(range.content_length()));
+            response_headers.insert(
+                axum::http::header::CONTENT_RANGE,
+                HeaderValue::from_str(&range.content_range(file_size))
+                    .map_err(|e| AppError::Internal(format!("Invalid Content-Range: {}", e)))?,
+            );
+            StatusCode::PARTIAL_CONTENT
+        }
+        None => {
+            response_headers.insert(axum::http::header::CONTENT_LENGTH, HeaderValue::from(file_size));
+            StatusCode::OK
+        }
+    };
 
+    let disposition = attachment_disposition(&file.original_filename);
     response_headers.insert(axum::http::header::CONTENT_DISPOSITION, disposition);
 
+    // Count each download once: a range resuming past the first byte is a
+    // continuation of one already counted.
+    if range.is_none_or(|range| range.start == 0) {
+        let db = state.db.clone();
+        let stmt_cache = state.stmt_cache.clone();
+        tokio::spawn(async move {
+            let result = match db.get().await {
+                Ok(client) => repositories::file::increment_access_count(&client, file_id, &stmt_cache).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to count download of file {}: {}", file_id, e);
+            }
+        });
+    }
+
     tracing::info!(
         "✅ Download stream ready - {} chunks, buffer={} (semaphore limit: max 2GB total)",
         chunks_count,
         buffer_chunks
     );
 
-    Ok((response_headers, body).into_response())
+    Ok((status, response_headers, body).into_response())
+}
+
+/// The query parameters for verifying a file.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VerifyFileQuery {
+    /// Also hash the plaintext and compare it against the stored checksum
+    /// (default `true`). With `false` only each chunk's GCM tag is checked.
+    #[serde(default)]
+    pub checksum: Option<bool>,
+}
+
+/// Decrypts every chunk of a file server-side and discards the plaintext.
+///
+/// Each chunk is checked on its own: whether its file exists on disk and
+/// whether it decrypts, which authenticates its AES-GCM tag. Unless
+/// `checksum=false` is given and if every chunk decrypts, the plaintext is
+/// also hashed and compared against the stored checksum. Nothing is streamed
+/// to the client; the report lists a status per chunk (`ok`, `missing`,
+/// `unreadable` or `corrupt`) and an overall `ok`.
+#[utoipa::path(
+    get,
+    path = "/api/files/{file_id}/verify",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID"), VerifyFileQuery),
+    responses(
+        (status = 200, description = "Integrity report; `ok` is false if any check failed"),
+        (status = 403, description = "Impersonated session"),
+        (status = 404, description = "File not found")
+    )
+)]
+pub async fn verify_file(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<VerifyFileQuery>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let verify_checksum = query.checksum.unwrap_or(true);
+
+    tracing::info!("🔍 Verify file {} for user {}", file_id, user_id);
+
+    // Verifying decrypts every chunk and reports the plaintext checksum.
+    if session.is_impersonated() {
+        tracing::warn!(
+            "❌ Impersonated session (admin {:?}) tried to verify file {}",
+            session.impersonated_by,
+            file_id
+        );
+        return Err(AppError::Unauthorized);
+    }
+
+    let _permit = state.download_limiter.acquire().await;
+
+    let client = state.db.get().await?;
+    let file = repositories::file::find_by_id(&client, file_id, user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let chunks_data = decode_chunks_metadata(&file)?;
+    let chunks_total = chunks_data.len();
+    let dek_array = decrypt_file_dek(&state, &file).await?;
+
+    let stored_checksum = file
+        .checksum
+        .as_deref()
+        .map(Checksum::parse_stored)
+        .transpose()?;
+    let algorithm = stored_checksum
+        .as_ref()
+        .map_or(state.config.checksum_algorithm, |checksum| checksum.algorithm);
+
+    let mut hasher = ChecksumHasher::new(algorithm);
+    let mut chunks_verified = 0usize;
+    let mut first_failed_chunk: Option<usize> = None;
+    let mut failure_reason: Option<String> = None;
+    let mut chunk_reports = Vec::with_capacity(chunks_total);
+
+    let blocking_decrypt = state.config.decrypt_on_blocking_pool(file.file_size);
+
+    for chunk_info in &chunks_data {
+        let (status, error) = match read_decrypted_chunk(&state.config.upload_dir, &dek_array, chunk_info, blocking_decrypt)
+            .await
+        {
+            Ok(chunk_plaintext) => {
+                // The plaintext is hashed in order, so hashing stops at the
+                // first failed chunk while the others are still checked.
+                if verify_checksum && first_failed_chunk.is_none() {
+                    hasher.update(&chunk_plaintext);
+                }
+                chunks_verified += 1;
+                ("ok", None)
+            }
+            Err(AppError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => ("missing", Some(e.to_string())),
+            Err(AppError::Io(e)) => ("unreadable", Some(e.to_string())),
+            Err(e) => ("corrupt", Some(e.to_string())),
+        };
+
+        if let Some(error) = &error
+            && first_failed_chunk.is_none()
+        {
+            first_failed_chunk = Some(chunk_info.index);
+            failure_reason = Some(error.clone());
+        }
+
+        chunk_reports.push(sonic_rs::json!({
+            "index": chunk_info.index,
+            "status": status,
+            "error": error
+        }));
+    }
+
+    let checksum_computed = (verify_checksum && first_failed_chunk.is_none()).then(|| hasher.finalize());
+
+    let checksum_match = match (&stored_checksum, &checksum_computed) {
+        (Some(stored), Some(computed)) => Some(stored == computed),
+        _ => None,
+    };
+
+    if checksum_match == Some(false) && failure_reason.is_none() {
+        failure_reason = Some(format!("{} checksum mismatch", algorithm.tag()));
+    }
+
+    let ok = first_failed_chunk.is_none() && checksum_match != Some(false);
+
+    if ok {
+        tracing::info!("✅ File {} verified: {} chunks", file_id, chunks_verified);
+    } else {
+        tracing::warn!(
+            "❌ File {} failed verification ({} of {} chunks ok, first failed {:?}): {:?}",
+            file_id,
+            chunks_verified,
+            chunks_total,
+            first_failed_chunk,
+            failure_reason
+        );
+    }
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "file_id": file_id.to_string(),
+        "ok": ok,
+        "chunks_total": chunks_total,
+        "chunks_verified": chunks_verified,
+        "chunks": chunk_reports,
+        "first_failed_chunk": first_failed_chunk,
+        "failure_reason": failure_reason,
+        "checksum_checked": verify_checksum,
+        "checksum_stored": stored_checksum.map(|checksum| checksum.to_string()),
+        "checksum_computed": checksum_computed.map(|checksum| checksum.to_string()),
+        "checksum_match": checksum_match
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/files/{file_id}",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    responses(
+        (status = 200, description = "File deleted and quota released"),
+        (status = 404, description = "File not found")
+    )
+)]
 pub async fn delete_file(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
     Path(file_id): Path<Uuid>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<impl IntoResponse> {
     let user_id = session.user_id;
     let client = state.db.get().await?;
@@ -928,12 +2362,25 @@ pub async fn delete_file(
     repositories::user::rollback_storage_usage(&client, &user_id, file.file_size, &state.stmt_cache)
         .await?;
 
+    state.folder_cache.invalidate_user(user_id).await;
+
+    if state.config.hard_delete_on_delete
+        && let Some(chunks_metadata) = file.chunks_metadata
+    {
+        crate::services::files::spawn_chunk_reclaim(state.clone(), file_id, chunks_metadata);
+    }
+
     tracing::info!(
         "🗑️ File deleted: {} ({} bytes quota released for user {})",
         file_id,
         file.file_size,
         user_id
     );
+    crate::services::audit::record(&state, crate::models::audit::AuditEvent {
+        resource_type: Some("file"),
+        resource_id: Some(file_id),
+        ..crate::services::audit::session_event(&state, "delete_file", &session, &headers, addr)
+    });
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "message": "File deleted successfully",
@@ -941,9 +2388,300 @@ pub async fn delete_file(
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/files/batch-delete",
+    tag = "files",
+    request_body = BatchDeleteRequest,
+    responses(
+        (status = 200, description = "Per-file results: `deleted`, `already_deleted` or `not_found`"),
+        (status = 400, description = "No file IDs, or more than MAX_BATCH_DELETE_FILES")
+    )
+)]
+pub async fn batch_delete_files(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    axum::Json(req): axum::Json<BatchDeleteRequest>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+
+    let mut file_ids = req.file_ids;
+    let mut seen = std::collections::HashSet::with_capacity(file_ids.len());
+    file_ids.retain(|id| seen.insert(*id));
+
+    if file_ids.is_empty() {
+        return Err(AppError::Validation("file_ids must not be empty".into()));
+    }
+    if file_ids.len() > state.config.max_batch_delete_files {
+        return Err(AppError::Validation(format!(
+            "At most {} files can be deleted at once",
+            state.config.max_batch_delete_files
+        )));
+    }
+
+    let mut client = state.db.get().await?;
+    let (deleted, already_deleted) =
+        repositories::file::soft_delete_files(&mut client, &file_ids, user_id, &state.stmt_cache)
+            .await?;
+    drop(client);
+
+    if !deleted.is_empty() {
+        state.folder_cache.invalidate_user(user_id).await;
+    }
+
+    let mut released = std::collections::HashMap::with_capacity(deleted.len());
+    let mut quota_released: i64 = 0;
+    for file in deleted {
+        quota_released += file.file_size;
+        released.insert(file.id, file.file_size);
+
+        if state.config.hard_delete_on_delete
+            && let Some(chunks_metadata) = file.chunks_metadata
+        {
+            crate::services::files::spawn_chunk_reclaim(state.clone(), file.id, chunks_metadata);
+        }
+
+        crate::services::audit::record(&state, crate::models::audit::AuditEvent {
+            resource_type: Some("file"),
+            resource_id: Some(file.id),
+            ..crate::services::audit::session_event(&state, "delete_file", &session, &headers, addr)
+        });
+    }
+
+    let results: Vec<_> = file_ids
+        .iter()
+        .map(|id| match released.get(id) {
+            Some(size) => sonic_rs::json!({
+                "file_id": id.to_string(),
+                "status": "deleted",
+                "quota_released": size
+            }),
+            None if already_deleted.contains(id) => sonic_rs::json!({
+                "file_id": id.to_string(),
+                "status": "already_deleted"
+            }),
+            None => sonic_rs::json!({
+                "file_id": id.to_string(),
+                "status": "not_found"
+            }),
+        })
+        .collect();
+
+    tracing::info!(
+        "🗑️ Batch delete: {}/{} files deleted ({} bytes quota released for user {})",
+        released.len(),
+        file_ids.len(),
+        quota_released,
+        user_id
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "results": results,
+        "deleted": released.len(),
+        "quota_released": quota_released
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/files/{file_id}",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    request_body = RenameFileRequest,
+    responses(
+        (status = 200, description = "File renamed"),
+        (status = 400, description = "Empty filename, control characters or too long"),
+        (status = 404, description = "File not found")
+    )
+)]
+pub async fn rename_file(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+    axum::Json(req): axum::Json<RenameFileRequest>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let filename = normalize_filename(&req.filename, state.config.max_filename_length)?;
+
+    let client = state.db.get().await?;
+    let filename = repositories::file::rename_file(&client, file_id, user_id, &filename, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    state.folder_cache.invalidate_user(user_id).await;
+
+    tracing::info!("✏️ File renamed: {} for user {}", file_id, user_id);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "File renamed successfully",
+        "id": file_id.to_string(),
+        "filename": filename
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/files/{file_id}/move",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    request_body = MoveFileRequest,
+    responses(
+        (status = 200, description = "File moved"),
+        (status = 400, description = "Target folder not found or deleted"),
+        (status = 404, description = "File not found")
+    )
+)]
+pub async fn move_file(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+    axum::Json(req): axum::Json<MoveFileRequest>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+
+    let client = state.db.get().await?;
+    let file = repositories::file::move_file(&client, file_id, user_id, req.folder_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    state.folder_cache.invalidate_user(user_id).await;
+
+    tracing::info!(
+        "📁 File moved: {} to folder {:?} for user {}",
+        file_id,
+        file.folder_id,
+        user_id
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "File moved successfully",
+        "id": file.id.to_string(),
+        "filename": file.original_filename,
+        "folder_id": file.folder_id.map(|id| id.to_string())
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/files/trash",
+    tag = "files",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Page of the user's deleted files that can still be restored")
+    )
+)]
+pub async fn list_trash(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Query(params): Query<PageQuery>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let params = params.validate()?;
+
+    let client = state.db.get().await?;
+    let files = repositories::file::list_deleted_files(
+        &client,
+        user_id,
+        params.limit,
+        params.offset,
+        &state.stmt_cache,
+    )
+    .await?;
+    let total = repositories::file::count_deleted_files(&client, user_id, &state.stmt_cache).await?;
+    let pagination = Pagination::new(params, files.len(), total);
+
+    let retention = chrono::Duration::seconds(state.config.trash_retention_secs());
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "files": files.iter().map(|f| sonic_rs::json!({
+            "id": f.id.to_string(),
+            "filename": f.original_filename,
+            "size_bytes": f.file_size,
+            "folder_id": f.folder_id.map(|id| id.to_string()),
+            "deleted_at": f.deleted_at.map(|at| at.to_rfc3339()),
+            "purge_after": f.deleted_at.map(|at| (at + retention).to_rfc3339())
+        })).collect::<Vec<_>>(),
+        "count": files.len(),
+        "pagination": pagination
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/files/{file_id}/restore",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    responses(
+        (status = 200, description = "File restored and its size charged back to the quota"),
+        (status = 400, description = "The file's chunks were removed from storage when it was deleted"),
+        (status = 404, description = "File not in the trash, or past the retention window"),
+        (status = 507, description = "Restoring the file would exceed the storage quota")
+    )
+)]
+pub async fn restore_file(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let user_id = session.user_id;
+    let mut client = state.db.get().await?;
+
+    let file = repositories::file::restore_file(
+        &mut client,
+        file_id,
+        user_id,
+        state.config.trash_retention_secs(),
+        &state.stmt_cache,
+    )
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    state.folder_cache.invalidate_user(user_id).await;
+    events::check_quota(&state, user_id, file.file_size);
+
+    tracing::info!(
+        "♻️ File restored: {} ({} bytes charged to user {})",
+        file_id,
+        file.file_size,
+        user_id
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "File restored successfully",
+        "id": file.id.to_string(),
+        "filename": file.original_filename,
+        "folder_id": file.folder_id.map(|id| id.to_string()),
+        "quota_charged": file.file_size
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/files/storage/info",
+    tag = "files",
+    responses(
+        (status = 200, description = "Storage quota and usage", body = StorageInfoResponse)
+    )
+)]
 pub async fn storage_info(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -954,21 +2692,40 @@ pub async fn storage_info(
     let (storage_quota_bytes, storage_used_bytes) =
         repositories::user::get_user_storage_info(&client, &user_id, &state.stmt_cache).await?;
 
-    let available_bytes = storage_quota_bytes - storage_used_bytes;
+    let reserved_bytes =
+        repositories::user::get_reserved_bytes(&client, &user_id, &state.stmt_cache).await?;
+
+    let available_bytes = storage_quota_bytes - storage_used_bytes - reserved_bytes;
     let usage_percentage =
         (storage_used_bytes as f64 / storage_quota_bytes as f64) * 100.0;
+    let status = QuotaStatus::classify(
+        storage_used_bytes,
+        storage_quota_bytes,
+        &state.config.quota_thresholds(),
+    );
 
     let response = sonic_rs::to_string(&sonic_rs::json!(StorageInfoResponse {
         storage_quota_bytes,
         storage_used_bytes,
+        reserved_bytes,
         available_bytes,
         usage_percentage,
+        status,
+        message: status.message(usage_percentage),
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/files/recalculate-quota",
+    tag = "files",
+    responses(
+        (status = 200, description = "Used storage recalculated from stored files and reservations from uploads in progress")
+    )
+)]
 pub async fn recalculate_user_quota(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -976,79 +2733,101 @@ pub async fn recalculate_user_quota(
     let user_id = session.user_id;
     tracing::warn!("🔄 Recalculating storage quota for user: {}", user_id);
 
+    // Reservations of sessions the sweeper never released (e.g. after a Redis
+    // flush or a missed sweep) are dropped here: only live sessions count.
+    let reserved_bytes: i64 = user_upload_sessions(&state, user_id)
+        .await?
+        .iter()
+        .filter(|(_, metadata)| metadata.quota_reserved)
+        .map(|(_, metadata)| metadata.total_size)
+        .sum();
+
     let client = state.db.get().await?;
-    let total_size: i64 =
-        repositories::file::list_user_files(&client, user_id, i64::MAX, 0, &state.stmt_cache)
-            .await?
-            .iter()
-            .map(|f| f.file_size)
-            .sum();
-
-    repositories::user::update_storage_with_quota_check(
-        &client,
-        &user_id,
-        total_size,
-        &state.stmt_cache,
-    )
-    .await?;
+    let total_size =
+        repositories::user::reconcile_storage(&client, &user_id, reserved_bytes, &state.stmt_cache)
+            .await?;
 
     tracing::info!(
-        "✅ Storage quota recalculated: {} bytes for user {}",
+        "✅ Storage quota recalculated: {} bytes used, {} reserved for user {}",
         total_size,
+        reserved_bytes,
         user_id
     );
 
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "message": "Storage quota recalculated",
-        "actual_storage_used": total_size
+        "actual_storage_used": total_size,
+        "reserved_bytes": reserved_bytes
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
+/// Removes every upload session older than `upload_expiration_secs`.
 pub async fn cleanup_expired_uploads(state: AppState) -> Result<()> {
     tracing::info!("🧹 Checking for expired uploads...");
 
-    let current_timestamp = Utc::now().timestamp();
+    let cleaned_count = sweep_expired_uploads(&state, "upload:*", Utc::now().timestamp()).await?;
+
+    tracing::info!(
+        "✅ Cleanup check completed - {} expired uploads removed",
+        cleaned_count
+    );
+
+    Ok(())
+}
+
+/// Removes the upload sessions matching `key_pattern` that had expired at `now`.
+///
+/// Sessions that never reached `finalize_upload` were never debited from
+/// `storage_used_bytes`, so only their chunk files, Redis keys and any quota
+/// reservation are released; the user's used storage is left untouched.
+///
+/// One Redis connection serves the whole SCAN. Each batch of keys is read
+/// with a single `MGET`, the expired sessions' storage is released
+/// concurrently, and their keys and session-cap entries are removed in one
+/// pipeline.
+///
+/// # Returns
+///
+/// The number of sessions removed.
+pub async fn sweep_expired_uploads(state: &AppState, key_pattern: &str, now: i64) -> Result<usize> {
+    let mut redis = state.redis.clone();
+    let config = bincode::config::standard();
     let mut cursor = 0u64;
     let mut cleaned_count = 0;
 
     loop {
-        let mut conn = state.redis.clone();
         let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
             .arg(cursor)
             .arg("MATCH")
-            .arg("upload:*")
+            .arg(key_pattern)
             .arg("COUNT")
             .arg(100)
-            .query_async(&mut conn)
+            .query_async(&mut redis)
             .await
             .unwrap_or((0, vec![]));
 
-        for key in keys {
-            let mut redis_conn = state.redis.clone();
-            if let Ok(metadata_bytes) = redis_conn
-                .get::<_, Vec<u8>>(&key)
+        if !keys.is_empty() {
+            let values: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(&mut redis)
                 .await
-            {
-                let config = bincode::config::standard();
-                if let Ok((metadata, _)) =
-                    bincode::decode_from_slice::<UploadMetadata, _>(&metadata_bytes, config)
-                {
-                    if current_timestamp - metadata.created_at > 86400 {
-                        tracing::warn!("⏰ Expired upload found: {}", key);
-                        cleanup_failed_upload(
-                            &state,
-                            metadata.user_id,
-                            &metadata.upload_session_id,
-                            &metadata,
-                        )
-                        .await?;
-                        cleaned_count += 1;
-                    }
-                }
-            }
+                .map_err(AppError::Redis)?;
+
+            let expired: Vec<(String, UploadMetadata)> = keys
+                .into_iter()
+                .zip(values)
+                .filter_map(|(key, value)| {
+                    let (metadata, _) =
+                        bincode::decode_from_slice::<UploadMetadata, _>(&value?, config).ok()?;
+                    (now - metadata.created_at > state.config.upload_expiration_secs as i64)
+                        .then_some((key, metadata))
+                })
+                .collect();
+
+            cleaned_count += remove_expired_uploads(state, &mut redis, expired).await?;
         }
 
         cursor = new_cursor;
@@ -1057,10 +2836,53 @@ pub async fn cleanup_expired_uploads(state: AppState) -> Result<()> {
         }
     }
 
-    tracing::info!(
-        "✅ Cleanup check completed - {} expired uploads removed",
-        cleaned_count
-    );
+    Ok(cleaned_count)
+}
 
-    Ok(())
+/// Releases the storage of expired upload sessions, then deletes the keys
+/// and session-cap entries of those released in one Redis pipeline.
+///
+/// A session whose storage could not be released keeps its key, so the next
+/// sweep retries it.
+///
+/// # Returns
+///
+/// The number of sessions removed.
+async fn remove_expired_uploads(
+    state: &AppState,
+    redis: &mut redis::aio::ConnectionManager,
+    expired: Vec<(String, UploadMetadata)>,
+) -> Result<usize> {
+    let released: Vec<(String, UploadMetadata)> = stream::iter(expired)
+        .map(|(key, metadata)| async move {
+            tracing::warn!("⏰ Expired upload found: {}", key);
+            match release_upload_storage(state, &metadata).await {
+                Ok(_) => Some((key, metadata)),
+                Err(e) => {
+                    tracing::error!("❌ Failed to clean up expired upload {}: {}", key, e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(SWEEP_CONCURRENCY)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    if released.is_empty() {
+        return Ok(0);
+    }
+
+    let mut pipe = redis::pipe();
+    for (key, metadata) in &released {
+        pipe.del(key)
+            .ignore()
+            .zrem(ACTIVE_UPLOADS_KEY, &metadata.upload_session_id)
+            .ignore()
+            .zrem(format!("upload_sessions:{}", metadata.user_id), &metadata.upload_session_id)
+            .ignore();
+    }
+    pipe.query_async::<()>(redis).await.map_err(AppError::Redis)?;
+
+    Ok(released.len())
 }
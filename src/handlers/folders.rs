@@ -1,46 +1,97 @@
 use axum::{
+    body::{Body, Bytes},
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Extension,
     Json,
 };
+use futures::{channel::mpsc, SinkExt};
 use uuid::Uuid;
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     error::{AppError, Result},
-    models::session::Session,
+    handlers::files::{
+        acquire_download_lock, attachment_disposition, decode_chunks_metadata, decrypt_file_dek,
+        read_decrypted_chunk,
+    },
+    handlers::zip::ZipStreamWriter,
+    models::{
+        file::File,
+        pagination::{default_limit, PageQuery, Pagination},
+        session::Session,
+    },
+    response::json_response,
     services::folders as folder_service,
     state::AppState,
 };
 
 /// The request payload for creating a folder.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateFolderRequest {
     pub name: String,
     pub description: Option<String>,
     pub parent_folder_id: Option<Uuid>,
 }
 
+/// The request payload for updating a folder; omitted fields are unchanged.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateFolderRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// The request payload for moving a folder.
+#[derive(Deserialize, ToSchema)]
+pub struct MoveFolderRequest {
+    /// The new parent folder; `null` moves the folder to the root.
+    pub parent_folder_id: Option<Uuid>,
+}
+
 /// The query parameters for listing folder contents.
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListFolderQuery {
     #[serde(default)]
     pub folder_id: Option<Uuid>,
+    /// The maximum number of entries to return, folders first (1 to 1000, default 50).
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// The number of entries to skip.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// Checks that a folder name is between 1 and 500 characters.
+fn validate_folder_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 500 {
+        return Err(AppError::Validation(
+            "Folder name must be between 1 and 500 characters".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
 /// Creates a new folder.
+#[utoipa::path(
+    post,
+    path = "/api/folders",
+    tag = "folders",
+    request_body = CreateFolderRequest,
+    responses(
+        (status = 201, description = "Folder created"),
+        (status = 400, description = "Invalid folder name or parent folder")
+    )
+)]
 pub async fn create_folder(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
     Json(req): Json<CreateFolderRequest>,
 ) -> Result<Response> {
-    if req.name.is_empty() || req.name.len() > 500 {
-        return Err(AppError::Validation(
-            "Folder name must be between 1 and 500 characters".to_string(),
-        ));
-    }
+    validate_folder_name(&req.name)?;
 
     let folder = folder_service::create_folder(
         &state,
@@ -60,15 +111,30 @@ pub async fn create_folder(
     }))
     .unwrap();
 
-    Ok((StatusCode::CREATED, response).into_response())
+    Ok(json_response(StatusCode::CREATED, response))
 }
 
 /// Lists the contents of a folder.
+#[utoipa::path(
+    get,
+    path = "/api/folders/list",
+    tag = "folders",
+    params(ListFolderQuery),
+    responses(
+        (status = 200, description = "Subfolders and files of the folder (root when omitted)")
+    )
+)]
 pub async fn list_folder_contents(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
     Query(query): Query<ListFolderQuery>,
 ) -> Result<Response> {
+    let page = PageQuery {
+        limit: query.limit,
+        offset: query.offset,
+    }
+    .validate()?;
+
     let (folders, files) = folder_service::list_folder_contents(
         &state,
         session.user_id,
@@ -76,8 +142,17 @@ pub async fn list_folder_contents(
     )
     .await?;
 
+    // Folders come before files; the page is a window over that combined order.
+    let total = (folders.len() + files.len()) as i64;
+    let skip = page.offset.min(total) as usize;
+    let take = page.limit as usize;
+    let folders_skipped = skip.min(folders.len());
+    let files_skip = skip - folders_skipped;
+
     let folders_json: Vec<_> = folders
         .into_iter()
+        .skip(folders_skipped)
+        .take(take)
         .map(|f| {
             sonic_rs::json!({
                 "id": f.id.to_string(),
@@ -90,6 +165,8 @@ pub async fn list_folder_contents(
 
     let files_json: Vec<_> = files
         .into_iter()
+        .skip(files_skip)
+        .take(take - folders_json.len())
         .map(|f| {
             sonic_rs::json!({
                 "id": f.id.to_string(),
@@ -104,14 +181,25 @@ pub async fn list_folder_contents(
     let response = sonic_rs::to_string(&sonic_rs::json!({
         "folders": folders_json,
         "files": files_json,
-        "count": folders_json.len() + files_json.len()
+        "count": folders_json.len() + files_json.len(),
+        "pagination": Pagination::new(page, folders_json.len() + files_json.len(), total)
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
 }
 
 /// Gets statistics for a folder.
+#[utoipa::path(
+    get,
+    path = "/api/folders/{folder_id}",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "Folder with file count, subfolder count and total size"),
+        (status = 404, description = "Folder not found")
+    )
+)]
 pub async fn get_folder_stats(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
@@ -136,15 +224,445 @@ pub async fn get_folder_stats(
     }))
     .unwrap();
 
-    Ok((StatusCode::OK, response).into_response())
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Gets the recursive storage usage of a folder.
+#[utoipa::path(
+    get,
+    path = "/api/folders/{folder_id}/usage",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "Recursive total size, direct size and the total of each immediate subfolder"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn get_folder_usage(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+) -> Result<Response> {
+    let usage = folder_service::get_folder_usage(&state, session.user_id, folder_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let subfolders: Vec<_> = usage
+        .subfolders
+        .iter()
+        .map(|s| {
+            sonic_rs::json!({
+                "id": s.id.to_string(),
+                "name": s.name,
+                "total_size": s.total_size
+            })
+        })
+        .collect();
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "id": usage.id.to_string(),
+        "total_size": usage.total_size,
+        "direct_size": usage.direct_size,
+        "subfolders": subfolders
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Gets the path from the root to a folder, for breadcrumbs.
+#[utoipa::path(
+    get,
+    path = "/api/folders/{folder_id}/path",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "The folders from the root down to this one"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn get_folder_path(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+) -> Result<Response> {
+    let path = folder_service::get_folder_path(&state, session.user_id, folder_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let path_json: Vec<_> = path
+        .iter()
+        .map(|f| {
+            sonic_rs::json!({
+                "id": f.id.to_string(),
+                "name": f.name
+            })
+        })
+        .collect();
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({ "path": path_json })).unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Renames a folder or edits its description.
+#[utoipa::path(
+    patch,
+    path = "/api/folders/{folder_id}",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    request_body = UpdateFolderRequest,
+    responses(
+        (status = 200, description = "Updated folder"),
+        (status = 400, description = "Invalid folder name, or nothing to update"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn update_folder(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+    Json(req): Json<UpdateFolderRequest>,
+) -> Result<Response> {
+    if req.name.is_none() && req.description.is_none() {
+        return Err(AppError::Validation(
+            "Provide a name or a description to update".to_string(),
+        ));
+    }
+    if let Some(name) = &req.name {
+        validate_folder_name(name)?;
+    }
+
+    let folder = folder_service::update_folder(
+        &state,
+        session.user_id,
+        folder_id,
+        req.name,
+        req.description,
+    )
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "id": folder.id.to_string(),
+        "name": folder.name,
+        "description": folder.description,
+        "parent_folder_id": folder.parent_folder_id.map(|id| id.to_string()),
+        "created_at": folder.created_at.to_rfc3339(),
+        "updated_at": folder.updated_at.to_rfc3339()
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Moves a folder under another folder or to the root.
+#[utoipa::path(
+    patch,
+    path = "/api/folders/{folder_id}/move",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    request_body = MoveFolderRequest,
+    responses(
+        (status = 200, description = "Folder moved"),
+        (status = 400, description = "Target folder not found, or inside the folder's own subtree"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn move_folder(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+    Json(req): Json<MoveFolderRequest>,
+) -> Result<Response> {
+    let folder = folder_service::move_folder(
+        &state,
+        session.user_id,
+        folder_id,
+        req.parent_folder_id,
+    )
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "id": folder.id.to_string(),
+        "name": folder.name,
+        "parent_folder_id": folder.parent_folder_id.map(|id| id.to_string()),
+        "updated_at": folder.updated_at.to_rfc3339(),
+        "message": "Folder moved successfully"
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Downloads the files directly inside a folder as one ZIP archive.
+///
+/// Entries are stored uncompressed and streamed as each chunk is decrypted, so
+/// memory stays at about one chunk per download whatever the folder's size.
+/// Subfolders are not included.
+#[utoipa::path(
+    get,
+    path = "/api/folders/{folder_id}/download",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "ZIP archive of the folder's files", content_type = "application/zip"),
+        (status = 400, description = "Another download of the user is in progress"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn download_folder(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+) -> Result<Response> {
+    let user_id = session.user_id;
+
+    if session.is_impersonated() {
+        tracing::warn!(
+            "❌ Impersonated session (admin {:?}) tried to download folder {}",
+            session.impersonated_by,
+            folder_id
+        );
+        return Err(AppError::Unauthorized);
+    }
+
+    let folder = folder_service::get_folder_with_stats(&state, user_id, folder_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let download_lock = acquire_download_lock(&state, user_id).await?;
+    let (_, files) = folder_service::list_folder_contents(&state, user_id, Some(folder_id)).await?;
+    let permit = state.download_limiter.acquire_owned().await;
+
+    tracing::info!(
+        "📦 Folder download {} - {} file(s) for user {}",
+        folder_id,
+        files.len(),
+        user_id
+    );
+
+    // A small channel applies backpressure: the writer decrypts the next chunk
+    // only once the client has taken the previous ones.
+    let (mut tx, rx) = mpsc::channel::<std::io::Result<Bytes>>(2);
+    let writer_state = state.clone();
+    tokio::spawn(async move {
+        let _permit = permit;
+        let _download_lock = download_lock;
+
+        if let Err(e) = write_folder_zip(&writer_state, &files, &mut tx).await
+            && e.kind() != std::io::ErrorKind::BrokenPipe
+        {
+            tracing::error!("❌ Folder download {} failed: {}", folder_id, e);
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/zip")),
+            (
+                header::CONTENT_DISPOSITION,
+                attachment_disposition(&format!("{}.zip", folder.name)),
+            ),
+        ],
+        Body::from_stream(rx),
+    )
+        .into_response())
+}
+
+/// Streams `files` into `tx` as a ZIP archive, decrypting one chunk at a time.
+///
+/// Returns `BrokenPipe` once the client has gone away.
+async fn write_folder_zip(
+    state: &AppState,
+    files: &[File],
+    tx: &mut mpsc::Sender<std::io::Result<Bytes>>,
+) -> std::io::Result<()> {
+    async fn send(tx: &mut mpsc::Sender<std::io::Result<Bytes>>, bytes: Bytes) -> std::io::Result<()> {
+        tx.send(Ok(bytes))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+    let io_error = |e: AppError| std::io::Error::other(e.to_string());
+
+    let mut zip = ZipStreamWriter::new();
+
+    for file in files {
+        let chunks = decode_chunks_metadata(file).map_err(io_error)?;
+        let dek = decrypt_file_dek(state, file).await.map_err(io_error)?;
+        let blocking_decrypt = state.config.decrypt_on_blocking_pool(file.file_size);
+
+        let (header, _) = zip.start_entry(
+            &file.original_filename,
+            file.file_size.max(0) as u64,
+            file.uploaded_at,
+        );
+        send(tx, header).await?;
+
+        for chunk_info in &chunks {
+            let plaintext = read_decrypted_chunk(&state.config.upload_dir, &dek, chunk_info, blocking_decrypt)
+                .await
+                .map_err(io_error)?;
+            zip.write(&plaintext);
+            send(tx, Bytes::from(plaintext)).await?;
+        }
+
+        send(tx, zip.finish_entry()?).await?;
+    }
+
+    send(tx, zip.finish()).await
 }
 
 /// Deletes a folder.
+#[utoipa::path(
+    delete,
+    path = "/api/folders/{folder_id}",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "Folder and its contents moved to trash; their quota is released")
+    )
+)]
 pub async fn delete_folder(
     State(state): State<AppState>,
     Extension(session): Extension<Session>,
     Path(folder_id): Path<Uuid>,
 ) -> Result<Response> {
-    folder_service::delete_folder(&state, session.user_id, folder_id).await?;
-    Ok((StatusCode::OK, r#"{"message":"Folder deleted successfully"}"#).into_response())
+    let freed_bytes = folder_service::delete_folder(&state, session.user_id, folder_id).await?;
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "Folder deleted successfully",
+        "freed_bytes": freed_bytes
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Restores a folder from the trash.
+#[utoipa::path(
+    post,
+    path = "/api/folders/{folder_id}/restore",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "Folder restored with the subfolders and files deleted along with it; files whose chunks were removed on delete are counted in `files_skipped`"),
+        (status = 400, description = "The parent folder is still deleted"),
+        (status = 404, description = "Folder not in the trash, or past the retention window"),
+        (status = 507, description = "Restoring the files would exceed the storage quota")
+    )
+)]
+pub async fn restore_folder(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+) -> Result<Response> {
+    let restored = folder_service::restore_folder(&state, session.user_id, folder_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    tracing::info!(
+        "♻️ Folder restored: {} ({} folders, {} files, {} skipped, {} bytes charged to user {})",
+        folder_id,
+        restored.folders_restored,
+        restored.files_restored,
+        restored.files_skipped,
+        restored.restored_bytes,
+        session.user_id
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "Folder restored successfully",
+        "id": restored.folder.id.to_string(),
+        "name": restored.folder.name,
+        "parent_folder_id": restored.folder.parent_folder_id.map(|id| id.to_string()),
+        "folders_restored": restored.folders_restored,
+        "files_restored": restored.files_restored,
+        "files_skipped": restored.files_skipped,
+        "quota_charged": restored.restored_bytes
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Permanently deletes a folder from the trash.
+#[utoipa::path(
+    delete,
+    path = "/api/folders/{folder_id}/permanent",
+    tag = "folders",
+    params(("folder_id" = Uuid, Path, description = "The folder ID")),
+    responses(
+        (status = 200, description = "Folder, subfolders and files removed and their chunks deleted from disk"),
+        (status = 400, description = "Folder is not deleted"),
+        (status = 404, description = "Folder not found")
+    )
+)]
+pub async fn purge_folder(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(folder_id): Path<Uuid>,
+) -> Result<Response> {
+    let report = folder_service::purge_folder(&state, session.user_id, folder_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    tracing::info!(
+        "🗑️ Folder {} purged: {} files, {} bytes reclaimed from disk",
+        folder_id,
+        report.files_purged,
+        report.reclaimed_bytes
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "Folder permanently deleted",
+        "files_purged": report.files_purged,
+        "quota_released": report.quota_released,
+        "reclaimed_bytes": report.reclaimed_bytes
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Permanently deletes the folders and files that have been in the trash
+/// for longer than `TRASH_EMPTY_GRACE_SECS`.
+#[utoipa::path(
+    post,
+    path = "/api/folders/trash/empty",
+    tag = "folders",
+    responses(
+        (status = 200, description = "Number of folders and files purged")
+    )
+)]
+pub async fn empty_trash(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<Response> {
+    let report = folder_service::empty_trash(
+        &state,
+        session.user_id,
+        state.config.trash_empty_grace_secs,
+    )
+    .await?;
+
+    tracing::info!(
+        "🗑️ Trash emptied for user {}: {} folders, {} files",
+        session.user_id,
+        report.folders_purged,
+        report.files_purged
+    );
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "Trash emptied",
+        "folders_purged": report.folders_purged,
+        "files_purged": report.files_purged,
+        "quota_released": report.quota_released
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
 }
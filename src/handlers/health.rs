@@ -0,0 +1,98 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Response,
+};
+use std::time::Duration;
+
+use crate::{
+    response::json_response,
+    state::AppState,
+};
+
+/// How long each dependency may take to answer a readiness probe.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reports that the process is up and serving requests.
+///
+/// Never touches a dependency, so a database or Redis outage does not get the
+/// instance restarted by its orchestrator.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The server is running"))
+)]
+pub async fn live() -> Response {
+    json_response(StatusCode::OK, r#"{"status":"ok"}"#.to_string())
+}
+
+/// Reports whether the server can serve traffic: Postgres answers
+/// `SELECT 1` and Redis answers `PING`.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Postgres and Redis are reachable"),
+        (status = 503, description = "A dependency is down; `failing` names it")
+    )
+)]
+pub async fn ready(State(state): State<AppState>) -> Response {
+    let (postgres, redis) = tokio::join!(check_postgres(&state), check_redis(&state));
+
+    let mut failing = Vec::new();
+    if let Err(e) = &postgres {
+        tracing::warn!("❌ Readiness check: Postgres unavailable: {}", e);
+        failing.push("postgres");
+    }
+    if let Err(e) = &redis {
+        tracing::warn!("❌ Readiness check: Redis unavailable: {}", e);
+        failing.push("redis");
+    }
+
+    let status = if failing.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = sonic_rs::to_string(&sonic_rs::json!({
+        "status": if failing.is_empty() { "ready" } else { "unavailable" },
+        "postgres": if postgres.is_ok() { "ok" } else { "down" },
+        "redis": if redis.is_ok() { "ok" } else { "down" },
+        "failing": failing
+    }))
+    .unwrap();
+
+    json_response(status, body)
+}
+
+/// Runs `SELECT 1` on a pooled Postgres connection.
+async fn check_postgres(state: &AppState) -> Result<(), String> {
+    let probe = async {
+        let client = state.db.get().await.map_err(|e| e.to_string())?;
+        client.simple_query("SELECT 1").await.map_err(|e| e.to_string())?;
+        Ok(())
+    };
+
+    tokio::time::timeout(READINESS_TIMEOUT, probe)
+        .await
+        .map_err(|_| "timed out".to_string())?
+}
+
+/// Sends `PING` to Redis.
+async fn check_redis(state: &AppState) -> Result<(), String> {
+    let mut redis = state.redis.clone();
+    let probe = async {
+        redis::cmd("PING")
+            .query_async::<String>(&mut redis)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+
+    tokio::time::timeout(READINESS_TIMEOUT, probe)
+        .await
+        .map_err(|_| "timed out".to_string())?
+}
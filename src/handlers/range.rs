@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+use crate::error::{AppError, Result};
+
+/// An inclusive byte range of a resource, as requested by a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    /// The offset of the first byte.
+    pub start: u64,
+    /// The offset of the last byte, inclusive.
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Parses a `Range` header value against a resource of `size` bytes.
+    ///
+    /// Supports a single `bytes=start-end`, `bytes=start-` or `bytes=-suffix`
+    /// range; an end past the resource is clamped to its last byte.
+    ///
+    /// # Returns
+    ///
+    /// `None` for a multi-range request, which callers answer with the whole
+    /// resource, or `RangeNotSatisfiable` if the value is malformed or the
+    /// range does not overlap the resource.
+    pub fn parse(value: &str, size: u64) -> Result<Option<Self>> {
+        let unsatisfiable = || AppError::RangeNotSatisfiable { size };
+
+        let spec = value.trim().strip_prefix("bytes=").ok_or_else(unsatisfiable)?;
+        if spec.contains(',') {
+            return Ok(None);
+        }
+
+        let (start, end) = spec.trim().split_once('-').ok_or_else(unsatisfiable)?;
+        let parse = |value: &str| value.trim().parse::<u64>().map_err(|_| unsatisfiable());
+
+        let range = match (start.trim().is_empty(), end.trim().is_empty()) {
+            (true, true) => return Err(unsatisfiable()),
+            (true, false) => {
+                let suffix = parse(end)?;
+                if suffix == 0 || size == 0 {
+                    return Err(unsatisfiable());
+                }
+                Self {
+                    start: size.saturating_sub(suffix),
+                    end: size - 1,
+                }
+            }
+            (false, open_end) => {
+                let start = parse(start)?;
+                let end = if open_end { u64::MAX } else { parse(end)? };
+                if start > end || start >= size {
+                    return Err(unsatisfiable());
+                }
+                Self {
+                    start,
+                    end: end.min(size - 1),
+                }
+            }
+        };
+
+        Ok(Some(range))
+    }
+
+    /// Returns the number of bytes in the range, for `Content-Length`.
+    pub fn content_length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Returns the indices of the first and last chunks that hold the range,
+    /// for chunks of `chunk_size` plaintext bytes.
+    pub fn chunk_span(&self, chunk_size: u64) -> (usize, usize) {
+        (
+            (self.start / chunk_size) as usize,
+            (self.end / chunk_size) as usize,
+        )
+    }
+
+    /// Returns the part of chunk `index` that falls inside the range, as
+    /// offsets into that chunk's plaintext of `chunk_len` bytes.
+    pub fn slice_of_chunk(&self, index: usize, chunk_size: u64, chunk_len: usize) -> Range<usize> {
+        let chunk_start = index as u64 * chunk_size;
+        let from = self.start.saturating_sub(chunk_start).min(chunk_len as u64);
+        let to = (self.end + 1).saturating_sub(chunk_start).min(chunk_len as u64);
+        from as usize..to.max(from) as usize
+    }
+
+    /// Formats the `Content-Range` value for a resource of `size` bytes.
+    pub fn content_range(&self, size: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, size)
+    }
+}
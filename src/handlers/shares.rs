@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Extension,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::files::stream_file,
+    models::session::Session,
+    repositories,
+    response::json_response,
+    services::shares as share_service,
+    state::AppState,
+};
+
+/// The request payload for creating a share link.
+#[derive(Deserialize, ToSchema)]
+pub struct CreateShareRequest {
+    /// How long the link stays valid, in seconds; defaults to `SHARE_LINK_TTL_SECS`.
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Creates a public download link for a file.
+#[utoipa::path(
+    post,
+    path = "/api/files/{file_id}/share",
+    tag = "files",
+    params(("file_id" = Uuid, Path, description = "The file ID")),
+    request_body = CreateShareRequest,
+    responses(
+        (status = 201, description = "Share link created"),
+        (status = 400, description = "Expiry out of range"),
+        (status = 404, description = "File not found")
+    )
+)]
+pub async fn create_share(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(file_id): Path<Uuid>,
+    Json(req): Json<CreateShareRequest>,
+) -> Result<Response> {
+    let user_id = session.user_id;
+
+    // A share link hands out the file's contents, which an impersonating
+    // admin must never be able to read.
+    if session.is_impersonated() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let ttl_secs = req.expires_in_secs.unwrap_or(state.config.share_link_ttl_secs);
+    if ttl_secs == 0 || ttl_secs > state.config.share_link_max_ttl_secs {
+        return Err(AppError::Validation(format!(
+            "expires_in_secs must be between 1 and {}",
+            state.config.share_link_max_ttl_secs
+        )));
+    }
+
+    let client = state.db.get().await?;
+    repositories::file::find_by_id(&client, file_id, user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let (token, share) = share_service::create_share(&state, user_id, file_id, ttl_secs).await?;
+
+    tracing::info!("🔗 Share link created for file {} by user {}", file_id, user_id);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "token": token,
+        "url": format!("/api/share/{}", token),
+        "file_id": file_id.to_string(),
+        "expires_at": share.expires_at.to_rfc3339()
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::CREATED, response))
+}
+
+/// Revokes a share link.
+#[utoipa::path(
+    delete,
+    path = "/api/files/{file_id}/share/{token}",
+    tag = "files",
+    params(
+        ("file_id" = Uuid, Path, description = "The file ID"),
+        ("token" = String, Path, description = "The share token")
+    ),
+    responses(
+        (status = 200, description = "Share link revoked"),
+        (status = 404, description = "No such share link for this file")
+    )
+)]
+pub async fn revoke_share(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path((file_id, token)): Path<(Uuid, String)>,
+) -> Result<Response> {
+    if !share_service::revoke_share(&state, session.user_id, file_id, &token).await? {
+        return Err(AppError::NotFound);
+    }
+
+    tracing::info!("🔗 Share link revoked for file {} by user {}", file_id, session.user_id);
+
+    let response = sonic_rs::to_string(&sonic_rs::json!({
+        "message": "Share link revoked"
+    }))
+    .unwrap();
+
+    Ok(json_response(StatusCode::OK, response))
+}
+
+/// Downloads a shared file without a session.
+///
+/// The file's DEK is unwrapped with the KEK like any download, so the owner's
+/// password is not needed. Streams and honors `Range` like `download_file`.
+#[utoipa::path(
+    get,
+    path = "/api/share/{token}",
+    tag = "files",
+    params(("token" = String, Path, description = "The share token")),
+    responses(
+        (status = 200, description = "Decrypted file contents", content_type = "application/octet-stream"),
+        (status = 206, description = "The byte range requested with a single-range `Range` header", content_type = "application/octet-stream"),
+        (status = 404, description = "Link expired, revoked or unknown, the file was deleted, or its owner was disabled"),
+        (status = 416, description = "Malformed `Range` header or range outside the file")
+    )
+)]
+pub async fn download_shared(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let share = share_service::find_share(&state, &token)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = state.db.get().await?;
+
+    // A disabled owner loses access to their files, and so do their links.
+    let owner = repositories::user::find_by_id(&client, &share.user_id, &state.stmt_cache).await?;
+    if !owner.is_some_and(|owner| owner.is_active) {
+        return Err(AppError::NotFound);
+    }
+
+    let file = repositories::file::find_by_id(&client, share.file_id, share.user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    drop(client);
+
+    tracing::info!("📥 Shared download of file {}", share.file_id);
+
+    stream_file(&state, file, &headers, None).await
+}
@@ -0,0 +1,107 @@
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    response::Response,
+    Extension,
+};
+use futures::{SinkExt, Stream, StreamExt};
+use std::time::Duration;
+
+use crate::{
+    error::Result,
+    handlers::files::load_upload_metadata,
+    middleware_layer::auth::SessionToken,
+    models::session::Session,
+    services::upload_progress,
+    state::AppState,
+};
+
+/// Pushes the progress of one of the caller's uploads over a WebSocket.
+///
+/// The first message is the current state of the upload, followed by one
+/// `progress` message per stored chunk. The socket closes after a
+/// `finalized` or `cancelled` message, once no chunk has arrived for
+/// `UPLOAD_EXPIRATION_SECS`, or when the caller's session is revoked or
+/// expires.
+#[utoipa::path(
+    get,
+    path = "/ws/uploads/{upload_session_id}",
+    tag = "files",
+    params(("upload_session_id" = String, Path, description = "The upload session ID")),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 400, description = "Unknown or expired upload session"),
+        (status = 429, description = "The user already has MAX_PROGRESS_SOCKETS_PER_USER sockets open")
+    )
+)]
+pub async fn upload_progress_socket(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Extension(token): Extension<SessionToken>,
+    Path(upload_session_id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response> {
+    // Subscribe before reading the snapshot, so no chunk stored in between is missed.
+    let messages =
+        upload_progress::subscribe(&state, session.user_id, token.id, &upload_session_id).await?;
+
+    let mut redis = state.redis.clone();
+    let redis_key = format!("upload:{}:{}", session.user_id, upload_session_id);
+    let metadata = load_upload_metadata(&mut redis, &redis_key).await?;
+
+    let snapshot = upload_progress::progress_message(
+        &upload_session_id,
+        metadata.chunks_received_count,
+        metadata.total_chunks,
+        metadata.chunks_written_bytes,
+    );
+    let idle_timeout = Duration::from_secs(state.config.upload_expiration_secs);
+
+    Ok(ws.on_upgrade(move |socket| forward_progress(socket, snapshot, messages, idle_timeout)))
+}
+
+async fn forward_progress(
+    socket: WebSocket,
+    snapshot: String,
+    messages: impl Stream<Item = String> + Send + 'static,
+    idle_timeout: Duration,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut messages = std::pin::pin!(messages);
+
+    if sender.send(Message::Text(snapshot.into())).await.is_err() {
+        return;
+    }
+
+    let reason = loop {
+        tokio::select! {
+            message = tokio::time::timeout(idle_timeout, messages.next()) => {
+                let message = match message {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break "Session ended",
+                    Err(_) => break "Upload session expired",
+                };
+                let closing = upload_progress::is_closing(&message);
+                if sender.send(Message::Text(message.into())).await.is_err() {
+                    return;
+                }
+                if closing {
+                    break "Upload session closed";
+                }
+            }
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    };
+
+    let _ = sender
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::NORMAL,
+            reason: reason.into(),
+        })))
+        .await;
+}
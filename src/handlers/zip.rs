@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use axum::body::Bytes;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+
+/// Sizes and CRC follow the data in a descriptor; names are UTF-8.
+const FLAGS: u16 = 0x0008 | 0x0800;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const U32_MARKER: u32 = u32::MAX;
+const U16_MARKER: u16 = u16::MAX;
+
+struct CentralEntry {
+    name: Vec<u8>,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+struct OpenEntry {
+    crc: crc32fast::Hasher,
+    size: u64,
+    zip64: bool,
+}
+
+/// Writes a ZIP archive of stored (uncompressed) entries as a sequence of
+/// byte blocks, so it can be streamed without knowing each entry's CRC up
+/// front.
+///
+/// Each entry is a local header from [`start_entry`](Self::start_entry), its
+/// data passed through [`write`](Self::write), and a data descriptor from
+/// [`finish_entry`](Self::finish_entry); [`finish`](Self::finish) returns the
+/// central directory. ZIP64 records are used only where a size, offset or
+/// entry count does not fit the classic format.
+pub struct ZipStreamWriter {
+    entries: Vec<CentralEntry>,
+    names: HashSet<String>,
+    offset: u64,
+    open: Option<OpenEntry>,
+}
+
+impl Default for ZipStreamWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipStreamWriter {
+    /// Creates an empty archive.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            names: HashSet::new(),
+            offset: 0,
+            open: None,
+        }
+    }
+
+    /// Returns the number of bytes produced so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.offset
+    }
+
+    /// Starts an entry of `size` bytes and returns its local header.
+    ///
+    /// Path separators in `name` are replaced so an entry can never be
+    /// extracted outside the archive's directory, and a name already in the
+    /// archive gets a ` (n)` suffix.
+    ///
+    /// # Returns
+    ///
+    /// The header and the entry name actually used.
+    pub fn start_entry(&mut self, name: &str, size: u64, modified: DateTime<Utc>) -> (Bytes, String) {
+        assert!(self.open.is_none(), "previous ZIP entry not finished");
+
+        let name = self.unique_name(name);
+        let (dos_time, dos_date) = dos_datetime(modified);
+        let zip64 = size >= U32_MARKER as u64 || self.offset >= U32_MARKER as u64;
+
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, if zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT });
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, dos_time);
+        put_u16(&mut header, dos_date);
+        put_u32(&mut header, 0);
+        put_u32(&mut header, if zip64 { U32_MARKER } else { 0 });
+        put_u32(&mut header, if zip64 { U32_MARKER } else { 0 });
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, if zip64 { 20 } else { 0 });
+        header.extend_from_slice(name.as_bytes());
+        if zip64 {
+            put_u16(&mut header, ZIP64_EXTRA_ID);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, 0);
+            put_u64(&mut header, 0);
+        }
+
+        self.entries.push(CentralEntry {
+            name: name.as_bytes().to_vec(),
+            crc: 0,
+            size: 0,
+            offset: self.offset,
+            dos_time,
+            dos_date,
+        });
+        self.open = Some(OpenEntry {
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+            zip64,
+        });
+        self.offset += header.len() as u64;
+
+        (Bytes::from(header), name)
+    }
+
+    /// Accounts for `data` written as part of the open entry.
+    pub fn write(&mut self, data: &[u8]) {
+        let open = self.open.as_mut().expect("no open ZIP entry");
+        open.crc.update(data);
+        open.size += data.len() as u64;
+        self.offset += data.len() as u64;
+    }
+
+    /// Closes the open entry and returns its data descriptor.
+    ///
+    /// Fails if more data was written than a non-ZIP64 entry can describe,
+    /// i.e. the `size` passed to `start_entry` was wrong.
+    pub fn finish_entry(&mut self) -> std::io::Result<Bytes> {
+        let open = self.open.take().expect("no open ZIP entry");
+        if !open.zip64 && open.size >= U32_MARKER as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "ZIP entry grew past its announced size",
+            ));
+        }
+
+        let crc = open.crc.finalize();
+        let entry = self.entries.last_mut().expect("open entry has a central record");
+        entry.crc = crc;
+        entry.size = open.size;
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, crc);
+        if open.zip64 {
+            put_u64(&mut descriptor, open.size);
+            put_u64(&mut descriptor, open.size);
+        } else {
+            put_u32(&mut descriptor, open.size as u32);
+            put_u32(&mut descriptor, open.size as u32);
+        }
+        self.offset += descriptor.len() as u64;
+
+        Ok(Bytes::from(descriptor))
+    }
+
+    /// Returns the central directory and end records that close the archive.
+    pub fn finish(self) -> Bytes {
+        assert!(self.open.is_none(), "last ZIP entry not finished");
+
+        let directory_offset = self.offset;
+        let mut out = Vec::new();
+
+        for entry in &self.entries {
+            let large_size = entry.size >= U32_MARKER as u64;
+            let large_offset = entry.offset >= U32_MARKER as u64;
+
+            let mut extra = Vec::new();
+            if large_size {
+                put_u64(&mut extra, entry.size);
+                put_u64(&mut extra, entry.size);
+            }
+            if large_offset {
+                put_u64(&mut extra, entry.offset);
+            }
+            let zip64 = !extra.is_empty();
+
+            put_u32(&mut out, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut out, VERSION_ZIP64);
+            put_u16(&mut out, if zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT });
+            put_u16(&mut out, FLAGS);
+            put_u16(&mut out, 0);
+            put_u16(&mut out, entry.dos_time);
+            put_u16(&mut out, entry.dos_date);
+            put_u32(&mut out, entry.crc);
+            let size = if large_size { U32_MARKER } else { entry.size as u32 };
+            put_u32(&mut out, size);
+            put_u32(&mut out, size);
+            put_u16(&mut out, entry.name.len() as u16);
+            put_u16(&mut out, if zip64 { extra.len() as u16 + 4 } else { 0 });
+            put_u16(&mut out, 0);
+            put_u16(&mut out, 0);
+            put_u16(&mut out, 0);
+            put_u32(&mut out, 0);
+            put_u32(&mut out, if large_offset { U32_MARKER } else { entry.offset as u32 });
+            out.extend_from_slice(&entry.name);
+            if zip64 {
+                put_u16(&mut out, ZIP64_EXTRA_ID);
+                put_u16(&mut out, extra.len() as u16);
+                out.extend_from_slice(&extra);
+            }
+        }
+
+        let directory_size = out.len() as u64;
+        let count = self.entries.len() as u64;
+        let needs_zip64 = count >= U16_MARKER as u64
+            || directory_size >= U32_MARKER as u64
+            || directory_offset >= U32_MARKER as u64;
+
+        if needs_zip64 {
+            let zip64_end_offset = directory_offset + directory_size;
+            put_u32(&mut out, ZIP64_END_SIGNATURE);
+            put_u64(&mut out, 44);
+            put_u16(&mut out, VERSION_ZIP64);
+            put_u16(&mut out, VERSION_ZIP64);
+            put_u32(&mut out, 0);
+            put_u32(&mut out, 0);
+            put_u64(&mut out, count);
+            put_u64(&mut out, count);
+            put_u64(&mut out, directory_size);
+            put_u64(&mut out, directory_offset);
+
+            put_u32(&mut out, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut out, 0);
+            put_u64(&mut out, zip64_end_offset);
+            put_u32(&mut out, 1);
+        }
+
+        put_u32(&mut out, END_SIGNATURE);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        let count16 = if needs_zip64 { U16_MARKER } else { count as u16 };
+        put_u16(&mut out, count16);
+        put_u16(&mut out, count16);
+        put_u32(&mut out, if needs_zip64 { U32_MARKER } else { directory_size as u32 });
+        put_u32(&mut out, if needs_zip64 { U32_MARKER } else { directory_offset as u32 });
+        put_u16(&mut out, 0);
+
+        Bytes::from(out)
+    }
+
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut base: String = name
+            .chars()
+            .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+            .collect();
+        // The length field is 16 bits; trim on a character boundary.
+        while base.len() > U16_MARKER as usize - 16 {
+            base.pop();
+        }
+
+        let mut candidate = base.clone();
+        let mut n = 2;
+        while !self.names.insert(candidate.clone()) {
+            candidate = crate::repositories::file::numbered_filename(&base, n);
+            n += 1;
+        }
+        candidate
+    }
+}
+
+/// Converts a timestamp to MS-DOS time and date, clamped to the 1980–2107
+/// range the format can hold.
+fn dos_datetime(at: DateTime<Utc>) -> (u16, u16) {
+    let year = at.year().clamp(1980, 2107);
+    let time = ((at.hour() as u16) << 11) | ((at.minute() as u16) << 5) | (at.second() as u16 / 2);
+    let date = (((year - 1980) as u16) << 9) | ((at.month() as u16) << 5) | at.day() as u16;
+    (time, date)
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,91 @@
+//! Encrypted, chunked file storage service.
+//!
+//! The binary in `main.rs` only wires configuration, background jobs and the
+//! listener; everything else lives here so tests can build the router with
+//! [`router::build_router`] and drive it without binding a socket.
+
+pub mod config;
+pub mod error;
+pub mod folder_cache;
+pub mod metrics;
+pub mod openapi;
+pub mod response;
+pub mod router;
+pub mod state;
+pub mod statement_cache;
+pub mod tenant;
+pub mod crypto {
+    pub mod aes;
+    pub mod dek;
+    pub mod kek;
+    pub mod csrf;
+    pub mod checksum;
+}
+
+pub mod models {
+    pub mod user;
+    pub mod session;
+    pub mod file;
+    pub mod folder;
+    pub mod pagination;
+    pub mod share;
+    pub mod audit;
+}
+
+pub mod repositories {
+    pub mod user;
+    pub mod file;
+    pub mod folder;
+    pub mod audit;
+    pub mod chunk_ref;
+    pub mod two_factor;
+}
+
+pub mod services {
+    pub mod auth;
+    pub mod files;
+    pub mod folders;
+    pub mod sessions;
+    pub mod antivirus;
+    pub mod shares;
+    pub mod kek_rotation;
+    pub mod chunk_store;
+    pub mod chunk_gc;
+    pub mod two_factor;
+    pub mod mailer;
+    pub mod password_reset;
+    pub mod audit;
+    pub mod upload_progress;
+    pub mod events;
+}
+
+pub mod handlers {
+    pub mod auth;
+    pub mod files;
+    pub mod folders;
+    pub mod admin;
+    pub mod chunk_buffer;
+    pub mod chunk_form;
+    pub mod range;
+    pub mod zip;
+    pub mod shares;
+    pub mod health;
+    pub mod upload_progress;
+    pub mod events;
+}
+
+pub mod middleware_layer {
+    pub mod auth;
+    pub mod csrf;
+    pub mod rate_limit;
+    pub mod ip_filter;
+    pub mod role;
+    pub mod security_headers;
+    pub mod redis_retry;
+}
+
+pub mod validation {
+    pub mod auth;
+    pub mod files;
+}
+
@@ -1,71 +1,12 @@
-use axum::{
-    Router,
-    routing::{get, post, delete},
-    middleware::from_fn_with_state,
-};
-use http::{Method, header};
+use std::future::IntoFuture;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use std::time::Duration;
-use tower_cookies::CookieManagerLayer;
-use tower_governor::governor::GovernorConfigBuilder;
-use tower_http::{
-    services::ServeDir,
-    trace::{TraceLayer, DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, DefaultOnFailure},
-    cors::CorsLayer,
-};
-
-use tracing::Level;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-mod config;
-mod error;
-mod state;
-mod statement_cache;
-mod crypto {
-    pub mod aes;
-    pub mod dek;
-    pub mod kek;
-    pub mod csrf;
-}
-
-mod models {
-    pub mod user;
-    pub mod session;
-    pub mod file;
-    pub mod folder;
-}
-
-mod repositories {
-    pub mod user;
-    pub mod file;
-    pub mod folder;
-}
-
-mod services {
-    pub mod auth;
-    pub mod files;
-    pub mod folders;
-}
 
-mod handlers {
-    pub mod auth;
-    pub mod files;
-    pub mod folders;
-}
-
-mod middleware_layer {
-    pub mod auth;
-    pub mod csrf;
-    pub mod rate_limit;
-}
-
-mod validation {
-    pub mod auth;
-}
+use anyhow::Context;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use config::Config;
-use state::AppState;
+use rocket::{config::Config, crypto, handlers, router, services, state::AppState};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -78,12 +19,32 @@ async fn main() -> anyhow::Result<()> {
 
     dotenvy::dotenv().ok();
 
+    rocket::metrics::install_recorder();
+
     let config = Config::from_env()?;
     tracing::info!("✅ Configuration loaded successfully");
 
+    tokio::fs::create_dir_all(&config.upload_dir)
+        .await
+        .with_context(|| format!("Failed to create UPLOAD_DIR {}", config.upload_dir.display()))?;
+    tracing::info!("✅ Storing chunks in {}", config.upload_dir.display());
+
     let state = AppState::new(&config).await?;
     tracing::info!("✅ AppState initialized with optimized pools");
 
+    if std::env::args().nth(1).as_deref() == Some("rewrap-keks") {
+        return rewrap_keks(&state).await;
+    }
+
+    match crypto::kek::verify_master_key(&state.db, state.config.master_key.as_ref()).await {
+        Ok(count) => tracing::info!("✅ MASTER_KEY verified against {} stored KEK(s)", count),
+        Err(e) if state.config.master_key_mismatch_fatal => {
+            tracing::error!("❌ FATAL: {}", e);
+            return Err(e.into());
+        }
+        Err(e) => tracing::error!("❌ {} (continuing: MASTER_KEY_MISMATCH_FATAL=false)", e),
+    }
+
     // Garantir que KEK v1 existe na startup
     match crypto::kek::ensure_kek_exists(
         &state.db,
@@ -101,86 +62,22 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    let cors = CorsLayer::new()
-        .allow_origin([
-            "http://localhost:3000".parse().unwrap(),
-            "http://127.0.0.1:3000".parse().unwrap(),
-            "http://[::1]:3000".parse().unwrap(),
-        ])
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::PATCH,
-            Method::OPTIONS,
-        ])
-        .allow_headers([
-            header::CONTENT_TYPE,
-            header::AUTHORIZATION,
-            header::ACCEPT,
-            header::COOKIE,
-            "x-csrf-token".parse().unwrap(),
-        ])
-        .allow_credentials(true)
-        .expose_headers(["x-csrf-token".parse().unwrap()])
-        .max_age(Duration::from_secs(86400));
-
-    let governor_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .per_second(10_000)
-            .burst_size(50_000)
-            .use_headers()
-            .finish()
-            .unwrap(),
-    );
+    if let Err(e) = services::kek_rotation::resume_rotation(&state).await {
+        tracing::error!("❌ Failed to resume KEK rotation: {}", e);
+    }
 
-    let auth_routes = Router::new()
-        .route("/api/auth/register", post(handlers::auth::register))
-        .route("/api/auth/login", post(handlers::auth::login))
-        .route("/api/auth/logout", post(handlers::auth::logout))
-        .route("/api/auth/change-password", post(handlers::auth::change_password));
-
-    let file_routes = Router::new()
-        .route("/api/files/upload/init", post(handlers::files::init_upload))
-        .route("/api/files/upload/chunk", post(handlers::files::upload_chunk))
-        .route("/api/files/upload/finalize", post(handlers::files::finalize_upload))
-        .route("/api/files/upload/cancel", post(handlers::files::cancel_upload))
-        .route("/api/files/recalculate-quota", post(handlers::files::recalculate_user_quota))
-        .route("/api/files/storage/info", get(handlers::files::storage_info))
-        .route("/api/files", get(handlers::files::list_files))
-        .route("/api/files/{file_id}", get(handlers::files::download_file))
-        .route("/api/files/{file_id}", delete(handlers::files::delete_file));
-
-    let folder_routes = Router::new()
-        .route("/api/folders/list", get(handlers::folders::list_folder_contents))
-        .route("/api/folders/{folder_id}", get(handlers::folders::get_folder_stats))
-        .route("/api/folders", post(handlers::folders::create_folder))
-        .route("/api/folders/{folder_id}", delete(handlers::folders::delete_folder));
-
-    let app = Router::new()
-        .merge(auth_routes)
-        .merge(file_routes)
-        .merge(folder_routes)
-        .layer(tower_governor::GovernorLayer::new(governor_conf))
-        .layer(from_fn_with_state(state.clone(), middleware_layer::csrf::verify_csrf))
-        .layer(from_fn_with_state(state.clone(), middleware_layer::auth::require_auth))
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true))
-                .on_request(DefaultOnRequest::default().level(Level::DEBUG))
-                .on_response(DefaultOnResponse::default().level(Level::DEBUG))
-                .on_failure(DefaultOnFailure::default().level(Level::ERROR)),
-        )
-        .layer(CookieManagerLayer::new())
-        .layer(cors)
-        .with_state(state.clone())
-        .fallback_service(ServeDir::new("files/public"));
+    let app = router::build_router(state.clone());
+
+    let shutdown = CancellationToken::new();
 
     let cleanup_state = state.clone();
-    tokio::spawn(async move {
+    let cleanup_shutdown = shutdown.clone();
+    let cleanup_task = tokio::spawn(async move {
         loop {
-            tokio::time::sleep(Duration::from_secs(3600)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(3600)) => {}
+                _ = cleanup_shutdown.cancelled() => break,
+            }
             tracing::info!("🧹 Running scheduled cleanup of expired uploads...");
             match handlers::files::cleanup_expired_uploads(cleanup_state.clone()).await {
                 Ok(_) => {
@@ -193,17 +90,146 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    let reaper_state = state.clone();
+    let reaper_shutdown = shutdown.clone();
+    let reaper_task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(3600)) => {}
+                _ = reaper_shutdown.cancelled() => break,
+            }
+            let grace_secs = reaper_state.config.trash_retention_secs();
+            match services::files::purge_deleted_files(&reaper_state, grace_secs).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("🗑️ Purged {} deleted file(s) past the trash window", purged),
+                Err(e) => tracing::error!("❌ Deleted file purge failed: {}", e),
+            }
+        }
+    });
+
+    let gc_state = state.clone();
+    let gc_shutdown = shutdown.clone();
+    let gc_task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(86400)) => {}
+                _ = gc_shutdown.cancelled() => break,
+            }
+            let min_age = Duration::from_secs(gc_state.config.upload_expiration_secs);
+            if let Err(e) = services::chunk_gc::collect_orphaned_chunks(&gc_state, min_age).await {
+                tracing::error!("❌ Orphaned chunk collection failed: {}", e);
+            }
+        }
+    });
+
+    let kek_cache = state.kek_cache.clone();
+    let kek_trim_interval = Duration::from_secs(state.config.kek_cache_ttl_secs.clamp(1, 60));
+    let kek_trim_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(kek_trim_interval) => {}
+                _ = kek_trim_shutdown.cancelled() => break,
+            }
+            let evicted = kek_cache.trim().await;
+            if evicted > 0 {
+                tracing::debug!("🔑 Evicted {} expired KEK(s) from the cache", evicted);
+            }
+        }
+    });
+
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     tracing::info!("🚀 Server listening on http://{}", addr);
     tracing::info!("✅ Background cleanup job started (runs every hour)");
     tracing::info!("✅ All systems operational");
 
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("🛑 Shutdown signal received, draining connections...");
+        signal_shutdown.cancel();
+    });
+
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(
+    let server_shutdown = shutdown.clone();
+    let server = axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
-    .await?;
+    .with_graceful_shutdown(async move { server_shutdown.cancelled().await })
+    .into_future();
+
+    let drain_timeout = Duration::from_secs(state.config.shutdown_timeout_secs);
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(drain_timeout).await;
+        } => {
+            tracing::warn!(
+                "⚠️ In-flight requests still running after {}s, closing them",
+                drain_timeout.as_secs()
+            );
+        }
+    }
+
+    // A cleanup pass already running is allowed to finish its current step.
+    let _ = tokio::join!(cleanup_task, reaper_task, gc_task);
+    tracing::info!("👋 Shutdown complete");
+
+    Ok(())
+}
+
+/// Resolves on Ctrl-C or, on Unix, on `SIGTERM`.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("❌ Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("❌ Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Re-encrypts every KEK from `OLD_MASTER_KEY` to the configured `MASTER_KEY`.
+async fn rewrap_keks(state: &AppState) -> anyhow::Result<()> {
+    let mut old_master_key_hex = std::env::var("OLD_MASTER_KEY")
+        .map_err(|_| anyhow::anyhow!("OLD_MASTER_KEY must be set to the previous master key"))?;
+    let old_master_key = zeroize::Zeroizing::new(
+        hex::decode(&old_master_key_hex)
+            .map_err(|_| anyhow::anyhow!("OLD_MASTER_KEY must be valid hexadecimal"))?,
+    );
+    zeroize::Zeroize::zeroize(&mut old_master_key_hex);
+
+    let rewrapped =
+        crypto::kek::rewrap_keks(
+            &state.db,
+            &old_master_key,
+            state.config.master_key.as_ref(),
+            &state.kek_cache,
+        )
+        .await?;
 
+    tracing::info!("✅ Rewrapped {} KEK(s) under the new MASTER_KEY", rewrapped);
     Ok(())
 }
@@ -0,0 +1,81 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use metrics::{counter, gauge};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::state::AppState;
+
+/// The number of requests that gave up waiting for a database connection.
+pub static DB_POOL_EXHAUSTED_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the Prometheus recorder as the global `metrics` recorder.
+///
+/// Safe to call more than once: the recorder is installed on the first call
+/// and the same handle is returned afterwards. Until it is installed, every
+/// counter and gauge update is a no-op.
+pub fn install_recorder() -> &'static PrometheusHandle {
+    PROMETHEUS.get_or_init(|| {
+        PrometheusBuilder::new()
+            .install_recorder()
+            .expect("failed to install the Prometheus recorder")
+    })
+}
+
+/// Records a request that timed out waiting for a database connection.
+pub fn record_db_pool_exhausted() {
+    let total = DB_POOL_EXHAUSTED_TOTAL.fetch_add(1, Ordering::Relaxed) + 1;
+    counter!("rocket_db_pool_exhausted_total").increment(1);
+    tracing::warn!(db_pool_exhausted_total = total, "⚠️ Database pool exhausted");
+}
+
+/// Records an encrypted and stored upload chunk of `bytes` plaintext bytes.
+pub fn record_upload_chunk(bytes: usize) {
+    counter!("rocket_upload_chunks_total").increment(1);
+    counter!("rocket_upload_bytes_total").increment(bytes as u64);
+}
+
+/// Records `bytes` decrypted bytes sent to a downloading client.
+pub fn record_download_bytes(bytes: usize) {
+    counter!("rocket_download_bytes_total").increment(bytes as u64);
+}
+
+/// Records the outcome of a login attempt.
+pub fn record_login(success: bool) {
+    counter!("rocket_logins_total", "outcome" => outcome(success)).increment(1);
+}
+
+/// Records the outcome of a registration attempt.
+pub fn record_registration(success: bool) {
+    counter!("rocket_registrations_total", "outcome" => outcome(success)).increment(1);
+}
+
+/// Records an error response by its HTTP status code.
+pub fn record_error(status: StatusCode) {
+    counter!("rocket_errors_total", "status" => status.as_str().to_string()).increment(1);
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success { "success" } else { "failure" }
+}
+
+/// Serves the collected metrics in the Prometheus text format.
+///
+/// Point-in-time gauges such as the free upload and download slots are
+/// sampled at scrape time.
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    gauge!("rocket_upload_slots_available").set(state.upload_limiter.available_permits() as f64);
+    gauge!("rocket_download_slots_available").set(state.download_limiter.available_permits() as f64);
+    gauge!("rocket_db_pool_connections_available").set(state.db.status().available as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        install_recorder().render(),
+    )
+}
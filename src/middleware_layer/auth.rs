@@ -1,7 +1,7 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
+    http::{header, HeaderMap, Request, StatusCode},
     middleware::Next,
     response::Response,
     Extension,
@@ -11,12 +11,24 @@ use uuid::Uuid;
 
 use crate::{
     error::AppError,
+    middleware_layer::redis_retry::with_retry,
     models::session::Session,
     state::AppState,
 };
 
 use redis::AsyncCommands;
 
+/// The session token a request authenticated with, inserted into the request
+/// extensions by [`require_auth`] next to the [`Session`].
+#[derive(Debug, Clone, Copy)]
+pub struct SessionToken {
+    /// The session ID.
+    pub id: Uuid,
+    /// Whether the token came from an `Authorization: Bearer` header rather
+    /// than the `session_id` cookie.
+    pub bearer: bool,
+}
+
 /// Extracts the session token from the request cookies.
 ///
 /// # Arguments
@@ -32,8 +44,23 @@ fn extract_session_token(cookies: &Cookies) -> Option<Uuid> {
         .and_then(|cookie| Uuid::parse_str(cookie.value()).ok())
 }
 
+/// Extracts the session token from an `Authorization: Bearer <session_id>`
+/// header.
+fn extract_bearer_token(headers: &HeaderMap) -> Option<Uuid> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    Uuid::parse_str(token.trim()).ok()
+}
+
 /// A middleware that requires a valid session to be present.
 ///
+/// The session ID is read from the `session_id` cookie, or from an
+/// `Authorization: Bearer` header when no cookie is present, for clients
+/// that are not browsers.
+///
 /// # Arguments
 ///
 /// * `state` - The application state.
@@ -52,22 +79,36 @@ pub async fn require_auth(
 ) -> Result<Response, StatusCode> {
     tracing::debug!("🔐 Checking authentication...");
     
-    let session_id = extract_session_token(&cookies)
-        .ok_or_else(|| {
-            tracing::warn!("❌ No session_id cookie found");
-            StatusCode::FORBIDDEN
-        })?;
+    let token = match extract_session_token(&cookies) {
+        Some(id) => SessionToken { id, bearer: false },
+        None => extract_bearer_token(request.headers())
+            .map(|id| SessionToken { id, bearer: true })
+            .ok_or_else(|| {
+                tracing::warn!("❌ No session_id cookie or bearer token found");
+                StatusCode::FORBIDDEN
+            })?,
+    };
+    let session_id = token.id;
 
     tracing::debug!("🔑 Found session_id: {}", session_id);
 
-    let session_json: String = state
-        .redis
-        .get(format!("session:{}", session_id))
-        .await
-        .map_err(|e| {
-            tracing::warn!("❌ Redis error or session not found: {}", e);
-            StatusCode::FORBIDDEN
-        })?;
+    let session_key = crate::services::sessions::session_key(&state, &session_id);
+    let session_json: Option<String> = with_retry(&state.redis, |mut redis| {
+        let session_key = &session_key;
+        async move { redis.get(session_key).await }
+    })
+    .await
+    .map_err(|e| {
+        // Redis is unreachable: the session may well be valid, so answer 503
+        // rather than treating the user as logged out.
+        tracing::error!("❌ Redis error while loading session: {}", e);
+        StatusCode::SERVICE_UNAVAILABLE
+    })?;
+
+    let session_json = session_json.ok_or_else(|| {
+        tracing::warn!("❌ Session not found: {}", session_id);
+        StatusCode::FORBIDDEN
+    })?;
 
     let session: Session = sonic_rs::from_str(&session_json)
         .map_err(|e| {
@@ -80,7 +121,7 @@ pub async fn require_auth(
         
         let _: () = state
             .redis
-            .del(format!("session:{}", session_id))
+            .del(&session_key)
             .await
             .unwrap_or(());
         
@@ -90,6 +131,7 @@ pub async fn require_auth(
     tracing::debug!("✅ User authenticated: {}", session.user_id);
 
     request.extensions_mut().insert(session);
+    request.extensions_mut().insert(token);
 
     Ok(next.run(request).await)
 }
@@ -8,10 +8,20 @@ use axum::{
 use tower_cookies::Cookies;
 use redis::AsyncCommands;
 
-use crate::{error::AppError, state::AppState};
+use crate::{
+    crypto::csrf::csrf_tokens_match,
+    error::AppError,
+    middleware_layer::{auth::SessionToken, redis_retry::with_retry},
+    state::AppState,
+};
 
 /// A middleware that verifies the CSRF token.
 ///
+/// Requests authenticated with a bearer token are exempt: browsers never
+/// attach an `Authorization` header on their own, so those requests cannot be
+/// forged cross-site. Must run after `require_auth`, which records how the
+/// request authenticated.
+///
 /// # Arguments
 ///
 /// * `state` - The application state.
@@ -23,7 +33,7 @@ use crate::{error::AppError, state::AppState};
 ///
 /// A `Response` or an error `AppError`.
 pub async fn verify_csrf(
-    State(mut state): State<AppState>,
+    State(state): State<AppState>,
     cookies: Cookies,
     req: Request<Body>,
     next: Next,
@@ -36,10 +46,15 @@ pub async fn verify_csrf(
         return next.run(req).await;
     }
 
+    if req.extensions().get::<SessionToken>().is_some_and(|token| token.bearer) {
+        tracing::debug!("✅ CSRF exemption: bearer-authenticated request");
+        return next.run(req).await;
+    }
+
     let csrf_token_cookie = match cookies.get("csrf_token") {
         Some(c) => c.value().to_string(),
         None => {
-            tracing::warn!("❌ CSRF: Cookie csrf_token não encontrado");
+            tracing::warn!("❌ CSRF: csrf_token cookie not found");
             return AppError::Authentication("Missing CSRF token cookie".to_string())
                 .into_response();
         }
@@ -53,13 +68,13 @@ pub async fn verify_csrf(
         Some(token) => match token.to_str() {
             Ok(t) => t.to_string(),
             Err(_) => {
-                tracing::warn!("❌ CSRF: Header com formato inválido");
+                tracing::warn!("❌ CSRF: malformed header");
                 return AppError::Authentication("Invalid CSRF token format".to_string())
                     .into_response();
             }
         },
         None => {
-            tracing::warn!("❌ CSRF: Header x-csrf-token não encontrado");
+            tracing::warn!("❌ CSRF: x-csrf-token header not found");
             return AppError::Authentication("Missing CSRF token header".to_string())
                 .into_response();
         }
@@ -70,29 +85,35 @@ pub async fn verify_csrf(
         &csrf_token_header[..20.min(csrf_token_header.len())]
     );
 
-    if csrf_token_cookie != csrf_token_header {
-        tracing::warn!("❌ CSRF: Tokens não conferem");
+    if !csrf_tokens_match(&csrf_token_cookie, &csrf_token_header) {
+        tracing::warn!("❌ CSRF: tokens do not match");
         return AppError::Authentication("CSRF token mismatch".to_string()).into_response();
     }
 
-    let csrf_key = format!("csrf:{}", csrf_token_cookie);
-    
-    match state
-        .redis
-        .get::<_, Option<String>>(&csrf_key)
-        .await
-    {
+    let csrf_key = crate::services::sessions::csrf_key(&state, &csrf_token_cookie);
+
+    let stored = with_retry(&state.redis, |mut redis| {
+        let csrf_key = &csrf_key;
+        async move { redis.get::<_, Option<String>>(csrf_key).await }
+    })
+    .await;
+
+    match stored {
         Ok(Some(_)) => {
-            tracing::debug!("✅ CSRF token válido");
+            tracing::debug!("✅ CSRF token valid");
             next.run(req).await
         }
         Ok(None) => {
-            tracing::warn!("❌ CSRF: Token expirado ou inválido");
+            tracing::warn!("❌ CSRF: token expired or invalid");
             AppError::Authentication("CSRF token expired or invalid".to_string()).into_response()
         }
         Err(e) => {
-            tracing::error!("❌ CSRF: Erro no Redis: {}", e);
-            AppError::Authentication("CSRF validation error".to_string()).into_response()
+            tracing::error!("❌ CSRF: Redis error: {}", e);
+            AppError::ServiceUnavailable {
+                message: "CSRF validation is temporarily unavailable".to_string(),
+                retry_after_secs: 1,
+            }
+            .into_response()
         }
     }
 }
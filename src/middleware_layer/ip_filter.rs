@@ -0,0 +1,101 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{error::AppError, state::AppState};
+
+/// The path of the routes guarded by the admin IP lists.
+const ADMIN_PATH: &str = "/api/admin";
+
+/// Returns whether `path` is `/api/admin` or lies beneath it.
+///
+/// A bare prefix match would also catch unrelated paths such as
+/// `/api/administrator`.
+pub fn is_admin_path(path: &str) -> bool {
+    path.strip_prefix(ADMIN_PATH)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Resolves the client IP of a request.
+///
+/// When `trust_proxy_headers` is set, `X-Real-IP` and then the last entry of
+/// `X-Forwarded-For` (the one appended by our own proxy) take precedence over
+/// the socket address. Never enable it when the server is reachable directly,
+/// since clients could then pick their own IP.
+pub fn client_ip(req: &Request<Body>, trust_proxy_headers: bool) -> Option<IpAddr> {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ci| ci.0.ip());
+
+    client_ip_from_parts(req.headers(), peer, trust_proxy_headers)
+}
+
+/// Resolves the client IP from a request's headers and socket address, for
+/// handlers that extract them separately; see [`client_ip`].
+pub fn client_ip_from_parts(headers: &HeaderMap, peer: Option<IpAddr>, trust_proxy_headers: bool) -> Option<IpAddr> {
+    if trust_proxy_headers {
+        let real_ip = headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+        if real_ip.is_some() {
+            return real_ip;
+        }
+
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    peer
+}
+
+/// A middleware that restricts the admin routes to configured networks.
+///
+/// It runs before authentication, so a disallowed source never reaches the
+/// session lookup. The denylist wins over the allowlist, and an empty
+/// allowlist allows every address not denied.
+pub async fn restrict_admin_ips(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !is_admin_path(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let allowlist = &state.config.admin_ip_allowlist;
+    let denylist = &state.config.admin_ip_denylist;
+
+    if allowlist.is_empty() && denylist.is_empty() {
+        return next.run(req).await;
+    }
+
+    let Some(ip) = client_ip(&req, state.config.trust_proxy_headers) else {
+        tracing::warn!("❌ Admin route requested without a resolvable client IP");
+        return AppError::Unauthorized.into_response();
+    };
+
+    if denylist.iter().any(|net| net.contains(&ip)) {
+        tracing::warn!("❌ Admin route requested from denied IP {}", ip);
+        return AppError::Unauthorized.into_response();
+    }
+
+    if !allowlist.is_empty() && !allowlist.iter().any(|net| net.contains(&ip)) {
+        tracing::warn!("❌ Admin route requested from IP {} outside the allowlist", ip);
+        return AppError::Unauthorized.into_response();
+    }
+
+    next.run(req).await
+}
@@ -1,6 +1,7 @@
 use axum::{
     body::Body,
     extract::{ConnectInfo, Request, State},
+    http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
     Extension,
@@ -11,11 +12,24 @@ use uuid::Uuid;
 
 use crate::{
     error::AppError,
+    middleware_layer::{ip_filter::client_ip, redis_retry::with_retry},
     models::session::Session,
     state::AppState,
     repositories,
+    validation::auth::normalize_username,
 };
 
+/// Reads an attempt counter, retrying transient Redis errors.
+///
+/// The limiters fail open: if Redis stays unreachable the request proceeds.
+async fn read_attempts(state: &AppState, key: &str) -> Option<i32> {
+    with_retry(&state.redis, |mut redis| async move {
+        redis::cmd("GET").arg(key).query_async(&mut redis).await
+    })
+    .await
+    .unwrap_or(None)
+}
+
 /// Extracts the real IP address from the request extensions.
 fn extract_real_ip(req: &Request<Body>) -> String {
     req.extensions()
@@ -31,13 +45,9 @@ pub async fn rate_limit_register(
     next: Next,
 ) -> Response {
     let ip = extract_real_ip(&req);
-    let key = format!("rate_limit:register:{}", ip);
+    let key = state.redis_key(format_args!("rate_limit:register:{}", ip));
     
-    let count: Option<i32> = redis::cmd("GET")
-        .arg(&key)
-        .query_async(&mut state.redis.clone())
-        .await
-        .unwrap_or(None);
+    let count = read_attempts(&state, &key).await;
 
     if let Some(attempts) = count {
         if attempts >= 2 {
@@ -70,75 +80,159 @@ pub async fn rate_limit_register(
     next.run(req).await
 }
 
+/// Counts a failed login towards the account lockout, and locks the account
+/// once `login_lockout_threshold` failures fall within the lockout window.
+async fn record_login_failure(state: &AppState, failures_key: &str, lockout_key: &str) {
+    let threshold = state.config.login_lockout_threshold;
+    if threshold == 0 {
+        return;
+    }
+
+    let mut redis = state.redis.clone();
+    let failures: u32 = redis::cmd("INCR")
+        .arg(failures_key)
+        .query_async(&mut redis)
+        .await
+        .unwrap_or(0);
+
+    // The window starts at the first failure and does not slide.
+    if failures == 1 {
+        let _: () = redis::cmd("EXPIRE")
+            .arg(failures_key)
+            .arg(state.config.login_lockout_window_secs)
+            .query_async(&mut redis)
+            .await
+            .unwrap_or(());
+    }
+
+    if failures >= threshold {
+        tracing::warn!(
+            "🔒 {} after {} failed logins, locked for {}s",
+            lockout_key,
+            failures,
+            state.config.login_lockout_duration_secs
+        );
+
+        let _: () = redis::cmd("SET")
+            .arg(lockout_key)
+            .arg(1)
+            .arg("EX")
+            .arg(state.config.login_lockout_duration_secs.max(1))
+            .query_async(&mut redis)
+            .await
+            .unwrap_or(());
+
+        let _: () = redis::cmd("DEL")
+            .arg(failures_key)
+            .query_async(&mut redis)
+            .await
+            .unwrap_or(());
+    }
+}
+
+/// The largest login request body read to find the username; a login
+/// request is a few short JSON fields.
+const MAX_LOGIN_BODY_BYTES: usize = 4 * 1024;
+
 /// A middleware that rate limits user login attempts.
+///
+/// Failed logins are throttled per username and client IP
+/// (`rate_limit:login:{username}:{ip}`), so one source guessing passwords is
+/// slowed down without locking the owner out from elsewhere. Failures also
+/// accumulate towards an account lockout (`lockout:{username}`) during which
+/// every login is rejected, whatever the password. Only rejected credentials
+/// (`401`) count towards either limit. Usernames are normalized first, so
+/// changing their case does not dodge them.
+///
+/// Accounts are shared by every tenant, so the lockout counters are kept
+/// outside the tenant prefix: an account locked through one tenant stays
+/// locked through all of them. Only the per-IP throttle is tenant-scoped.
 pub async fn rate_limit_login(
     State(state): State<AppState>,
     req: Request<Body>,
     next: Next,
 ) -> Response {
-    async fn extract_username_from_body(body_bytes: &[u8]) -> Option<String> {
-        if let Ok(json) = sonic_rs::from_slice::<sonic_rs::Value>(body_bytes) {
-            json.get("username")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-        } else {
-            None
-        }
+    fn extract_username_from_body(body_bytes: &[u8]) -> Option<String> {
+        let json = sonic_rs::from_slice::<sonic_rs::Value>(body_bytes).ok()?;
+        json.get("username")
+            .and_then(|v| v.as_str())
+            .map(normalize_username)
     }
 
+    let ip = client_ip(&req, state.config.trust_proxy_headers)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
     let (parts, body) = req.into_parts();
-    let body_bytes = axum::body::to_bytes(body, usize::MAX)
-        .await
-        .unwrap_or_default();
+    let body_bytes = match axum::body::to_bytes(body, MAX_LOGIN_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return AppError::PayloadTooLarge(format!(
+                "Login requests are limited to {} bytes",
+                MAX_LOGIN_BODY_BYTES
+            ))
+            .into_response();
+        }
+    };
 
     let username = extract_username_from_body(&body_bytes)
-        .await
         .unwrap_or_else(|| "unknown".to_string());
 
-    let key = format!("rate_limit:login:{}", username);
-    
-    let count: Option<i32> = redis::cmd("GET")
-        .arg(&key)
-        .query_async(&mut state.redis.clone())
-        .await
-        .unwrap_or(None);
+    let key = state.redis_key(format_args!("rate_limit:login:{}:{}", username, ip));
+    let failures_key = format!("login_failures:{}", username);
+    let lockout_key = format!("lockout:{}", username);
 
-    if let Some(attempts) = count {
-        if attempts >= 5 {
-            let ttl: Option<i32> = redis::cmd("TTL")
-                .arg(&key)
-                .query_async(&mut state.redis.clone())
-                .await
-                .unwrap_or(None);
-
-            return AppError::Authentication(format!(
-                "Too many failed login attempts. Try again in {} minutes",
-                ttl.unwrap_or(0) / 60
-            )).into_response();
-        }
+    if state.config.login_lockout_threshold > 0
+        && read_attempts(&state, &lockout_key).await.is_some()
+    {
+        return AppError::Authentication("Account temporarily locked".to_string()).into_response();
     }
 
-    let new_body = Body::from(body_bytes.clone());
-    let new_req = Request::from_parts(parts, new_body);
-    
-    let response = next.run(new_req).await;
-
-    if response.status().is_client_error() {
-        let _: () = redis::cmd("INCR")
+    let throttle = state.config.login_throttle_attempts;
+    if throttle > 0
+        && let Some(attempts) = read_attempts(&state, &key).await
+        && attempts >= throttle as i32
+    {
+        let ttl: Option<i32> = redis::cmd("TTL")
             .arg(&key)
             .query_async(&mut state.redis.clone())
             .await
-            .unwrap_or(());
+            .unwrap_or(None);
 
-        let _: () = redis::cmd("EXPIRE")
-            .arg(&key)
-            .arg(43200)
-            .query_async(&mut state.redis.clone())
-            .await
-            .unwrap_or(());
+        return AppError::Authentication(format!(
+            "Too many failed login attempts. Try again in {} minutes",
+            ttl.unwrap_or(0) / 60
+        )).into_response();
+    }
+
+    let new_req = Request::from_parts(parts, Body::from(body_bytes));
+
+    let response = next.run(new_req).await;
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        if throttle > 0 {
+            let attempts: u32 = redis::cmd("INCR")
+                .arg(&key)
+                .query_async(&mut state.redis.clone())
+                .await
+                .unwrap_or(0);
+
+            // The window starts at the first failure and does not slide.
+            if attempts == 1 {
+                let _: () = redis::cmd("EXPIRE")
+                    .arg(&key)
+                    .arg(state.config.login_throttle_window_secs.max(1))
+                    .query_async(&mut state.redis.clone())
+                    .await
+                    .unwrap_or(());
+            }
+        }
+
+        record_login_failure(&state, &failures_key, &lockout_key).await;
     } else if response.status().is_success() {
         let _: () = redis::cmd("DEL")
             .arg(&key)
+            .arg(&failures_key)
             .query_async(&mut state.redis.clone())
             .await
             .unwrap_or(());
@@ -155,13 +249,9 @@ pub async fn rate_limit_change_password(
     next: Next,
 ) -> Response {
     let user_id = session.user_id;
-    let key = format!("rate_limit:change_password:{}", user_id);
+    let key = state.redis_key(format_args!("rate_limit:change_password:{}", user_id));
     
-    let count: Option<i32> = redis::cmd("GET")
-        .arg(&key)
-        .query_async(&mut state.redis.clone())
-        .await
-        .unwrap_or(None);
+    let count = read_attempts(&state, &key).await;
 
     if let Some(attempts) = count {
         if attempts >= 2 {
@@ -222,9 +312,11 @@ pub async fn check_storage_quota(
             let available = storage_quota_bytes - storage_used_bytes;
             
             if available <= 0 {
-                return AppError::Validation(
-                    "Storage quota exceeded. Cannot upload more files.".to_string()
-                ).into_response();
+                return AppError::QuotaExceeded {
+                    required: 1,
+                    available,
+                }
+                .into_response();
             }
             
             next.run(req).await
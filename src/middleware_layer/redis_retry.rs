@@ -0,0 +1,45 @@
+use rand::Rng;
+use redis::{aio::ConnectionManager, RedisError, RedisResult};
+use std::future::Future;
+use tokio::time::{sleep, Duration};
+
+/// How many times a read is attempted before its error is returned.
+const MAX_ATTEMPTS: u32 = 3;
+/// The base delay between attempts; each retry waits a multiple plus jitter.
+const BASE_DELAY_MS: u64 = 20;
+/// The maximum random jitter added to each delay.
+const JITTER_MS: u64 = 15;
+
+/// Whether a Redis error is a connection hiccup worth retrying, as opposed to
+/// a command or type error that would fail again.
+fn is_transient(e: &RedisError) -> bool {
+    e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout()
+}
+
+/// Runs a read-only Redis operation, retrying transient connection errors.
+///
+/// Up to three attempts are made over roughly 100ms. Each attempt gets its
+/// own clone of the connection manager, which reconnects in the background.
+/// Only the transport can fail this way: a missing key still comes back as
+/// `Ok(None)` on the first attempt, so callers keep rejecting absent sessions
+/// and tokens immediately. Use it only for idempotent reads.
+pub async fn with_retry<T, F, Fut>(redis: &ConnectionManager, op: F) -> RedisResult<T>
+where
+    F: Fn(ConnectionManager) -> Fut,
+    Fut: Future<Output = RedisResult<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match op(redis.clone()).await {
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                let jitter = rand::thread_rng().gen_range(0..=JITTER_MS);
+                let delay = BASE_DELAY_MS * u64::from(attempt) + jitter;
+                tracing::debug!("🔁 Transient Redis error (attempt {}), retrying in {}ms: {}", attempt, delay, e);
+                sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::session::Session,
+    repositories,
+    state::AppState,
+};
+
+/// The role required to access the admin routes.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Checks whether a user holds the given role.
+async fn has_role(state: &AppState, user_id: Uuid, role: &str) -> Result<bool> {
+    let client = state.db.get().await?;
+    let user = repositories::user::find_by_id(&client, &user_id, &state.stmt_cache)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    Ok(user.is_active && user.roles.iter().any(|r| r == role))
+}
+
+/// A middleware that requires the session user to hold the admin role.
+///
+/// Roles are read from the database on every request rather than embedded in
+/// the session, so revoking a role takes effect immediately. Impersonated
+/// sessions never pass this check, even when the target user is an admin.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if session.is_impersonated() {
+        tracing::warn!(
+            "❌ Impersonated session for user {} tried to reach an admin route",
+            session.user_id
+        );
+        return AppError::Unauthorized.into_response();
+    }
+
+    match has_role(&state, session.user_id, ADMIN_ROLE).await {
+        Ok(true) => next.run(req).await,
+        Ok(false) => {
+            tracing::warn!("❌ User {} is not an admin", session.user_id);
+            AppError::Unauthorized.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
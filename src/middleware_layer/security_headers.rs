@@ -0,0 +1,49 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::state::AppState;
+
+/// Adds the standard hardening headers to every response.
+///
+/// `Strict-Transport-Security` is only sent when `APP_ENV=production`, the
+/// same condition under which cookies are marked `Secure`, so local HTTP
+/// development is never pinned to HTTPS. Headers a handler already set are
+/// left alone.
+pub async fn apply_security_headers(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    headers
+        .entry(header::REFERRER_POLICY)
+        .or_insert(HeaderValue::from_static("strict-origin-when-cross-origin"));
+
+    if !state.config.content_security_policy.is_empty()
+        && let Ok(csp) = HeaderValue::from_str(&state.config.content_security_policy)
+    {
+        headers.entry(header::CONTENT_SECURITY_POLICY).or_insert(csp);
+    }
+
+    if state.config.is_production() && state.config.hsts_max_age_secs > 0 {
+        let hsts = format!("max-age={}; includeSubDomains", state.config.hsts_max_age_secs);
+        if let Ok(hsts) = HeaderValue::from_str(&hsts) {
+            headers.entry(header::STRICT_TRANSPORT_SECURITY).or_insert(hsts);
+        }
+    }
+
+    response
+}
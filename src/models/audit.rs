@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A security-relevant action to record in the audit log.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub action: &'static str,
+    /// The user who acted, if known.
+    pub user_id: Option<Uuid>,
+    /// The admin who acted through an impersonation session for `user_id`.
+    pub impersonated_by: Option<Uuid>,
+    /// The username the action was attempted with, for actions such as a
+    /// failed login where no user is known.
+    pub username: Option<String>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub resource_type: Option<&'static str>,
+    pub resource_id: Option<Uuid>,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+impl AuditEvent {
+    /// Creates a successful event with no user or resource attached.
+    pub fn new(action: &'static str, ip_address: Option<IpAddr>, user_agent: Option<String>) -> Self {
+        Self {
+            action,
+            user_id: None,
+            impersonated_by: None,
+            username: None,
+            ip_address,
+            user_agent,
+            resource_type: None,
+            resource_id: None,
+            success: true,
+            error_message: None,
+        }
+    }
+}
+
+/// A recorded entry of the audit log.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    /// The user who acted, if known.
+    pub user_id: Option<Uuid>,
+    /// The admin who acted through an impersonation session for `user_id`.
+    pub impersonated_by: Option<Uuid>,
+    /// The username the action was attempted with, e.g. for failed logins.
+    pub username: Option<String>,
+    pub action: String,
+    /// The client IP the request came from.
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<Uuid>,
+    /// `success` or `failure`.
+    pub status: String,
+    pub error_message: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&Row> for AuditLogEntry {
+    fn from(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            impersonated_by: row.get("impersonated_by"),
+            username: row.get("username"),
+            action: row.get("action"),
+            ip_address: row.get("ip_address"),
+            user_agent: row.get("user_agent"),
+            resource_type: row.get("resource_type"),
+            resource_id: row.get("resource_id"),
+            status: row.get("status"),
+            error_message: row.get("error_message"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
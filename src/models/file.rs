@@ -1,8 +1,14 @@
+use base64::{Engine as _, engine::general_purpose};
+use bincode::{Decode, Encode};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use tokio_postgres::Row;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::error::{AppError, Result as AppResult};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct File {
     pub id: Uuid,
@@ -16,7 +22,7 @@ pub struct File {
     pub dek_version: i32,
     pub file_size: i64,
     pub mime_type: Option<String>,
-    pub checksum_sha256: Option<String>,
+    pub checksum: Option<String>,
     pub upload_status: String,
     pub uploaded_at: DateTime<Utc>,
     pub is_deleted: bool,
@@ -38,7 +44,7 @@ impl From<&Row> for File {
             dek_version: row.get("dek_version"),
             file_size: row.get("file_size"),
             mime_type: row.get("mime_type"),
-            checksum_sha256: row.get("checksum_sha256"),
+            checksum: row.get("checksum"),
             upload_status: row.get("upload_status"),
             uploaded_at: row.get("uploaded_at"),
             is_deleted: row.get("is_deleted"),
@@ -48,6 +54,167 @@ impl From<&Row> for File {
     }
 }
 
+/// The fields of a finalized upload, for inserting its file row.
+#[derive(Debug, Clone)]
+pub struct NewFile {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub folder_id: Option<Uuid>,
+    /// The requested name; a name conflict may store the file under another.
+    pub original_filename: String,
+    pub total_chunks: i32,
+    pub chunks_metadata: Vec<u8>,
+    /// The file's DEK, encrypted with the KEK of `dek_version`.
+    pub encrypted_dek: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub dek_version: i32,
+    pub file_size: i64,
+    pub mime_type: Option<String>,
+    pub checksum: Option<String>,
+}
+
+/// Marks a chunk list encoded by [`ChunkInfo::encode_list`].
+///
+/// Legacy lists start with the bincode varint length of the list, which never
+/// begins with `0xFF`, so the marker tells the two formats apart.
+const CHUNK_LIST_MARKER: u8 = 0xFF;
+/// The version of the chunk list format written after [`CHUNK_LIST_MARKER`].
+const CHUNK_LIST_VERSION: u8 = 1;
+
+/// The location and nonce of one encrypted chunk of a file, as stored in
+/// `files.chunks_metadata`.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct ChunkInfo {
+    pub index: usize,
+    pub nonce: [u8; 12],
+    pub filename: Vec<u8>,
+    pub size_encrypted: i64,
+    /// Whether the plaintext was compressed with zstd before encryption.
+    pub compressed: bool,
+    /// The length of the chunk's plaintext before compression. `0` for chunks
+    /// stored before compression was supported, which are never compressed.
+    pub original_len: i64,
+}
+
+/// A chunk as stored before the compression fields were added.
+#[derive(Decode)]
+struct LegacyChunkInfo {
+    index: usize,
+    nonce: [u8; 12],
+    filename: Vec<u8>,
+    size_encrypted: i64,
+}
+
+impl From<LegacyChunkInfo> for ChunkInfo {
+    fn from(legacy: LegacyChunkInfo) -> Self {
+        Self {
+            index: legacy.index,
+            nonce: legacy.nonce,
+            filename: legacy.filename,
+            size_encrypted: legacy.size_encrypted,
+            compressed: false,
+            original_len: 0,
+        }
+    }
+}
+
+impl ChunkInfo {
+    /// Creates a new `ChunkInfo`.
+    pub fn new(
+        index: usize,
+        nonce: [u8; 12],
+        filename: String,
+        size_encrypted: i64,
+        compressed: bool,
+        original_len: i64,
+    ) -> Self {
+        Self {
+            index,
+            nonce,
+            filename: filename.into_bytes(),
+            size_encrypted,
+            compressed,
+            original_len,
+        }
+    }
+
+    /// Returns the chunk's file name inside the upload directory.
+    pub fn get_filename(&self) -> AppResult<String> {
+        String::from_utf8(self.filename.clone())
+            .map_err(|_| AppError::Internal("Invalid filename encoding".to_string()))
+    }
+
+    /// Restores a chunk's plaintext after decryption, decompressing it if it
+    /// was stored compressed.
+    pub fn decompress(&self, decrypted: Vec<u8>) -> AppResult<Vec<u8>> {
+        if !self.compressed {
+            return Ok(decrypted);
+        }
+
+        self.decompress_slice(&decrypted)
+    }
+
+    /// Decompresses a compressed chunk's decrypted bytes into a new buffer.
+    pub fn decompress_slice(&self, decrypted: &[u8]) -> AppResult<Vec<u8>> {
+        let plaintext = zstd::bulk::decompress(decrypted, self.original_len.max(0) as usize)
+            .map_err(|e| AppError::Internal(format!("Chunk {} decompression failed: {}", self.index, e)))?;
+        if plaintext.len() as i64 != self.original_len {
+            return Err(AppError::Internal(format!(
+                "Chunk {} decompressed to {} bytes, expected {}",
+                self.index,
+                plaintext.len(),
+                self.original_len
+            )));
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Encodes a chunk list for `files.chunks_metadata`.
+    pub fn encode_list(chunks: &[ChunkInfo]) -> AppResult<Vec<u8>> {
+        let mut raw = vec![CHUNK_LIST_MARKER, CHUNK_LIST_VERSION];
+        bincode::encode_into_std_write(chunks, &mut raw, bincode::config::standard())
+            .map_err(|e| AppError::Internal(format!("Bincode encode failed: {}", e)))?;
+
+        Ok(raw)
+    }
+
+    /// Decodes a chunk list written by [`ChunkInfo::encode_list`], or a legacy
+    /// list written before chunks could be compressed.
+    pub fn decode_list(raw: &[u8]) -> AppResult<Vec<ChunkInfo>> {
+        let config = bincode::config::standard();
+        let decode_error = |e: bincode::error::DecodeError| {
+            AppError::Internal(format!("Bincode decode failed: {}", e))
+        };
+
+        match raw {
+            [CHUNK_LIST_MARKER, CHUNK_LIST_VERSION, list @ ..] => {
+                let (chunks, _): (Vec<ChunkInfo>, usize) =
+                    bincode::decode_from_slice(list, config).map_err(decode_error)?;
+                Ok(chunks)
+            }
+            [CHUNK_LIST_MARKER, version, ..] => Err(AppError::Internal(format!(
+                "Unsupported chunk list version {}",
+                version
+            ))),
+            _ => {
+                let (chunks, _): (Vec<LegacyChunkInfo>, usize) =
+                    bincode::decode_from_slice(raw, config).map_err(decode_error)?;
+                Ok(chunks.into_iter().map(ChunkInfo::from).collect())
+            }
+        }
+    }
+
+    /// Returns the plaintext size of every chunk in `chunks` but the last.
+    ///
+    /// Finalize records the upload's chunk size in each chunk's
+    /// `size_encrypted`, so a file keeps the layout it was uploaded with when
+    /// `CHUNK_SIZE_BYTES` later changes. `None` for a file without chunks.
+    pub fn layout_chunk_size(chunks: &[ChunkInfo]) -> Option<u64> {
+        chunks.first().map(|chunk| chunk.size_encrypted.max(1) as u64)
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct FileListItem {
     pub id: Uuid,
@@ -56,3 +223,218 @@ pub struct FileListItem {
     pub mime_type: Option<String>,
     pub uploaded_at: DateTime<Utc>,
 }
+
+/// The folder a file listing is restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FolderScope {
+    /// Files outside any folder.
+    Root,
+    /// Files directly inside the given folder.
+    Folder(Uuid),
+}
+
+impl FromStr for FolderScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "root" {
+            return Ok(Self::Root);
+        }
+
+        Uuid::parse_str(s)
+            .map(Self::Folder)
+            .map_err(|_| anyhow::anyhow!("invalid folder_id '{}' (expected a folder ID or root)", s))
+    }
+}
+
+/// Optional filters applied when listing a user's files.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// Only files in this folder, or all folders when `None`.
+    pub folder: Option<FolderScope>,
+    /// Only files with exactly this MIME type.
+    pub mime_type: Option<String>,
+    /// Only files whose name contains this text, case-insensitively.
+    pub search: Option<String>,
+}
+
+/// The column a file listing is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSortKey {
+    /// The original filename.
+    Name,
+    /// The plaintext size.
+    Size,
+    /// When the upload was finalized.
+    #[default]
+    UploadedAt,
+    /// The number of downloads.
+    AccessCount,
+}
+
+impl FileSortKey {
+    /// The SQL column this key sorts by. Only these fixed names ever reach the
+    /// query, never the raw parameter.
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Name => "original_filename",
+            Self::Size => "file_size",
+            Self::UploadedAt => "uploaded_at",
+            Self::AccessCount => "access_count",
+        }
+    }
+
+    /// The direction used when the request names no `order`: alphabetical
+    /// for names, largest, newest or most downloaded first otherwise.
+    pub fn default_direction(self) -> SortDirection {
+        match self {
+            Self::Name => SortDirection::Asc,
+            _ => SortDirection::Desc,
+        }
+    }
+}
+
+/// The direction of a sorted listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    /// Smallest first.
+    Asc,
+    /// Largest first.
+    Desc,
+}
+
+impl SortDirection {
+    /// The SQL keyword for this direction.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// The sort order of a file listing. Defaults to newest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileOrder {
+    /// The column to sort by.
+    pub key: FileSortKey,
+    /// The direction to sort in.
+    pub direction: SortDirection,
+}
+
+impl FileOrder {
+    /// Builds the order for `key`, using its default direction unless
+    /// `direction` is given.
+    pub fn new(key: FileSortKey, direction: Option<SortDirection>) -> Self {
+        Self {
+            key,
+            direction: direction.unwrap_or(key.default_direction()),
+        }
+    }
+
+    /// The `ORDER BY` expression, with the file ID as a tie-breaker so that
+    /// pages never overlap or skip files with equal sort values.
+    pub fn sql(self) -> String {
+        let direction = self.direction.keyword();
+        let nulls = if self.key == FileSortKey::AccessCount {
+            " NULLS LAST"
+        } else {
+            ""
+        };
+        format!("{} {}{}, id {}", self.key.column(), direction, nulls, direction)
+    }
+}
+
+impl Default for FileOrder {
+    fn default() -> Self {
+        Self::new(FileSortKey::default(), None)
+    }
+}
+
+/// The position of a file in the default newest-first order, used as an
+/// opaque cursor so that deep pages do not make Postgres skip rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileCursor {
+    /// When the file was uploaded.
+    pub uploaded_at: DateTime<Utc>,
+    /// The file ID, breaking ties between equal timestamps.
+    pub id: Uuid,
+}
+
+impl FileCursor {
+    /// The cursor pointing just past `file`.
+    pub fn after(file: &File) -> Self {
+        Self {
+            uploaded_at: file.uploaded_at,
+            id: file.id,
+        }
+    }
+
+    /// Encodes the cursor as URL-safe base64.
+    pub fn encode(&self) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.uploaded_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    /// Decodes a cursor produced by [`FileCursor::encode`].
+    pub fn decode(cursor: &str) -> AppResult<Self> {
+        let invalid = || AppError::Validation("Invalid cursor".to_string());
+
+        let decoded = general_purpose::URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (micros, id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            uploaded_at: micros
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_micros)
+                .ok_or_else(invalid)?,
+            id: Uuid::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Where a page of a file listing starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageStart {
+    /// Skip this many files.
+    Offset(i64),
+    /// Start right after this file, in the default order.
+    After(FileCursor),
+}
+
+/// What to do when an upload is finalized under a name that already exists in
+/// the target folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Store the new file alongside the existing one under the same name.
+    #[default]
+    KeepBoth,
+    /// Soft-delete the existing file and release its quota.
+    Overwrite,
+    /// Store the new file under the first free name of the form `name (2).ext`.
+    Rename,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep-both" => Ok(Self::KeepBoth),
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            other => anyhow::bail!(
+                "unknown conflict policy '{}' (expected keep-both, overwrite or rename)",
+                other
+            ),
+        }
+    }
+}
@@ -60,3 +60,36 @@ pub struct FolderWithStats {
     /// The total size of the files in the folder in bytes.
     pub total_size: i64,
 }
+
+/// The recursive storage usage of a folder, broken down by immediate subfolder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderUsage {
+    /// The unique identifier for the folder.
+    pub id: Uuid,
+    /// The total size of the files in the folder and all its subfolders in bytes.
+    pub total_size: i64,
+    /// The total size of the files directly in the folder in bytes.
+    pub direct_size: i64,
+    /// The recursive usage of each immediate subfolder.
+    pub subfolders: Vec<SubfolderUsage>,
+}
+
+/// The recursive storage usage of one immediate subfolder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubfolderUsage {
+    /// The unique identifier for the subfolder.
+    pub id: Uuid,
+    /// The name of the subfolder.
+    pub name: String,
+    /// The total size of the files in the subfolder and all its subfolders in bytes.
+    pub total_size: i64,
+}
+
+/// One folder on the path from the root to a folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderPathEntry {
+    /// The unique identifier for the folder.
+    pub id: Uuid,
+    /// The name of the folder.
+    pub name: String,
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{AppError, Result};
+
+/// The largest page a list endpoint returns.
+pub const MAX_PAGE_LIMIT: i64 = 1000;
+
+/// The page size used when a request does not give one.
+pub fn default_limit() -> i64 {
+    50
+}
+
+/// The query parameters shared by paginated list endpoints.
+#[derive(Debug, Clone, Copy, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// The maximum number of items to return (1 to 1000, default 50).
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// The number of items to skip.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+impl PageQuery {
+    /// Rejects a negative offset or a limit outside `1..=MAX_PAGE_LIMIT`.
+    pub fn validate(self) -> Result<Self> {
+        if !(1..=MAX_PAGE_LIMIT).contains(&self.limit) {
+            return Err(AppError::Validation(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_LIMIT
+            )));
+        }
+
+        if self.offset < 0 {
+            return Err(AppError::Validation("offset must not be negative".to_string()));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Pagination metadata returned alongside every list response.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Pagination {
+    /// The page size that was requested.
+    pub limit: i64,
+    /// The number of items skipped before this page.
+    pub offset: i64,
+    /// The total number of items across all pages.
+    pub total: i64,
+    /// Whether another page follows this one.
+    pub has_more: bool,
+}
+
+impl Pagination {
+    /// Builds the metadata for a page of `returned` items out of `total`.
+    pub fn new(page: PageQuery, returned: usize, total: i64) -> Self {
+        Self {
+            limit: page.limit,
+            offset: page.offset,
+            total,
+            has_more: page.offset + (returned as i64) < total,
+        }
+    }
+}
@@ -4,10 +4,11 @@ use uuid::Uuid;
 
 /// Represents a user session.
 ///
-/// ⚠️ IMPORTANT: The `dek` field stores the DEK ENCRYPTED with the KEK.
+/// ⚠️ IMPORTANT: The `dek` field stores the DEK ENCRYPTED with the KEK
+/// version in `dek_kek_version`.
 /// Format: [ciphertext || nonce] where nonce is 12 bytes at the end.
 /// NEVER use `dek` directly to encrypt/decrypt data!
-/// Always decrypt with the KEK before use.
+/// Always decrypt it with `services::sessions::open_session_dek` before use.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     /// The ID of the user this session belongs to.
@@ -15,8 +16,32 @@ pub struct Session {
     /// ⚠️ Encrypted DEK (encrypted_dek || 12-byte nonce).
     /// MUST be decrypted with KEK before any use.
     pub dek: Vec<u8>,
+    /// The KEK version `dek` is encrypted with. `None` for sessions without a
+    /// DEK and for sessions created before the DEK was encrypted at rest.
+    #[serde(default)]
+    pub dek_kek_version: Option<i32>,
     /// The timestamp when the session was created.
     pub created_at: DateTime<Utc>,
     /// The timestamp when the session expires.
     pub expires_at: DateTime<Utc>,
+    /// The ID of the admin who issued this session on the user's behalf, if any.
+    ///
+    /// Impersonated sessions are issued without the user's password, so they
+    /// carry no DEK and can only see and manage metadata, never file contents.
+    #[serde(default)]
+    pub impersonated_by: Option<Uuid>,
+    /// The CSRF token issued with this session, so both can be revoked together.
+    #[serde(default)]
+    pub csrf_token: Option<String>,
+    /// The `User-Agent` of the client that opened the session, shown when the
+    /// user lists their sessions.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    /// Returns whether this session was issued by an admin via impersonation.
+    pub fn is_impersonated(&self) -> bool {
+        self.impersonated_by.is_some()
+    }
 }
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A public link to one file, stored in Redis under `share:{token}` until it
+/// expires or is revoked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    /// The shared file.
+    pub file_id: Uuid,
+    /// The owner of the file, who created the link.
+    pub user_id: Uuid,
+    /// The timestamp when the link was created.
+    pub created_at: DateTime<Utc>,
+    /// The timestamp when the link stops working.
+    pub expires_at: DateTime<Utc>,
+}
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use tokio_postgres::Row;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Represents a user in the system.
@@ -21,6 +23,9 @@ pub struct User {
     pub encrypted_dek: Option<Vec<u8>>,
     /// The salt used to derive the key that encrypts the data encryption key.
     pub dek_salt: Option<Vec<u8>>,
+    /// The Argon2id cost of that key, as stored by `KdfParams`; `None` for
+    /// DEKs wrapped before it was recorded.
+    pub dek_kdf_params: Option<String>,
     /// The version of the key encryption key used to encrypt the data encryption key.
     pub dek_kek_version: i32,
     /// The user's storage quota in bytes.
@@ -35,6 +40,8 @@ pub struct User {
     pub last_password_change: Option<DateTime<Utc>>,
     /// Whether the user is active.
     pub is_active: bool,
+    /// Whether logins require a TOTP or recovery code.
+    pub two_factor_enabled: bool,
 }
 
 impl From<&Row> for User {
@@ -48,6 +55,7 @@ impl From<&Row> for User {
             roles: row.get("roles"),
             encrypted_dek: row.get("encrypted_dek"),
             dek_salt: row.get("dek_salt"),
+            dek_kdf_params: row.get("dek_kdf_params"),
             dek_kek_version: row.get("dek_kek_version"),
             storage_quota_bytes: row.get("storage_quota_bytes"),
             storage_used_bytes: row.get("storage_used_bytes"),
@@ -55,6 +63,92 @@ impl From<&Row> for User {
             updated_at: row.get("updated_at"),
             last_password_change: row.get("last_password_change"),
             is_active: row.get("is_active"),
+            two_factor_enabled: row.get("two_factor_enabled"),
+        }
+    }
+}
+
+/// A user's DEK as stored: encrypted with a key derived from their password.
+#[derive(Clone, Debug)]
+pub struct WrappedDek {
+    /// The encrypted data encryption key.
+    pub encrypted_dek: Vec<u8>,
+    /// The salt the wrapping key was derived with.
+    pub dek_salt: Vec<u8>,
+    /// The Argon2id cost of the wrapping key, as stored by `KdfParams`.
+    pub dek_kdf_params: String,
+}
+
+/// The fields of a user being registered, for inserting their row.
+#[derive(Clone, Debug)]
+pub struct NewUser {
+    pub id: Uuid,
+    pub name: String,
+    /// The normalized username.
+    pub username: String,
+    /// The normalized email address, if one was given.
+    pub email: Option<String>,
+    pub password_hash: String,
+    pub dek: WrappedDek,
+}
+
+/// The usage percentages at which a user's storage is reported as running out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaThresholds {
+    pub warning_percent: f64,
+    pub critical_percent: f64,
+    pub full_percent: f64,
+}
+
+/// How close a user is to their storage quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaStatus {
+    /// Usage is below the warning threshold.
+    Ok,
+    /// Usage reached the warning threshold.
+    Warning,
+    /// Usage reached the critical threshold.
+    Critical,
+    /// Usage reached the full threshold; uploads are likely to be rejected.
+    Full,
+}
+
+impl QuotaStatus {
+    /// Classifies `used_bytes` of `quota_bytes`. A quota of zero or less is
+    /// always full.
+    pub fn classify(used_bytes: i64, quota_bytes: i64, thresholds: &QuotaThresholds) -> Self {
+        if quota_bytes <= 0 {
+            return QuotaStatus::Full;
+        }
+
+        let usage_percentage = used_bytes as f64 * 100.0 / quota_bytes as f64;
+        if usage_percentage >= thresholds.full_percent {
+            QuotaStatus::Full
+        } else if usage_percentage >= thresholds.critical_percent {
+            QuotaStatus::Critical
+        } else if usage_percentage >= thresholds.warning_percent {
+            QuotaStatus::Warning
+        } else {
+            QuotaStatus::Ok
+        }
+    }
+
+    /// Returns a message describing the status, for display to the user.
+    pub fn message(self, usage_percentage: f64) -> String {
+        match self {
+            QuotaStatus::Ok => "Storage usage is within your quota".to_string(),
+            QuotaStatus::Warning => format!(
+                "You have used {:.0}% of your storage",
+                usage_percentage
+            ),
+            QuotaStatus::Critical => format!(
+                "Storage is almost full ({:.0}% used); free up space soon",
+                usage_percentage
+            ),
+            QuotaStatus::Full => {
+                "Storage is full; delete files to upload more".to_string()
+            }
         }
     }
 }
@@ -0,0 +1,249 @@
+use axum::{
+    http::{header, HeaderValue},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityRequirement, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::handlers;
+
+/// The OpenAPI description of the HTTP API, generated from the handler
+/// annotations and the request/response payload types.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Rocket Secure Cloud Storage",
+        description = "Encrypted file storage API. Authenticated routes use the `session_id` cookie; state-changing requests also require the `X-CSRF-Token` header to match the `csrf_token` cookie. Clients without cookies can send `Authorization: Bearer <session_id>` instead, which needs no CSRF token."
+    ),
+    paths(
+        handlers::auth::register,
+        handlers::auth::login,
+        handlers::auth::logout,
+        handlers::auth::logout_all,
+        handlers::auth::change_password,
+        handlers::auth::forgot_password,
+        handlers::auth::reset_password,
+        handlers::auth::enroll_two_factor,
+        handlers::auth::verify_two_factor,
+        handlers::auth::list_sessions,
+        handlers::events::event_stream,
+        handlers::auth::revoke_session,
+        handlers::files::init_upload,
+        handlers::files::upload_chunk,
+        handlers::files::finalize_upload,
+        handlers::files::cancel_upload,
+        handlers::files::list_active_uploads,
+        handlers::files::upload_status,
+        handlers::upload_progress::upload_progress_socket,
+        handlers::files::list_files,
+        handlers::files::download_file,
+        handlers::files::verify_file,
+        handlers::files::delete_file,
+        handlers::files::batch_delete_files,
+        handlers::files::rename_file,
+        handlers::files::move_file,
+        handlers::shares::create_share,
+        handlers::shares::revoke_share,
+        handlers::shares::download_shared,
+        handlers::files::list_trash,
+        handlers::files::restore_file,
+        handlers::files::storage_info,
+        handlers::files::recalculate_user_quota,
+        handlers::folders::create_folder,
+        handlers::folders::list_folder_contents,
+        handlers::folders::get_folder_stats,
+        handlers::folders::get_folder_usage,
+        handlers::folders::get_folder_path,
+        handlers::folders::update_folder,
+        handlers::folders::move_folder,
+        handlers::folders::download_folder,
+        handlers::folders::delete_folder,
+        handlers::folders::restore_folder,
+        handlers::folders::purge_folder,
+        handlers::folders::empty_trash,
+        handlers::admin::impersonate_user,
+        handlers::admin::file_diagnostics,
+        handlers::admin::force_logout_user,
+        handlers::admin::rotate_kek,
+        handlers::admin::kek_rotation_status,
+        handlers::admin::collect_orphaned_chunks,
+        handlers::admin::list_users,
+        handlers::admin::set_user_quota,
+        handlers::admin::set_user_active,
+        handlers::admin::list_audit_logs,
+        handlers::health::live,
+        handlers::health::ready,
+    ),
+    components(schemas(
+        handlers::auth::RegisterRequest,
+        handlers::auth::LoginRequest,
+        handlers::auth::ChangePasswordRequest,
+        handlers::auth::ForgotPasswordRequest,
+        handlers::auth::ResetPasswordRequest,
+        handlers::auth::AuthResponse,
+        handlers::auth::SessionInfo,
+        handlers::auth::SessionListResponse,
+        handlers::auth::TwoFactorEnrollResponse,
+        handlers::auth::TwoFactorVerifyRequest,
+        handlers::auth::TwoFactorVerifyResponse,
+        handlers::files::InitUploadRequest,
+        handlers::files::UploadChunkForm,
+        handlers::files::FinalizeUploadRequest,
+        handlers::files::CancelUploadRequest,
+        handlers::files::BatchDeleteRequest,
+        handlers::files::RenameFileRequest,
+        handlers::files::MoveFileRequest,
+        handlers::shares::CreateShareRequest,
+        crate::models::file::ConflictPolicy,
+        crate::models::file::FileSortKey,
+        crate::models::file::SortDirection,
+        handlers::files::StorageInfoResponse,
+        crate::models::user::QuotaStatus,
+        crate::models::pagination::Pagination,
+        handlers::folders::CreateFolderRequest,
+        handlers::folders::UpdateFolderRequest,
+        handlers::folders::MoveFolderRequest,
+        crate::services::kek_rotation::RotationState,
+        crate::services::kek_rotation::RotationStatus,
+        crate::services::chunk_gc::ChunkGcReport,
+        handlers::admin::SetQuotaRequest,
+        handlers::admin::SetActiveRequest,
+        crate::models::audit::AuditLogEntry,
+    )),
+    modifiers(&SessionSecurity),
+    tags(
+        (name = "auth", description = "Registration, login and session management"),
+        (name = "files", description = "Chunked encrypted uploads, downloads and quota"),
+        (name = "folders", description = "Folder hierarchy management"),
+        (name = "admin", description = "Admin-only support and management endpoints"),
+        (name = "health", description = "Liveness and readiness probes")
+    )
+)]
+pub struct ApiDoc;
+
+/// The documented paths served without a session; they mirror the public
+/// routes in `router.rs`.
+const PUBLIC_PATHS: &[&str] = &[
+    "/api/auth/register",
+    "/api/auth/login",
+    "/api/auth/forgot-password",
+    "/api/auth/reset-password",
+    "/api/share/{token}",
+    "/health/live",
+    "/health/ready",
+];
+
+/// Declares how requests authenticate and marks every non-public operation
+/// with it: the `session_id` cookie, plus the `X-CSRF-Token` header for
+/// state-changing methods, or a bearer session token instead of both.
+struct SessionSecurity;
+
+impl Modify for SessionSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "session_id",
+                "Set by register and login.",
+            ))),
+        );
+        components.add_security_scheme(
+            "csrf_token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-CSRF-Token",
+                "The value of the `csrf_token` cookie; required with the session cookie on POST, PUT, PATCH and DELETE.",
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer_session",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+
+        let read = vec![
+            SecurityRequirement::new("session_cookie", Vec::<String>::new()),
+            SecurityRequirement::new("bearer_session", Vec::<String>::new()),
+        ];
+        let write = vec![
+            SecurityRequirement::new("session_cookie", Vec::<String>::new())
+                .add("csrf_token", Vec::<String>::new()),
+            SecurityRequirement::new("bearer_session", Vec::<String>::new()),
+        ];
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if PUBLIC_PATHS.contains(&path.as_str()) {
+                continue;
+            }
+
+            if let Some(operation) = item.get.as_mut() {
+                operation.security = Some(read.clone());
+            }
+            for operation in [&mut item.post, &mut item.put, &mut item.patch, &mut item.delete]
+                .into_iter()
+                .flatten()
+            {
+                operation.security = Some(write.clone());
+            }
+        }
+    }
+}
+
+/// Serves the OpenAPI specification as JSON.
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+/// The Swagger UI release loaded by `/docs`.
+const SWAGGER_UI_CDN: &str = "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5.17.14";
+
+/// The `Content-Security-Policy` of the docs page: the default policy
+/// with Swagger UI's scripts, styles and images allowed from the CDN.
+/// Swagger UI sets inline styles, so `style-src` also allows those.
+const DOCS_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' https://cdn.jsdelivr.net; \
+    style-src 'self' 'unsafe-inline' https://cdn.jsdelivr.net; \
+    img-src 'self' data: https://cdn.jsdelivr.net; \
+    object-src 'none'; frame-ancestors 'none'; base-uri 'self'";
+
+/// Serves Swagger UI for the specification at `/api-docs/openapi.json`.
+///
+/// The page sets its own `Content-Security-Policy`, which the security
+/// headers middleware leaves in place, so the CDN is allowed here only.
+pub async fn swagger_ui() -> Response {
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Rocket API</title>
+  <link rel="stylesheet" href="{cdn}/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="{cdn}/swagger-ui-bundle.js"></script>
+  <script src="/docs/swagger-init.js"></script>
+</body>
+</html>
+"#,
+        cdn = SWAGGER_UI_CDN
+    );
+
+    let mut response = Html(page).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(DOCS_CONTENT_SECURITY_POLICY),
+    );
+    response
+}
+
+/// Starts Swagger UI; served as a file because the page's policy does not
+/// allow inline scripts.
+pub async fn swagger_init_js() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+        "window.ui = SwaggerUIBundle({ url: '/api-docs/openapi.json', dom_id: '#swagger-ui' });\n",
+    )
+}
@@ -0,0 +1,99 @@
+use deadpool_postgres::Client;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    models::audit::{AuditEvent, AuditLogEntry},
+    statement_cache::StatementCache,
+};
+
+/// Inserts an [`AuditEvent`] into the audit log.
+pub async fn insert_event(client: &Client, event: &AuditEvent, stmt_cache: &StatementCache) -> Result<()> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        INSERT INTO audit_logs (
+            user_id, impersonated_by, username, action, ip_address, user_agent,
+            resource_type, resource_id, status, error_message
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        )
+        .await?;
+
+    client
+        .execute(
+            &stmt,
+            &[
+                &event.user_id,
+                &event.impersonated_by,
+                &event.username,
+                &event.action,
+                &event.ip_address.map(|ip| ip.to_string()),
+                &event.user_agent,
+                &event.resource_type,
+                &event.resource_id,
+                &if event.success { "success" } else { "failure" },
+                &event.error_message,
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Lists a page of audit entries, newest first, optionally only those of one
+/// user or action.
+pub async fn list_audit_logs(
+    client: &Client,
+    user_id: Option<Uuid>,
+    action: Option<&str>,
+    limit: i64,
+    offset: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<AuditLogEntry>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT
+            id, user_id, impersonated_by, username, action, ip_address, user_agent,
+            resource_type, resource_id, status, error_message, created_at
+        FROM audit_logs
+        WHERE ($1::UUID IS NULL OR user_id = $1)
+          AND ($2::TEXT IS NULL OR action = $2)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&user_id, &action, &limit, &offset]).await?;
+
+    Ok(rows.iter().map(AuditLogEntry::from).collect())
+}
+
+/// Counts the audit entries [`list_audit_logs`] pages through.
+pub async fn count_audit_logs(
+    client: &Client,
+    user_id: Option<Uuid>,
+    action: Option<&str>,
+    stmt_cache: &StatementCache,
+) -> Result<i64> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT COUNT(*) AS total
+        FROM audit_logs
+        WHERE ($1::UUID IS NULL OR user_id = $1)
+          AND ($2::TEXT IS NULL OR action = $2)
+        "#,
+        )
+        .await?;
+
+    let row = client.query_one(&stmt, &[&user_id, &action]).await?;
+
+    Ok(row.try_get("total")?)
+}
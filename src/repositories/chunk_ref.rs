@@ -0,0 +1,100 @@
+use deadpool_postgres::{Client, Transaction};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    statement_cache::StatementCache,
+};
+
+/// Takes a reference on a content-addressed chunk, creating its row on first
+/// use.
+///
+/// While the caller holds the reference, the chunk file is never unlinked.
+///
+/// # Returns
+///
+/// The chunk's reference count including the new reference.
+pub async fn acquire_chunk_ref(
+    client: &Client,
+    content_hash: &str,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<i32> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        INSERT INTO chunk_refs (content_hash, user_id, ref_count)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (content_hash) DO UPDATE SET ref_count = chunk_refs.ref_count + 1
+        RETURNING ref_count
+        "#,
+        )
+        .await?;
+
+    let row = client.query_one(&stmt, &[&content_hash, &user_id]).await?;
+
+    Ok(row.get("ref_count"))
+}
+
+/// Drops one reference on a content-addressed chunk within `transaction`,
+/// deleting its row when the last reference goes.
+///
+/// The row stays locked until the transaction ends, so a concurrent
+/// [`acquire_chunk_ref`] waits until the caller has unlinked the chunk file
+/// and then starts over with a fresh row.
+///
+/// # Returns
+///
+/// The references left, or `None` if the chunk had no row.
+pub async fn release_chunk_ref(
+    transaction: &Transaction<'_>,
+    content_hash: &str,
+    stmt_cache: &StatementCache,
+) -> Result<Option<i32>> {
+    let delete_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            transaction,
+            "DELETE FROM chunk_refs WHERE content_hash = $1 AND ref_count = 1",
+        )
+        .await?;
+
+    if transaction.execute(&delete_stmt, &[&content_hash]).await? > 0 {
+        return Ok(Some(0));
+    }
+
+    let decrement_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            transaction,
+            r#"
+        UPDATE chunk_refs
+        SET ref_count = ref_count - 1
+        WHERE content_hash = $1
+        RETURNING ref_count
+        "#,
+        )
+        .await?;
+
+    let row = transaction.query_opt(&decrement_stmt, &[&content_hash]).await?;
+
+    Ok(row.map(|r| r.get("ref_count")))
+}
+
+/// Returns which of `content_hashes` have a `chunk_refs` row.
+pub async fn existing_chunk_refs(
+    client: &Client,
+    content_hashes: &[String],
+    stmt_cache: &StatementCache,
+) -> Result<HashSet<String>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            "SELECT content_hash FROM chunk_refs WHERE content_hash = ANY($1)",
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&content_hashes]).await?;
+
+    Ok(rows.iter().map(|r| r.get("content_hash")).collect())
+}
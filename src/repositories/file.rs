@@ -1,54 +1,200 @@
 use deadpool_postgres::Client;
+use tokio_postgres::types::ToSql;
 use uuid::Uuid;
 
 use crate::{
-    error::Result,
-    models::file::File,
+    error::{AppError, Result},
+    models::file::{ConflictPolicy, File, FileFilter, FileOrder, FolderScope, NewFile, PageStart},
     statement_cache::StatementCache,
 };
 
-/// Creates a new file record in the database.
+/// Creates a new file record in the database, resolving a name conflict in the
+/// target folder according to `conflict`.
+///
+/// The lookup, any soft-delete of the replaced files and the insert run in one
+/// transaction, serialized per `(user_id, folder_id)` with an advisory lock so
+/// two concurrent finalizations cannot both claim the same name. The file's
+/// size is debited from the quota in the same transaction, from the upload's
+/// reservation when `quota_reserved` is set, so a file is never kept without
+/// being charged; `AppError::QuotaExceeded` rolls everything back. Returns the
+/// created file and the IDs and chunk metadata of the files it replaced.
 pub async fn create_file(
-    client: &Client,
-    id: Uuid,
-    user_id: Uuid,
-    original_filename: String,
-    total_chunks: i32,
-    chunks_metadata: Vec<u8>,
-    encrypted_dek: Vec<u8>,
-    nonce: Vec<u8>,
-    dek_version: i32,
-    file_size: i64,
-    mime_type: Option<String>,
-    checksum_sha256: Option<String>,
-    stmt_cache: &StatementCache,
-) -> Result<File> {
-    let stmt = stmt_cache
-        .get_or_prepare_client(
-            client,
+    client: &mut Client,
+    file: NewFile,
+    conflict: ConflictPolicy,
+    quota_reserved: bool,
+    stmt_cache: &StatementCache,
+) -> Result<(File, Vec<(Uuid, Option<Vec<u8>>)>)> {
+    let NewFile {
+        id,
+        user_id,
+        folder_id,
+        original_filename,
+        total_chunks,
+        chunks_metadata,
+        encrypted_dek,
+        nonce,
+        dek_version,
+        file_size,
+        mime_type,
+        checksum,
+    } = file;
+
+    let transaction = client.transaction().await?;
+
+    let lock_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT pg_advisory_xact_lock(
+            hashtextextended($1::uuid::text || ':' || COALESCE($2::uuid::text, 'root'), 0)
+        )
+        "#,
+        )
+        .await?;
+
+    transaction.execute(&lock_stmt, &[&user_id, &folder_id]).await?;
+
+    if let Some(folder_id) = folder_id {
+        let folder_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT 1 FROM folders
+            WHERE id = $1 AND user_id = $2 AND is_deleted = false
+            "#,
+            )
+            .await?;
+
+        if transaction
+            .query_opt(&folder_stmt, &[&folder_id, &user_id])
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound);
+        }
+    }
+
+    let same_name_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT id, file_size, chunks_metadata
+        FROM files
+        WHERE user_id = $1
+          AND folder_id IS NOT DISTINCT FROM $2
+          AND original_filename = $3
+          AND is_deleted = false
+        FOR UPDATE
+        "#,
+        )
+        .await?;
+
+    let existing = transaction
+        .query(&same_name_stmt, &[&user_id, &folder_id, &original_filename])
+        .await?;
+
+    let mut final_filename = original_filename;
+    let mut replaced_files = Vec::new();
+
+    if !existing.is_empty() {
+        match conflict {
+            ConflictPolicy::KeepBoth => {}
+            ConflictPolicy::Overwrite => {
+                let delete_stmt = stmt_cache
+                    .get_or_prepare_transaction(
+                        &transaction,
+                        r#"
+                    UPDATE files
+                    SET is_deleted = true, deleted_at = NOW()
+                    WHERE id = $1 AND user_id = $2 AND is_deleted = false
+                    "#,
+                    )
+                    .await?;
+
+                let mut released_bytes: i64 = 0;
+                for row in &existing {
+                    let existing_id: Uuid = row.get("id");
+                    let existing_size: i64 = row.get("file_size");
+                    transaction
+                        .execute(&delete_stmt, &[&existing_id, &user_id])
+                        .await?;
+                    released_bytes += existing_size;
+                    replaced_files.push((existing_id, row.get("chunks_metadata")));
+                }
+
+                let rollback_stmt = stmt_cache
+                    .get_or_prepare_transaction(
+                        &transaction,
+                        r#"
+                    SELECT rollback_storage_usage($1, $2) as success
+                    "#,
+                    )
+                    .await?;
+
+                transaction
+                    .query_one(&rollback_stmt, &[&user_id, &released_bytes])
+                    .await?;
+            }
+            ConflictPolicy::Rename => {
+                let exists_stmt = stmt_cache
+                    .get_or_prepare_transaction(
+                        &transaction,
+                        r#"
+                    SELECT 1 FROM files
+                    WHERE user_id = $1
+                      AND folder_id IS NOT DISTINCT FROM $2
+                      AND original_filename = $3
+                      AND is_deleted = false
+                    LIMIT 1
+                    "#,
+                    )
+                    .await?;
+
+                let mut copy_number = 2u32;
+                loop {
+                    let candidate = numbered_filename(&final_filename, copy_number);
+                    let taken = transaction
+                        .query_opt(&exists_stmt, &[&user_id, &folder_id, &candidate])
+                        .await?
+                        .is_some();
+                    if !taken {
+                        final_filename = candidate;
+                        break;
+                    }
+                    copy_number += 1;
+                }
+            }
+        }
+    }
+
+    let insert_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
             r#"
         INSERT INTO files (
-            id, user_id, original_filename, total_chunks, chunks_metadata,
+            id, user_id, folder_id, original_filename, total_chunks, chunks_metadata,
             encrypted_dek, nonce, dek_version, file_size, mime_type,
-            checksum_sha256, upload_status
+            checksum, upload_status
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, 'completed')
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, 'completed')
         RETURNING
             id, user_id, folder_id, original_filename, total_chunks,
             chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
-            mime_type, checksum_sha256, upload_status, uploaded_at,
+            mime_type, checksum, upload_status, uploaded_at,
             is_deleted, deleted_at, access_count
         "#,
         )
         .await?;
 
-    let row = client
+    let row = transaction
         .query_one(
-            &stmt,
+            &insert_stmt,
             &[
                 &id,
                 &user_id,
-                &original_filename,
+                &folder_id,
+                &final_filename,
                 &total_chunks,
                 &chunks_metadata,
                 &encrypted_dek,
@@ -56,12 +202,57 @@ pub async fn create_file(
                 &dek_version,
                 &file_size,
                 &mime_type,
-                &checksum_sha256,
+                &checksum,
             ],
         )
         .await?;
 
-    Ok(File::from(&row))
+    if quota_reserved {
+        let commit_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT commit_storage_reservation($1, $2) as new_storage_used
+            "#,
+            )
+            .await?;
+
+        transaction
+            .query_one(&commit_stmt, &[&user_id, &file_size])
+            .await?;
+    } else {
+        let quota_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT success, available_bytes
+            FROM update_storage_with_quota_check($1, $2)
+            "#,
+            )
+            .await?;
+
+        let quota = transaction
+            .query_one(&quota_stmt, &[&user_id, &file_size])
+            .await?;
+        if !quota.try_get::<_, bool>("success")? {
+            return Err(AppError::QuotaExceeded {
+                required: file_size,
+                available: quota.try_get("available_bytes")?,
+            });
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok((File::from(&row), replaced_files))
+}
+
+/// Builds the `n`th copy name of a file, e.g. `report (2).pdf`.
+pub(crate) fn numbered_filename(filename: &str, n: u32) -> String {
+    match filename.rfind('.') {
+        Some(dot) if dot > 0 => format!("{} ({}){}", &filename[..dot], n, &filename[dot..]),
+        _ => format!("{} ({})", filename, n),
+    }
 }
 
 /// Finds a file by its ID and user ID.
@@ -78,7 +269,7 @@ pub async fn find_by_id(
         SELECT
             id, user_id, folder_id, original_filename, total_chunks,
             chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
-            mime_type, checksum_sha256, upload_status, uploaded_at,
+            mime_type, checksum, upload_status, uploaded_at,
             is_deleted, deleted_at, access_count
         FROM files
         WHERE id = $1 AND user_id = $2 AND is_deleted = false
@@ -91,14 +282,12 @@ pub async fn find_by_id(
     Ok(row.map(|r| File::from(&r)))
 }
 
-/// Lists the files for a given user.
-pub async fn list_user_files(
+/// Finds a file by ID regardless of owner or deletion state, for admin tooling.
+pub async fn find_by_id_any_owner(
     client: &Client,
-    user_id: Uuid,
-    limit: i64,
-    offset: i64,
+    file_id: Uuid,
     stmt_cache: &StatementCache,
-) -> Result<Vec<File>> {
+) -> Result<Option<File>> {
     let stmt = stmt_cache
         .get_or_prepare_client(
             client,
@@ -106,23 +295,136 @@ pub async fn list_user_files(
         SELECT
             id, user_id, folder_id, original_filename, total_chunks,
             chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
-            mime_type, checksum_sha256, upload_status, uploaded_at,
+            mime_type, checksum, upload_status, uploaded_at,
             is_deleted, deleted_at, access_count
         FROM files
-        WHERE user_id = $1 AND is_deleted = false
-        ORDER BY uploaded_at DESC
-        LIMIT $2 OFFSET $3
+        WHERE id = $1
         "#,
         )
         .await?;
 
-    let rows = client
-        .query(&stmt, &[&user_id, &limit, &offset])
-        .await?;
+    let row = client.query_opt(&stmt, &[&file_id]).await?;
+
+    Ok(row.map(|r| File::from(&r)))
+}
+
+/// Escapes the `ILIKE` wildcards in `text` and wraps it for a substring match.
+fn contains_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Builds the `WHERE` clause of a filtered file listing.
+///
+/// `$1` is always the user ID; each active filter appends one parameter.
+fn file_filter_clause<'a>(
+    user_id: &'a Uuid,
+    filter: &'a FileFilter,
+    search_pattern: &'a Option<String>,
+) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+    let mut clause = "user_id = $1 AND is_deleted = false".to_string();
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![user_id];
+
+    match &filter.folder {
+        Some(FolderScope::Root) => clause.push_str(" AND folder_id IS NULL"),
+        Some(FolderScope::Folder(folder_id)) => {
+            params.push(folder_id);
+            clause.push_str(&format!(" AND folder_id = ${}", params.len()));
+        }
+        None => {}
+    }
+
+    if let Some(mime_type) = &filter.mime_type {
+        params.push(mime_type);
+        clause.push_str(&format!(" AND mime_type = ${}", params.len()));
+    }
+
+    if let Some(pattern) = search_pattern {
+        params.push(pattern);
+        clause.push_str(&format!(" AND original_filename ILIKE ${}", params.len()));
+    }
+
+    (clause, params)
+}
+
+/// Lists the files of a user that match `filter`, sorted by `order`.
+///
+/// A page starting at a [`PageStart::After`] cursor seeks with a
+/// `(uploaded_at, id)` row comparison instead of skipping rows, so it is only
+/// meaningful with the default newest-first `order`.
+pub async fn list_user_files(
+    client: &Client,
+    user_id: Uuid,
+    filter: &FileFilter,
+    order: FileOrder,
+    limit: i64,
+    start: PageStart,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<File>> {
+    let search_pattern = filter.search.as_deref().map(contains_pattern);
+    let (mut clause, mut params) = file_filter_clause(&user_id, filter, &search_pattern);
+
+    let (offset, cursor) = match start {
+        PageStart::Offset(offset) => (offset, None),
+        PageStart::After(cursor) => (0, Some(cursor)),
+    };
+    if let Some(cursor) = &cursor {
+        params.push(&cursor.uploaded_at);
+        params.push(&cursor.id);
+        clause.push_str(&format!(
+            " AND (uploaded_at, id) < (${}, ${})",
+            params.len() - 1,
+            params.len()
+        ));
+    }
+    params.push(&limit);
+    params.push(&offset);
+
+    let query = format!(
+        r#"
+        SELECT
+            id, user_id, folder_id, original_filename, total_chunks,
+            chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
+            mime_type, checksum, upload_status, uploaded_at,
+            is_deleted, deleted_at, access_count
+        FROM files
+        WHERE {}
+        ORDER BY {}
+        LIMIT ${} OFFSET ${}
+        "#,
+        clause,
+        order.sql(),
+        params.len() - 1,
+        params.len()
+    );
+    let stmt = stmt_cache.get_or_prepare_client(client, &query).await?;
+
+    let rows = client.query(&stmt, &params).await?;
 
     Ok(rows.iter().map(File::from).collect())
 }
 
+/// Counts the non-deleted files of a user that match `filter`.
+pub async fn count_user_files(
+    client: &Client,
+    user_id: Uuid,
+    filter: &FileFilter,
+    stmt_cache: &StatementCache,
+) -> Result<i64> {
+    let search_pattern = filter.search.as_deref().map(contains_pattern);
+    let (clause, params) = file_filter_clause(&user_id, filter, &search_pattern);
+
+    let query = format!("SELECT COUNT(*) AS total FROM files WHERE {}", clause);
+    let stmt = stmt_cache.get_or_prepare_client(client, &query).await?;
+
+    let row = client.query_one(&stmt, &params).await?;
+
+    Ok(row.try_get("total")?)
+}
+
 /// Soft deletes a file.
 pub async fn soft_delete_file(
     client: &Client,
@@ -147,6 +449,444 @@ pub async fn soft_delete_file(
     Ok(row.map(|r| r.get("file_size")))
 }
 
+/// A file removed by [`soft_delete_files`].
+pub struct DeletedFile {
+    pub id: Uuid,
+    pub file_size: i64,
+    pub chunks_metadata: Option<Vec<u8>>,
+}
+
+/// Soft-deletes those of `file_ids` the user owns and has not deleted yet,
+/// releasing their quota in the same transaction.
+///
+/// # Returns
+///
+/// The deleted files, and which of the other IDs were already deleted.
+pub async fn soft_delete_files(
+    client: &mut Client,
+    file_ids: &[Uuid],
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<(Vec<DeletedFile>, Vec<Uuid>)> {
+    let transaction = client.transaction().await?;
+
+    let delete_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        UPDATE files
+        SET is_deleted = true, deleted_at = NOW()
+        WHERE id = ANY($1) AND user_id = $2 AND is_deleted = false
+        RETURNING id, file_size, chunks_metadata
+        "#,
+        )
+        .await?;
+
+    let deleted: Vec<DeletedFile> = transaction
+        .query(&delete_stmt, &[&file_ids, &user_id])
+        .await?
+        .iter()
+        .map(|row| DeletedFile {
+            id: row.get("id"),
+            file_size: row.get("file_size"),
+            chunks_metadata: row.get("chunks_metadata"),
+        })
+        .collect();
+
+    let freed_bytes: i64 = deleted.iter().map(|f| f.file_size).sum();
+    if freed_bytes > 0 {
+        let rollback_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                "SELECT rollback_storage_usage($1, $2) as success",
+            )
+            .await?;
+
+        transaction
+            .query_one(&rollback_stmt, &[&user_id, &freed_bytes])
+            .await?;
+    }
+
+    let already_deleted_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT id FROM files
+        WHERE id = ANY($1) AND user_id = $2 AND is_deleted = true AND NOT (id = ANY($3))
+        "#,
+        )
+        .await?;
+
+    let deleted_ids: Vec<Uuid> = deleted.iter().map(|f| f.id).collect();
+    let already_deleted: Vec<Uuid> = transaction
+        .query(&already_deleted_stmt, &[&file_ids, &user_id, &deleted_ids])
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+
+    transaction.commit().await?;
+
+    Ok((deleted, already_deleted))
+}
+
+/// Renames a user's file.
+///
+/// # Returns
+///
+/// `None` if the user has no such file, otherwise the new filename.
+pub async fn rename_file(
+    client: &Client,
+    file_id: Uuid,
+    user_id: Uuid,
+    filename: &str,
+    stmt_cache: &StatementCache,
+) -> Result<Option<String>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE files
+        SET original_filename = $3
+        WHERE id = $1 AND user_id = $2 AND is_deleted = false
+        RETURNING original_filename
+        "#,
+        )
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&file_id, &user_id, &filename]).await?;
+
+    Ok(row.map(|r| r.get("original_filename")))
+}
+
+/// Moves a user's file into `folder_id`, or to the root when `None`.
+///
+/// # Returns
+///
+/// `None` if the user has no such file, or `Validation` if the target folder
+/// does not exist, is deleted or belongs to someone else.
+pub async fn move_file(
+    client: &Client,
+    file_id: Uuid,
+    user_id: Uuid,
+    folder_id: Option<Uuid>,
+    stmt_cache: &StatementCache,
+) -> Result<Option<File>> {
+    if let Some(folder_id) = folder_id {
+        let stmt = stmt_cache
+            .get_or_prepare_client(
+                client,
+                r#"
+                SELECT id FROM folders
+                WHERE id = $1 AND user_id = $2 AND is_deleted = false
+                "#,
+            )
+            .await?;
+
+        client
+            .query_opt(&stmt, &[&folder_id, &user_id])
+            .await?
+            .ok_or_else(|| AppError::Validation("Target folder not found".to_string()))?;
+    }
+
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE files
+        SET folder_id = $3
+        WHERE id = $1 AND user_id = $2 AND is_deleted = false
+        RETURNING
+            id, user_id, folder_id, original_filename, total_chunks,
+            chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
+            mime_type, checksum, upload_status, uploaded_at,
+            is_deleted, deleted_at, access_count
+        "#,
+        )
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&file_id, &user_id, &folder_id]).await?;
+
+    Ok(row.map(|r| File::from(&r)))
+}
+
+/// Lists a user's soft-deleted files, most recently deleted first.
+pub async fn list_deleted_files(
+    client: &Client,
+    user_id: Uuid,
+    limit: i64,
+    offset: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<File>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT
+            id, user_id, folder_id, original_filename, total_chunks,
+            chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
+            mime_type, checksum, upload_status, uploaded_at,
+            is_deleted, deleted_at, access_count
+        FROM files
+        WHERE user_id = $1 AND is_deleted = true AND chunks_reclaimed = false
+        ORDER BY deleted_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        )
+        .await?;
+
+    let rows = client
+        .query(&stmt, &[&user_id, &limit, &offset])
+        .await?;
+
+    Ok(rows.iter().map(File::from).collect())
+}
+
+/// Counts the soft-deleted files of a user that can still be restored.
+pub async fn count_deleted_files(
+    client: &Client,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<i64> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT COUNT(*) AS total
+        FROM files
+        WHERE user_id = $1 AND is_deleted = true AND chunks_reclaimed = false
+        "#,
+        )
+        .await?;
+
+    let row = client.query_one(&stmt, &[&user_id]).await?;
+
+    Ok(row.try_get("total")?)
+}
+
+/// Restores a soft-deleted file that was deleted less than `grace_secs` ago
+/// and charges its size back to the user's quota.
+///
+/// Both run in one transaction, so a restore that would exceed the quota
+/// leaves the file in the trash. A file whose folder has since been deleted
+/// is restored to the root.
+///
+/// # Returns
+///
+/// `None` if there is no such file in the trash, otherwise the restored file.
+/// Fails with `Validation` if the file's chunks were already reclaimed.
+pub async fn restore_file(
+    client: &mut Client,
+    file_id: Uuid,
+    user_id: Uuid,
+    grace_secs: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Option<File>> {
+    let transaction = client.transaction().await?;
+
+    let restore_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        UPDATE files
+        SET is_deleted = false,
+            deleted_at = NULL,
+            folder_id = CASE
+                WHEN folder_id IS NULL OR EXISTS (
+                    SELECT 1 FROM folders
+                    WHERE folders.id = files.folder_id AND folders.is_deleted = false
+                ) THEN folder_id
+                ELSE NULL
+            END
+        WHERE id = $1
+          AND user_id = $2
+          AND is_deleted = true
+          AND chunks_reclaimed = false
+          AND deleted_at >= NOW() - make_interval(secs => $3::BIGINT::DOUBLE PRECISION)
+        RETURNING
+            id, user_id, folder_id, original_filename, total_chunks,
+            chunks_metadata, encrypted_dek, nonce, dek_version, file_size,
+            mime_type, checksum, upload_status, uploaded_at,
+            is_deleted, deleted_at, access_count
+        "#,
+        )
+        .await?;
+
+    let Some(row) = transaction
+        .query_opt(&restore_stmt, &[&file_id, &user_id, &grace_secs])
+        .await?
+    else {
+        let reclaimed_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT 1 FROM files
+            WHERE id = $1 AND user_id = $2 AND is_deleted = true AND chunks_reclaimed = true
+            "#,
+            )
+            .await?;
+
+        if transaction
+            .query_opt(&reclaimed_stmt, &[&file_id, &user_id])
+            .await?
+            .is_some()
+        {
+            return Err(AppError::Validation(
+                "The file's contents were removed from storage when it was deleted; it cannot be restored"
+                    .to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    let file = File::from(&row);
+
+    let quota_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT success, available_bytes
+        FROM update_storage_with_quota_check($1, $2)
+        "#,
+        )
+        .await?;
+
+    let quota = transaction
+        .query_one(&quota_stmt, &[&user_id, &file.file_size])
+        .await?;
+    if !quota.try_get::<_, bool>("success")? {
+        return Err(AppError::QuotaExceeded {
+            required: file.file_size,
+            available: quota.try_get("available_bytes")?,
+        });
+    }
+
+    transaction.commit().await?;
+
+    Ok(Some(file))
+}
+
+/// Lists soft-deleted files whose `deleted_at` is older than `older_than_secs`,
+/// oldest first, optionally only those of one user.
+///
+/// # Returns
+///
+/// The IDs of up to `limit` files.
+pub async fn list_purgeable_files(
+    client: &Client,
+    user_id: Option<Uuid>,
+    older_than_secs: i64,
+    limit: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<Uuid>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT id
+        FROM files
+        WHERE is_deleted = true
+          AND deleted_at < NOW() - make_interval(secs => $1::BIGINT::DOUBLE PRECISION)
+          AND ($3::UUID IS NULL OR user_id = $3)
+        ORDER BY deleted_at
+        LIMIT $2
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&older_than_secs, &limit, &user_id]).await?;
+
+    Ok(rows.iter().map(|r| r.get("id")).collect())
+}
+
+/// Lists the chunk metadata of every file with an ID greater than `after`,
+/// deleted or not, in ID order.
+///
+/// # Returns
+///
+/// The IDs and `chunks_metadata` of up to `limit` files.
+pub async fn list_chunk_metadata_after(
+    client: &Client,
+    after: Uuid,
+    limit: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<(Uuid, Vec<u8>)>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT id, chunks_metadata
+        FROM files
+        WHERE id > $1 AND chunks_metadata IS NOT NULL
+        ORDER BY id
+        LIMIT $2
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&after, &limit]).await?;
+
+    Ok(rows.iter().map(|r| (r.get("id"), r.get("chunks_metadata"))).collect())
+}
+
+/// The chunks of a file removed by [`purge_deleted_file`].
+pub struct PurgedFileChunks {
+    pub chunks_metadata: Option<Vec<u8>>,
+    /// Whether the chunks were already reclaimed when the file was deleted.
+    pub chunks_reclaimed: bool,
+}
+
+/// Permanently removes a soft-deleted file row.
+///
+/// # Returns
+///
+/// `None` if the file no longer exists or was restored in the meantime,
+/// otherwise its chunks so the chunk files can be removed.
+pub async fn purge_deleted_file(
+    client: &Client,
+    file_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<Option<PurgedFileChunks>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            "DELETE FROM files WHERE id = $1 AND is_deleted = true RETURNING chunks_metadata, chunks_reclaimed",
+        )
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&file_id]).await?;
+
+    Ok(row.map(|r| PurgedFileChunks {
+        chunks_metadata: r.get("chunks_metadata"),
+        chunks_reclaimed: r.get("chunks_reclaimed"),
+    }))
+}
+
+/// Marks a deleted file's chunks as reclaimed, before they are removed from
+/// disk on delete (`HARD_DELETE_ON_DELETE`).
+///
+/// # Returns
+///
+/// Whether the chunks are now the caller's to reclaim: `false` if the file
+/// was restored in the meantime, or its chunks were already claimed.
+pub async fn claim_chunk_reclaim(
+    client: &Client,
+    file_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE files
+        SET chunks_reclaimed = true
+        WHERE id = $1 AND is_deleted = true AND chunks_reclaimed = false
+        "#,
+        )
+        .await?;
+
+    Ok(client.execute(&stmt, &[&file_id]).await? > 0)
+}
+
 /// Increments the access count for a file.
 pub async fn increment_access_count(
     client: &Client,
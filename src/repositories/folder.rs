@@ -3,7 +3,7 @@ use uuid::Uuid;
 
 use crate::{
     error::{AppError, Result},
-    models::{file::File, folder::{Folder, FolderWithStats}},
+    models::{file::File, folder::{Folder, FolderPathEntry, FolderUsage, FolderWithStats, SubfolderUsage}},
     statement_cache::StatementCache,
 };
 
@@ -86,7 +86,7 @@ pub async fn list_folder_contents(
             r#"
         SELECT
             id, user_id, folder_id, original_filename, total_chunks, chunks_metadata,
-            encrypted_dek, nonce, dek_version, file_size, mime_type, checksum_sha256,
+            encrypted_dek, nonce, dek_version, file_size, mime_type, checksum,
             upload_status, uploaded_at, is_deleted, deleted_at, access_count
         FROM files
         WHERE user_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND is_deleted = false
@@ -101,6 +101,145 @@ pub async fn list_folder_contents(
     Ok((folders, files))
 }
 
+/// Updates the name and/or description of a user's folder, keeping the
+/// current value of any field passed as `None`.
+///
+/// # Returns
+///
+/// `None` if the user has no such non-deleted folder, otherwise the updated
+/// folder.
+pub async fn update_folder(
+    client: &Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    name: Option<String>,
+    description: Option<String>,
+    stmt_cache: &StatementCache,
+) -> Result<Option<Folder>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE folders
+        SET name = COALESCE($3, name),
+            description = COALESCE($4, description),
+            updated_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND is_deleted = false
+        RETURNING id, user_id, parent_folder_id, name, description, is_deleted, deleted_at, created_at, updated_at
+        "#,
+        )
+        .await?;
+
+    let row = client
+        .query_opt(&stmt, &[&folder_id, &user_id, &name, &description])
+        .await?;
+
+    Ok(row.map(|r| Folder::from(&r)))
+}
+
+/// Moves a user's folder under `parent_folder_id`, or to the root when `None`.
+///
+/// Moves are serialized per user with an advisory lock, so two concurrent
+/// moves cannot each pass the cycle check and together form a cycle.
+///
+/// # Returns
+///
+/// `None` if the user has no such non-deleted folder, or `Validation` if the
+/// target parent does not exist or lies inside the folder's own subtree.
+pub async fn move_folder(
+    client: &mut Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    parent_folder_id: Option<Uuid>,
+    stmt_cache: &StatementCache,
+) -> Result<Option<Folder>> {
+    let transaction = client.transaction().await?;
+
+    let lock_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT pg_advisory_xact_lock(hashtextextended($1::uuid::text || ':folder-tree', 0))
+        "#,
+        )
+        .await?;
+
+    transaction.execute(&lock_stmt, &[&user_id]).await?;
+
+    let folder_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT id FROM folders
+        WHERE id = $1 AND user_id = $2 AND is_deleted = false
+        "#,
+        )
+        .await?;
+
+    if transaction
+        .query_opt(&folder_stmt, &[&folder_id, &user_id])
+        .await?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    if let Some(parent_id) = parent_folder_id {
+        if transaction
+            .query_opt(&folder_stmt, &[&parent_id, &user_id])
+            .await?
+            .is_none()
+        {
+            return Err(AppError::Validation("Target folder not found".to_string()));
+        }
+
+        let cycle_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            WITH RECURSIVE subtree AS (
+                SELECT id FROM folders WHERE id = $1 AND user_id = $2
+                UNION ALL
+                SELECT f.id FROM folders f
+                INNER JOIN subtree s ON f.parent_folder_id = s.id
+            )
+            SELECT 1 FROM subtree WHERE id = $3
+            "#,
+            )
+            .await?;
+
+        if transaction
+            .query_opt(&cycle_stmt, &[&folder_id, &user_id, &parent_id])
+            .await?
+            .is_some()
+        {
+            return Err(AppError::Validation(
+                "Cannot move folder into its own subtree".to_string(),
+            ));
+        }
+    }
+
+    let move_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        UPDATE folders
+        SET parent_folder_id = $3, updated_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND is_deleted = false
+        RETURNING id, user_id, parent_folder_id, name, description, is_deleted, deleted_at, created_at, updated_at
+        "#,
+        )
+        .await?;
+
+    let row = transaction
+        .query_one(&move_stmt, &[&folder_id, &user_id, &parent_folder_id])
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(Some(Folder::from(&row)))
+}
+
 /// Gets a folder with its statistics.
 pub async fn get_folder_with_stats(
     client: &mut Client,
@@ -168,14 +307,135 @@ pub async fn get_folder_with_stats(
     }
 }
 
+/// Gets the recursive storage usage of a folder.
+///
+/// Walks the folder tree once, tagging every descendant with the immediate
+/// subfolder it sits under, so the breakdown costs a single query however many
+/// subfolders there are.
+pub async fn get_folder_usage(
+    client: &Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<Option<FolderUsage>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT id, NULL::uuid AS branch_id, NULL::varchar AS branch_name
+            FROM folders WHERE id = $1 AND user_id = $2 AND is_deleted = false
+            UNION ALL
+            SELECT f.id, COALESCE(ft.branch_id, f.id), COALESCE(ft.branch_name, f.name)
+            FROM folders f
+            INNER JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            WHERE f.is_deleted = false
+        )
+        SELECT ft.branch_id, ft.branch_name, COALESCE(SUM(fi.file_size), 0)::BIGINT AS size
+        FROM folder_tree ft
+        LEFT JOIN files fi ON fi.folder_id = ft.id AND fi.user_id = $2 AND fi.is_deleted = false
+        GROUP BY ft.branch_id, ft.branch_name
+        ORDER BY size DESC, ft.branch_name
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&folder_id, &user_id]).await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut usage = FolderUsage {
+        id: folder_id,
+        total_size: 0,
+        direct_size: 0,
+        subfolders: Vec::with_capacity(rows.len() - 1),
+    };
+    for row in &rows {
+        let size: i64 = row.get("size");
+        usage.total_size += size;
+        match row.get::<_, Option<Uuid>>("branch_id") {
+            Some(id) => usage.subfolders.push(SubfolderUsage {
+                id,
+                name: row.get("branch_name"),
+                total_size: size,
+            }),
+            None => usage.direct_size = size,
+        }
+    }
+
+    Ok(Some(usage))
+}
+
+
+/// Gets the path from the root to a folder.
+///
+/// Every ancestor must belong to the user. The walk stops at a folder it has
+/// already visited, so a corrupted `parent_folder_id` chain cannot loop.
+///
+/// # Returns
+///
+/// The folders ordered from the root down to `folder_id`, or `None` if the
+/// folder does not exist.
+pub async fn get_folder_path(
+    client: &Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<Option<Vec<FolderPathEntry>>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        WITH RECURSIVE ancestors AS (
+            SELECT id, name, parent_folder_id, 0 AS depth, ARRAY[id] AS visited, false AS is_cycle
+            FROM folders WHERE id = $1 AND user_id = $2 AND is_deleted = false
+            UNION ALL
+            SELECT f.id, f.name, f.parent_folder_id, a.depth + 1, a.visited || f.id, f.id = ANY(a.visited)
+            FROM folders f
+            INNER JOIN ancestors a ON f.id = a.parent_folder_id
+            WHERE f.user_id = $2 AND NOT a.is_cycle
+        )
+        SELECT id, name, parent_folder_id, is_cycle FROM ancestors ORDER BY depth DESC
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&folder_id, &user_id]).await?;
+    let Some(top) = rows.first() else {
+        return Ok(None);
+    };
+
+    if top.get::<_, bool>("is_cycle") {
+        tracing::error!("❌ Folder {} has a cycle in its ancestry", folder_id);
+        return Err(AppError::Internal("Folder hierarchy contains a cycle".to_string()));
+    }
+    if top.get::<_, Option<Uuid>>("parent_folder_id").is_some() {
+        tracing::error!("❌ Folder {} has an ancestor outside its owner's folders", folder_id);
+        return Err(AppError::Internal("Folder hierarchy is broken".to_string()));
+    }
+
+    Ok(Some(
+        rows.iter()
+            .map(|row| FolderPathEntry {
+                id: row.get("id"),
+                name: row.get("name"),
+            })
+            .collect(),
+    ))
+}
 
 /// Recursively deletes a folder and its contents.
+///
+/// The quota held by the soft-deleted files is released in the same
+/// transaction. Returns the number of bytes freed and the IDs and chunk
+/// metadata of the deleted files.
 pub async fn delete_folder_recursive(
     client: &mut Client,
     folder_id: Uuid,
     user_id: Uuid,
     stmt_cache: &StatementCache,
-) -> Result<()> {
+) -> Result<(i64, Vec<(Uuid, Option<Vec<u8>>)>)> {
     // Note: Using a transaction to ensure atomicity
     let transaction = client.transaction().await?;
 
@@ -183,25 +443,53 @@ pub async fn delete_folder_recursive(
         .get_or_prepare_transaction(
             &transaction,
             r#"
-        UPDATE files
-        SET is_deleted = true, deleted_at = NOW()
-        WHERE folder_id IN (
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1 AND user_id = $2
-                UNION ALL
-                SELECT f.id FROM folders f
-                INNER JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            SELECT id FROM folder_tree
+        WITH RECURSIVE folder_tree AS (
+            SELECT id FROM folders WHERE id = $1 AND user_id = $2
+            UNION ALL
+            SELECT f.id FROM folders f
+            INNER JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        ),
+        deleted_files AS (
+            UPDATE files
+            SET is_deleted = true, deleted_at = NOW()
+            WHERE folder_id IN (SELECT id FROM folder_tree)
+              AND user_id = $2
+              AND is_deleted = false
+            RETURNING id, file_size, chunks_metadata
         )
+        SELECT id, file_size, chunks_metadata FROM deleted_files
         "#,
         )
         .await?;
 
-    transaction
-        .execute(&update_files_stmt, &[&folder_id, &user_id])
+    let deleted_rows = transaction
+        .query(&update_files_stmt, &[&folder_id, &user_id])
         .await?;
 
+    let freed_bytes: i64 = deleted_rows
+        .iter()
+        .map(|row| row.get::<_, i64>("file_size"))
+        .sum();
+    let deleted_files: Vec<(Uuid, Option<Vec<u8>>)> = deleted_rows
+        .iter()
+        .map(|row| (row.get("id"), row.get("chunks_metadata")))
+        .collect();
+
+    if freed_bytes > 0 {
+        let rollback_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT rollback_storage_usage($1, $2) as success
+            "#,
+            )
+            .await?;
+
+        transaction
+            .query_one(&rollback_stmt, &[&user_id, &freed_bytes])
+            .await?;
+    }
+
     let update_folders_stmt = stmt_cache
         .get_or_prepare_transaction(
             &transaction,
@@ -227,5 +515,312 @@ pub async fn delete_folder_recursive(
 
     transaction.commit().await?;
 
-    Ok(())
+    Ok((freed_bytes, deleted_files))
+}
+
+/// A file removed by [`purge_deleted_folder`].
+pub struct PurgedFile {
+    pub id: Uuid,
+    /// Whether the file was already soft-deleted, and so had its quota released.
+    pub was_deleted: bool,
+    /// Whether the chunks were already reclaimed when the file was deleted.
+    pub chunks_reclaimed: bool,
+    pub chunks_metadata: Option<Vec<u8>>,
+}
+
+/// Permanently removes a soft-deleted folder, its subfolders and every file in
+/// them, releasing the quota of files that were not deleted yet.
+///
+/// # Returns
+///
+/// `None` if the folder does not exist, otherwise the bytes of quota released
+/// and the removed files, whose chunks are left for the caller to reclaim.
+/// Fails with `Validation` if the folder is not deleted.
+pub async fn purge_deleted_folder(
+    client: &mut Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<Option<(i64, Vec<PurgedFile>)>> {
+    let transaction = client.transaction().await?;
+
+    let lock_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            "SELECT is_deleted FROM folders WHERE id = $1 AND user_id = $2 FOR UPDATE",
+        )
+        .await?;
+
+    let Some(folder) = transaction.query_opt(&lock_stmt, &[&folder_id, &user_id]).await? else {
+        return Ok(None);
+    };
+    if !folder.get::<_, bool>("is_deleted") {
+        return Err(AppError::Validation(
+            "Only a deleted folder can be deleted permanently".to_string(),
+        ));
+    }
+
+    let delete_files_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT id FROM folders WHERE id = $1 AND user_id = $2
+            UNION
+            SELECT f.id FROM folders f
+            INNER JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        DELETE FROM files
+        WHERE folder_id IN (SELECT id FROM folder_tree) AND user_id = $2
+        RETURNING id, file_size, is_deleted, chunks_reclaimed, chunks_metadata
+        "#,
+        )
+        .await?;
+
+    let rows = transaction
+        .query(&delete_files_stmt, &[&folder_id, &user_id])
+        .await?;
+
+    let released_bytes: i64 = rows
+        .iter()
+        .filter(|row| !row.get::<_, bool>("is_deleted"))
+        .map(|row| row.get::<_, i64>("file_size"))
+        .sum();
+    let files: Vec<PurgedFile> = rows
+        .iter()
+        .map(|row| PurgedFile {
+            id: row.get("id"),
+            was_deleted: row.get("is_deleted"),
+            chunks_reclaimed: row.get("chunks_reclaimed"),
+            chunks_metadata: row.get("chunks_metadata"),
+        })
+        .collect();
+
+    if released_bytes > 0 {
+        let rollback_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                "SELECT rollback_storage_usage($1, $2) as success",
+            )
+            .await?;
+
+        transaction
+            .query_one(&rollback_stmt, &[&user_id, &released_bytes])
+            .await?;
+    }
+
+    // Subfolders go with it through ON DELETE CASCADE.
+    let delete_folder_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            "DELETE FROM folders WHERE id = $1 AND user_id = $2",
+        )
+        .await?;
+
+    transaction
+        .execute(&delete_folder_stmt, &[&folder_id, &user_id])
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(Some((released_bytes, files)))
+}
+
+/// Lists a user's deleted folders whose `deleted_at` is older than
+/// `older_than_secs` and whose parent, if any, is not deleted itself.
+///
+/// Purging these removes every deleted folder tree past the grace period.
+pub async fn list_purgeable_folders(
+    client: &Client,
+    user_id: Uuid,
+    older_than_secs: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<Uuid>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT f.id
+        FROM folders f
+        LEFT JOIN folders parent ON parent.id = f.parent_folder_id
+        WHERE f.user_id = $1
+          AND f.is_deleted = true
+          AND f.deleted_at < NOW() - make_interval(secs => $2::BIGINT::DOUBLE PRECISION)
+          AND (parent.id IS NULL OR parent.is_deleted = false)
+        ORDER BY f.deleted_at
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&user_id, &older_than_secs]).await?;
+
+    Ok(rows.iter().map(|r| r.get("id")).collect())
+}
+
+/// A folder tree brought back by [`restore_folder`].
+pub struct RestoredFolder {
+    pub folder: Folder,
+    /// The number of folders restored, including the folder itself.
+    pub folders_restored: usize,
+    pub files_restored: usize,
+    /// The files deleted along with the folder that stay deleted because
+    /// their chunks were already reclaimed.
+    pub files_skipped: usize,
+    /// The bytes charged back to the quota.
+    pub restored_bytes: i64,
+}
+
+/// Restores a deleted folder, if it was deleted no more than `grace_secs`
+/// ago, together with the subfolders and files deleted along with it.
+///
+/// A folder delete stamps everything it removes with the same `deleted_at`,
+/// so only descendants carrying the folder's timestamp are restored; files
+/// deleted on their own beforehand stay in the trash, as do files whose
+/// chunks were reclaimed on delete. Their size is charged back to the quota in
+/// the same transaction.
+///
+/// # Returns
+///
+/// `None` if there is no such folder in the trash. Fails with `Validation` if
+/// the parent folder is still deleted, and `QuotaExceeded` if the files no
+/// longer fit.
+pub async fn restore_folder(
+    client: &mut Client,
+    folder_id: Uuid,
+    user_id: Uuid,
+    grace_secs: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Option<RestoredFolder>> {
+    let transaction = client.transaction().await?;
+
+    let lock_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT f.deleted_at, COALESCE(parent.is_deleted, false) AS parent_is_deleted
+        FROM folders f
+        LEFT JOIN folders parent ON parent.id = f.parent_folder_id
+        WHERE f.id = $1
+          AND f.user_id = $2
+          AND f.is_deleted = true
+          AND f.deleted_at >= NOW() - make_interval(secs => $3::BIGINT::DOUBLE PRECISION)
+        FOR UPDATE OF f
+        "#,
+        )
+        .await?;
+
+    let Some(row) = transaction
+        .query_opt(&lock_stmt, &[&folder_id, &user_id, &grace_secs])
+        .await?
+    else {
+        return Ok(None);
+    };
+    if row.get::<_, bool>("parent_is_deleted") {
+        return Err(AppError::Validation(
+            "The parent folder is deleted; restore it first".to_string(),
+        ));
+    }
+    let deleted_at: chrono::DateTime<chrono::Utc> = row.get("deleted_at");
+
+    let restore_folders_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT id FROM folders WHERE id = $1 AND user_id = $2
+            UNION
+            SELECT f.id FROM folders f
+            INNER JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            WHERE f.is_deleted = true AND f.deleted_at = $3
+        )
+        UPDATE folders
+        SET is_deleted = false, deleted_at = NULL
+        WHERE id IN (SELECT id FROM folder_tree)
+        RETURNING id, user_id, parent_folder_id, name, description, is_deleted, deleted_at, created_at, updated_at
+        "#,
+        )
+        .await?;
+
+    let folder_rows = transaction
+        .query(&restore_folders_stmt, &[&folder_id, &user_id, &deleted_at])
+        .await?;
+    let folder_ids: Vec<Uuid> = folder_rows.iter().map(|r| r.get("id")).collect();
+    let folder = folder_rows
+        .iter()
+        .find(|r| r.get::<_, Uuid>("id") == folder_id)
+        .map(Folder::from)
+        .ok_or(AppError::NotFound)?;
+
+    let restore_files_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        UPDATE files
+        SET is_deleted = false, deleted_at = NULL
+        WHERE folder_id = ANY($1)
+          AND user_id = $2
+          AND is_deleted = true
+          AND chunks_reclaimed = false
+          AND deleted_at = $3
+        RETURNING file_size
+        "#,
+        )
+        .await?;
+
+    let file_rows = transaction
+        .query(&restore_files_stmt, &[&folder_ids, &user_id, &deleted_at])
+        .await?;
+    let restored_bytes: i64 = file_rows.iter().map(|r| r.get::<_, i64>("file_size")).sum();
+
+    let skipped_files_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            r#"
+        SELECT COUNT(*) AS total
+        FROM files
+        WHERE folder_id = ANY($1)
+          AND user_id = $2
+          AND is_deleted = true
+          AND chunks_reclaimed = true
+          AND deleted_at = $3
+        "#,
+        )
+        .await?;
+
+    let files_skipped: i64 = transaction
+        .query_one(&skipped_files_stmt, &[&folder_ids, &user_id, &deleted_at])
+        .await?
+        .get("total");
+
+    if restored_bytes > 0 {
+        let quota_stmt = stmt_cache
+            .get_or_prepare_transaction(
+                &transaction,
+                r#"
+            SELECT success, available_bytes
+            FROM update_storage_with_quota_check($1, $2)
+            "#,
+            )
+            .await?;
+
+        let quota = transaction
+            .query_one(&quota_stmt, &[&user_id, &restored_bytes])
+            .await?;
+        if !quota.try_get::<_, bool>("success")? {
+            return Err(AppError::QuotaExceeded {
+                required: restored_bytes,
+                available: quota.try_get("available_bytes")?,
+            });
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(Some(RestoredFolder {
+        folder,
+        folders_restored: folder_ids.len(),
+        files_restored: file_rows.len(),
+        files_skipped: files_skipped as usize,
+        restored_bytes,
+    }))
 }
@@ -0,0 +1,167 @@
+use deadpool_postgres::Client;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    statement_cache::StatementCache,
+};
+
+/// A user's stored TOTP secret.
+pub struct TotpSecret {
+    /// The secret encrypted with the KEK, as `ciphertext || nonce`.
+    pub encrypted: Vec<u8>,
+    /// The KEK version the secret was encrypted with.
+    pub kek_version: i32,
+    /// Whether the enrollment has been confirmed.
+    pub enabled: bool,
+}
+
+/// Stores a new, unconfirmed TOTP secret for a user who has not enabled
+/// two-factor authentication yet.
+///
+/// # Returns
+///
+/// `false` if two-factor authentication is already enabled, in which case
+/// nothing is changed.
+pub async fn set_pending_secret(
+    client: &Client,
+    user_id: Uuid,
+    encrypted: &[u8],
+    kek_version: i32,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET totp_secret_encrypted = $2, totp_kek_version = $3, totp_last_step = NULL
+        WHERE id = $1 AND two_factor_enabled = false
+        "#,
+        )
+        .await?;
+
+    let updated = client.execute(&stmt, &[&user_id, &encrypted, &kek_version]).await?;
+
+    Ok(updated > 0)
+}
+
+/// Finds a user's TOTP secret, confirmed or not.
+pub async fn find_secret(
+    client: &Client,
+    user_id: Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<Option<TotpSecret>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT totp_secret_encrypted, totp_kek_version, two_factor_enabled
+        FROM users
+        WHERE id = $1 AND totp_secret_encrypted IS NOT NULL AND totp_kek_version IS NOT NULL
+        "#,
+        )
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&user_id]).await?;
+
+    Ok(row.map(|r| TotpSecret {
+        encrypted: r.get("totp_secret_encrypted"),
+        kek_version: r.get("totp_kek_version"),
+        enabled: r.get("two_factor_enabled"),
+    }))
+}
+
+/// Records `step` as the last TOTP step used by a user.
+///
+/// # Returns
+///
+/// `false` if that step or a later one was already used, so the code is a
+/// replay.
+pub async fn advance_last_step(
+    client: &Client,
+    user_id: Uuid,
+    step: i64,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET totp_last_step = $2
+        WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+        "#,
+        )
+        .await?;
+
+    let updated = client.execute(&stmt, &[&user_id, &step]).await?;
+
+    Ok(updated > 0)
+}
+
+/// Enables two-factor authentication for a user and replaces their recovery
+/// codes with `code_hashes`.
+pub async fn enable(
+    client: &mut Client,
+    user_id: Uuid,
+    code_hashes: &[String],
+    stmt_cache: &StatementCache,
+) -> Result<()> {
+    let transaction = client.transaction().await?;
+
+    let enable_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            "UPDATE users SET two_factor_enabled = true WHERE id = $1",
+        )
+        .await?;
+    transaction.execute(&enable_stmt, &[&user_id]).await?;
+
+    let delete_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            "DELETE FROM user_recovery_codes WHERE user_id = $1",
+        )
+        .await?;
+    transaction.execute(&delete_stmt, &[&user_id]).await?;
+
+    let insert_stmt = stmt_cache
+        .get_or_prepare_transaction(
+            &transaction,
+            "INSERT INTO user_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::TEXT[])",
+        )
+        .await?;
+    transaction.execute(&insert_stmt, &[&user_id, &code_hashes]).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Marks an unused recovery code of a user as used.
+///
+/// # Returns
+///
+/// `true` if the code existed and had not been used yet.
+pub async fn consume_recovery_code(
+    client: &Client,
+    user_id: Uuid,
+    code_hash: &str,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE user_recovery_codes
+        SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        )
+        .await?;
+
+    let updated = client.execute(&stmt, &[&user_id, &code_hash]).await?;
+
+    Ok(updated > 0)
+}
@@ -1,28 +1,31 @@
 use deadpool_postgres::Client;
+use tokio_postgres::error::SqlState;
 use uuid::Uuid;
 
 use crate::{
     error::{AppError, Result},
-    models::user::User,
+    models::user::{NewUser, User, WrappedDek},
     statement_cache::StatementCache,
 };
 
+/// The unique index that keeps one user per email address.
+const EMAIL_UNIQUE_INDEX: &str = "idx_users_email_unique";
+
 /// Creates a new user in the database.
+///
+/// A username or email that is already registered is reported as an
+/// `AppError::Validation`.
 pub async fn create_user(
     client: &Client,
-    id: Uuid,
-    email: Option<String>,
-    password_hash: String,
-    encrypted_dek: Vec<u8>,
-    dek_salt: Vec<u8>,
+    user: &NewUser,
     stmt_cache: &StatementCache,
 ) -> Result<User> {
     let stmt = stmt_cache
         .get_or_prepare_client(
             client,
             r#"
-        INSERT INTO users (id, email, password, encrypted_dek, dek_salt)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO users (id, name, username, email, password, encrypted_dek, dek_salt, dek_kdf_params)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         RETURNING 
             id,
             name,
@@ -32,13 +35,15 @@ pub async fn create_user(
             roles,
             encrypted_dek,
             dek_salt,
+            dek_kdf_params,
             dek_kek_version,
             storage_quota_bytes,
             storage_used_bytes,
             created_at,
             updated_at,
             last_password_change,
-            is_active
+            is_active,
+            two_factor_enabled
         "#,
         )
         .await?;
@@ -47,18 +52,70 @@ pub async fn create_user(
         .query_one(
             &stmt,
             &[
-                &id,
-                &email,
-                &password_hash,
-                &encrypted_dek,
-                &dek_salt,
+                &user.id,
+                &user.name,
+                &user.username,
+                &user.email,
+                &user.password_hash,
+                &user.dek.encrypted_dek,
+                &user.dek.dek_salt,
+                &user.dek.dek_kdf_params,
             ],
         )
-        .await?;
+        .await
+        .map_err(|e| match e.as_db_error() {
+            Some(db_error) if *db_error.code() == SqlState::UNIQUE_VIOLATION => {
+                if db_error.constraint() == Some(EMAIL_UNIQUE_INDEX) {
+                    AppError::Validation("Email is already registered".to_string())
+                } else {
+                    AppError::Validation("Username is already taken".to_string())
+                }
+            }
+            _ => AppError::Postgres(e),
+        })?;
 
     Ok(User::from(&row))
 }
 
+/// Finds an active user by their username, ignoring case.
+pub async fn find_by_username(
+    client: &Client,
+    username: &str,
+    stmt_cache: &StatementCache,
+) -> Result<Option<User>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT 
+            id,
+            name,
+            username,
+            email,
+            password,
+            roles,
+            encrypted_dek,
+            dek_salt,
+            dek_kdf_params,
+            dek_kek_version,
+            storage_quota_bytes,
+            storage_used_bytes,
+            created_at,
+            updated_at,
+            last_password_change,
+            is_active,
+            two_factor_enabled
+        FROM users 
+        WHERE LOWER(username) = LOWER($1) AND is_active = true
+        "#,
+        )
+        .await?;
+
+    let row = client.query_opt(&stmt, &[&username]).await?;
+
+    Ok(row.map(|r| User::from(&r)))
+}
+
 /// Finds a user by their email address.
 pub async fn find_by_email(
     client: &Client,
@@ -78,13 +135,15 @@ pub async fn find_by_email(
             roles,
             encrypted_dek,
             dek_salt,
+            dek_kdf_params,
             dek_kek_version,
             storage_quota_bytes,
             storage_used_bytes,
             created_at,
             updated_at,
             last_password_change,
-            is_active
+            is_active,
+            two_factor_enabled
         FROM users 
         WHERE email = $1 AND is_active = true
         "#,
@@ -115,13 +174,15 @@ pub async fn find_by_id(
             roles,
             encrypted_dek,
             dek_salt,
+            dek_kdf_params,
             dek_kek_version,
             storage_quota_bytes,
             storage_used_bytes,
             created_at,
             updated_at,
             last_password_change,
-            is_active
+            is_active,
+            two_factor_enabled
         FROM users 
         WHERE id = $1
         "#,
@@ -138,8 +199,7 @@ pub async fn update_password(
     client: &Client,
     user_id: &Uuid,
     new_password: String,
-    encrypted_dek: Vec<u8>,
-    dek_salt: Vec<u8>,
+    dek: &WrappedDek,
     stmt_cache: &StatementCache,
 ) -> Result<()> {
     let stmt = stmt_cache
@@ -151,8 +211,9 @@ pub async fn update_password(
             password = $1,
             encrypted_dek = $2,
             dek_salt = $3,
+            dek_kdf_params = $4,
             last_password_change = NOW()
-        WHERE id = $4
+        WHERE id = $5
         "#,
         )
         .await?;
@@ -160,13 +221,159 @@ pub async fn update_password(
     client
         .execute(
             &stmt,
-            &[&new_password, &encrypted_dek, &dek_salt, &user_id],
+            &[&new_password, &dek.encrypted_dek, &dek.dek_salt, &dek.dek_kdf_params, &user_id],
         )
         .await?;
 
     Ok(())
 }
 
+/// Replaces the wrapping of a user's DEK without changing the password.
+///
+/// Only applies if the DEK is still wrapped with `previous_salt`, so a
+/// password change racing the update is never overwritten.
+///
+/// # Returns
+///
+/// Whether the wrapping was replaced.
+pub async fn update_dek_wrapping(
+    client: &Client,
+    user_id: &Uuid,
+    previous_salt: &[u8],
+    dek: &WrappedDek,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET
+            encrypted_dek = $1,
+            dek_salt = $2,
+            dek_kdf_params = $3
+        WHERE id = $4 AND dek_salt = $5
+        "#,
+        )
+        .await?;
+
+    let updated = client
+        .execute(
+            &stmt,
+            &[&dek.encrypted_dek, &dek.dek_salt, &dek.dek_kdf_params, &user_id, &previous_salt],
+        )
+        .await?;
+
+    Ok(updated == 1)
+}
+
+/// Enables or disables a user's account. Disabled users cannot log in.
+///
+/// # Returns
+///
+/// Whether the user exists.
+pub async fn set_user_active(
+    client: &Client,
+    user_id: &Uuid,
+    is_active: bool,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET is_active = $2
+        WHERE id = $1
+        "#,
+        )
+        .await?;
+
+    let updated = client.execute(&stmt, &[&user_id, &is_active]).await?;
+
+    Ok(updated > 0)
+}
+
+/// Lists a page of all users, oldest first.
+pub async fn list_users(
+    client: &Client,
+    limit: i64,
+    offset: i64,
+    stmt_cache: &StatementCache,
+) -> Result<Vec<User>> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT 
+            id,
+            name,
+            username,
+            email,
+            password,
+            roles,
+            encrypted_dek,
+            dek_salt,
+            dek_kdf_params,
+            dek_kek_version,
+            storage_quota_bytes,
+            storage_used_bytes,
+            created_at,
+            updated_at,
+            last_password_change,
+            is_active,
+            two_factor_enabled
+        FROM users 
+        ORDER BY created_at, id
+        LIMIT $1 OFFSET $2
+        "#,
+        )
+        .await?;
+
+    let rows = client.query(&stmt, &[&limit, &offset]).await?;
+
+    Ok(rows.iter().map(User::from).collect())
+}
+
+/// Counts all users.
+pub async fn count_users(client: &Client, stmt_cache: &StatementCache) -> Result<i64> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(client, "SELECT COUNT(*) AS total FROM users")
+        .await?;
+
+    let row = client.query_one(&stmt, &[]).await?;
+
+    Ok(row.try_get("total")?)
+}
+
+/// Sets a user's storage quota. A quota below the current usage blocks new
+/// uploads but leaves existing files alone.
+///
+/// # Returns
+///
+/// Whether the user exists.
+pub async fn set_storage_quota(
+    client: &Client,
+    user_id: &Uuid,
+    storage_quota_bytes: i64,
+    stmt_cache: &StatementCache,
+) -> Result<bool> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET storage_quota_bytes = $2
+        WHERE id = $1
+        "#,
+        )
+        .await?;
+
+    let updated = client.execute(&stmt, &[&user_id, &storage_quota_bytes]).await?;
+
+    Ok(updated > 0)
+}
+
 /// The result of a storage check.
 #[derive(Debug)]
 pub struct StorageCheckResult {
@@ -228,6 +435,117 @@ pub async fn rollback_storage_usage(
     Ok(())
 }
 
+/// Reserves quota for an in-progress upload.
+///
+/// Fails (with `success` false) if the quota minus current usage and other
+/// reservations cannot hold `bytes`.
+pub async fn reserve_storage(
+    client: &Client,
+    user_id: &Uuid,
+    bytes: i64,
+    stmt_cache: &StatementCache,
+) -> Result<StorageCheckResult> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT success, available_bytes
+        FROM reserve_storage($1, $2)
+        "#,
+        )
+        .await?;
+
+    let row = client.query_one(&stmt, &[&user_id, &bytes]).await?;
+
+    Ok(StorageCheckResult {
+        success: row.try_get("success")?,
+        available_bytes: row.try_get("available_bytes")?,
+        new_storage_used: 0,
+    })
+}
+
+/// Releases a reservation held by a canceled, expired or failed upload.
+pub async fn release_storage_reservation(
+    client: &Client,
+    user_id: &Uuid,
+    bytes: i64,
+    stmt_cache: &StatementCache,
+) -> Result<()> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT release_storage_reservation($1, $2) as success
+        "#,
+        )
+        .await?;
+
+    client.query_one(&stmt, &[&user_id, &bytes]).await?;
+
+    Ok(())
+}
+
+/// Resets a user's storage accounting from the source of truth: used bytes
+/// become the sum of the user's live files, and reserved bytes the quota held
+/// by their upload sessions still in progress.
+///
+/// Returns the recomputed used bytes.
+pub async fn reconcile_storage(
+    client: &Client,
+    user_id: &Uuid,
+    reserved_bytes: i64,
+    stmt_cache: &StatementCache,
+) -> Result<i64> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        UPDATE users
+        SET storage_used_bytes = (
+                SELECT COALESCE(SUM(file_size), 0)::BIGINT
+                FROM files
+                WHERE user_id = $1 AND is_deleted = false
+            ),
+            reserved_bytes = $2
+        WHERE id = $1
+        RETURNING storage_used_bytes
+        "#,
+        )
+        .await?;
+
+    let row = client
+        .query_opt(&stmt, &[&user_id, &reserved_bytes])
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(row.try_get("storage_used_bytes")?)
+}
+
+/// Gets the quota currently held by a user's upload reservations.
+pub async fn get_reserved_bytes(
+    client: &Client,
+    user_id: &Uuid,
+    stmt_cache: &StatementCache,
+) -> Result<i64> {
+    let stmt = stmt_cache
+        .get_or_prepare_client(
+            client,
+            r#"
+        SELECT reserved_bytes
+        FROM users
+        WHERE id = $1
+        "#,
+        )
+        .await?;
+
+    let row = client
+        .query_opt(&stmt, &[&user_id])
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(row.try_get("reserved_bytes")?)
+}
+
 /// Gets a user's storage information.
 pub async fn get_user_storage_info(
     client: &Client,
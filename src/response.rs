@@ -0,0 +1,17 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// Builds a response from an already-serialized JSON body.
+///
+/// Handlers serialize with `sonic_rs` to a `String`, which axum would
+/// otherwise send as `text/plain`; this sets `Content-Type: application/json`.
+pub fn json_response(status: StatusCode, body: String) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,224 @@
+use axum::{
+    Router,
+    body::Body,
+    extract::Request,
+    response::IntoResponse,
+    routing::{get, post, delete, patch},
+    middleware::from_fn_with_state,
+};
+use http::{Method, header};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_cookies::CookieManagerLayer;
+use tower::ServiceExt;
+use tower_governor::governor::GovernorConfigBuilder;
+use tower_http::{
+    services::ServeDir,
+    trace::{TraceLayer, DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, DefaultOnFailure},
+    cors::CorsLayer,
+};
+use tracing::Level;
+
+use crate::{
+    error::AppError,
+    handlers, metrics, middleware_layer, openapi,
+    state::AppState,
+    tenant::{self, TenantMode},
+};
+
+/// Builds the application router with all routes and middleware.
+///
+/// The router is not bound to a socket, so tests can drive it directly with
+/// `tower::ServiceExt::oneshot`. Handlers that extract `ConnectInfo` need the
+/// caller to add `axum::extract::connect_info::MockConnectInfo` in that case.
+///
+/// With `TENANT_MODE` enabled, every tenant in `TENANTS` gets its own copy of
+/// the routes over [`AppState::for_tenant`], and each request is dispatched
+/// to the one its tenant resolves to; requests naming no listed tenant get
+/// `404`. The health probes stay outside any tenant.
+pub fn build_router(state: AppState) -> Router {
+    if state.config.tenant_mode == TenantMode::Off {
+        return build_tenant_router(state);
+    }
+
+    let tenant_routers: Arc<HashMap<String, Router>> = Arc::new(
+        state
+            .config
+            .tenants
+            .iter()
+            .map(|tenant| (tenant.clone(), build_tenant_router(state.for_tenant(tenant))))
+            .collect(),
+    );
+    let config = Arc::new(state.config.clone());
+
+    let dispatch = move |req: Request<Body>| {
+        let tenant_routers = tenant_routers.clone();
+        let config = config.clone();
+        async move {
+            let router = tenant::resolve_tenant(req.headers(), &config)
+                .and_then(|tenant| tenant_routers.get(&tenant).cloned());
+            match router {
+                Some(router) => match router.oneshot(req).await {
+                    Ok(response) => response,
+                    Err(never) => match never {},
+                },
+                None => AppError::NotFound.into_response(),
+            }
+        }
+    };
+
+    Router::new()
+        .route("/health/live", get(handlers::health::live))
+        .route("/health/ready", get(handlers::health::ready))
+        .with_state(state)
+        .fallback(dispatch)
+}
+
+/// Builds the routes and middleware serving one tenant, or the whole
+/// application when tenants are off.
+fn build_tenant_router(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(state.config.cors_allowed_origins.clone())
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::PATCH,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::COOKIE,
+            "x-csrf-token".parse().unwrap(),
+        ])
+        .allow_credentials(true)
+        .expose_headers([
+            "x-csrf-token".parse().unwrap(),
+            "x-total-chunks".parse().unwrap(),
+            header::CONTENT_RANGE,
+            header::ACCEPT_RANGES,
+            header::CONTENT_DISPOSITION,
+        ])
+        .max_age(Duration::from_secs(86400));
+
+    let governor_conf = Arc::new(
+        GovernorConfigBuilder::default()
+            .per_second(10_000)
+            .burst_size(50_000)
+            .use_headers()
+            .finish()
+            .unwrap(),
+    );
+
+    let auth_routes = Router::new()
+        .route("/api/auth/logout", post(handlers::auth::logout))
+        .route("/api/auth/logout-all", post(handlers::auth::logout_all))
+        .route("/api/auth/change-password", post(handlers::auth::change_password))
+        .route("/api/auth/2fa/enroll", post(handlers::auth::enroll_two_factor))
+        .route("/api/auth/2fa/verify", post(handlers::auth::verify_two_factor))
+        .route("/api/auth/sessions", get(handlers::auth::list_sessions))
+        .route("/api/events", get(handlers::events::event_stream))
+        .route("/api/auth/sessions/{session_id}", delete(handlers::auth::revoke_session));
+
+    let file_routes = Router::new()
+        .route("/api/files/upload/init", post(handlers::files::init_upload))
+        .route("/api/files/upload/chunk", post(handlers::files::upload_chunk))
+        .route("/api/files/upload/finalize", post(handlers::files::finalize_upload))
+        .route("/api/files/upload/cancel", post(handlers::files::cancel_upload))
+        .route("/api/files/upload/active", get(handlers::files::list_active_uploads))
+        .route("/api/files/upload/status", get(handlers::files::upload_status))
+        .route("/ws/uploads/{upload_session_id}", get(handlers::upload_progress::upload_progress_socket))
+        .route("/api/files/recalculate-quota", post(handlers::files::recalculate_user_quota))
+        .route("/api/files/storage/info", get(handlers::files::storage_info))
+        .route("/api/files", get(handlers::files::list_files))
+        .route("/api/files/trash", get(handlers::files::list_trash))
+        .route("/api/files/batch-delete", post(handlers::files::batch_delete_files))
+        .route("/api/files/{file_id}", get(handlers::files::download_file))
+        .route("/api/files/{file_id}", delete(handlers::files::delete_file))
+        .route("/api/files/{file_id}", patch(handlers::files::rename_file))
+        .route("/api/files/{file_id}/verify", get(handlers::files::verify_file).post(handlers::files::verify_file))
+        .route("/api/files/{file_id}/restore", post(handlers::files::restore_file))
+        .route("/api/files/{file_id}/move", patch(handlers::files::move_file))
+        .route("/api/files/{file_id}/share", post(handlers::shares::create_share))
+        .route("/api/files/{file_id}/share/{token}", delete(handlers::shares::revoke_share));
+
+    let folder_routes = Router::new()
+        .route("/api/folders/list", get(handlers::folders::list_folder_contents))
+        .route("/api/folders/{folder_id}", get(handlers::folders::get_folder_stats))
+        .route("/api/folders/{folder_id}/usage", get(handlers::folders::get_folder_usage))
+        .route("/api/folders/{folder_id}/path", get(handlers::folders::get_folder_path))
+        .route("/api/folders", post(handlers::folders::create_folder))
+        .route("/api/folders/{folder_id}", patch(handlers::folders::update_folder))
+        .route("/api/folders/{folder_id}", delete(handlers::folders::delete_folder))
+        .route("/api/folders/{folder_id}/restore", post(handlers::folders::restore_folder))
+        .route("/api/folders/{folder_id}/permanent", delete(handlers::folders::purge_folder))
+        .route("/api/folders/trash/empty", post(handlers::folders::empty_trash))
+        .route("/api/folders/{folder_id}/move", patch(handlers::folders::move_folder))
+        .route("/api/folders/{folder_id}/download", get(handlers::folders::download_folder));
+
+    let admin_routes = Router::new()
+        .route("/api/admin/users", get(handlers::admin::list_users))
+        .route("/api/admin/users/{user_id}/quota", patch(handlers::admin::set_user_quota))
+        .route("/api/admin/users/{user_id}/active", patch(handlers::admin::set_user_active))
+        .route("/api/admin/audit", get(handlers::admin::list_audit_logs))
+        .route("/api/admin/users/{user_id}/impersonate", post(handlers::admin::impersonate_user))
+        .route("/api/admin/files/{file_id}/diagnostics", get(handlers::admin::file_diagnostics))
+        .route("/api/admin/users/{user_id}/logout-all", post(handlers::admin::force_logout_user))
+        .route("/api/admin/kek/rotate", post(handlers::admin::rotate_kek))
+        .route("/api/admin/kek/rotation", get(handlers::admin::kek_rotation_status))
+        .route("/api/admin/gc/chunks", post(handlers::admin::collect_orphaned_chunks))
+        .route_layer(from_fn_with_state(state.clone(), middleware_layer::role::require_admin));
+
+    // Registration and login create the session, so they cannot sit behind
+    // `require_auth` or the CSRF check.
+    let public_routes = Router::new()
+        .route("/api/auth/register", post(handlers::auth::register))
+        .route("/api/auth/forgot-password", post(handlers::auth::forgot_password))
+        .route("/api/auth/reset-password", post(handlers::auth::reset_password))
+        .route(
+            "/api/auth/login",
+            post(handlers::auth::login)
+                .layer(from_fn_with_state(state.clone(), middleware_layer::rate_limit::rate_limit_login)),
+        )
+        .route("/api/openapi.json", get(openapi::openapi_json))
+        .route("/api-docs/openapi.json", get(openapi::openapi_json))
+        .route("/docs", get(openapi::swagger_ui))
+        .route("/docs/swagger-init.js", get(openapi::swagger_init_js))
+        .route("/metrics", get(metrics::metrics_handler))
+        .route("/api/share/{token}", get(handlers::shares::download_shared))
+        .layer(tower_governor::GovernorLayer::new(governor_conf.clone()));
+
+    // Load balancer probes: unauthenticated and exempt from rate limiting, so
+    // a burst of probes never makes a healthy instance look down.
+    let health_routes = Router::new()
+        .route("/health/live", get(handlers::health::live))
+        .route("/health/ready", get(handlers::health::ready));
+
+    Router::new()
+        .merge(auth_routes)
+        .merge(file_routes)
+        .merge(folder_routes)
+        .merge(admin_routes)
+        .layer(tower_governor::GovernorLayer::new(governor_conf))
+        .layer(from_fn_with_state(state.clone(), middleware_layer::csrf::verify_csrf))
+        .layer(from_fn_with_state(state.clone(), middleware_layer::auth::require_auth))
+        .layer(from_fn_with_state(state.clone(), middleware_layer::ip_filter::restrict_admin_ips))
+        .merge(public_routes)
+        .merge(health_routes)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::default().include_headers(true))
+                .on_request(DefaultOnRequest::default().level(Level::DEBUG))
+                .on_response(DefaultOnResponse::default().level(Level::DEBUG))
+                .on_failure(DefaultOnFailure::default().level(Level::ERROR)),
+        )
+        .layer(CookieManagerLayer::new())
+        .layer(cors)
+        .with_state(state.clone())
+        .fallback_service(ServeDir::new(state.public_dir()))
+        .layer(from_fn_with_state(state, middleware_layer::security_headers::apply_security_headers))
+}
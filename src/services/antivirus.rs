@@ -0,0 +1,109 @@
+use std::path::Path;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    time::{timeout, Duration},
+};
+
+use crate::{
+    config::Config,
+    error::{AppError, Result},
+    models::file::ChunkInfo,
+};
+
+/// The largest piece sent to clamd in one INSTREAM frame.
+const INSTREAM_FRAME_SIZE: usize = 1024 * 1024;
+
+/// The outcome of an antivirus scan.
+#[derive(Debug)]
+pub enum ScanVerdict {
+    /// No signature matched.
+    Clean,
+    /// A signature matched; holds the signature name reported by clamd.
+    Infected(String),
+}
+
+/// Decrypts (and decompresses) the chunks of an upload and streams the
+/// plaintext to clamd.
+///
+/// # Arguments
+///
+/// * `config` - The application's configuration (clamd address and timeout).
+/// * `dek` - The DEK the chunks were encrypted with.
+/// * `upload_dir` - The directory holding the chunk files.
+/// * `chunks` - The chunks, in upload order.
+///
+/// # Returns
+///
+/// The scan verdict, or an error if clamd could not be reached, timed out or
+/// returned an error (e.g. the stream exceeded its `StreamMaxLength`).
+pub async fn scan_encrypted_chunks(
+    config: &Config,
+    dek: &[u8; 32],
+    upload_dir: &Path,
+    chunks: &[ChunkInfo],
+) -> Result<ScanVerdict> {
+    let scan = async {
+        if config.clamd_address.starts_with('/') {
+            #[cfg(unix)]
+            {
+                let stream = tokio::net::UnixStream::connect(&config.clamd_address).await?;
+                return instream(stream, dek, upload_dir, chunks).await;
+            }
+            #[cfg(not(unix))]
+            return Err(AppError::Internal(
+                "Unix socket clamd addresses are not supported on this platform".to_string(),
+            ));
+        }
+
+        let stream = TcpStream::connect(&config.clamd_address).await?;
+        instream(stream, dek, upload_dir, chunks).await
+    };
+
+    timeout(Duration::from_secs(config.antivirus_timeout_secs), scan)
+        .await
+        .map_err(|_| AppError::Internal("Antivirus scan timed out".to_string()))?
+}
+
+/// Runs the clamd `INSTREAM` command over an open connection.
+async fn instream<S>(mut stream: S, dek: &[u8; 32], upload_dir: &Path, chunks: &[ChunkInfo]) -> Result<ScanVerdict>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(b"zINSTREAM\0").await?;
+
+    for chunk in chunks {
+        let chunk_encrypted = tokio::fs::read(upload_dir.join(chunk.get_filename()?)).await?;
+        let chunk_plaintext =
+            chunk.decompress(crate::crypto::aes::decrypt(dek, &chunk_encrypted, &chunk.nonce)?)?;
+
+        for frame in chunk_plaintext.chunks(INSTREAM_FRAME_SIZE) {
+            stream.write_all(&(frame.len() as u32).to_be_bytes()).await?;
+            stream.write_all(frame).await?;
+        }
+    }
+
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    stream.flush().await?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+
+    let reply = String::from_utf8_lossy(&reply);
+    let reply = reply.trim_end_matches('\0').trim();
+
+    tracing::debug!("🦠 clamd reply: {}", reply);
+
+    if reply.ends_with("OK") {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = reply.strip_suffix("FOUND") {
+        let signature = signature
+            .trim()
+            .trim_start_matches("stream:")
+            .trim()
+            .to_string();
+        Ok(ScanVerdict::Infected(signature))
+    } else {
+        Err(AppError::Internal(format!("clamd error: {}", reply)))
+    }
+}
@@ -0,0 +1,65 @@
+use axum::http::{header, HeaderMap};
+use std::net::SocketAddr;
+
+use crate::{
+    middleware_layer::ip_filter::client_ip_from_parts,
+    models::{audit::AuditEvent, session::Session},
+    repositories::audit as audit_repo,
+    state::AppState,
+};
+
+/// The longest `User-Agent` kept in the audit log, in characters.
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Starts an [`AuditEvent`] for a request, with the client IP resolved as
+/// configured by `TRUST_PROXY_HEADERS`.
+pub fn event(state: &AppState, action: &'static str, headers: &HeaderMap, addr: SocketAddr) -> AuditEvent {
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect());
+
+    AuditEvent::new(
+        action,
+        client_ip_from_parts(headers, Some(addr.ip()), state.config.trust_proxy_headers),
+        user_agent,
+    )
+}
+
+/// Starts an [`AuditEvent`] for an action taken with `session`.
+///
+/// Actions taken through an admin's impersonation session are attributed to
+/// the user and carry the admin in `impersonated_by`.
+pub fn session_event(
+    state: &AppState,
+    action: &'static str,
+    session: &Session,
+    headers: &HeaderMap,
+    addr: SocketAddr,
+) -> AuditEvent {
+    AuditEvent {
+        user_id: Some(session.user_id),
+        impersonated_by: session.impersonated_by,
+        ..event(state, action, headers, addr)
+    }
+}
+
+/// Writes an event to the audit log in the background.
+///
+/// The request does not wait for the write, so a slow or failing database
+/// never delays or fails the action being audited; a failed write is logged.
+pub fn record(state: &AppState, event: AuditEvent) {
+    let db = state.db.clone();
+    let stmt_cache = state.stmt_cache.clone();
+    tokio::spawn(async move {
+        let result = async {
+            let client = db.get().await?;
+            audit_repo::insert_event(&client, &event, &stmt_cache).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!("❌ Failed to write audit log entry {:?}: {}", event, e);
+        }
+    });
+}
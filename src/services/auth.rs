@@ -1,11 +1,11 @@
 use crate::crypto::dek;
 use crate::error::{AppError, Result};
-use crate::models::user::User;
+use crate::models::user::{NewUser, User, WrappedDek};
 use crate::repositories::user as user_repo;
 use crate::state::AppState;
 use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2, ParamsBuilder,
+    Argon2, Params,
 };
 use rand::{
     rngs::OsRng,
@@ -14,15 +14,11 @@ use rand::{
 use uuid::Uuid;
 use zeroize::Zeroize;
 
-/// The memory cost for Argon2 in MB.
-const ARGON2_MEMORY_MB: u32 = 19;
-/// The number of iterations for Argon2.
-const ARGON2_ITERATIONS: u32 = 3;
-/// The parallelism factor for Argon2.
-const ARGON2_PARALLELISM: u32 = 6;
-
-/// Hashes a password using Argon2id.
-fn hash_password(password: &str) -> Result<String> {
+/// Hashes a password using Argon2id with the given cost.
+///
+/// The cost is embedded in the hash, so raising it only affects new hashes
+/// and existing ones keep verifying.
+fn hash_password(password: &str, params: Params) -> Result<String> {
     let mut password_bytes = password.as_bytes().to_vec();
 
     let mut salt_bytes = [0u8; 16];
@@ -31,16 +27,7 @@ fn hash_password(password: &str) -> Result<String> {
     let salt = SaltString::encode_b64(&salt_bytes)
         .map_err(|e| AppError::Encryption(format!("Salt encoding error: {}", e)))?;
 
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        ParamsBuilder::new()
-            .m_cost(ARGON2_MEMORY_MB * 1024)
-            .t_cost(ARGON2_ITERATIONS)
-            .p_cost(ARGON2_PARALLELISM)
-            .build()
-            .map_err(|e| AppError::Encryption(format!("Argon2 params: {}", e)))?,
-    );
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
 
     let password_hash = argon2
         .hash_password(&password_bytes, &salt)
@@ -52,7 +39,8 @@ fn hash_password(password: &str) -> Result<String> {
     Ok(password_hash)
 }
 
-/// Verifies a password against a hash.
+/// Verifies a password against a hash, using the algorithm and cost
+/// recorded in the hash itself.
 fn verify_password(password: &str, hash: &str) -> Result<bool> {
     let mut password_bytes = password.as_bytes().to_vec();
     let parsed_hash = PasswordHash::new(hash)
@@ -72,23 +60,36 @@ pub async fn create_user(
     state: &AppState,
     name: String,
     username: String,
+    email: Option<String>,
     password: String,
 ) -> Result<User> {
     tracing::debug!("🔐 Creating user: {}", username);
-    let hashed_password = hash_password(&password)?;
-    let (encrypted_dek, dek_salt) = dek::create_user_dek(&password)?;
+    let params = state.config.argon2_params.clone();
+    let kdf_params = dek::KdfParams::from(&params);
+    let (hashed_password, (encrypted_dek, dek_salt)) = state
+        .password_hasher
+        .run(move || {
+            Ok((
+                hash_password(&password, params)?,
+                dek::create_user_dek(&password, &kdf_params)?,
+            ))
+        })
+        .await?;
     
     let client = state.db.get().await?;
-    let user = user_repo::create_user(
-        &client,
-        Uuid::new_v4(),
-        Some(username),
-        hashed_password,
-        encrypted_dek,
-        dek_salt,
-        &state.stmt_cache,
-    )
-    .await?;
+    let new_user = NewUser {
+        id: Uuid::new_v4(),
+        name,
+        username,
+        email,
+        password_hash: hashed_password,
+        dek: WrappedDek {
+            encrypted_dek,
+            dek_salt,
+            dek_kdf_params: kdf_params.to_string(),
+        },
+    };
+    let user = user_repo::create_user(&client, &new_user, &state.stmt_cache).await?;
 
     tracing::info!("✅ User created with ID: {}", user.id);
     Ok(user)
@@ -103,11 +104,17 @@ pub async fn authenticate_user(
     tracing::debug!("🔐 Authenticating user: {}", username);
 
     let client = state.db.get().await?;
-    let user = user_repo::find_by_email(&client, &username, &state.stmt_cache)
+    let user = user_repo::find_by_username(&client, &username, &state.stmt_cache)
         .await?
         .ok_or_else(|| AppError::Authentication("Invalid username or password".to_string()))?;
 
-    if !verify_password(&password, &user.password)? {
+    let password_hash = user.password.clone();
+    let is_valid = state
+        .password_hasher
+        .run(move || verify_password(&password, &password_hash))
+        .await?;
+
+    if !is_valid {
         return Err(AppError::Authentication(
             "Invalid username or password".to_string(),
         ));
@@ -132,14 +139,6 @@ pub async fn change_password(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !verify_password(&old_password, &user.password)? {
-        return Err(AppError::Authentication(
-            "Invalid current password".to_string(),
-        ));
-    }
-
-    let new_hashed_password = hash_password(&new_password)?;
-
     let enc_dek = user
         .encrypted_dek
         .clone()
@@ -149,15 +148,43 @@ pub async fn change_password(
         .clone()
         .ok_or_else(|| AppError::Encryption("Missing DEK salt".to_string()))?;
 
-    let (new_encrypted_dek, new_dek_salt) =
-        dek::change_user_password_dek(&enc_dek, &dek_salt, &old_password, &new_password)?;
+    let password_hash = user.password.clone();
+    let params = state.config.argon2_params.clone();
+    let old_kdf_params = dek::KdfParams::from_stored(user.dek_kdf_params.as_deref())?;
+    let new_kdf_params = dek::KdfParams::from(&params);
+    let (new_hashed_password, new_encrypted_dek, new_dek_salt) = state
+        .password_hasher
+        .run(move || {
+            if !verify_password(&old_password, &password_hash)? {
+                return Err(AppError::Authentication(
+                    "Invalid current password".to_string(),
+                ));
+            }
+
+            let new_hashed_password = hash_password(&new_password, params)?;
+            let (new_encrypted_dek, new_dek_salt) =
+                dek::change_user_password_dek(
+                    &enc_dek,
+                    &dek_salt,
+                    &old_kdf_params,
+                    &old_password,
+                    &new_password,
+                    &new_kdf_params,
+                )?;
+
+            Ok((new_hashed_password, new_encrypted_dek, new_dek_salt))
+        })
+        .await?;
 
     user_repo::update_password(
         &client,
         &user_id,
         new_hashed_password,
-        new_encrypted_dek,
-        new_dek_salt,
+        &WrappedDek {
+            encrypted_dek: new_encrypted_dek,
+            dek_salt: new_dek_salt,
+            dek_kdf_params: new_kdf_params.to_string(),
+        },
         &state.stmt_cache,
     )
     .await?;
@@ -166,3 +193,122 @@ pub async fn change_password(
 
     Ok(())
 }
+
+/// Sets a new password for a user who no longer knows the old one.
+///
+/// The stored DEK is wrapped with a key derived from the old password, so it
+/// cannot be rewrapped without it; a new DEK is generated instead and used for
+/// uploads from now on. Existing files stay readable because each file keeps
+/// its own copy of the DEK it was encrypted with, wrapped with the KEK.
+pub async fn reset_password(
+    state: &AppState,
+    user_id: Uuid,
+    new_password: String,
+) -> Result<()> {
+    tracing::info!("🔑 Resetting password for user: {}", user_id);
+
+    let params = state.config.argon2_params.clone();
+    let kdf_params = dek::KdfParams::from(&params);
+    let (new_hashed_password, (new_encrypted_dek, new_dek_salt)) = state
+        .password_hasher
+        .run(move || {
+            Ok((
+                hash_password(&new_password, params)?,
+                dek::create_user_dek(&new_password, &kdf_params)?,
+            ))
+        })
+        .await?;
+
+    let client = state.db.get().await?;
+    user_repo::update_password(
+        &client,
+        &user_id,
+        new_hashed_password,
+        &WrappedDek {
+            encrypted_dek: new_encrypted_dek,
+            dek_salt: new_dek_salt,
+            dek_kdf_params: kdf_params.to_string(),
+        },
+        &state.stmt_cache,
+    )
+    .await?;
+
+    tracing::info!("✅ Password reset for user: {}", user_id);
+
+    Ok(())
+}
+
+/// Unwraps a user's DEK with their password, for a new session.
+///
+/// A DEK wrapped at a different Argon2 cost than the configured one is
+/// rewrapped at the configured cost while the password is at hand, so raising
+/// `ARGON2_*` migrates users as they log in. A failed migration is logged and
+/// retried at the next login; it never fails the login itself.
+pub async fn unlock_user_dek(
+    state: &AppState,
+    user: &User,
+    password: String,
+) -> Result<zeroize::Zeroizing<String>> {
+    let enc_dek = user
+        .encrypted_dek
+        .clone()
+        .ok_or_else(|| AppError::Encryption("Missing encrypted DEK".to_string()))?;
+    let dek_salt = user
+        .dek_salt
+        .clone()
+        .ok_or_else(|| AppError::Encryption("Missing DEK salt".to_string()))?;
+    let stored_params = dek::KdfParams::from_stored(user.dek_kdf_params.as_deref())?;
+    let configured_params = dek::KdfParams::from(&state.config.argon2_params);
+
+    if stored_params == configured_params {
+        return state
+            .password_hasher
+            .run(move || dek::decrypt_user_dek(&enc_dek, &dek_salt, &stored_params, &password))
+            .await;
+    }
+
+    let (dek_hex, rewrapped) = state
+        .password_hasher
+        .run(move || {
+            let dek_hex = dek::decrypt_user_dek(&enc_dek, &dek_salt, &stored_params, &password)?;
+            let dek_bytes = zeroize::Zeroizing::new(
+                hex::decode(dek_hex.as_str())
+                    .map_err(|e| AppError::Encryption(format!("Invalid DEK encoding: {}", e)))?,
+            );
+            let rewrapped = dek::wrap_dek(&dek_bytes, &password, &configured_params);
+            Ok((dek_hex, rewrapped))
+        })
+        .await?;
+
+    let migrated = match rewrapped {
+        Ok((new_encrypted_dek, new_dek_salt)) => match state.db.get().await {
+            Ok(client) => user_repo::update_dek_wrapping(
+                &client,
+                &user.id,
+                user.dek_salt.as_deref().unwrap_or_default(),
+                &WrappedDek {
+                    encrypted_dek: new_encrypted_dek,
+                    dek_salt: new_dek_salt,
+                    dek_kdf_params: configured_params.to_string(),
+                },
+                &state.stmt_cache,
+            )
+            .await
+            .map(|_| ()),
+            Err(e) => Err(e.into()),
+        },
+        Err(e) => Err(e),
+    };
+
+    match migrated {
+        Ok(()) => tracing::info!(
+            "🔐 Rewrapped DEK of user {} from {} to {}",
+            user.id,
+            stored_params,
+            configured_params
+        ),
+        Err(e) => tracing::warn!("⚠️ Could not rewrap DEK of user {}: {}", user.id, e),
+    }
+
+    Ok(dek_hex)
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::file::ChunkInfo,
+    repositories::{chunk_ref, file as file_repo},
+    services::chunk_store,
+    state::AppState,
+};
+
+/// How many files' chunk lists are read per database round trip.
+const FILES_BATCH_SIZE: i64 = 500;
+
+/// The outcome of an orphaned chunk collection.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ChunkGcReport {
+    /// The chunk files found in the upload directory.
+    pub scanned: usize,
+    /// The chunk files left alone because they were modified too recently.
+    pub too_recent: usize,
+    /// The orphaned chunk files removed.
+    pub reclaimed: usize,
+    /// The bytes freed on disk.
+    pub reclaimed_bytes: u64,
+}
+
+/// An orphan candidate found on disk.
+struct Candidate {
+    path: PathBuf,
+    size: u64,
+}
+
+/// Removes chunk files in the upload directory that nothing references.
+///
+/// A session-named chunk (`{session}_{idx}.encrypted_chunk`) is removed only
+/// if its session has no live `upload:*` key in Redis and no file row,
+/// deleted or not, lists it in its `chunks_metadata`. A content-addressed
+/// chunk is removed only if it has no `chunk_refs` row and no file lists it.
+/// Files modified within `min_age` and names that match neither pattern are
+/// never touched, and the run stops without removing anything if Redis cannot
+/// be scanned or any file's chunk list cannot be decoded.
+pub async fn collect_orphaned_chunks(state: &AppState, min_age: Duration) -> Result<ChunkGcReport> {
+    let mut report = ChunkGcReport::default();
+    let mut sessions: HashMap<String, Vec<Candidate>> = HashMap::new();
+    let mut hashes: HashMap<String, Candidate> = HashMap::new();
+
+    let mut entries = match tokio::fs::read_dir(&state.config.upload_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+
+    let now = SystemTime::now();
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Some(key) = chunk_key(&name) else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        report.scanned += 1;
+
+        let old_enough = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= min_age);
+        if !old_enough {
+            report.too_recent += 1;
+            continue;
+        }
+
+        let candidate = Candidate {
+            path: entry.path(),
+            size: metadata.len(),
+        };
+        match key {
+            ChunkKey::Session(session) => sessions.entry(session).or_default().push(candidate),
+            ChunkKey::Hash(hash) => {
+                hashes.insert(hash, candidate);
+            }
+        }
+    }
+
+    if sessions.is_empty() && hashes.is_empty() {
+        return Ok(report);
+    }
+
+    drop_live_sessions(state, &mut sessions).await?;
+
+    let client = state.db.get().await?;
+    if !hashes.is_empty() {
+        let candidates: Vec<String> = hashes.keys().cloned().collect();
+        for hash in chunk_ref::existing_chunk_refs(&client, &candidates, &state.stmt_cache).await? {
+            hashes.remove(&hash);
+        }
+    }
+
+    let mut last_id = Uuid::nil();
+    while !sessions.is_empty() || !hashes.is_empty() {
+        let batch =
+            file_repo::list_chunk_metadata_after(&client, last_id, FILES_BATCH_SIZE, &state.stmt_cache).await?;
+        let Some((id, _)) = batch.last() else {
+            break;
+        };
+        last_id = *id;
+
+        for (file_id, chunks_metadata) in &batch {
+            let chunks = ChunkInfo::decode_list(chunks_metadata).map_err(|e| {
+                AppError::Internal(format!(
+                    "Chunk list of file {} cannot be decoded, not collecting chunks: {}",
+                    file_id, e
+                ))
+            })?;
+            for chunk in &chunks {
+                match chunk.get_filename().ok().as_deref().and_then(chunk_key) {
+                    Some(ChunkKey::Session(session)) => {
+                        sessions.remove(&session);
+                    }
+                    Some(ChunkKey::Hash(hash)) => {
+                        hashes.remove(&hash);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    for candidate in sessions.into_values().flatten().chain(hashes.into_values()) {
+        match tokio::fs::remove_file(&candidate.path).await {
+            Ok(()) => {
+                report.reclaimed += 1;
+                report.reclaimed_bytes += candidate.size;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("⚠️ Failed to remove orphaned chunk {:?}: {}", candidate.path, e),
+        }
+    }
+
+    tracing::info!(
+        "🧹 Chunk GC: reclaimed {} orphaned chunk file(s), {} bytes ({} scanned, {} too recent)",
+        report.reclaimed,
+        report.reclaimed_bytes,
+        report.scanned,
+        report.too_recent
+    );
+
+    Ok(report)
+}
+
+/// Removes the sessions that still have an `upload:*` key in Redis.
+async fn drop_live_sessions(state: &AppState, sessions: &mut HashMap<String, Vec<Candidate>>) -> Result<()> {
+    if sessions.is_empty() {
+        return Ok(());
+    }
+
+    let mut redis = state.redis.clone();
+    let mut cursor = 0u64;
+    loop {
+        let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg("upload:*")
+            .arg("COUNT")
+            .arg(1000)
+            .query_async(&mut redis)
+            .await?;
+
+        for key in keys {
+            if let Some((_, session)) = key.rsplit_once(':') {
+                sessions.remove(session);
+            }
+        }
+
+        cursor = new_cursor;
+        if cursor == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// What a chunk file name is keyed by.
+enum ChunkKey {
+    /// The upload session that wrote it.
+    Session(String),
+    /// Its content hash.
+    Hash(String),
+}
+
+/// Parses a chunk file name, or `None` if it is not one.
+fn chunk_key(name: &str) -> Option<ChunkKey> {
+    if let Some(hash) = chunk_store::content_hash_of(name) {
+        return Some(ChunkKey::Hash(hash.to_string()));
+    }
+
+    let (session, index) = name.strip_suffix(".encrypted_chunk")?.split_once('_')?;
+    Uuid::parse_str(session).ok()?;
+    index.parse::<usize>().ok()?;
+    Some(ChunkKey::Session(session.to_string()))
+}
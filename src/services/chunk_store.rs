@@ -0,0 +1,143 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::{
+    crypto::aes,
+    error::{AppError, Result},
+    repositories::chunk_ref,
+    state::AppState,
+};
+
+/// The suffix of every chunk file in the upload directory.
+const CHUNK_SUFFIX: &str = ".encrypted_chunk";
+
+/// A chunk stored under its content hash by [`store_chunk`].
+#[derive(Debug)]
+pub struct StoredChunk {
+    /// The hex content hash the chunk file is named after.
+    pub content_hash: String,
+    /// The nonce the chunk was encrypted with, derived from its content hash.
+    pub nonce: [u8; aes::NONCE_SIZE],
+    /// The size of the encrypted chunk.
+    pub size_encrypted: i64,
+    /// Whether an existing chunk file was reused instead of writing a new one.
+    pub reused: bool,
+}
+
+/// Hashes a chunk's plaintext, as stored, keyed with the owner's DEK.
+///
+/// Keying the hash keeps chunk file names from revealing their contents and
+/// means chunks are only ever shared between files of the same user, which
+/// are encrypted under the same DEK.
+pub fn content_hash(dek: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(dek);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Returns the file name of the chunk with the given hex content hash.
+pub fn chunk_filename(content_hash: &str) -> String {
+    format!("{}{}", content_hash, CHUNK_SUFFIX)
+}
+
+/// Returns the content hash a chunk file name is addressed by, or `None` for
+/// a chunk named after its upload session.
+pub fn content_hash_of(filename: &str) -> Option<&str> {
+    let hash = filename.strip_suffix(CHUNK_SUFFIX)?;
+    let is_hash = hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    is_hash.then_some(hash)
+}
+
+fn chunk_path(upload_dir: &Path, content_hash: &str) -> PathBuf {
+    upload_dir.join(chunk_filename(content_hash))
+}
+
+/// Stores a chunk of `user_id` content-addressed, reusing the chunk file if
+/// the user already stored the same data, and takes a reference on it.
+///
+/// The nonce is derived from the content hash, so the same data always
+/// encrypts to the same bytes and concurrent writers of one chunk cannot
+/// corrupt it. The caller must release the reference with [`release_chunk`]
+/// once no upload session or file names the chunk anymore.
+pub async fn store_chunk(state: &AppState, user_id: Uuid, dek: &[u8; 32], data: &[u8]) -> Result<StoredChunk> {
+    let hash = content_hash(dek, data);
+    let mut nonce = [0u8; aes::NONCE_SIZE];
+    nonce.copy_from_slice(&hash[..aes::NONCE_SIZE]);
+    let content_hash = hex::encode(hash);
+
+    let client = state.db.get().await?;
+    chunk_ref::acquire_chunk_ref(&client, &content_hash, user_id, &state.stmt_cache).await?;
+    drop(client);
+
+    let stored = StoredChunk {
+        content_hash,
+        nonce,
+        size_encrypted: (data.len() + aes::TAG_SIZE) as i64,
+        reused: false,
+    };
+
+    let path = chunk_path(&state.config.upload_dir, &stored.content_hash);
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Ok(StoredChunk { reused: true, ..stored });
+    }
+
+    if let Err(e) = write_chunk_file(dek, data, &nonce, &path).await {
+        if let Err(release_error) = release_chunk(state, &stored.content_hash).await {
+            tracing::warn!("⚠️ Failed to release chunk {}: {}", stored.content_hash, release_error);
+        }
+        return Err(e);
+    }
+
+    Ok(stored)
+}
+
+/// Encrypts a chunk and writes it to `path` through a temporary file, so a
+/// reader never sees a partly written chunk.
+async fn write_chunk_file(dek: &[u8; 32], data: &[u8], nonce: &[u8; aes::NONCE_SIZE], path: &Path) -> Result<()> {
+    let chunk_encrypted = aes::encrypt_with_nonce(dek, data, nonce)?;
+
+    let tmp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+    tokio::fs::write(&tmp_path, &chunk_encrypted).await?;
+    if let Err(e) = tokio::fs::rename(&tmp_path, path).await {
+        tokio::fs::remove_file(&tmp_path).await.ok();
+        return Err(AppError::Io(e));
+    }
+
+    Ok(())
+}
+
+/// Drops one reference on a content-addressed chunk and unlinks its file
+/// when that was the last one.
+///
+/// # Returns
+///
+/// The number of bytes freed on disk.
+pub async fn release_chunk(state: &AppState, content_hash: &str) -> Result<u64> {
+    let mut client = state.db.get().await?;
+    let transaction = client.transaction().await?;
+
+    let remaining = chunk_ref::release_chunk_ref(&transaction, content_hash, &state.stmt_cache).await?;
+
+    let mut freed = 0;
+    match remaining {
+        Some(0) => {
+            // Unlink before committing: the deleted row stays locked until
+            // then, so nobody can take a new reference on the file meanwhile.
+            let path = chunk_path(&state.config.upload_dir, content_hash);
+            let size = tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => freed = size,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(AppError::Io(e)),
+            }
+        }
+        Some(_) => {}
+        None => tracing::warn!("⚠️ Released unknown chunk {}", content_hash),
+    }
+
+    transaction.commit().await?;
+
+    Ok(freed)
+}
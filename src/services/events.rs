@@ -0,0 +1,125 @@
+use futures::{Stream, StreamExt};
+use redis::AsyncCommands;
+use sonic_rs::JsonValueTrait;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    repositories::user as user_repo,
+    services::sessions,
+    state::AppState,
+};
+
+/// The quota usage percentages that trigger a `quota_warning` event when an
+/// operation crosses them.
+pub const QUOTA_WARNING_PERCENTS: [i64; 3] = [80, 90, 100];
+
+/// The Redis pub/sub channel carrying the events of one user.
+fn channel(user_id: Uuid) -> String {
+    format!("user_events:{}", user_id)
+}
+
+/// An event on a user's activity stream.
+pub struct UserEvent {
+    /// The SSE event name, e.g. `upload_completed`.
+    pub kind: String,
+    /// The event's JSON payload.
+    pub data: String,
+}
+
+/// Publishes an event to a user's activity stream in a background task.
+///
+/// Events are best effort: they are dropped when nobody is listening, and a
+/// failed publish is only logged.
+pub fn publish(state: &AppState, user_id: Uuid, kind: &'static str, data: sonic_rs::Value) {
+    let mut redis = state.redis.clone();
+    let message = sonic_rs::to_string(&sonic_rs::json!({ "event": kind, "data": data })).unwrap();
+
+    tokio::spawn(async move {
+        if let Err(e) = redis.publish::<_, _, ()>(channel(user_id), message).await {
+            tracing::warn!("⚠️ Failed to publish {} event for user {}: {}", kind, user_id, e);
+        }
+    });
+}
+
+/// Returns the highest of [`QUOTA_WARNING_PERCENTS`] that usage crossed going
+/// from `used_before` to `used_after` bytes of `quota`.
+pub fn crossed_quota_threshold(used_before: i64, used_after: i64, quota: i64) -> Option<i64> {
+    if quota <= 0 {
+        return None;
+    }
+
+    QUOTA_WARNING_PERCENTS
+        .iter()
+        .rev()
+        .find(|&&percent| {
+            // Compared as used * 100 >= quota * percent to avoid rounding.
+            let reached = |used: i64| i128::from(used) * 100 >= i128::from(quota) * i128::from(percent);
+            reached(used_after) && !reached(used_before)
+        })
+        .copied()
+}
+
+/// Publishes a `quota_warning` event if charging `added_bytes` to the user's
+/// quota crossed one of [`QUOTA_WARNING_PERCENTS`]. Runs in the background.
+pub fn check_quota(state: &AppState, user_id: Uuid, added_bytes: i64) {
+    if added_bytes <= 0 {
+        return;
+    }
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let storage = async {
+            let client = state.db.get().await?;
+            user_repo::get_user_storage_info(&client, &user_id, &state.stmt_cache).await
+        };
+        let (quota, used) = match storage.await {
+            Ok(storage) => storage,
+            Err(e) => {
+                tracing::warn!("⚠️ Failed to check quota of user {}: {}", user_id, e);
+                return;
+            }
+        };
+
+        if let Some(percent) = crossed_quota_threshold(used - added_bytes, used, quota) {
+            publish(&state, user_id, "quota_warning", sonic_rs::json!({
+                "threshold_percent": percent,
+                "storage_used_bytes": used,
+                "storage_quota_bytes": quota
+            }));
+        }
+    });
+}
+
+/// Subscribes to a user's activity stream.
+///
+/// Each subscriber holds its own Redis connection, which closes when the
+/// returned stream is dropped. Subscriptions count towards the user's
+/// `MAX_EVENT_STREAMS_PER_USER`, failing with `RateLimitExceeded` above it,
+/// and the stream ends once `session_id` is revoked or expires.
+pub async fn subscribe(
+    state: &AppState,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<impl Stream<Item = UserEvent> + Send + use<>> {
+    let slot = state.event_subscriptions.acquire(user_id)?;
+
+    let client = redis::Client::open(state.config.redis_url.as_str())?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel(user_id)).await?;
+
+    let events = pubsub
+        .into_on_message()
+        .filter_map(|msg| async move {
+            let message: String = msg.get_payload().ok()?;
+            let value: sonic_rs::Value = sonic_rs::from_str(&message).ok()?;
+            let kind = value["event"].as_str()?.to_string();
+            let data = sonic_rs::to_string(&value["data"]).ok()?;
+            Some(UserEvent { kind, data })
+        })
+        .inspect(move |_| {
+            let _ = &slot;
+        });
+
+    Ok(sessions::until_session_ends(state, session_id, events))
+}
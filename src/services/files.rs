@@ -1,20 +1,24 @@
+use std::path::Path;
 use uuid::Uuid;
 use crate::{
     error::Result,
-    models::file::File,
+    models::file::{ChunkInfo, File, FileFilter, FileOrder, PageStart},
     repositories::file as file_repo,
+    services::chunk_store,
     state::AppState,
 };
 
-/// Lists the files for a given user.
+/// Lists the files of a user that match `filter`, sorted by `order`.
 pub async fn list_files(
     state: &AppState,
     user_id: Uuid,
+    filter: &FileFilter,
+    order: FileOrder,
     limit: i64,
-    offset: i64,
+    start: PageStart,
 ) -> Result<Vec<File>> {
     let client = state.db.get().await?;
-    file_repo::list_user_files(&client, user_id, limit, offset, &state.stmt_cache).await
+    file_repo::list_user_files(&client, user_id, filter, order, limit, start, &state.stmt_cache).await
 }
 
 /// Gets a user's storage information.
@@ -26,3 +30,170 @@ pub async fn get_user_storage_info(state: &AppState, user_id: Uuid) -> Result<(i
     let available = storage_quota_bytes - storage_used_bytes;
     Ok((storage_quota_bytes, storage_used_bytes, available))
 }
+
+/// Removes the chunk files of a deleted file from disk in a background task.
+///
+/// The file is first marked `chunks_reclaimed`, which keeps it out of the
+/// trash and from being restored; a file restored before that keeps its
+/// chunks. Only plain file names ending in `.encrypted_chunk` that all share the same
+/// upload-session prefix are removed, so a corrupt or tampered metadata blob
+/// can never reach outside the upload directory or into another file's chunks.
+/// Content-addressed chunks are only released, and unlinked once no other
+/// file or upload references them.
+pub fn spawn_chunk_reclaim(state: AppState, file_id: Uuid, chunks_metadata: Vec<u8>) {
+    tokio::spawn(async move {
+        let claimed = async {
+            let client = state.db.get().await?;
+            file_repo::claim_chunk_reclaim(&client, file_id, &state.stmt_cache).await
+        }
+        .await;
+
+        match claimed {
+            Ok(true) => {
+                reclaim_chunks(&state, file_id, &chunks_metadata).await;
+            }
+            Ok(false) => tracing::debug!("File {} was restored before its chunks were reclaimed", file_id),
+            Err(e) => tracing::error!("❌ Cannot mark chunks of file {} as reclaimed: {}", file_id, e),
+        }
+    });
+}
+
+/// Removes the chunk files of a deleted file from disk, with the same
+/// safeguards as [`spawn_chunk_reclaim`]. Chunks already gone are skipped.
+///
+/// # Returns
+///
+/// The number of bytes freed.
+pub async fn reclaim_chunks(state: &AppState, file_id: Uuid, chunks_metadata: &[u8]) -> u64 {
+    let chunks = match ChunkInfo::decode_list(chunks_metadata) {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            tracing::error!("❌ Cannot reclaim chunks of file {}: {}", file_id, e);
+            return 0;
+        }
+    };
+
+    let mut session_prefix: Option<String> = None;
+    let mut reclaimed_bytes: u64 = 0;
+    let mut removed = 0usize;
+
+    for chunk in &chunks {
+        let Ok(filename) = chunk.get_filename() else {
+            tracing::warn!("⚠️ Skipping chunk {} of file {}: invalid name", chunk.index, file_id);
+            continue;
+        };
+
+        if let Some(hash) = chunk_store::content_hash_of(&filename) {
+            match chunk_store::release_chunk(state, hash).await {
+                Ok(freed) => {
+                    reclaimed_bytes += freed;
+                    removed += usize::from(freed > 0);
+                }
+                Err(e) => tracing::warn!("⚠️ Failed to release chunk {} of file {}: {}", hash, file_id, e),
+            }
+            continue;
+        }
+
+        let is_plain_name = Path::new(&filename).file_name().and_then(|n| n.to_str())
+            == Some(filename.as_str());
+        let prefix = filename.split_once('_').map(|(prefix, _)| prefix.to_string());
+
+        if !is_plain_name || !filename.ends_with(".encrypted_chunk") || prefix.is_none() {
+            tracing::warn!("⚠️ Refusing to remove suspicious chunk path {:?} of file {}", filename, file_id);
+            continue;
+        }
+
+        match &session_prefix {
+            None => session_prefix = prefix,
+            Some(expected) if Some(expected) != prefix.as_ref() => {
+                tracing::warn!("⚠️ Chunk {:?} does not belong to file {}", filename, file_id);
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let chunk_path = state.config.upload_dir.join(&filename);
+        let size = tokio::fs::metadata(&chunk_path).await.map(|m| m.len()).unwrap_or(0);
+
+        match tokio::fs::remove_file(&chunk_path).await {
+            Ok(()) => {
+                reclaimed_bytes += size;
+                removed += 1;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("⚠️ Failed to remove chunk {:?}: {}", chunk_path, e),
+        }
+    }
+
+    tracing::info!(
+        "🧹 Reclaimed {} bytes from {}/{} chunks of file {}",
+        reclaimed_bytes,
+        removed,
+        chunks.len(),
+        file_id
+    );
+
+    reclaimed_bytes
+}
+
+/// The number of deleted files purged per database round trip.
+const PURGE_BATCH_SIZE: i64 = 100;
+
+/// Permanently removes files that have been soft-deleted for longer than
+/// `grace_secs`: their rows are deleted, then their chunk files on disk.
+///
+/// Quota was already released when the file was deleted, so it is not
+/// touched here.
+///
+/// # Returns
+///
+/// The number of files purged.
+pub async fn purge_deleted_files(state: &AppState, grace_secs: i64) -> Result<usize> {
+    purge_files(state, None, grace_secs).await
+}
+
+/// Like [`purge_deleted_files`], for the files of one user only.
+pub async fn purge_user_deleted_files(state: &AppState, user_id: Uuid, grace_secs: i64) -> Result<usize> {
+    purge_files(state, Some(user_id), grace_secs).await
+}
+
+async fn purge_files(state: &AppState, user_id: Option<Uuid>, grace_secs: i64) -> Result<usize> {
+    let client = state.db.get().await?;
+    let mut purged = 0usize;
+
+    loop {
+        let batch =
+            file_repo::list_purgeable_files(&client, user_id, grace_secs, PURGE_BATCH_SIZE, &state.stmt_cache)
+                .await?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let batch_len = batch.len();
+        let mut batch_purged = 0usize;
+        for file_id in batch {
+            // The row goes first, so a file restored concurrently keeps its chunks.
+            let Some(purged) =
+                file_repo::purge_deleted_file(&client, file_id, &state.stmt_cache).await?
+            else {
+                continue;
+            };
+
+            // Chunks reclaimed at deletion are gone already; releasing shared
+            // chunks again would drop other files' references.
+            if let Some(chunks_metadata) = purged.chunks_metadata
+                && !purged.chunks_reclaimed
+            {
+                reclaim_chunks(state, file_id, &chunks_metadata).await;
+            }
+            batch_purged += 1;
+        }
+
+        purged += batch_purged;
+        if batch_len < PURGE_BATCH_SIZE as usize || batch_purged == 0 {
+            break;
+        }
+    }
+
+    Ok(purged)
+}
@@ -1,7 +1,7 @@
 use uuid::Uuid;
 use crate::{
-    error::Result,
-    models::folder::{Folder, FolderWithStats},
+    error::{AppError, Result},
+    models::folder::{Folder, FolderPathEntry, FolderUsage, FolderWithStats},
     repositories::folder as folder_repo,
     state::AppState,
 };
@@ -17,7 +17,7 @@ pub async fn create_folder(
     let folder_id = Uuid::new_v4();
     
     let mut client = state.db.get().await?;
-    folder_repo::create_folder(
+    let folder = folder_repo::create_folder(
         &mut client,
         folder_id,
         user_id,
@@ -26,17 +26,85 @@ pub async fn create_folder(
         description,
         &state.stmt_cache,
     )
-    .await
+    .await?;
+
+    state.folder_cache.invalidate_user(user_id).await;
+
+    Ok(folder)
+}
+
+/// Updates a folder's name and/or description.
+pub async fn update_folder(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+    name: Option<String>,
+    description: Option<String>,
+) -> Result<Option<Folder>> {
+    let client = state.db.get().await?;
+    let folder = folder_repo::update_folder(
+        &client,
+        folder_id,
+        user_id,
+        name,
+        description,
+        &state.stmt_cache,
+    )
+    .await?;
+
+    if folder.is_some() {
+        state.folder_cache.invalidate_user(user_id).await;
+    }
+
+    Ok(folder)
 }
 
-/// Lists the contents of a folder.
+/// Moves a folder under a new parent, or to the root.
+pub async fn move_folder(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+    parent_folder_id: Option<Uuid>,
+) -> Result<Option<Folder>> {
+    let mut client = state.db.get().await?;
+    let folder = folder_repo::move_folder(
+        &mut client,
+        folder_id,
+        user_id,
+        parent_folder_id,
+        &state.stmt_cache,
+    )
+    .await?;
+
+    if folder.is_some() {
+        state.folder_cache.invalidate_user(user_id).await;
+    }
+
+    Ok(folder)
+}
+
+/// Lists the contents of a folder, served from the listing cache when enabled.
 pub async fn list_folder_contents(
     state: &AppState,
     user_id: Uuid,
     folder_id: Option<Uuid>,
 ) -> Result<(Vec<Folder>, Vec<crate::models::file::File>)> {
+    if let Some(listing) = state.folder_cache.get(user_id, folder_id).await {
+        return Ok(listing);
+    }
+
+    let version = state.folder_cache.version(user_id).await;
+
     let mut client = state.db.get().await?;
-    folder_repo::list_folder_contents(&mut client, folder_id, user_id, &state.stmt_cache).await
+    let listing =
+        folder_repo::list_folder_contents(&mut client, folder_id, user_id, &state.stmt_cache).await?;
+
+    state
+        .folder_cache
+        .insert(user_id, folder_id, version, listing.clone())
+        .await;
+
+    Ok(listing)
 }
 
 /// Gets a folder with its statistics.
@@ -49,12 +117,153 @@ pub async fn get_folder_with_stats(
     folder_repo::get_folder_with_stats(&mut client, folder_id, user_id, &state.stmt_cache).await
 }
 
-/// Deletes a folder and its contents.
+/// Gets the recursive storage usage of a folder.
+pub async fn get_folder_usage(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Option<FolderUsage>> {
+    let client = state.db.get().await?;
+    folder_repo::get_folder_usage(&client, folder_id, user_id, &state.stmt_cache).await
+}
+
+/// Gets the path from the root to a folder.
+pub async fn get_folder_path(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Option<Vec<FolderPathEntry>>> {
+    let client = state.db.get().await?;
+    folder_repo::get_folder_path(&client, folder_id, user_id, &state.stmt_cache).await
+}
+
+/// Deletes a folder and its contents, returning the bytes of quota freed.
 pub async fn delete_folder(
     state: &AppState,
     user_id: Uuid,
     folder_id: Uuid,
-) -> Result<()> {
+) -> Result<i64> {
+    let mut client = state.db.get().await?;
+    let (freed_bytes, deleted_files) =
+        folder_repo::delete_folder_recursive(&mut client, folder_id, user_id, &state.stmt_cache)
+            .await?;
+
+    state.folder_cache.invalidate_user(user_id).await;
+
+    if state.config.hard_delete_on_delete {
+        for (file_id, chunks_metadata) in deleted_files {
+            if let Some(chunks_metadata) = chunks_metadata {
+                crate::services::files::spawn_chunk_reclaim(state.clone(), file_id, chunks_metadata);
+            }
+        }
+    }
+
+    Ok(freed_bytes)
+}
+
+/// Restores a folder from the trash with everything deleted along with it.
+pub async fn restore_folder(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Option<folder_repo::RestoredFolder>> {
+    let mut client = state.db.get().await?;
+    let restored = folder_repo::restore_folder(
+        &mut client,
+        folder_id,
+        user_id,
+        state.config.trash_retention_secs(),
+        &state.stmt_cache,
+    )
+    .await?;
+
+    if let Some(restored) = &restored {
+        state.folder_cache.invalidate_user(user_id).await;
+        crate::services::events::check_quota(state, user_id, restored.restored_bytes);
+    }
+
+    Ok(restored)
+}
+
+/// The outcome of permanently deleting folders or emptying the trash.
+#[derive(Debug, Default)]
+pub struct PurgeReport {
+    /// The number of folders removed, counting subfolders only through their top folder.
+    pub folders_purged: usize,
+    /// The number of files removed.
+    pub files_purged: usize,
+    /// The bytes of quota released by files that were not deleted yet.
+    pub quota_released: i64,
+    /// The bytes of chunk files removed from disk for the purged folders.
+    pub reclaimed_bytes: u64,
+}
+
+/// Permanently deletes a folder that is already in the trash, with its
+/// subfolders and files. Rows are removed first, then the chunk files.
+///
+/// # Returns
+///
+/// `None` if the folder does not exist.
+pub async fn purge_folder(
+    state: &AppState,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Option<PurgeReport>> {
     let mut client = state.db.get().await?;
-    folder_repo::delete_folder_recursive(&mut client, folder_id, user_id, &state.stmt_cache).await
+    let Some((quota_released, files)) =
+        folder_repo::purge_deleted_folder(&mut client, folder_id, user_id, &state.stmt_cache)
+            .await?
+    else {
+        return Ok(None);
+    };
+    drop(client);
+
+    state.folder_cache.invalidate_user(user_id).await;
+
+    let mut report = PurgeReport {
+        folders_purged: 1,
+        files_purged: files.len(),
+        quota_released,
+        reclaimed_bytes: 0,
+    };
+    for file in files {
+        // Files whose chunks were reclaimed on delete have nothing left to release.
+        if let Some(chunks_metadata) = file.chunks_metadata
+            && !file.chunks_reclaimed
+        {
+            report.reclaimed_bytes +=
+                crate::services::files::reclaim_chunks(state, file.id, &chunks_metadata).await;
+        }
+    }
+
+    Ok(Some(report))
+}
+
+/// Permanently deletes a user's folders and files that have been in the trash
+/// for longer than `grace_secs`.
+pub async fn empty_trash(state: &AppState, user_id: Uuid, grace_secs: i64) -> Result<PurgeReport> {
+    let client = state.db.get().await?;
+    let folder_ids =
+        folder_repo::list_purgeable_folders(&client, user_id, grace_secs, &state.stmt_cache).await?;
+    drop(client);
+
+    let mut report = PurgeReport::default();
+    for folder_id in folder_ids {
+        // A folder restored or purged concurrently is skipped.
+        match purge_folder(state, user_id, folder_id).await {
+            Ok(Some(purged)) => {
+                report.folders_purged += purged.folders_purged;
+                report.files_purged += purged.files_purged;
+                report.quota_released += purged.quota_released;
+                report.reclaimed_bytes += purged.reclaimed_bytes;
+            }
+            Ok(None) | Err(AppError::Validation(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    report.files_purged +=
+        crate::services::files::purge_user_deleted_files(state, user_id, grace_secs).await?;
+
+    Ok(report)
 }
@@ -0,0 +1,546 @@
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{aes, kek},
+    error::{AppError, Result},
+    models::session::Session,
+    services::sessions,
+    state::AppState,
+};
+
+/// The Redis key holding the progress of the current or last KEK rotation.
+pub const ROTATION_STATUS_KEY: &str = "kek_rotation:status";
+/// The Redis key that ensures only one rewrap task runs at a time.
+const ROTATION_LOCK_KEY: &str = "kek_rotation:lock";
+/// How long the rotation lock survives without being refreshed, so a crashed
+/// task does not block the next rotation forever.
+const ROTATION_LOCK_TTL_SECS: u64 = 300;
+/// How many files or TOTP secrets are rewrapped per database round trip, and
+/// how many session keys each Redis `SCAN` asks for.
+const REWRAP_BATCH_SIZE: i64 = 500;
+/// Replaces a session only if it still holds the JSON it was read with, and
+/// keeps its TTL, so a session revoked or rewritten meanwhile is left alone.
+const REPLACE_SESSION_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL') return 1 else return 0 end";
+
+/// The state of a KEK rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationState {
+    /// File DEKs, TOTP secrets or session DEKs are still being rewrapped.
+    Running,
+    /// Everything was visited; only `skipped` keys remain on an old version.
+    Completed,
+    /// The task stopped on an error; it resumes on the next startup.
+    Failed,
+}
+
+/// The progress of a KEK rotation, stored as JSON under [`ROTATION_STATUS_KEY`].
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RotationStatus {
+    /// The KEK version keys are being rewrapped under.
+    pub target_version: i32,
+    /// The state of the rotation.
+    pub state: RotationState,
+    /// The number of file DEKs rewrapped so far by this run.
+    pub rewrapped: u64,
+    /// The number of TOTP secrets rewrapped so far by this run.
+    #[serde(default)]
+    pub totp_secrets_rewrapped: u64,
+    /// The number of session-held DEKs rewrapped so far by this run.
+    #[serde(default)]
+    pub sessions_rewrapped: u64,
+    /// The number of file DEKs, TOTP secrets and session DEKs that could not
+    /// be unwrapped and were left on their old version.
+    pub skipped: u64,
+    /// The number of files still wrapped under an older KEK version.
+    pub remaining: i64,
+    /// The number of TOTP secrets still sealed under an older KEK version.
+    #[serde(default)]
+    pub remaining_totp_secrets: i64,
+    /// The error that stopped the rotation, if it failed.
+    pub error: Option<String>,
+    /// When this run started.
+    #[schema(value_type = String, format = DateTime)]
+    pub started_at: DateTime<Utc>,
+    /// When the status was last written.
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Reads the status of the current or last KEK rotation, if any.
+pub async fn rotation_status(state: &AppState) -> Result<Option<RotationStatus>> {
+    let mut redis = state.redis.clone();
+    let json: Option<String> = redis.get(ROTATION_STATUS_KEY).await?;
+    Ok(json.and_then(|json| sonic_rs::from_str(&json).ok()))
+}
+
+/// Takes the rotation lock.
+///
+/// # Returns
+///
+/// `true` if the lock was acquired, `false` if another rewrap task holds it.
+async fn acquire_rotation_lock(state: &AppState) -> Result<bool> {
+    let mut redis = state.redis.clone();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(ROTATION_LOCK_KEY)
+        .arg("locked")
+        .arg("NX")
+        .arg("EX")
+        .arg(ROTATION_LOCK_TTL_SECS)
+        .query_async(&mut redis)
+        .await?;
+    Ok(acquired.is_some())
+}
+
+/// Generates a new KEK version and starts rewrapping every file DEK, TOTP
+/// secret and session DEK under it in the background.
+///
+/// # Returns
+///
+/// The new KEK version, or `Validation` if a rotation is still in progress.
+pub async fn start_rotation(state: &AppState) -> Result<i32> {
+    if !acquire_rotation_lock(state).await? {
+        return Err(AppError::Validation(
+            "A KEK rotation is already in progress. Wait for it to finish.".to_string(),
+        ));
+    }
+
+    let version = match kek::rotate_kek(&state.db, state.config.master_key.as_ref(), &state.kek_cache).await {
+        Ok(version) => version,
+        Err(e) => {
+            let mut redis = state.redis.clone();
+            let _: () = redis.del(ROTATION_LOCK_KEY).await?;
+            return Err(e);
+        }
+    };
+
+    tracing::warn!("🔑 KEK rotated to version {}, rewrapping keys", version);
+    spawn_rewrap(state.clone(), version);
+
+    Ok(version)
+}
+
+/// Resumes an interrupted rotation at startup.
+///
+/// If any file DEK or TOTP secret is still wrapped under a version other than
+/// the active one and no other instance holds the rotation lock, the rewrap
+/// task is restarted. Already rewrapped keys are skipped, so resuming is safe.
+pub async fn resume_rotation(state: &AppState) -> Result<()> {
+    let version = kek::active_kek_version(&state.db).await?;
+    if count_remaining(state, version).await? == 0
+        && count_remaining_totp_secrets(state, version).await? == 0
+    {
+        return Ok(());
+    }
+
+    if !acquire_rotation_lock(state).await? {
+        tracing::info!("🔑 A KEK rotation is already running elsewhere, not resuming");
+        return Ok(());
+    }
+
+    tracing::warn!("🔑 Resuming rewrap of keys under KEK version {}", version);
+    spawn_rewrap(state.clone(), version);
+
+    Ok(())
+}
+
+/// Runs [`rewrap_keys`] in a background task and releases the rotation
+/// lock when it ends.
+fn spawn_rewrap(state: AppState, target_version: i32) {
+    tokio::spawn(async move {
+        match rewrap_keys(&state, target_version).await {
+            Ok(rewrapped) => {
+                tracing::info!("✅ Rewrapped {} key(s) under KEK version {}", rewrapped, target_version)
+            }
+            Err(e) => tracing::error!("❌ KEK rotation to version {} failed: {}", target_version, e),
+        }
+
+        let mut redis = state.redis.clone();
+        if let Err(e) = redis.del::<_, ()>(ROTATION_LOCK_KEY).await {
+            tracing::warn!("Failed to release KEK rotation lock: {}", e);
+        }
+    });
+}
+
+/// Counts the files whose DEK is not wrapped under `target_version`.
+async fn count_remaining(state: &AppState, target_version: i32) -> Result<i64> {
+    let client = state.db.get().await?;
+    let row = client
+        .query_one(
+            "SELECT COUNT(*) AS remaining FROM files WHERE dek_version <> $1",
+            &[&target_version],
+        )
+        .await?;
+    Ok(row.get("remaining"))
+}
+
+/// Counts the TOTP secrets not sealed under `target_version`.
+async fn count_remaining_totp_secrets(state: &AppState, target_version: i32) -> Result<i64> {
+    let client = state.db.get().await?;
+    let row = client
+        .query_one(
+            r#"
+        SELECT COUNT(*) AS remaining
+        FROM users
+        WHERE totp_secret_encrypted IS NOT NULL AND totp_kek_version <> $1
+        "#,
+            &[&target_version],
+        )
+        .await?;
+    Ok(row.get("remaining"))
+}
+
+/// Writes the rotation status to Redis and refreshes the rotation lock.
+async fn write_status(state: &AppState, status: &RotationStatus) -> Result<()> {
+    let json = sonic_rs::to_string(status)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize rotation status: {}", e)))?;
+
+    let mut redis = state.redis.clone();
+    let _: () = redis.set(ROTATION_STATUS_KEY, json).await?;
+    let _: () = redis
+        .expire(ROTATION_LOCK_KEY, ROTATION_LOCK_TTL_SECS as i64)
+        .await?;
+    Ok(())
+}
+
+/// Rewraps every file DEK, TOTP secret and session DEK that is not yet under
+/// `target_version`.
+///
+/// Files and then users with a TOTP secret are walked once in id order, in
+/// batches. Each update only applies while the row still carries the version
+/// it was read with, so a concurrent finalize or enrollment, or a second run,
+/// never overwrites a key that was already rewrapped. Sessions are walked
+/// last, in every tenant's Redis namespace. A key that cannot be unwrapped is
+/// logged and skipped rather than stopping the rotation; file DEKs and TOTP
+/// secrets are retried on the next resume. Progress is written to
+/// [`ROTATION_STATUS_KEY`] after every batch.
+///
+/// Rotated-out KEKs are only evicted from the cache once nothing was skipped
+/// and no file DEK or TOTP secret still references them.
+///
+/// # Returns
+///
+/// The number of keys rewrapped.
+pub async fn rewrap_keys(state: &AppState, target_version: i32) -> Result<u64> {
+    let started_at = Utc::now();
+    let mut status = RotationStatus {
+        target_version,
+        state: RotationState::Running,
+        rewrapped: 0,
+        totp_secrets_rewrapped: 0,
+        sessions_rewrapped: 0,
+        skipped: 0,
+        remaining: count_remaining(state, target_version).await?,
+        remaining_totp_secrets: count_remaining_totp_secrets(state, target_version).await?,
+        error: None,
+        started_at,
+        updated_at: started_at,
+    };
+    write_status(state, &status).await?;
+
+    let result = rewrap_all(state, &mut status).await;
+
+    status.updated_at = Utc::now();
+    match &result {
+        Ok(()) => {
+            status.state = RotationState::Completed;
+            status.remaining = count_remaining(state, target_version).await?;
+            status.remaining_totp_secrets = count_remaining_totp_secrets(state, target_version).await?;
+            if status.remaining == 0 && status.remaining_totp_secrets == 0 && status.skipped == 0 {
+                let evicted = state.kek_cache.evict_older_than(target_version).await;
+                tracing::debug!("🔑 Evicted {} rotated-out KEK(s) from the cache", evicted);
+            }
+        }
+        Err(e) => {
+            status.state = RotationState::Failed;
+            status.error = Some(e.to_string());
+        }
+    }
+    write_status(state, &status).await?;
+
+    result.map(|()| status.rewrapped + status.totp_secrets_rewrapped + status.sessions_rewrapped)
+}
+
+/// Rewraps file DEKs, then TOTP secrets, then session DEKs.
+async fn rewrap_all(state: &AppState, status: &mut RotationStatus) -> Result<()> {
+    let new_kek = kek::load_kek_key(
+        &state.db,
+        state.config.master_key.as_ref(),
+        &state.kek_cache,
+        status.target_version,
+    )
+    .await?;
+
+    rewrap_file_batches(state, &new_kek, status).await?;
+    rewrap_totp_batches(state, &new_kek, status).await?;
+    rewrap_session_deks(state, &new_kek, status).await
+}
+
+/// Rewraps batches of file DEKs until every file has been visited.
+async fn rewrap_file_batches(
+    state: &AppState,
+    new_kek: &[u8; aes::KEY_SIZE],
+    status: &mut RotationStatus,
+) -> Result<()> {
+    let target_version = status.target_version;
+    let client = state.db.get().await?;
+    let select_stmt = client
+        .prepare(
+            r#"
+        SELECT id, encrypted_dek, nonce, dek_version
+        FROM files
+        WHERE dek_version <> $1 AND id > $2
+        ORDER BY id
+        LIMIT $3
+        "#,
+        )
+        .await?;
+    let update_stmt = client
+        .prepare(
+            r#"
+        UPDATE files
+        SET encrypted_dek = $2, nonce = $3, dek_version = $4
+        WHERE id = $1 AND dek_version = $5
+        "#,
+        )
+        .await?;
+
+    let mut last_id = Uuid::nil();
+    loop {
+        let rows = client
+            .query(&select_stmt, &[&target_version, &last_id, &REWRAP_BATCH_SIZE])
+            .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        for row in &rows {
+            let file_id: Uuid = row.get("id");
+            let old_version: i32 = row.get("dek_version");
+            let encrypted_dek: Vec<u8> = row.get("encrypted_dek");
+            last_id = file_id;
+
+            let dek = match unwrap_key(state, old_version, &encrypted_dek, row.get("nonce")).await {
+                Ok(dek) => dek,
+                Err(e) => {
+                    tracing::error!(
+                        "❌ Skipping file {}: DEK cannot be unwrapped with KEK version {}: {}",
+                        file_id,
+                        old_version,
+                        e
+                    );
+                    status.skipped += 1;
+                    continue;
+                }
+            };
+            let (new_encrypted_dek, new_nonce) = aes::encrypt(new_kek, &dek)?;
+
+            let updated = client
+                .execute(
+                    &update_stmt,
+                    &[&file_id, &new_encrypted_dek, &new_nonce.to_vec(), &target_version, &old_version],
+                )
+                .await?;
+            status.rewrapped += updated;
+        }
+
+        status.remaining = count_remaining(state, target_version).await?;
+        status.updated_at = Utc::now();
+        write_status(state, status).await?;
+    }
+}
+
+/// Rewraps batches of TOTP secrets until every enrolled user has been visited.
+async fn rewrap_totp_batches(
+    state: &AppState,
+    new_kek: &[u8; aes::KEY_SIZE],
+    status: &mut RotationStatus,
+) -> Result<()> {
+    let target_version = status.target_version;
+    let client = state.db.get().await?;
+    let select_stmt = client
+        .prepare(
+            r#"
+        SELECT id, totp_secret_encrypted, totp_kek_version
+        FROM users
+        WHERE totp_secret_encrypted IS NOT NULL AND totp_kek_version <> $1 AND id > $2
+        ORDER BY id
+        LIMIT $3
+        "#,
+        )
+        .await?;
+    let update_stmt = client
+        .prepare(
+            r#"
+        UPDATE users
+        SET totp_secret_encrypted = $2, totp_kek_version = $3
+        WHERE id = $1 AND totp_kek_version = $4 AND totp_secret_encrypted = $5
+        "#,
+        )
+        .await?;
+
+    let mut last_id = Uuid::nil();
+    loop {
+        let rows = client
+            .query(&select_stmt, &[&target_version, &last_id, &REWRAP_BATCH_SIZE])
+            .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        for row in &rows {
+            let user_id: Uuid = row.get("id");
+            let old_version: i32 = row.get("totp_kek_version");
+            let sealed: Vec<u8> = row.get("totp_secret_encrypted");
+            last_id = user_id;
+
+            let secret = match open_sealed(state, old_version, &sealed).await {
+                Ok(secret) => secret,
+                Err(e) => {
+                    tracing::error!(
+                        "❌ Skipping the TOTP secret of user {}: it cannot be unwrapped with KEK version {}: {}",
+                        user_id,
+                        old_version,
+                        e
+                    );
+                    status.skipped += 1;
+                    continue;
+                }
+            };
+            let resealed = seal(new_kek, &secret)?;
+
+            let updated = client
+                .execute(
+                    &update_stmt,
+                    &[&user_id, &resealed, &target_version, &old_version, &sealed],
+                )
+                .await?;
+            status.totp_secrets_rewrapped += updated;
+        }
+
+        status.remaining_totp_secrets = count_remaining_totp_secrets(state, target_version).await?;
+        status.updated_at = Utc::now();
+        write_status(state, status).await?;
+    }
+}
+
+/// Rewraps the DEK held by every session, in every tenant, that is not yet
+/// under the target version.
+///
+/// Sessions live in Redis, so they are found with `SCAN` and each one is
+/// replaced with [`REPLACE_SESSION_SCRIPT`]. Sessions opened after the
+/// rotation started are already sealed under the target version.
+async fn rewrap_session_deks(
+    state: &AppState,
+    new_kek: &[u8; aes::KEY_SIZE],
+    status: &mut RotationStatus,
+) -> Result<()> {
+    let target_version = status.target_version;
+    let mut redis = state.redis.clone();
+
+    for namespace in state.all_tenants() {
+        let pattern = namespace.redis_key(format_args!("session:*"));
+        let mut cursor = 0u64;
+        loop {
+            let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(REWRAP_BATCH_SIZE)
+                .query_async(&mut redis)
+                .await?;
+
+            for key in keys {
+                let session_json: Option<String> = redis.get(&key).await?;
+                let Some(session_json) = session_json else {
+                    continue;
+                };
+                let Ok(mut session) = sonic_rs::from_str::<Session>(&session_json) else {
+                    continue;
+                };
+                let outdated = matches!(session.dek_kek_version, Some(version) if version != target_version);
+                if session.dek.is_empty() || !outdated {
+                    continue;
+                }
+
+                let dek = match sessions::open_session_dek(state, &session).await {
+                    Ok(dek) => dek,
+                    Err(e) => {
+                        tracing::error!(
+                            "❌ Skipping a session of user {}: its DEK cannot be unwrapped: {}",
+                            session.user_id,
+                            e
+                        );
+                        status.skipped += 1;
+                        continue;
+                    }
+                };
+                session.dek = seal(new_kek, dek.as_slice())?;
+                session.dek_kek_version = Some(target_version);
+                let rewrapped_json = sonic_rs::to_string(&session)
+                    .map_err(|e| AppError::Internal(format!("Session serialization failed: {}", e)))?;
+
+                let replaced: i64 = redis::cmd("EVAL")
+                    .arg(REPLACE_SESSION_SCRIPT)
+                    .arg(1)
+                    .arg(&key)
+                    .arg(&session_json)
+                    .arg(&rewrapped_json)
+                    .query_async(&mut redis)
+                    .await?;
+                status.sessions_rewrapped += replaced as u64;
+            }
+
+            status.updated_at = Utc::now();
+            write_status(state, status).await?;
+
+            cursor = new_cursor;
+            if cursor == 0 {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Encrypts `key` with `kek` in the `ciphertext || nonce` layout used for
+/// TOTP secrets and session DEKs.
+fn seal(kek: &[u8; aes::KEY_SIZE], key: &[u8]) -> Result<Vec<u8>> {
+    let (mut sealed, nonce) = aes::encrypt(kek, key)?;
+    sealed.extend_from_slice(&nonce);
+    Ok(sealed)
+}
+
+/// Unwraps a key stored in the `ciphertext || nonce` layout with the KEK
+/// version it is sealed under.
+async fn open_sealed(state: &AppState, version: i32, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    if sealed.len() <= aes::NONCE_SIZE {
+        return Err(AppError::Encryption("Invalid sealed key".to_string()));
+    }
+    let (ciphertext, nonce) = sealed.split_at(sealed.len() - aes::NONCE_SIZE);
+    unwrap_key(state, version, ciphertext, nonce.to_vec()).await
+}
+
+/// Unwraps a key with the KEK version it is stored under.
+async fn unwrap_key(
+    state: &AppState,
+    version: i32,
+    encrypted_dek: &[u8],
+    nonce: Vec<u8>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let nonce: [u8; 12] = nonce
+        .as_slice()
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid nonce size".to_string()))?;
+    let kek =
+        kek::load_kek_key(&state.db, state.config.master_key.as_ref(), &state.kek_cache, version)
+            .await?;
+    Ok(Zeroizing::new(aes::decrypt(&kek, encrypted_dek, &nonce)?))
+}
@@ -0,0 +1,47 @@
+use futures::future::BoxFuture;
+
+use crate::error::Result;
+
+/// An email to deliver.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails, such as password reset tokens.
+///
+/// Implement it for your mail transport and set [`AppState::mailer`] to use
+/// it.
+///
+/// [`AppState::mailer`]: crate::state::AppState::mailer
+pub trait Mailer: Send + Sync {
+    /// Sends `email`, returning once the transport has accepted it.
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<()>>;
+}
+
+/// Writes emails to the log instead of sending them.
+///
+/// The default mailer. Bodies carry secrets such as reset tokens, so they are
+/// only logged when `log_body` is set, which is the case outside production.
+pub struct LogMailer {
+    pub log_body: bool,
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if self.log_body {
+                tracing::info!("📧 Email to {} ({}):\n{}", email.to, email.subject, email.body);
+            } else {
+                tracing::warn!(
+                    "📧 No mail transport configured, dropping email to {} ({})",
+                    email.to,
+                    email.subject
+                );
+            }
+            Ok(())
+        })
+    }
+}
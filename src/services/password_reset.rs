@@ -0,0 +1,98 @@
+use rand::{rngs::OsRng, RngCore};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    repositories::user as user_repo,
+    services::mailer::Email,
+    state::AppState,
+};
+
+/// The length of a reset token, in bytes.
+const TOKEN_LEN: usize = 32;
+
+/// The Redis key mapping a token's hash to the user it resets. Only the hash
+/// is stored, so a Redis dump does not hand out usable tokens.
+fn token_key(token: &str) -> String {
+    format!("password_reset:{}", hex::encode(Sha256::digest(token.as_bytes())))
+}
+
+/// The Redis key holding the token key of a user's outstanding reset, so a new
+/// request invalidates the previous token.
+fn user_key(user_id: &Uuid) -> String {
+    format!("password_reset_user:{}", user_id)
+}
+
+/// Emails a reset token to the active user registered with `email`, if any.
+///
+/// Unknown emails are silently ignored so callers cannot use the endpoint to
+/// find out which emails are registered.
+pub async fn request_reset(state: &AppState, email: &str) -> Result<()> {
+    let client = state.db.get().await?;
+    let Some(user) = user_repo::find_by_email(&client, email, &state.stmt_cache).await? else {
+        tracing::debug!("Password reset requested for an unknown email");
+        return Ok(());
+    };
+    drop(client);
+
+    if !user.is_active {
+        tracing::warn!("⚠️ Password reset requested for inactive user {}", user.id);
+        return Ok(());
+    }
+
+    let mut token_bytes = [0u8; TOKEN_LEN];
+    OsRng.fill_bytes(&mut token_bytes);
+    let token = hex::encode(token_bytes);
+    let key = token_key(&token);
+    let ttl_secs = state.config.password_reset_ttl_secs;
+
+    let mut redis = state.redis.clone();
+    let previous: Option<String> = redis.get(user_key(&user.id)).await?;
+    if let Some(previous) = previous {
+        let _: () = redis.del(previous).await?;
+    }
+    let _: () = redis.set_ex(&key, user.id.to_string(), ttl_secs).await?;
+    let _: () = redis.set_ex(user_key(&user.id), &key, ttl_secs).await?;
+
+    let email = Email {
+        to: email.to_string(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone asked to reset the password of your account {}.\n\n\
+             Your reset token is:\n\n{}\n\n\
+             It expires in {} minutes. If you did not ask for this, ignore this email.",
+            user.username,
+            token,
+            ttl_secs / 60
+        ),
+    };
+    if let Err(e) = state.mailer.send(email).await {
+        let _: () = redis.del(&[key, user_key(&user.id)]).await?;
+        return Err(e);
+    }
+
+    tracing::info!("📧 Password reset token sent to user {}", user.id);
+
+    Ok(())
+}
+
+/// Uses up a reset token.
+///
+/// # Returns
+///
+/// The user the token was issued to, or `Validation` if the token is unknown,
+/// expired or already used.
+pub async fn consume_token(state: &AppState, token: &str) -> Result<Uuid> {
+    let invalid = || AppError::Validation("Invalid or expired reset token".to_string());
+
+    let mut redis = state.redis.clone();
+    let user_id: Option<String> = redis.get_del(token_key(token.trim())).await?;
+    let user_id = user_id
+        .and_then(|id| Uuid::parse_str(&id).ok())
+        .ok_or_else(invalid)?;
+    let _: () = redis.del(user_key(&user_id)).await?;
+
+    Ok(user_id)
+}
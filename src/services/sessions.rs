@@ -0,0 +1,286 @@
+use futures::{Stream, StreamExt};
+use redis::AsyncCommands;
+use std::time::Duration;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{aes, kek},
+    error::{AppError, Result},
+    models::session::Session,
+    state::AppState,
+};
+
+/// Returns the Redis key holding a session.
+pub fn session_key(state: &AppState, session_id: &Uuid) -> String {
+    state.redis_key(format_args!("session:{}", session_id))
+}
+
+/// Returns the Redis key marking a CSRF token as issued.
+pub fn csrf_key(state: &AppState, csrf_token: &str) -> String {
+    state.redis_key(format_args!("csrf:{}", csrf_token))
+}
+
+/// The number of leading characters of a session ID shown in session listings.
+pub const MASKED_SESSION_ID_LEN: usize = 8;
+
+/// Masks a session ID for display: enough to tell a user's sessions apart
+/// and revoke one, but useless as a credential.
+pub fn mask_session_id(session_id: &Uuid) -> String {
+    session_id.to_string()[..MASKED_SESSION_ID_LEN].to_string()
+}
+
+/// Returns the Redis key of the set indexing all sessions of a user.
+pub fn user_sessions_key(state: &AppState, user_id: &Uuid) -> String {
+    state.redis_key(format_args!("user_sessions:{}", user_id))
+}
+
+/// Encrypts a user's DEK with the active KEK for storage in a session, so a
+/// Redis dump never exposes file keys.
+///
+/// # Arguments
+///
+/// * `dek_hex` - The hex-encoded DEK, as returned by `decrypt_user_dek`.
+///
+/// # Returns
+///
+/// The encrypted DEK in the `ciphertext || nonce` layout of [`Session::dek`]
+/// and the KEK version it was encrypted with.
+pub async fn seal_session_dek(state: &AppState, dek_hex: &str) -> Result<(Vec<u8>, i32)> {
+    let dek = Zeroizing::new(
+        hex::decode(dek_hex).map_err(|e| AppError::Encryption(format!("Invalid DEK encoding: {}", e)))?,
+    );
+
+    let version = kek::active_kek_version(&state.db).await?;
+    let kek =
+        kek::load_kek_key(&state.db, state.config.master_key.as_ref(), &state.kek_cache, version)
+            .await?;
+
+    let (mut sealed, nonce) = aes::encrypt(&kek, &dek)?;
+    sealed.extend_from_slice(&nonce);
+
+    Ok((sealed, version))
+}
+
+/// Decrypts the DEK stored in a session with the KEK it was encrypted with.
+///
+/// Sessions without a DEK (impersonated ones) fail with `Encryption`; sessions
+/// created before the DEK was encrypted at rest fail with `Authentication`, so
+/// the user logs in again and gets a sealed one.
+pub async fn open_session_dek(state: &AppState, session: &Session) -> Result<Zeroizing<[u8; 32]>> {
+    if session.dek.is_empty() {
+        return Err(AppError::Encryption("User DEK not available in session".to_string()));
+    }
+
+    let version = session.dek_kek_version.ok_or_else(|| {
+        AppError::Authentication("Session is outdated, please log in again".to_string())
+    })?;
+
+    if session.dek.len() <= aes::NONCE_SIZE {
+        return Err(AppError::Encryption("Invalid DEK in session".to_string()));
+    }
+    let (ciphertext, nonce) = session.dek.split_at(session.dek.len() - aes::NONCE_SIZE);
+    let nonce: [u8; 12] = nonce
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid DEK in session".to_string()))?;
+
+    let kek =
+        kek::load_kek_key(&state.db, state.config.master_key.as_ref(), &state.kek_cache, version)
+            .await?;
+    let dek = Zeroizing::new(aes::decrypt(&kek, ciphertext, &nonce)?);
+    let dek: [u8; 32] = dek
+        .as_slice()
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid DEK in session".to_string()))?;
+
+    Ok(Zeroizing::new(dek))
+}
+
+/// Stores a new session in Redis, issues its CSRF token, and indexes it under
+/// `user_sessions:{user_id}` so it can later be listed or revoked.
+///
+/// The CSRF token lives exactly as long as the session (`ttl_secs`), so a
+/// logged-in user never hits an expired token mid-session.
+///
+/// # Returns
+///
+/// The new session ID and its CSRF token.
+pub async fn create_session(
+    state: &AppState,
+    mut session: Session,
+    ttl_secs: u64,
+) -> Result<(Uuid, String)> {
+    let session_id = Uuid::new_v4();
+    let csrf_token = crate::crypto::csrf::generate_csrf_token()?;
+    session.csrf_token = Some(csrf_token.clone());
+
+    let session_json = sonic_rs::to_string(&session)
+        .map_err(|e| AppError::Internal(format!("Session serialization failed: {}", e)))?;
+
+    let mut redis = state.redis.clone();
+
+    let _: () = redis
+        .set_ex(session_key(state, &session_id), &session_json, ttl_secs)
+        .await?;
+
+    let _: () = redis
+        .set_ex(csrf_key(state, &csrf_token), "valid", ttl_secs)
+        .await?;
+
+    let index_key = user_sessions_key(state, &session.user_id);
+    let _: () = redis.sadd(&index_key, session_id.to_string()).await?;
+    let _: () = redis
+        .expire(&index_key, state.config.session_duration_days * 86400)
+        .await?;
+
+    tracing::debug!("✅ Session {} stored and indexed for user {}", session_id, session.user_id);
+
+    Ok((session_id, csrf_token))
+}
+
+/// Returns whether a session still exists and has not expired.
+pub async fn is_session_live(state: &AppState, session_id: &Uuid) -> Result<bool> {
+    let mut redis = state.redis.clone();
+    let session_json: Option<String> = redis.get(session_key(state, session_id)).await?;
+
+    Ok(session_json
+        .and_then(|json| sonic_rs::from_str::<Session>(&json).ok())
+        .is_some_and(|session| chrono::Utc::now() <= session.expires_at))
+}
+
+/// Ends `stream` once its session is revoked or expires.
+///
+/// Long-lived streams authenticate only when they open, so the session is
+/// re-checked every `STREAM_SESSION_CHECK_SECS`. A Redis error leaves the
+/// stream open, as the session may well still be valid.
+pub fn until_session_ends<S>(state: &AppState, session_id: Uuid, stream: S) -> impl Stream<Item = S::Item> + Send + use<S>
+where
+    S: Stream + Send + 'static,
+{
+    let state = state.clone();
+    let session_ended = async move {
+        let period = Duration::from_secs(state.config.stream_session_check_secs);
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            match is_session_live(&state, &session_id).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => tracing::warn!("⚠️ Cannot re-check session {}: {}", mask_session_id(&session_id), e),
+            }
+        }
+    };
+
+    stream.take_until(session_ended)
+}
+
+/// Lists the live sessions of a user, newest first.
+///
+/// Index entries whose session has expired or was deleted are dropped from
+/// `user_sessions:{user_id}` along the way.
+pub async fn list_sessions(state: &AppState, user_id: &Uuid) -> Result<Vec<(Uuid, Session)>> {
+    let mut redis = state.redis.clone();
+    let index_key = user_sessions_key(state, user_id);
+
+    let members: Vec<String> = redis.smembers(&index_key).await?;
+    let mut sessions = Vec::with_capacity(members.len());
+
+    for member in members {
+        let session_json: Option<String> = match Uuid::parse_str(&member) {
+            Ok(session_id) => redis.get(session_key(state, &session_id)).await?,
+            Err(_) => None,
+        };
+
+        let session = session_json
+            .and_then(|json| sonic_rs::from_str::<Session>(&json).ok())
+            .filter(|session| session.user_id == *user_id);
+
+        match (Uuid::parse_str(&member), session) {
+            (Ok(session_id), Some(session)) => sessions.push((session_id, session)),
+            _ => {
+                let _: () = redis.srem(&index_key, &member).await?;
+            }
+        }
+    }
+
+    sessions.sort_by_key(|(_, session)| std::cmp::Reverse(session.created_at));
+
+    Ok(sessions)
+}
+
+/// Revokes a single session of a user, along with its CSRF token.
+pub async fn revoke_session(state: &AppState, user_id: &Uuid, session_id: &Uuid) -> Result<()> {
+    let mut redis = state.redis.clone();
+    let key = session_key(state, session_id);
+
+    let session_json: Option<String> = redis.get(&key).await?;
+    if let Some(json) = session_json
+        && let Ok(session) = sonic_rs::from_str::<Session>(&json)
+        && let Some(token) = session.csrf_token
+    {
+        let _: () = redis.del(csrf_key(state, &token)).await?;
+    }
+
+    let _: () = redis.del(&key).await?;
+    let _: () = redis
+        .srem(user_sessions_key(state, user_id), session_id.to_string())
+        .await?;
+
+    Ok(())
+}
+
+/// Revokes every indexed session of a user, optionally sparing one.
+///
+/// Accounts are shared across tenants, so the sessions are revoked in every
+/// tenant's namespace and the untenanted one, not just the one `state`
+/// serves. A deactivated user or a changed password therefore leaves no
+/// session behind in another tenant.
+///
+/// # Returns
+///
+/// The number of sessions revoked.
+pub async fn revoke_all_sessions(
+    state: &AppState,
+    user_id: &Uuid,
+    except: Option<Uuid>,
+) -> Result<usize> {
+    let mut revoked = 0;
+
+    for tenant_state in state.all_tenants() {
+        revoked += revoke_indexed_sessions(&tenant_state, user_id, except).await?;
+    }
+
+    tracing::info!("✅ Revoked {} sessions for user {}", revoked, user_id);
+
+    Ok(revoked)
+}
+
+/// Revokes the sessions of a user indexed in `state`'s namespace, optionally
+/// sparing one.
+async fn revoke_indexed_sessions(
+    state: &AppState,
+    user_id: &Uuid,
+    except: Option<Uuid>,
+) -> Result<usize> {
+    let mut redis = state.redis.clone();
+    let index_key = user_sessions_key(state, user_id);
+
+    let members: Vec<String> = redis.smembers(&index_key).await?;
+    let mut revoked = 0;
+
+    for member in members {
+        let Ok(session_id) = Uuid::parse_str(&member) else {
+            let _: () = redis.srem(&index_key, &member).await?;
+            continue;
+        };
+
+        if Some(session_id) == except {
+            continue;
+        }
+
+        revoke_session(state, user_id, &session_id).await?;
+        revoked += 1;
+    }
+
+    Ok(revoked)
+}
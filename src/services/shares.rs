@@ -0,0 +1,144 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use rand::{RngCore, rngs::OsRng};
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::share::ShareLink,
+    state::AppState,
+};
+
+/// The size of a share token in bytes.
+const SHARE_TOKEN_SIZE: usize = 32;
+
+/// Returns the Redis key holding a share link.
+///
+/// Share links live in the tenant's namespace like sessions, so a link only
+/// works through the tenant it was created in.
+pub fn share_key(state: &AppState, token: &str) -> String {
+    state.redis_key(format_args!("share:{}", token))
+}
+
+/// Returns the Redis key of the set indexing all share links of a user.
+pub fn user_shares_key(state: &AppState, user_id: &Uuid) -> String {
+    state.redis_key(format_args!("user_shares:{}", user_id))
+}
+
+/// Returns whether `token` has the shape of a token from `create_share`, so
+/// arbitrary path segments never reach Redis.
+fn is_well_formed(token: &str) -> bool {
+    general_purpose::URL_SAFE_NO_PAD
+        .decode(token)
+        .is_ok_and(|bytes| bytes.len() == SHARE_TOKEN_SIZE)
+}
+
+/// Creates a share link to `file_id` that expires after `ttl_secs`, and
+/// indexes it under `user_shares:{user_id}` so it can be revoked with the
+/// user's other links.
+///
+/// The caller must have checked that `user_id` owns the file.
+///
+/// # Returns
+///
+/// The URL-safe token and the stored link.
+pub async fn create_share(
+    state: &AppState,
+    user_id: Uuid,
+    file_id: Uuid,
+    ttl_secs: u64,
+) -> Result<(String, ShareLink)> {
+    let mut token = [0u8; SHARE_TOKEN_SIZE];
+    OsRng.fill_bytes(&mut token);
+    let token = general_purpose::URL_SAFE_NO_PAD.encode(token);
+
+    let created_at = Utc::now();
+    let share = ShareLink {
+        file_id,
+        user_id,
+        created_at,
+        expires_at: created_at + chrono::Duration::seconds(ttl_secs as i64),
+    };
+
+    let share_json = sonic_rs::to_string(&share)
+        .map_err(|e| AppError::Internal(format!("Share serialization failed: {}", e)))?;
+
+    let mut redis = state.redis.clone();
+    let _: () = redis.set_ex(share_key(state, &token), &share_json, ttl_secs).await?;
+
+    // The index outlives every link in it; expired members are dropped when
+    // the links are revoked.
+    let index_key = user_shares_key(state, &user_id);
+    let _: () = redis.sadd(&index_key, &token).await?;
+    let _: () = redis.expire(&index_key, state.config.share_link_max_ttl_secs as i64).await?;
+
+    Ok((token, share))
+}
+
+/// Looks up a live share link.
+///
+/// # Returns
+///
+/// `None` if the token is malformed, expired or revoked.
+pub async fn find_share(state: &AppState, token: &str) -> Result<Option<ShareLink>> {
+    if !is_well_formed(token) {
+        return Ok(None);
+    }
+
+    let mut redis = state.redis.clone();
+    let share_json: Option<String> = redis.get(share_key(state, token)).await?;
+
+    share_json
+        .map(|json| {
+            sonic_rs::from_str::<ShareLink>(&json)
+                .map_err(|e| AppError::Internal(format!("Share deserialization failed: {}", e)))
+        })
+        .transpose()
+}
+
+/// Revokes a share link of `file_id` created by `user_id`.
+///
+/// # Returns
+///
+/// Whether a matching link existed.
+pub async fn revoke_share(state: &AppState, user_id: Uuid, file_id: Uuid, token: &str) -> Result<bool> {
+    match find_share(state, token).await? {
+        Some(share) if share.user_id == user_id && share.file_id == file_id => {
+            let mut redis = state.redis.clone();
+            let _: () = redis.del(share_key(state, token)).await?;
+            let _: () = redis.srem(user_shares_key(state, &user_id), token).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Revokes every share link of a user.
+///
+/// Like `revoke_all_sessions`, this covers every tenant's namespace, since
+/// the account and its files are shared by all of them.
+///
+/// # Returns
+///
+/// The number of links revoked.
+pub async fn revoke_all_shares(state: &AppState, user_id: &Uuid) -> Result<usize> {
+    let mut revoked = 0;
+
+    for tenant_state in state.all_tenants() {
+        let mut redis = tenant_state.redis.clone();
+        let index_key = user_shares_key(&tenant_state, user_id);
+
+        let tokens: Vec<String> = redis.smembers(&index_key).await?;
+        for token in &tokens {
+            let deleted: usize = redis.del(share_key(&tenant_state, token)).await?;
+            revoked += deleted;
+        }
+
+        let _: () = redis.del(&index_key).await?;
+    }
+
+    tracing::info!("✅ Revoked {} share links for user {}", revoked, user_id);
+
+    Ok(revoked)
+}
@@ -0,0 +1,253 @@
+use rand::{rngs::OsRng, Rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, TOTP};
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{aes, kek},
+    error::{AppError, Result},
+    models::user::User,
+    repositories::two_factor as two_factor_repo,
+    state::AppState,
+};
+
+/// The issuer shown next to the account in authenticator apps.
+const TOTP_ISSUER: &str = "Rocket";
+/// The length of a TOTP secret, in bytes.
+const TOTP_SECRET_LEN: usize = 20;
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+/// How many steps either side of the current one a code is accepted for, to
+/// absorb clock drift.
+const TOTP_SKEW_STEPS: i64 = 1;
+
+/// How many recovery codes are issued when enrollment is confirmed.
+const RECOVERY_CODE_COUNT: usize = 10;
+/// The characters of a recovery code, without easily confused ones; 16 of
+/// them carry 80 random bits.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const RECOVERY_CODE_LEN: usize = 16;
+
+/// A started enrollment, for the user to add to their authenticator app.
+pub struct Enrollment {
+    /// The base32 secret, for manual entry.
+    pub secret: String,
+    /// The `otpauth://` URI, usually shown as a QR code.
+    pub otpauth_uri: String,
+}
+
+fn build_totp(secret: Vec<u8>, account_name: &str) -> Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| AppError::Internal(format!("Invalid TOTP parameters: {}", e)))
+}
+
+/// Encrypts a TOTP secret with the active KEK.
+///
+/// # Returns
+///
+/// The secret in the `ciphertext || nonce` layout and the KEK version.
+async fn seal_secret(state: &AppState, secret: &[u8]) -> Result<(Vec<u8>, i32)> {
+    let version = kek::active_kek_version(&state.db).await?;
+    let kek = kek::load_kek_key(&state.db, state.config.master_key.as_ref(), &state.kek_cache, version).await?;
+
+    let (mut sealed, nonce) = aes::encrypt(&kek, secret)?;
+    sealed.extend_from_slice(&nonce);
+
+    Ok((sealed, version))
+}
+
+async fn open_secret(state: &AppState, stored: &two_factor_repo::TotpSecret) -> Result<Zeroizing<Vec<u8>>> {
+    if stored.encrypted.len() <= aes::NONCE_SIZE {
+        return Err(AppError::Encryption("Invalid TOTP secret".to_string()));
+    }
+    let (ciphertext, nonce) = stored.encrypted.split_at(stored.encrypted.len() - aes::NONCE_SIZE);
+    let nonce: [u8; aes::NONCE_SIZE] = nonce
+        .try_into()
+        .map_err(|_| AppError::Encryption("Invalid TOTP secret".to_string()))?;
+
+    let kek = kek::load_kek_key(
+        &state.db,
+        state.config.master_key.as_ref(),
+        &state.kek_cache,
+        stored.kek_version,
+    )
+    .await?;
+
+    Ok(Zeroizing::new(aes::decrypt(&kek, ciphertext, &nonce)?))
+}
+
+/// Returns the step `code` is valid for around now, if any.
+fn matching_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let current = (now / TOTP_STEP_SECS) as i64;
+
+    (-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|offset| current + offset)
+        .filter(|step| *step >= 0)
+        .find(|step| {
+            let expected = totp.generate(*step as u64 * TOTP_STEP_SECS);
+            bool::from(expected.as_bytes().ct_eq(code.as_bytes()))
+        })
+}
+
+/// Checks a TOTP code against the user's stored secret and uses up its step.
+///
+/// # Returns
+///
+/// `false` if the code is wrong or its step was already used.
+async fn check_totp_code(
+    state: &AppState,
+    user: &User,
+    stored: &two_factor_repo::TotpSecret,
+    code: &str,
+) -> Result<bool> {
+    let secret = open_secret(state, stored).await?;
+    let totp = build_totp(secret.to_vec(), &user.username)?;
+
+    let Some(step) = matching_step(&totp, code) else {
+        return Ok(false);
+    };
+
+    let client = state.db.get().await?;
+    two_factor_repo::advance_last_step(&client, user.id, step, &state.stmt_cache).await
+}
+
+/// Strips the spaces and dashes users type into codes, and uppercases them.
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn hash_recovery_code(normalized: &str) -> String {
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let code: String = (0..RECOVERY_CODE_LEN)
+        .map(|_| RECOVERY_CODE_ALPHABET[OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+
+    code.as_bytes()
+        .chunks(4)
+        .map(|group| std::str::from_utf8(group).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Generates a TOTP secret for a user and stores it, encrypted with the KEK,
+/// until the user confirms it with [`confirm_enrollment`].
+///
+/// Starting over replaces an unconfirmed secret. Fails with `Validation` if
+/// two-factor authentication is already enabled.
+pub async fn start_enrollment(state: &AppState, user: &User) -> Result<Enrollment> {
+    if user.two_factor_enabled {
+        return Err(AppError::Validation("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let mut secret = Zeroizing::new(vec![0u8; TOTP_SECRET_LEN]);
+    OsRng.fill_bytes(&mut secret);
+    let totp = build_totp(secret.to_vec(), &user.username)?;
+
+    let (sealed, kek_version) = seal_secret(state, &secret).await?;
+    let client = state.db.get().await?;
+    if !two_factor_repo::set_pending_secret(&client, user.id, &sealed, kek_version, &state.stmt_cache).await? {
+        return Err(AppError::Validation("Two-factor authentication is already enabled".to_string()));
+    }
+
+    tracing::info!("🔐 Two-factor enrollment started for user {}", user.id);
+
+    Ok(Enrollment {
+        secret: totp.get_secret_base32(),
+        otpauth_uri: totp.get_url(),
+    })
+}
+
+/// Enables two-factor authentication once the user proves their
+/// authenticator app produces valid codes for the pending secret.
+///
+/// # Returns
+///
+/// The new recovery codes. Only their hashes are stored, so this is the one
+/// time they can be shown.
+pub async fn confirm_enrollment(state: &AppState, user: &User, code: &str) -> Result<Vec<String>> {
+    let client = state.db.get().await?;
+    let stored = two_factor_repo::find_secret(&client, user.id, &state.stmt_cache)
+        .await?
+        .ok_or_else(|| AppError::Validation("Start two-factor enrollment first".to_string()))?;
+    drop(client);
+
+    if stored.enabled {
+        return Err(AppError::Validation("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let code = normalize_code(code);
+    if !is_totp_code(&code) || !check_totp_code(state, user, &stored, &code).await? {
+        return Err(AppError::Validation("Invalid two-factor code".to_string()));
+    }
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+    let hashes: Vec<String> = codes.iter().map(|code| hash_recovery_code(&normalize_code(code))).collect();
+
+    let mut client = state.db.get().await?;
+    two_factor_repo::enable(&mut client, user.id, &hashes, &state.stmt_cache).await?;
+
+    tracing::info!("✅ Two-factor authentication enabled for user {}", user.id);
+
+    Ok(codes)
+}
+
+/// Checks the second factor of a login for a user with two-factor
+/// authentication enabled.
+///
+/// `code` is either a current TOTP code, each usable once, or an unused
+/// recovery code, which is used up.
+pub async fn verify_login_code(state: &AppState, user: &User, code: Option<&str>) -> Result<()> {
+    let code = code.map(normalize_code).filter(|code| !code.is_empty()).ok_or_else(|| {
+        AppError::Authentication("Two-factor authentication code required".to_string())
+    })?;
+    let invalid = || AppError::Authentication("Invalid two-factor authentication code".to_string());
+
+    let client = state.db.get().await?;
+    let valid = if is_totp_code(&code) {
+        let stored = two_factor_repo::find_secret(&client, user.id, &state.stmt_cache)
+            .await?
+            .ok_or_else(invalid)?;
+        drop(client);
+        check_totp_code(state, user, &stored, &code).await?
+    } else {
+        let used = two_factor_repo::consume_recovery_code(
+            &client,
+            user.id,
+            &hash_recovery_code(&code),
+            &state.stmt_cache,
+        )
+        .await?;
+        if used {
+            tracing::warn!("🔐 User {} logged in with a recovery code", user.id);
+        }
+        used
+    };
+
+    if !valid {
+        tracing::warn!("❌ Invalid two-factor code for user {}", user.id);
+        return Err(invalid());
+    }
+
+    Ok(())
+}
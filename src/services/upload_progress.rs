@@ -0,0 +1,98 @@
+use futures::{Stream, StreamExt};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{error::Result, services::sessions, state::AppState};
+
+/// The Redis pub/sub channel carrying the progress of one upload session.
+fn channel(upload_session_id: &str) -> String {
+    format!("upload_progress:{}", upload_session_id)
+}
+
+/// Builds the message sent for every stored chunk.
+pub fn progress_message(
+    upload_session_id: &str,
+    chunks_received: usize,
+    total_chunks: usize,
+    bytes_written: i64,
+) -> String {
+    let progress_percentage = if total_chunks > 0 {
+        chunks_received as f64 * 100.0 / total_chunks as f64
+    } else {
+        0.0
+    };
+
+    sonic_rs::to_string(&sonic_rs::json!({
+        "type": "progress",
+        "upload_session_id": upload_session_id,
+        "chunks_received": chunks_received,
+        "total_chunks": total_chunks,
+        "bytes_written": bytes_written,
+        "progress_percentage": progress_percentage
+    }))
+    .unwrap()
+}
+
+/// Builds the message that ends an upload session: `finalized` with the new
+/// file's ID, or `cancelled` when the session is discarded, whether by the
+/// client, a failed finalize or expiry.
+pub fn closed_message(upload_session_id: &str, kind: &'static str, file_id: Option<uuid::Uuid>) -> String {
+    sonic_rs::to_string(&sonic_rs::json!({
+        "type": kind,
+        "upload_session_id": upload_session_id,
+        "file_id": file_id.map(|id| id.to_string())
+    }))
+    .unwrap()
+}
+
+/// Whether `message` ends its upload session, so listeners can stop.
+pub fn is_closing(message: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Kind<'a> {
+        #[serde(rename = "type", borrow)]
+        kind: &'a str,
+    }
+
+    sonic_rs::from_str::<Kind>(message).is_ok_and(|m| m.kind != "progress")
+}
+
+/// Publishes a progress message for an upload session.
+///
+/// Progress is best effort: a failed publish is logged and never fails the
+/// upload itself.
+pub async fn publish(state: &AppState, upload_session_id: &str, message: String) {
+    let mut redis = state.redis.clone();
+    if let Err(e) = redis.publish::<_, _, ()>(channel(upload_session_id), message).await {
+        tracing::warn!("⚠️ Failed to publish progress of upload {}: {}", upload_session_id, e);
+    }
+}
+
+/// Subscribes to the progress of an upload session.
+///
+/// Pub/sub needs a dedicated connection, so each subscriber opens its own
+/// rather than borrowing the shared connection manager. Subscriptions count
+/// towards the user's `MAX_PROGRESS_SOCKETS_PER_USER`, failing with
+/// `RateLimitExceeded` above it, and the stream ends once `session_id` is
+/// revoked or expires.
+pub async fn subscribe(
+    state: &AppState,
+    user_id: Uuid,
+    session_id: Uuid,
+    upload_session_id: &str,
+) -> Result<impl Stream<Item = String> + Send + 'static> {
+    let slot = state.progress_subscriptions.acquire(user_id)?;
+
+    let client = redis::Client::open(state.config.redis_url.as_str())?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel(upload_session_id)).await?;
+
+    let messages = pubsub
+        .into_on_message()
+        .filter_map(|msg| async move { msg.get_payload::<String>().ok() })
+        .inspect(move |_| {
+            let _ = &slot;
+        });
+
+    Ok(sessions::until_session_ends(state, session_id, messages))
+}
@@ -2,13 +2,19 @@ use deadpool_postgres::{
     Config as DeadpoolConfig, ManagerConfig, Pool, RecyclingMethod, Runtime, PoolConfig, Timeouts,
 };
 use redis::aio::ConnectionManager;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::Semaphore;
 use tokio_postgres::{Config as PgConfig, NoTls};
+use uuid::Uuid;
 
 use crate::config::Config;
 use crate::crypto::kek::KekCache;
+use crate::folder_cache::FolderListingCache;
 use crate::error::{AppError, Result};
+use crate::services::mailer::{LogMailer, Mailer};
 use crate::statement_cache::StatementCache;
 
 /// The number of slots in the upload buffer.
@@ -35,6 +41,12 @@ impl UploadRateLimiter {
         self.semaphore.acquire().await.unwrap()
     }
 
+    /// Acquires a permit that can be moved into a response body, for
+    /// transfers that outlive the handler.
+    pub async fn acquire_owned(&self) -> tokio::sync::OwnedSemaphorePermit {
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+
     /// Returns the number of available permits.
     pub fn available_permits(&self) -> usize {
         self.semaphore.available_permits()
@@ -60,12 +72,129 @@ impl DownloadRateLimiter {
         self.semaphore.acquire().await.unwrap()
     }
 
+    /// Acquires a permit that can be moved into a response body, for
+    /// transfers that outlive the handler.
+    pub async fn acquire_owned(&self) -> tokio::sync::OwnedSemaphorePermit {
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+
     /// Returns the number of available permits.
     pub fn available_permits(&self) -> usize {
         self.semaphore.available_permits()
     }
 }
 
+/// Caps the live subscriptions of one kind (progress sockets, event streams)
+/// that a user may hold on this instance, as each one pins a Redis
+/// connection for as long as it stays open.
+#[derive(Clone)]
+pub struct SubscriptionLimiter {
+    open: Arc<Mutex<HashMap<Uuid, usize>>>,
+    max_per_user: usize,
+}
+
+impl SubscriptionLimiter {
+    /// Creates a new `SubscriptionLimiter`.
+    pub fn new(max_per_user: usize) -> Self {
+        Self {
+            open: Arc::new(Mutex::new(HashMap::new())),
+            max_per_user,
+        }
+    }
+
+    /// Takes one of the user's slots, released when the returned guard is
+    /// dropped.
+    ///
+    /// Fails with `RateLimitExceeded` if the user already holds them all.
+    pub fn acquire(&self, user_id: Uuid) -> Result<SubscriptionGuard> {
+        let mut open = self.open.lock().unwrap();
+        if open.get(&user_id).copied().unwrap_or(0) >= self.max_per_user {
+            return Err(AppError::RateLimitExceeded(format!(
+                "At most {} subscriptions may be open at once",
+                self.max_per_user
+            )));
+        }
+        *open.entry(user_id).or_insert(0) += 1;
+
+        Ok(SubscriptionGuard {
+            open: self.open.clone(),
+            user_id,
+        })
+    }
+
+    /// Returns the number of subscriptions a user holds.
+    pub fn open_for(&self, user_id: Uuid) -> usize {
+        self.open.lock().unwrap().get(&user_id).copied().unwrap_or(0)
+    }
+}
+
+/// A slot taken from a [`SubscriptionLimiter`].
+pub struct SubscriptionGuard {
+    open: Arc<Mutex<HashMap<Uuid, usize>>>,
+    user_id: Uuid,
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().unwrap();
+        if let Some(count) = open.get_mut(&self.user_id) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+/// Bounds how many Argon2 computations run at once.
+///
+/// Each hash allocates its full memory cost up front, so without a cap a burst
+/// of logins can exhaust memory. Work runs on the blocking pool so it never
+/// stalls the async workers.
+#[derive(Clone)]
+pub struct PasswordHashLimiter {
+    semaphore: Arc<Semaphore>,
+    queue_timeout: Duration,
+}
+
+impl PasswordHashLimiter {
+    /// Creates a new `PasswordHashLimiter`.
+    pub fn new(max_concurrent: usize, queue_timeout: Duration) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            queue_timeout,
+        }
+    }
+
+    /// Runs a password-hashing closure on the blocking pool once a slot is free.
+    ///
+    /// The slot is held by the blocking task itself, so it is only released
+    /// once the hash finishes, even if the caller is dropped first.
+    ///
+    /// Returns `AppError::RateLimitExceeded` if no slot frees up within the
+    /// queue timeout.
+    pub async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = tokio::time::timeout(self.queue_timeout, self.semaphore.clone().acquire_owned())
+            .await
+            .map_err(|_| {
+                tracing::warn!("⚠️ Password hashing queue is full");
+                AppError::RateLimitExceeded("Server is busy, please try again shortly".to_string())
+            })?
+            .map_err(|e| AppError::Internal(format!("Password hash semaphore closed: {}", e)))?;
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("Password hashing task failed: {}", e)))?
+    }
+}
+
 /// The application's state.
 #[derive(Clone)]
 pub struct AppState {
@@ -83,6 +212,18 @@ pub struct AppState {
     pub download_limiter: DownloadRateLimiter,
     // The prepared statement cache.
     pub stmt_cache: StatementCache,
+    /// The folder listing cache.
+    pub folder_cache: FolderListingCache,
+    /// The Argon2 concurrency limiter.
+    pub password_hasher: PasswordHashLimiter,
+    /// The per-user cap on upload progress sockets.
+    pub progress_subscriptions: SubscriptionLimiter,
+    /// The per-user cap on account event streams.
+    pub event_subscriptions: SubscriptionLimiter,
+    /// Delivers emails; logs them unless replaced with a real transport.
+    pub mailer: Arc<dyn Mailer>,
+    /// The tenant this state serves, set by [`AppState::for_tenant`].
+    pub tenant: Option<Arc<str>>,
 }
 
 impl AppState {
@@ -104,7 +245,11 @@ impl AppState {
 
         deadpool_cfg.pool = Some(PoolConfig {
             max_size: 48,
-            timeouts: Timeouts::new(),
+            timeouts: Timeouts {
+                wait: Some(Duration::from_millis(config.db_acquire_timeout_ms)),
+                create: Some(Duration::from_secs(5)),
+                recycle: Some(Duration::from_secs(5)),
+            },
             ..Default::default()
         });
         deadpool_cfg.manager = Some(ManagerConfig {
@@ -122,18 +267,59 @@ impl AppState {
 
         tracing::info!("✅ Redis Connection Manager initialized (pooled)");
 
-        let kek_cache = KekCache::new();
-        tracing::info!("✅ KEK Cache initialized");
+        let kek_cache = KekCache::new(
+            config.kek_cache_capacity,
+            Duration::from_secs(config.kek_cache_ttl_secs),
+        );
+        tracing::info!(
+            "✅ KEK Cache initialized (capacity {}, ttl {}s)",
+            config.kek_cache_capacity,
+            config.kek_cache_ttl_secs
+        );
 
         let stmt_cache = StatementCache::new();
         tracing::info!("✅ Statement Cache initialized");
 
+        let folder_cache = FolderListingCache::new(
+            config.folder_cache_capacity,
+            Duration::from_secs(config.folder_cache_ttl_secs),
+        );
+        if folder_cache.is_enabled() {
+            tracing::info!(
+                "✅ Folder listing cache initialized (capacity {}, ttl {}s)",
+                config.folder_cache_capacity,
+                config.folder_cache_ttl_secs
+            );
+        }
+
         let upload_limiter = UploadRateLimiter::new(UPLOAD_BUFFER_SLOTS);
         tracing::info!("✅ Upload RateLimiter initialized (max 2GB)");
 
         let download_limiter = DownloadRateLimiter::new(DOWNLOAD_BUFFER_SLOTS);
         tracing::info!("✅ Download RateLimiter initialized (max 2GB)");
 
+        let password_hasher = PasswordHashLimiter::new(
+            config.max_concurrent_password_hashes,
+            Duration::from_millis(config.password_hash_queue_timeout_ms),
+        );
+        tracing::info!(
+            "✅ Password hash limiter initialized (max {} concurrent)",
+            config.max_concurrent_password_hashes
+        );
+        tracing::info!(
+            "🔐 Argon2id password hashing: {} MiB memory, {} iterations, {} lanes",
+            config.argon2_params.m_cost() / 1024,
+            config.argon2_params.t_cost(),
+            config.argon2_params.p_cost()
+        );
+
+        let progress_subscriptions = SubscriptionLimiter::new(config.max_progress_sockets_per_user);
+        let event_subscriptions = SubscriptionLimiter::new(config.max_event_streams_per_user);
+
+        let mailer: Arc<dyn Mailer> = Arc::new(LogMailer {
+            log_body: !config.is_production(),
+        });
+
         Ok(AppState {
             db,
             redis,
@@ -142,6 +328,51 @@ impl AppState {
             upload_limiter,
             download_limiter,
             stmt_cache,
+            folder_cache,
+            password_hasher,
+            progress_subscriptions,
+            event_subscriptions,
+            mailer,
+            tenant: None,
         })
     }
+
+    /// Returns this state scoped to one tenant: session, CSRF and rate-limit
+    /// keys move under `tenant:{tenant}:` in Redis, and static files are
+    /// served from `PUBLIC_DIR/{tenant}`. Everything else stays shared.
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        let mut state = self.clone();
+        state.tenant = Some(Arc::from(tenant));
+        state
+    }
+
+    /// Returns this state once for the untenanted Redis namespace and once
+    /// for each tenant in `TENANTS`, whichever tenant it serves itself.
+    ///
+    /// Accounts are shared across tenants, so anything that must reach all
+    /// of a user's sessions, such as revoking them, walks every namespace.
+    pub fn all_tenants(&self) -> Vec<Self> {
+        let mut untenanted = self.clone();
+        untenanted.tenant = None;
+
+        let tenants = self.config.tenants.iter().map(|tenant| untenanted.for_tenant(tenant));
+        std::iter::once(untenanted.clone()).chain(tenants).collect()
+    }
+
+    /// Returns the directory static files are served from: `PUBLIC_DIR`, or
+    /// its `{tenant}` subdirectory for a tenant.
+    pub fn public_dir(&self) -> PathBuf {
+        match &self.tenant {
+            Some(tenant) => self.config.public_dir.join(&**tenant),
+            None => self.config.public_dir.clone(),
+        }
+    }
+
+    /// Returns `key` under this state's tenant prefix, if it has a tenant.
+    pub fn redis_key(&self, key: std::fmt::Arguments<'_>) -> String {
+        match &self.tenant {
+            Some(tenant) => format!("tenant:{}:{}", tenant, key),
+            None => key.to_string(),
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use axum::http::{header, HeaderMap};
+use std::str::FromStr;
+
+use crate::config::Config;
+
+/// Where the tenant of a request is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TenantMode {
+    /// A single tenant; Redis keys and directories are not partitioned.
+    #[default]
+    Off,
+    /// The `TENANT_HEADER` request header, set by a trusted proxy.
+    Header,
+    /// The first label of the `Host` header under `TENANT_BASE_DOMAIN`.
+    Subdomain,
+}
+
+impl FromStr for TenantMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "header" => Ok(Self::Header),
+            "subdomain" => Ok(Self::Subdomain),
+            other => anyhow::bail!(
+                "unknown tenant mode '{}' (expected off, header or subdomain)",
+                other
+            ),
+        }
+    }
+}
+
+/// Returns whether `id` can name a tenant: 1 to 63 lowercase ASCII letters,
+/// digits or hyphens, so it is safe in Redis keys, paths and DNS labels.
+pub fn is_valid_tenant_id(id: &str) -> bool {
+    (1..=63).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Resolves the tenant a request is addressed to.
+///
+/// # Returns
+///
+/// The tenant ID, or `None` if tenants are off, the request names no tenant,
+/// or it names one that is not listed in `TENANTS`.
+pub fn resolve_tenant(headers: &HeaderMap, config: &Config) -> Option<String> {
+    let tenant = match config.tenant_mode {
+        TenantMode::Off => return None,
+        TenantMode::Header => headers
+            .get(&config.tenant_header)?
+            .to_str()
+            .ok()?
+            .trim()
+            .to_ascii_lowercase(),
+        TenantMode::Subdomain => {
+            let host = headers.get(header::HOST)?.to_str().ok()?;
+            let host = host.rsplit_once(':').map_or(host, |(host, _port)| host);
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            let label = host.strip_suffix(config.tenant_base_domain.as_str())?.strip_suffix('.')?;
+            if label.contains('.') {
+                return None;
+            }
+            label.to_string()
+        }
+    };
+
+    config.tenants.contains(&tenant).then_some(tenant)
+}
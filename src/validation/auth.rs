@@ -1,5 +1,14 @@
 use crate::error::{AppError, Result};
 
+/// Normalizes a username for storage and lookup.
+///
+/// Usernames are matched case-insensitively, so "Alice" and " alice " name
+/// the same account. Only the username is normalized; the display `name` is
+/// kept as entered.
+pub fn normalize_username(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
 /// Validates a username.
 ///
 /// # Arguments
@@ -31,16 +40,71 @@ pub fn validate_username(username: &str) -> Result<()> {
     Ok(())
 }
 
+/// The longest email address accepted, as limited by SMTP.
+const MAX_EMAIL_LENGTH: usize = 254;
+
+/// The longest local part (before the `@`) of an email address.
+const MAX_EMAIL_LOCAL_LENGTH: usize = 64;
+
+/// Normalizes and validates an email address.
+///
+/// Only the shape is checked: a local part and a dotted domain around a
+/// single `@`, without whitespace. Addresses are compared case-insensitively,
+/// so they are stored lowercased.
+///
+/// # Returns
+///
+/// The trimmed, lowercased address.
+pub fn normalize_email(email: &str) -> Result<String> {
+    let invalid = || AppError::Validation("Invalid email address".to_string());
+
+    let email = email.trim().to_lowercase();
+    if email.len() > MAX_EMAIL_LENGTH || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(invalid());
+    }
+
+    let (local, domain) = email.split_once('@').ok_or_else(invalid)?;
+    let valid_local = !local.is_empty() && local.len() <= MAX_EMAIL_LOCAL_LENGTH;
+    let valid_domain = domain.contains('.')
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-'));
+    if !valid_local || !valid_domain {
+        return Err(invalid());
+    }
+
+    Ok(email)
+}
+
+/// The character classes a password must contain, on top of the length limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    /// Require at least one uppercase letter.
+    pub require_uppercase: bool,
+    /// Require at least one lowercase letter.
+    pub require_lowercase: bool,
+    /// Require at least one digit.
+    pub require_digit: bool,
+    /// Require at least one character that is not a letter or digit.
+    pub require_symbol: bool,
+}
+
+/// A character class a password may be required to contain: whether the
+/// policy requires it, how to recognize it, and its name in error messages.
+type CharacterRule = (bool, fn(char) -> bool, &'static str);
+
 /// Validates a password.
 ///
 /// # Arguments
 ///
 /// * `password` - The password to validate.
+/// * `policy` - The character classes the password must contain.
 ///
 /// # Returns
 ///
-/// A `Result<()>` indicating whether the password is valid.
-pub fn validate_password(password: &str) -> Result<()> {
+/// A `Result<()>` indicating whether the password is valid, naming the first
+/// requirement it fails.
+pub fn validate_password(password: &str, policy: &PasswordPolicy) -> Result<()> {
     if password.len() < 8 {
         return Err(AppError::Validation(
             "Password must be at least 8 characters long".to_string(),
@@ -53,5 +117,21 @@ pub fn validate_password(password: &str) -> Result<()> {
         ));
     }
 
+    let requirements: [CharacterRule; 4] = [
+        (policy.require_uppercase, char::is_uppercase, "an uppercase letter"),
+        (policy.require_lowercase, char::is_lowercase, "a lowercase letter"),
+        (policy.require_digit, |c| c.is_ascii_digit(), "a digit"),
+        (policy.require_symbol, |c| !c.is_alphanumeric(), "a symbol"),
+    ];
+
+    for (required, matches, name) in requirements {
+        if required && !password.chars().any(matches) {
+            return Err(AppError::Validation(format!(
+                "Password must contain at least {}",
+                name
+            )));
+        }
+    }
+
     Ok(())
 }
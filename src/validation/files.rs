@@ -0,0 +1,109 @@
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::{AppError, Result};
+
+/// Returns whether a character is a bidi control or zero-width character.
+///
+/// These are invisible in most UIs and can make a name render differently
+/// from what it is, e.g. `invoice\u{202E}fdp.exe` showing as `invoiceexe.pdf`.
+fn is_invisible_format_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Normalizes and validates a filename supplied by a client.
+///
+/// # Arguments
+///
+/// * `filename` - The filename to normalize.
+/// * `max_length` - The maximum length in characters after normalization.
+///
+/// # Returns
+///
+/// A `Result` containing the NFC-normalized filename with bidi-control and
+/// zero-width characters removed.
+pub fn normalize_filename(filename: &str, max_length: usize) -> Result<String> {
+    let normalized: String = filename
+        .nfc()
+        .filter(|c| !is_invisible_format_char(*c))
+        .collect();
+    let normalized = normalized.trim().to_string();
+
+    if normalized.is_empty() || normalized == "." || normalized == ".." {
+        return Err(AppError::Validation("Filename cannot be empty".to_string()));
+    }
+
+    if normalized.chars().any(|c| c.is_control()) {
+        return Err(AppError::Validation(
+            "Filename cannot contain control characters".to_string(),
+        ));
+    }
+
+    if normalized.chars().count() > max_length {
+        return Err(AppError::Validation(format!(
+            "Filename must be at most {} characters",
+            max_length
+        )));
+    }
+
+    Ok(normalized)
+}
+
+/// The MIME top-level types a client may declare for an upload.
+const ALLOWED_MIME_TYPES: &[&str] = &["application", "audio", "font", "image", "model", "text", "video"];
+
+/// The longest MIME type accepted, parameters included.
+const MAX_MIME_TYPE_LENGTH: usize = 127;
+
+/// Returns whether `s` is a non-empty RFC 6838 restricted name.
+fn is_mime_token(s: &str) -> bool {
+    !s.is_empty()
+        && s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+}
+
+/// Normalizes and validates a MIME type declared by a client.
+///
+/// The type must be `type/subtype` with a top-level type from
+/// [`ALLOWED_MIME_TYPES`]; the only parameter kept is `charset`.
+///
+/// # Returns
+///
+/// The lowercased MIME type, e.g. `text/plain; charset=utf-8`.
+pub fn normalize_mime_type(mime_type: &str) -> Result<String> {
+    let invalid = || AppError::Validation(format!("Invalid MIME type '{}'", mime_type.trim()));
+
+    let lowered = mime_type.trim().to_ascii_lowercase();
+    if lowered.len() > MAX_MIME_TYPE_LENGTH {
+        return Err(invalid());
+    }
+
+    let mut parts = lowered.split(';').map(str::trim);
+    let essence = parts.next().unwrap_or_default();
+    let (top_level, subtype) = essence.split_once('/').ok_or_else(invalid)?;
+
+    if !ALLOWED_MIME_TYPES.contains(&top_level) || !is_mime_token(subtype) {
+        return Err(invalid());
+    }
+
+    let mut normalized = format!("{}/{}", top_level, subtype);
+    for parameter in parts {
+        match parameter.split_once('=') {
+            Some(("charset", charset)) if is_mime_token(charset) => {
+                normalized.push_str("; charset=");
+                normalized.push_str(charset);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(normalized)
+}
@@ -0,0 +1,29 @@
+use rocket::handlers::auth::{LoginRequest, RegisterRequest};
+
+#[test]
+fn login_payload_debug_redacts_the_password_and_code() {
+    let payload = LoginRequest {
+        username: "alice".to_string(),
+        password: "SecurePass123!@#".to_string(),
+        totp_code: Some("123456".to_string()),
+    };
+
+    let debug = format!("{:?}", payload);
+    assert!(debug.contains("alice"));
+    assert!(!debug.contains("SecurePass123!@#"));
+    assert!(!debug.contains("123456"));
+}
+
+#[test]
+fn register_payload_debug_redacts_the_password() {
+    let payload = RegisterRequest {
+        name: "Alice".to_string(),
+        username: "alice".to_string(),
+        password: "SecurePass123!@#".to_string(),
+        email: None,
+    };
+
+    let debug = format!("{:?}", payload);
+    assert!(debug.contains("alice"));
+    assert!(!debug.contains("SecurePass123!@#"));
+}
@@ -0,0 +1,81 @@
+use futures::{stream, StreamExt};
+use std::{path::Path, sync::Arc};
+use tempfile::TempDir;
+use rocket::{
+    crypto::aes,
+    handlers::chunk_buffer::{memory_ceiling, ChunkBufferPool},
+    models::file::ChunkInfo,
+};
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const CHUNKS: usize = 32;
+const SLOTS: usize = 4;
+
+/// Writes an encrypted synthetic file of `CHUNKS` chunks to `dir` and
+/// returns its chunk list.
+fn write_synthetic_file(dir: &Path, dek: &[u8; 32]) -> Vec<ChunkInfo> {
+    let prefix = uuid::Uuid::new_v4();
+
+    (0..CHUNKS)
+        .map(|index| {
+            let plaintext = vec![index as u8; CHUNK_SIZE];
+            let (ciphertext, nonce) = aes::encrypt(dek, &plaintext).unwrap();
+            let filename = format!("{}_{}.encrypted_chunk", prefix, index);
+            std::fs::write(dir.join(&filename), ciphertext).unwrap();
+            ChunkInfo::new(index, nonce, filename, CHUNK_SIZE as i64, false, CHUNK_SIZE as i64)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_pooled_download_stays_under_memory_ceiling() {
+    let dek = *aes::generate_key().as_bytes();
+    let dir = TempDir::new().unwrap();
+    let chunks = write_synthetic_file(dir.path(), &dek);
+    let pool = Arc::new(ChunkBufferPool::new(dir.path().to_path_buf(), SLOTS));
+
+    let mut decrypted = stream::iter(chunks.iter())
+        .map(|chunk| pool.read_decrypted_chunk(&dek, chunk, chunk.index % 2 == 0))
+        .buffered(SLOTS - 1);
+
+    let mut index = 0;
+    while let Some(chunk) = decrypted.next().await {
+        let chunk = chunk.unwrap();
+        assert_eq!(chunk.len(), CHUNK_SIZE);
+        assert!(chunk.iter().all(|&b| b == index as u8));
+        index += 1;
+    }
+    assert_eq!(index, CHUNKS);
+
+    // Each chunk's buffer comes back once the consumer drops it, so the
+    // stream never needs more than one buffer per slot.
+    assert!(pool.allocated() <= SLOTS, "allocated {} buffers", pool.allocated());
+    let held = pool.allocated() as u64 * (CHUNK_SIZE + aes::TAG_SIZE) as u64;
+    assert!(held <= memory_ceiling(SLOTS, CHUNK_SIZE as u64));
+
+    // Chunks that are kept alive hold on to their buffers, so every further
+    // chunk needs a new one.
+    let retained = Arc::new(ChunkBufferPool::new(dir.path().to_path_buf(), SLOTS));
+    let mut kept = Vec::new();
+    for chunk in &chunks {
+        kept.push(retained.read_decrypted_chunk(&dek, chunk, false).await.unwrap());
+    }
+    assert_eq!(retained.allocated(), CHUNKS);
+    drop(kept);
+}
+
+#[tokio::test]
+async fn test_pooled_read_rejects_a_tampered_chunk() {
+    let dek = *aes::generate_key().as_bytes();
+    let dir = TempDir::new().unwrap();
+    let chunks = write_synthetic_file(dir.path(), &dek);
+    let path = dir.path().join(chunks[0].get_filename().unwrap());
+    let mut ciphertext = std::fs::read(&path).unwrap();
+    ciphertext[0] ^= 1;
+    std::fs::write(&path, ciphertext).unwrap();
+
+    let pool = Arc::new(ChunkBufferPool::new(dir.path().to_path_buf(), 1));
+    assert!(pool.read_decrypted_chunk(&dek, &chunks[0], false).await.is_err());
+    assert!(pool.read_decrypted_chunk(&dek, &chunks[1], false).await.is_ok());
+    assert_eq!(pool.allocated(), 1);
+}
@@ -0,0 +1,104 @@
+use axum::{
+    body::Body,
+    extract::{FromRequest, Multipart},
+    http::Request,
+};
+use std::time::Duration;
+
+use rocket::{
+    error::AppError,
+    handlers::chunk_form::{read_chunk_form, ChunkFormLimits},
+};
+
+const BOUNDARY: &str = "chunk-form-test-boundary";
+
+fn limits() -> ChunkFormLimits {
+    ChunkFormLimits {
+        max_fields: 4,
+        field_timeout: Duration::from_secs(5),
+        read_timeout: Duration::from_secs(5),
+        max_chunk_bytes: 16,
+    }
+}
+
+/// Builds a multipart extractor from `(name, value)` fields.
+async fn multipart(fields: &[(&str, &[u8])]) -> Multipart {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n", BOUNDARY, name).as_bytes(),
+        );
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+    let request = Request::builder()
+        .method("POST")
+        .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+        .body(Body::from(body))
+        .unwrap();
+
+    Multipart::from_request(request, &()).await.unwrap()
+}
+
+const SESSION_ID: &[u8] = b"6f1c1b8e-2d3a-4c55-9a51-0f4f3c1e7d20";
+
+#[tokio::test]
+async fn parses_a_well_formed_chunk() {
+    let mut form = multipart(&[
+        ("upload_session_id", SESSION_ID),
+        ("chunk_index", b"3"),
+        ("chunk", b"hello"),
+    ])
+    .await;
+
+    let chunk = read_chunk_form(&mut form, &limits()).await.unwrap();
+
+    assert_eq!(chunk.session_id.to_string().as_bytes(), SESSION_ID);
+    assert_eq!(chunk.chunk_index, 3);
+    assert_eq!(chunk.data, b"hello");
+}
+
+#[tokio::test]
+async fn rejects_oversized_chunk() {
+    let mut form = multipart(&[
+        ("upload_session_id", SESSION_ID),
+        ("chunk_index", b"0"),
+        ("chunk", &[0u8; 17]),
+    ])
+    .await;
+
+    let err = read_chunk_form(&mut form, &limits()).await.unwrap_err();
+    assert!(matches!(err, AppError::Validation(_)), "{err}");
+}
+
+#[tokio::test]
+async fn rejects_duplicate_and_missing_fields() {
+    let mut form = multipart(&[
+        ("upload_session_id", SESSION_ID),
+        ("chunk_index", b"0"),
+        ("chunk_index", b"1"),
+        ("chunk", b"data"),
+    ])
+    .await;
+    assert!(read_chunk_form(&mut form, &limits()).await.is_err());
+
+    let mut form = multipart(&[("upload_session_id", SESSION_ID), ("chunk", b"data")]).await;
+    assert!(read_chunk_form(&mut form, &limits()).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_too_many_fields() {
+    let mut form = multipart(&[
+        ("upload_session_id", SESSION_ID),
+        ("chunk_index", b"0"),
+        ("extra_a", b"x"),
+        ("extra_b", b"x"),
+        ("chunk", b"data"),
+    ])
+    .await;
+
+    let err = read_chunk_form(&mut form, &limits()).await.unwrap_err();
+    assert!(matches!(err, AppError::Multipart(_)), "{err}");
+}
@@ -0,0 +1,42 @@
+use rocket::models::file::ChunkInfo;
+
+#[test]
+fn test_chunk_list_round_trips() {
+    let chunks = vec![
+        ChunkInfo::new(0, [1; 12], "a_0.encrypted_chunk".to_string(), 1024, true, 1024),
+        ChunkInfo::new(1, [2; 12], "a_1.encrypted_chunk".to_string(), 1024, false, 17),
+    ];
+
+    let decoded = ChunkInfo::decode_list(&ChunkInfo::encode_list(&chunks).unwrap()).unwrap();
+    assert_eq!(decoded.len(), 2);
+    assert!(decoded[0].compressed);
+    assert_eq!(decoded[0].original_len, 1024);
+    assert!(!decoded[1].compressed);
+    assert_eq!(decoded[1].get_filename().unwrap(), "a_1.encrypted_chunk");
+}
+
+#[test]
+fn test_legacy_chunk_list_decodes_as_uncompressed() {
+    // Chunk lists written before compression carried only these four fields.
+    let legacy = vec![(0usize, [7u8; 12], b"old_0.encrypted_chunk".to_vec(), 4096i64)];
+    let raw = bincode::encode_to_vec(&legacy, bincode::config::standard()).unwrap();
+
+    let decoded = ChunkInfo::decode_list(&raw).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].nonce, [7; 12]);
+    assert_eq!(decoded[0].size_encrypted, 4096);
+    assert!(!decoded[0].compressed);
+    assert_eq!(decoded[0].decompress(b"as stored".to_vec()).unwrap(), b"as stored");
+}
+
+#[test]
+fn test_decompress_restores_the_plaintext() {
+    let plaintext = b"2024-01-01 INFO request served\n".repeat(64);
+    let compressed = zstd::bulk::compress(&plaintext, zstd::DEFAULT_COMPRESSION_LEVEL).unwrap();
+    let chunk = ChunkInfo::new(0, [0; 12], "c_0.encrypted_chunk".to_string(), 4096, true, plaintext.len() as i64);
+
+    assert_eq!(chunk.decompress(compressed.clone()).unwrap(), plaintext);
+
+    let truncated = ChunkInfo { original_len: 10, ..chunk };
+    assert!(truncated.decompress(compressed).is_err());
+}
@@ -0,0 +1,227 @@
+use std::{collections::HashMap, time::Duration};
+
+use rocket::config::Config;
+
+const MASTER_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+/// Builds a `Config` from the given variables plus the required ones.
+fn config_with(vars: &[(&str, &str)]) -> Config {
+    let mut env: HashMap<&str, &str> = vars.iter().copied().collect();
+    env.entry("DATABASE_URL").or_insert("postgres://localhost/unused");
+    env.entry("MASTER_KEY").or_insert(MASTER_KEY);
+
+    Config::from_lookup(|key| {
+        env.get(key)
+            .map(|v| v.to_string())
+            .ok_or(std::env::VarError::NotPresent)
+    })
+    .expect("invalid configuration")
+}
+
+#[test]
+fn chunk_read_timeout_scales_with_size_and_is_clamped() {
+    let config = config_with(&[
+        ("UPLOAD_MIN_BYTES_PER_SEC", "1000"),
+        ("UPLOAD_CHUNK_TIMEOUT_MIN_SECS", "10"),
+        ("UPLOAD_CHUNK_TIMEOUT_MAX_SECS", "100"),
+    ]);
+
+    assert_eq!(config.chunk_read_timeout(50_000), Duration::from_secs(50));
+    assert_eq!(config.chunk_read_timeout(50_001), Duration::from_secs(51));
+    assert_eq!(config.chunk_read_timeout(1), Duration::from_secs(10));
+    assert_eq!(config.chunk_read_timeout(10_000_000), Duration::from_secs(100));
+}
+
+#[test]
+fn app_env_controls_production_mode() {
+    assert!(!config_with(&[]).is_production());
+    assert!(!config_with(&[("APP_ENV", "staging")]).is_production());
+    assert!(config_with(&[("APP_ENV", "production")]).is_production());
+}
+
+/// Returns the error `Config::from_lookup` reports for the given variables.
+fn config_error(vars: &[(&str, &str)]) -> String {
+    let mut env: HashMap<&str, &str> = vars.iter().copied().collect();
+    env.entry("DATABASE_URL").or_insert("postgres://localhost/unused");
+    env.entry("MASTER_KEY").or_insert(MASTER_KEY);
+
+    match Config::from_lookup(|key| {
+        env.get(key)
+            .map(|v| v.to_string())
+            .ok_or(std::env::VarError::NotPresent)
+    }) {
+        Ok(_) => panic!("configuration should be rejected"),
+        Err(e) => format!("{:#}", e),
+    }
+}
+
+#[test]
+fn tenants_are_off_by_default_and_validated() {
+    use rocket::tenant::TenantMode;
+
+    let config = config_with(&[]);
+    assert_eq!(config.tenant_mode, TenantMode::Off);
+    assert!(config.tenants.is_empty());
+    assert_eq!(config.tenant_header.as_str(), "x-tenant-id");
+
+    let config = config_with(&[("TENANT_MODE", "header"), ("TENANTS", "acme, Globex")]);
+    assert_eq!(config.tenant_mode, TenantMode::Header);
+    assert_eq!(config.tenants, ["acme", "globex"]);
+
+    assert!(config_error(&[("TENANT_MODE", "header")]).contains("TENANTS"));
+    assert!(config_error(&[("TENANT_MODE", "path")]).contains("TENANT_MODE"));
+    assert!(config_error(&[("TENANTS", "acme,../etc")]).contains("TENANTS"));
+    assert!(config_error(&[("TENANT_MODE", "subdomain"), ("TENANTS", "acme")]).contains("TENANT_BASE_DOMAIN"));
+}
+
+#[test]
+fn blocking_decrypt_applies_above_threshold_when_enabled() {
+    let config = config_with(&[("BLOCKING_DECRYPT_MIN_BYTES", "1000")]);
+    assert!(!config.decrypt_on_blocking_pool(999));
+    assert!(config.decrypt_on_blocking_pool(1000));
+
+    let disabled = config_with(&[("BLOCKING_DECRYPT_ENABLED", "false")]);
+    assert!(!disabled.decrypt_on_blocking_pool(i64::MAX));
+}
+
+#[test]
+fn cors_origins_default_to_localhost_and_are_configurable() {
+    let defaults = config_with(&[]);
+    assert_eq!(defaults.cors_allowed_origins.len(), 3);
+    assert_eq!(defaults.cors_allowed_origins[0], "http://localhost:3000");
+
+    let config = config_with(&[(
+        "CORS_ALLOWED_ORIGINS",
+        "https://app.example.com, https://admin.example.com:8443",
+    )]);
+    assert_eq!(
+        config.cors_allowed_origins,
+        ["https://app.example.com", "https://admin.example.com:8443"]
+    );
+}
+
+#[test]
+fn cors_origins_reject_wildcards_and_paths() {
+    assert!(config_error(&[("CORS_ALLOWED_ORIGINS", "*")]).contains("wildcards"));
+    assert!(config_error(&[("CORS_ALLOWED_ORIGINS", "https://*.example.com")]).contains("wildcards"));
+    assert!(config_error(&[("CORS_ALLOWED_ORIGINS", "https://app.example.com/")]).contains("no path"));
+    assert!(config_error(&[("CORS_ALLOWED_ORIGINS", "app.example.com")]).contains("CORS_ALLOWED_ORIGINS"));
+    assert!(config_error(&[("CORS_ALLOWED_ORIGINS", " , ")]).contains("at least one origin"));
+}
+
+#[test]
+fn upload_limits_default_and_reject_zero_chunk_size() {
+    let config = config_with(&[]);
+    assert_eq!(config.max_file_size_bytes, 50 * 1024 * 1024 * 1024);
+    assert_eq!(config.chunk_size_bytes, 6 * 1024 * 1024);
+    assert_eq!(config.upload_expiration_secs, 86400);
+
+    let config = config_with(&[("CHUNK_SIZE_BYTES", "1048576"), ("MAX_FILE_SIZE_BYTES", "1000")]);
+    assert_eq!(config.chunk_size_bytes, 1048576);
+    assert_eq!(config.max_file_size_bytes, 1000);
+
+    assert!(config_error(&[("CHUNK_SIZE_BYTES", "0")]).contains("CHUNK_SIZE_BYTES"));
+}
+
+#[test]
+fn argon2_params_default_and_validate() {
+    let params = config_with(&[]).argon2_params;
+    assert_eq!((params.m_cost(), params.t_cost(), params.p_cost()), (19 * 1024, 3, 6));
+
+    let params = config_with(&[("ARGON2_MEMORY_MB", "64"), ("ARGON2_ITERATIONS", "4"), ("ARGON2_PARALLELISM", "2")])
+        .argon2_params;
+    assert_eq!((params.m_cost(), params.t_cost(), params.p_cost()), (64 * 1024, 4, 2));
+
+    assert!(config_error(&[("ARGON2_ITERATIONS", "0")]).contains("Argon2"));
+    assert!(config_error(&[("ARGON2_PARALLELISM", "0")]).contains("Argon2"));
+}
+
+#[test]
+fn storage_dirs_default_and_are_configurable() {
+    let config = config_with(&[]);
+    assert_eq!(config.upload_dir, std::path::Path::new("uploads/files"));
+    assert_eq!(config.public_dir, std::path::Path::new("files/public"));
+
+    let config = config_with(&[("UPLOAD_DIR", "/mnt/storage/chunks"), ("PUBLIC_DIR", "/srv/www")]);
+    assert_eq!(config.upload_dir, std::path::Path::new("/mnt/storage/chunks"));
+    assert_eq!(config.public_dir, std::path::Path::new("/srv/www"));
+}
+
+#[test]
+fn disk_reserve_defaults_to_one_gib() {
+    assert_eq!(config_with(&[]).disk_reserve_bytes, 1024 * 1024 * 1024);
+    assert_eq!(config_with(&[("DISK_RESERVE_BYTES", "0")]).disk_reserve_bytes, 0);
+    assert!(config_error(&[("DISK_RESERVE_BYTES", "lots")]).contains("DISK_RESERVE_BYTES"));
+}
+
+#[test]
+fn password_reset_tokens_expire_after_half_an_hour_by_default() {
+    assert_eq!(config_with(&[]).password_reset_ttl_secs, 1800);
+    assert_eq!(config_with(&[("PASSWORD_RESET_TTL_SECS", "600")]).password_reset_ttl_secs, 600);
+    assert!(config_error(&[("PASSWORD_RESET_TTL_SECS", "-1")]).contains("PASSWORD_RESET_TTL_SECS"));
+}
+
+#[test]
+fn login_throttle_defaults_to_five_attempts_per_fifteen_minutes() {
+    let config = config_with(&[]);
+    assert_eq!(config.login_throttle_attempts, 5);
+    assert_eq!(config.login_throttle_window_secs, 900);
+
+    let config = config_with(&[("LOGIN_THROTTLE_ATTEMPTS", "0"), ("LOGIN_THROTTLE_WINDOW_SECS", "60")]);
+    assert_eq!(config.login_throttle_attempts, 0);
+    assert_eq!(config.login_throttle_window_secs, 60);
+    assert!(config_error(&[("LOGIN_THROTTLE_ATTEMPTS", "-1")]).contains("LOGIN_THROTTLE_ATTEMPTS"));
+}
+
+#[test]
+fn subscriptions_are_capped_and_sessions_rechecked_by_default() {
+    let config = config_with(&[]);
+    assert_eq!(config.max_progress_sockets_per_user, 10);
+    assert_eq!(config.max_event_streams_per_user, 5);
+    assert_eq!(config.stream_session_check_secs, 30);
+    assert!(config_error(&[("STREAM_SESSION_CHECK_SECS", "0")]).contains("STREAM_SESSION_CHECK_SECS"));
+}
+
+#[test]
+fn batch_deletes_are_capped_at_a_thousand_files_by_default() {
+    assert_eq!(config_with(&[]).max_batch_delete_files, 1000);
+    assert_eq!(config_with(&[("MAX_BATCH_DELETE_FILES", "50")]).max_batch_delete_files, 50);
+    assert!(config_error(&[("MAX_BATCH_DELETE_FILES", "many")]).contains("MAX_BATCH_DELETE_FILES"));
+}
+
+#[test]
+fn emptying_the_trash_spares_items_deleted_in_the_last_minute_by_default() {
+    assert_eq!(config_with(&[]).trash_empty_grace_secs, 60);
+    assert_eq!(config_with(&[("TRASH_EMPTY_GRACE_SECS", "0")]).trash_empty_grace_secs, 0);
+    assert!(config_error(&[("TRASH_EMPTY_GRACE_SECS", "soon")]).contains("TRASH_EMPTY_GRACE_SECS"));
+}
+
+#[test]
+fn quota_thresholds_default_to_80_95_and_100_percent() {
+    let thresholds = config_with(&[]).quota_thresholds();
+    assert_eq!(thresholds.warning_percent, 80.0);
+    assert_eq!(thresholds.critical_percent, 95.0);
+    assert_eq!(thresholds.full_percent, 100.0);
+
+    let config = config_with(&[("QUOTA_WARNING_PERCENT", "70"), ("QUOTA_CRITICAL_PERCENT", "90")]);
+    assert_eq!(config.quota_warning_percent, 70.0);
+    assert_eq!(config.quota_critical_percent, 90.0);
+
+    assert!(config_error(&[("QUOTA_FULL_PERCENT", "full")]).contains("QUOTA_FULL_PERCENT"));
+    assert!(config_error(&[("QUOTA_WARNING_PERCENT", "96")]).contains("QUOTA_WARNING_PERCENT"));
+    assert!(config_error(&[("QUOTA_WARNING_PERCENT", "0")]).contains("QUOTA_WARNING_PERCENT"));
+}
+
+#[test]
+fn impersonation_sessions_must_have_a_positive_lifetime() {
+    assert_eq!(config_with(&[]).impersonation_session_minutes, 30);
+    assert_eq!(config_with(&[("IMPERSONATION_SESSION_MINUTES", "5")]).impersonation_session_minutes, 5);
+    assert!(config_error(&[("IMPERSONATION_SESSION_MINUTES", "0")]).contains("IMPERSONATION_SESSION_MINUTES"));
+    assert!(config_error(&[("IMPERSONATION_SESSION_MINUTES", "-10")]).contains("IMPERSONATION_SESSION_MINUTES"));
+}
+
+#[test]
+fn password_hash_concurrency_must_be_positive() {
+    assert_eq!(config_with(&[]).max_concurrent_password_hashes, 4);
+    assert!(config_error(&[("MAX_CONCURRENT_PASSWORD_HASHES", "0")]).contains("MAX_CONCURRENT_PASSWORD_HASHES"));
+}
@@ -0,0 +1,13 @@
+use rocket::crypto::csrf::{csrf_tokens_match, generate_csrf_token};
+
+#[test]
+fn test_csrf_tokens_match_only_identical_well_formed_tokens() {
+    let token = generate_csrf_token().unwrap();
+    let other = generate_csrf_token().unwrap();
+
+    assert!(csrf_tokens_match(&token, &token));
+    assert!(!csrf_tokens_match(&token, &other));
+    assert!(!csrf_tokens_match(&token, &token[..token.len() - 1]));
+    assert!(!csrf_tokens_match(&token, "not base64!"));
+    assert!(!csrf_tokens_match("", ""));
+}
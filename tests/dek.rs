@@ -0,0 +1,53 @@
+use rocket::crypto::dek::{
+    change_user_password_dek, create_user_dek, decrypt_user_dek, KdfParams,
+};
+
+/// A cost cheap enough for tests but different from the legacy defaults.
+const PARAMS: KdfParams = KdfParams {
+    m_cost: 8 * 1024,
+    t_cost: 1,
+    p_cost: 2,
+};
+
+#[test]
+fn test_dek_round_trips_with_explicit_params() {
+    let (wrapped, salt) = create_user_dek("correct horse", &PARAMS).unwrap();
+
+    let first = decrypt_user_dek(&wrapped, &salt, &PARAMS, "correct horse").unwrap();
+    let second = decrypt_user_dek(&wrapped, &salt, &PARAMS, "correct horse").unwrap();
+    assert_eq!(first.len(), 64);
+    assert_eq!(*first, *second);
+
+    assert!(decrypt_user_dek(&wrapped, &salt, &PARAMS, "wrong horse").is_err());
+    assert!(decrypt_user_dek(&wrapped, &salt, &KdfParams::LEGACY, "correct horse").is_err());
+}
+
+#[test]
+fn test_legacy_dek_migrates_to_new_params() {
+    let (legacy, legacy_salt) = create_user_dek("correct horse", &KdfParams::LEGACY).unwrap();
+    let dek = decrypt_user_dek(&legacy, &legacy_salt, &KdfParams::LEGACY, "correct horse").unwrap();
+
+    let (migrated, salt) = change_user_password_dek(
+        &legacy,
+        &legacy_salt,
+        &KdfParams::LEGACY,
+        "correct horse",
+        "correct horse",
+        &PARAMS,
+    )
+    .unwrap();
+
+    assert_ne!(salt, legacy_salt);
+    assert_eq!(*decrypt_user_dek(&migrated, &salt, &PARAMS, "correct horse").unwrap(), *dek);
+}
+
+#[test]
+fn test_kdf_params_parse_and_display() {
+    assert_eq!(PARAMS.to_string(), "m=8192,t=1,p=2");
+    assert_eq!("m=8192,t=1,p=2".parse::<KdfParams>().unwrap(), PARAMS);
+    assert_eq!(KdfParams::from_stored(None).unwrap(), KdfParams::LEGACY);
+
+    for invalid in ["", "m=1,t=2", "m=1,t=2,p=x", "m=1,t=2,q=3"] {
+        assert!(invalid.parse::<KdfParams>().is_err(), "{:?} should not parse", invalid);
+    }
+}
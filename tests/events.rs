@@ -0,0 +1,28 @@
+use rocket::services::events::crossed_quota_threshold;
+
+const QUOTA: i64 = 1000;
+
+#[test]
+fn test_reports_the_threshold_that_was_crossed() {
+    assert_eq!(crossed_quota_threshold(700, 800, QUOTA), Some(80));
+    assert_eq!(crossed_quota_threshold(850, 901, QUOTA), Some(90));
+    assert_eq!(crossed_quota_threshold(999, 1000, QUOTA), Some(100));
+}
+
+#[test]
+fn test_a_jump_over_several_thresholds_reports_the_highest() {
+    assert_eq!(crossed_quota_threshold(0, 950, QUOTA), Some(90));
+    assert_eq!(crossed_quota_threshold(100, 1000, QUOTA), Some(100));
+}
+
+#[test]
+fn test_staying_between_thresholds_reports_nothing() {
+    assert_eq!(crossed_quota_threshold(0, 799, QUOTA), None);
+    assert_eq!(crossed_quota_threshold(800, 899, QUOTA), None);
+    assert_eq!(crossed_quota_threshold(1000, 1000, QUOTA), None);
+}
+
+#[test]
+fn test_a_zero_quota_never_warns() {
+    assert_eq!(crossed_quota_threshold(0, 10, 0), None);
+}
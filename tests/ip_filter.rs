@@ -0,0 +1,14 @@
+use rocket::middleware_layer::ip_filter::is_admin_path;
+
+#[test]
+fn admin_path_matches_the_admin_tree_only() {
+    assert!(is_admin_path("/api/admin"));
+    assert!(is_admin_path("/api/admin/"));
+    assert!(is_admin_path("/api/admin/users"));
+    assert!(is_admin_path("/api/admin/kek/rotate"));
+
+    assert!(!is_admin_path("/api/administrator"));
+    assert!(!is_admin_path("/api/admins/users"));
+    assert!(!is_admin_path("/api/files"));
+    assert!(!is_admin_path("/admin"));
+}
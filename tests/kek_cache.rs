@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use rocket::crypto::kek::KekCache;
+
+#[tokio::test]
+async fn test_kek_cache_evicts_least_recently_used_at_capacity() {
+    let cache = KekCache::new(2, Duration::from_secs(60));
+    cache.insert(1, vec![1; 32]).await;
+    cache.insert(2, vec![2; 32]).await;
+
+    // Touch version 1 so version 2 becomes the least recently used.
+    assert!(cache.get(1).await.is_some());
+    cache.insert(3, vec![3; 32]).await;
+
+    assert_eq!(cache.len().await, 2);
+    assert_eq!(cache.get(1).await.as_deref(), Some(&vec![1; 32]));
+    assert!(cache.get(2).await.is_none());
+    assert_eq!(cache.get(3).await.as_deref(), Some(&vec![3; 32]));
+}
+
+#[tokio::test]
+async fn test_kek_cache_expires_entries_after_ttl() {
+    let cache = KekCache::new(4, Duration::from_millis(20));
+    cache.insert(1, vec![1; 32]).await;
+    cache.insert(2, vec![2; 32]).await;
+
+    tokio::time::sleep(Duration::from_millis(40)).await;
+
+    assert_eq!(cache.trim().await, 2);
+    assert!(cache.is_empty().await);
+
+    cache.insert(1, vec![1; 32]).await;
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    assert!(cache.get(1).await.is_none());
+    assert!(cache.is_empty().await);
+}
+
+#[tokio::test]
+async fn test_kek_cache_remove_and_clear() {
+    let cache = KekCache::new(4, Duration::from_secs(60));
+    cache.insert(1, vec![1; 32]).await;
+    cache.insert(2, vec![2; 32]).await;
+
+    cache.remove(1).await;
+    assert!(cache.get(1).await.is_none());
+    assert_eq!(cache.len().await, 1);
+
+    cache.clear().await;
+    assert!(cache.is_empty().await);
+}
+
+#[tokio::test]
+async fn test_kek_cache_evicts_rotated_out_versions() {
+    let cache = KekCache::new(4, Duration::from_secs(60));
+    for version in 1..=3 {
+        cache.insert(version, vec![version as u8; 32]).await;
+    }
+
+    assert_eq!(cache.evict_older_than(3).await, 2);
+    assert!(cache.get(1).await.is_none());
+    assert!(cache.get(2).await.is_none());
+    assert!(cache.get(3).await.is_some());
+}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use rocket::{error::AppError, state::PasswordHashLimiter};
+
+#[tokio::test]
+async fn slot_stays_taken_until_the_hash_finishes_after_the_caller_is_dropped() {
+    let limiter = PasswordHashLimiter::new(1, Duration::from_millis(50));
+
+    let running = limiter.clone();
+    let caller = tokio::spawn(async move {
+        running
+            .run(|| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            })
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    caller.abort();
+    let _ = caller.await;
+
+    let queued = limiter.run(|| Ok(())).await;
+    assert!(matches!(queued, Err(AppError::RateLimitExceeded(_))));
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(limiter.run(|| Ok(())).await.is_ok());
+}
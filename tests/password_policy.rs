@@ -0,0 +1,49 @@
+use rocket::{
+    error::AppError,
+    validation::auth::{validate_password, PasswordPolicy},
+};
+
+const STRICT: PasswordPolicy = PasswordPolicy {
+    require_uppercase: true,
+    require_lowercase: true,
+    require_digit: true,
+    require_symbol: true,
+};
+
+fn rejection(password: &str, policy: &PasswordPolicy) -> String {
+    match validate_password(password, policy) {
+        Err(AppError::Validation(message)) => message,
+        other => panic!("{:?} should be rejected, got {:?}", password, other),
+    }
+}
+
+#[test]
+fn test_default_policy_only_checks_length() {
+    let policy = PasswordPolicy::default();
+    assert!(validate_password("alllowercase", &policy).is_ok());
+    assert!(rejection("short", &policy).contains("at least 8 characters"));
+    assert!(rejection(&"a".repeat(129), &policy).contains("at most 128 characters"));
+}
+
+#[test]
+fn test_strict_policy_accepts_compliant_password() {
+    assert!(validate_password("Secure#Pass1", &STRICT).is_ok());
+}
+
+#[test]
+fn test_strict_policy_names_the_missing_class() {
+    assert_eq!(rejection("secure#pass1", &STRICT), "Password must contain at least an uppercase letter");
+    assert_eq!(rejection("SECURE#PASS1", &STRICT), "Password must contain at least a lowercase letter");
+    assert_eq!(rejection("Secure#Pass", &STRICT), "Password must contain at least a digit");
+    assert_eq!(rejection("SecurePass1", &STRICT), "Password must contain at least a symbol");
+}
+
+#[test]
+fn test_each_requirement_applies_on_its_own() {
+    let digit_only = PasswordPolicy {
+        require_digit: true,
+        ..PasswordPolicy::default()
+    };
+    assert!(validate_password("nodigits-here", &digit_only).is_err());
+    assert!(validate_password("one1digit", &digit_only).is_ok());
+}
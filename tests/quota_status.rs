@@ -0,0 +1,39 @@
+use rocket::models::user::{QuotaStatus, QuotaThresholds};
+
+const THRESHOLDS: QuotaThresholds = QuotaThresholds {
+    warning_percent: 80.0,
+    critical_percent: 95.0,
+    full_percent: 100.0,
+};
+
+fn status(used: i64) -> QuotaStatus {
+    QuotaStatus::classify(used, 1000, &THRESHOLDS)
+}
+
+#[test]
+fn test_each_threshold_is_inclusive() {
+    assert_eq!(status(0), QuotaStatus::Ok);
+    assert_eq!(status(799), QuotaStatus::Ok);
+    assert_eq!(status(800), QuotaStatus::Warning);
+    assert_eq!(status(949), QuotaStatus::Warning);
+    assert_eq!(status(950), QuotaStatus::Critical);
+    assert_eq!(status(999), QuotaStatus::Critical);
+    assert_eq!(status(1000), QuotaStatus::Full);
+}
+
+#[test]
+fn test_usage_over_the_quota_is_full() {
+    assert_eq!(status(1500), QuotaStatus::Full);
+}
+
+#[test]
+fn test_a_zero_quota_is_full() {
+    assert_eq!(QuotaStatus::classify(0, 0, &THRESHOLDS), QuotaStatus::Full);
+}
+
+#[test]
+fn test_messages_mention_the_usage() {
+    assert_eq!(QuotaStatus::Warning.message(81.4), "You have used 81% of your storage");
+    assert!(QuotaStatus::Critical.message(96.0).contains("96%"));
+    assert!(QuotaStatus::Full.message(100.0).contains("full"));
+}
@@ -0,0 +1,58 @@
+use rocket::{error::AppError, handlers::range::ByteRange};
+
+const SIZE: u64 = 1000;
+
+fn range(start: u64, end: u64) -> Option<ByteRange> {
+    Some(ByteRange { start, end })
+}
+
+#[test]
+fn test_parse_single_ranges() {
+    assert_eq!(ByteRange::parse("bytes=0-99", SIZE).unwrap(), range(0, 99));
+    assert_eq!(ByteRange::parse("bytes=900-", SIZE).unwrap(), range(900, 999));
+    assert_eq!(ByteRange::parse("bytes=-100", SIZE).unwrap(), range(900, 999));
+    assert_eq!(ByteRange::parse("bytes=-5000", SIZE).unwrap(), range(0, 999));
+    assert_eq!(ByteRange::parse("bytes=990-5000", SIZE).unwrap(), range(990, 999));
+}
+
+#[test]
+fn test_multi_range_falls_back_to_full_body() {
+    assert_eq!(ByteRange::parse("bytes=0-9,20-29", SIZE).unwrap(), None);
+}
+
+#[test]
+fn test_malformed_or_unsatisfiable_ranges_are_rejected() {
+    for value in [
+        "items=0-9",
+        "bytes=",
+        "bytes=-",
+        "bytes=a-9",
+        "bytes=10-5",
+        "bytes=1000-",
+        "bytes=-0",
+    ] {
+        match ByteRange::parse(value, SIZE) {
+            Err(AppError::RangeNotSatisfiable { size }) => assert_eq!(size, SIZE),
+            other => panic!("{:?} should be unsatisfiable, got {:?}", value, other),
+        }
+    }
+
+    assert!(ByteRange::parse("bytes=0-0", 0).is_err());
+}
+
+#[test]
+fn test_range_maps_to_chunk_slices() {
+    let chunk_size = 100;
+    let range = ByteRange { start: 150, end: 349 };
+
+    assert_eq!(range.chunk_span(chunk_size), (1, 3));
+    assert_eq!(range.slice_of_chunk(1, chunk_size, 100), 50..100);
+    assert_eq!(range.slice_of_chunk(2, chunk_size, 100), 0..100);
+    assert_eq!(range.slice_of_chunk(3, chunk_size, 100), 0..50);
+    assert_eq!(range.content_length(), 200);
+    assert_eq!(range.content_range(SIZE), "bytes 150-349/1000");
+
+    // A short final chunk is sliced to what it holds.
+    let tail = ByteRange { start: 950, end: 999 };
+    assert_eq!(tail.slice_of_chunk(9, chunk_size, 30), 30..30);
+}
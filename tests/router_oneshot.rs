@@ -0,0 +1,4029 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, header},
+};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+
+use common::{
+    cookie_value, json_body, register_user, session_user_id, test_app, test_config, test_router,
+    test_state, test_state_with,
+};
+
+#[tokio::test]
+async fn test_openapi_is_public() {
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(Request::get("/api/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert!(body["paths"]["/api/files"].is_object());
+}
+
+#[tokio::test]
+async fn test_openapi_documents_session_auth_and_serves_swagger_ui() {
+    let app = test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/api-docs/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+
+    let schemes = &body["components"]["securitySchemes"];
+    assert_eq!(schemes["session_cookie"]["in"], "cookie");
+    assert_eq!(schemes["session_cookie"]["name"], "session_id");
+    assert_eq!(schemes["csrf_token"]["name"], "X-CSRF-Token");
+
+    assert!(body["paths"]["/api/auth/login"]["post"].get("security").is_none());
+    let list_security = body["paths"]["/api/files"]["get"]["security"].as_array().unwrap();
+    assert!(list_security.iter().all(|r| r.get("csrf_token").is_none()));
+    let delete_security = body["paths"]["/api/files/{file_id}"]["delete"]["security"].as_array().unwrap();
+    assert!(delete_security
+        .iter()
+        .any(|r| r.get("session_cookie").is_some() && r.get("csrf_token").is_some()));
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/docs").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let csp = response.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap().to_string();
+    assert!(csp.contains("script-src 'self' https://cdn.jsdelivr.net"));
+
+    let response = app
+        .oneshot(Request::get("/docs/swagger-init.js").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!response.headers()[header::CONTENT_SECURITY_POLICY]
+        .to_str()
+        .unwrap()
+        .contains("cdn.jsdelivr.net"));
+}
+
+#[tokio::test]
+async fn test_protected_route_requires_session() {
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(Request::get("/api/files/storage/info").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_security_headers_on_every_response() {
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(Request::get("/api/files/storage/info").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let headers = response.headers();
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    assert!(headers.contains_key(header::REFERRER_POLICY));
+    assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+}
+
+#[tokio::test]
+async fn test_register_then_read_storage_info() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("oneshot_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({
+                        "name": "Oneshot User",
+                        "username": username,
+                        "password": "SecurePass123!@#"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 201, "Registration failed");
+    let session_id = cookie_value(&response, "session_id").expect("session cookie not set");
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, format!("session_id={}", session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let body = json_body(response).await;
+    assert_eq!(body["storage_used_bytes"], 0);
+    assert_eq!(body["reserved_bytes"], 0);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["message"], "Storage usage is within your quota");
+}
+
+#[tokio::test]
+async fn test_bearer_token_authenticates_without_cookies_or_csrf() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let bearer = format!("Bearer {}", session_id);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::AUTHORIZATION, &bearer)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["storage_used_bytes"], 0);
+
+    // Bearer requests carry no ambient credentials, so writes need no CSRF token.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/folders")
+                .header(header::AUTHORIZATION, &bearer)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": "Bearer" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+
+    // With the cookie present, the cookie wins and CSRF is enforced.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/folders")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header(header::AUTHORIZATION, &bearer)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": "Cookie" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_ne!(response.status().as_u16(), 201);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::AUTHORIZATION, "Bearer not-a-session")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_concurrent_upload_inits_up_to_the_per_user_limit() {
+    let config = test_config();
+    let limit = config.max_active_uploads_per_user;
+    let app = test_router(test_state_with(config).await);
+    let (session_id, csrf_token) = register_user(&app).await;
+
+    let init = |n: usize| {
+        let app = app.clone();
+        let request = Request::post("/api/files/upload/init")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(
+                json!({ "filename": format!("parallel_{}.bin", n), "file_size": 10, "total_chunks": 1 })
+                    .to_string(),
+            ))
+            .unwrap();
+        async move { app.oneshot(request).await.unwrap() }
+    };
+
+    let responses = futures::future::join_all((0..limit).map(init)).await;
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 200, "concurrent upload init failed");
+    }
+
+    let response = init(limit).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn test_upload_init_requires_csrf_token() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+
+    let init_body = json!({
+        "filename": "csrf.bin",
+        "file_size": 10,
+        "total_chunks": 1
+    })
+    .to_string();
+
+    for csrf_header in [None, Some("not-the-token")] {
+        let mut request = Request::post("/api/files/upload/init")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(
+                header::COOKIE,
+                format!("session_id={}; csrf_token={}", session_id, csrf_token),
+            );
+        if let Some(value) = csrf_header {
+            request = request.header("x-csrf-token", value);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::from(init_body.clone())).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "upload init accepted without a valid CSRF token ({:?})",
+            csrf_header
+        );
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
+
+#[tokio::test]
+async fn test_tenants_keep_sessions_apart() {
+    let mut config = test_config();
+    config.tenant_mode = rocket::tenant::TenantMode::Header;
+    config.tenants = vec!["acme".to_string(), "globex".to_string()];
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-tenant-id", "acme")
+                .body(Body::from(
+                    json!({ "name": "Tenant User", "username": format!("tenant_{}", nanos), "password": "SecurePass123!@#" })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let session_id = cookie_value(&response, "session_id").unwrap();
+
+    let mut redis = state.redis.clone();
+    let stored: Option<String> = redis::cmd("GET")
+        .arg(format!("tenant:acme:session:{}", session_id))
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+    assert!(stored.is_some());
+
+    let list_files = |tenant: Option<&str>| {
+        let mut request = Request::get("/api/files").header(header::COOKIE, format!("session_id={}", session_id));
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let response = app.clone().oneshot(list_files(Some("acme"))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.clone().oneshot(list_files(Some("globex"))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.clone().oneshot(list_files(Some("initech"))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.clone().oneshot(list_files(None)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .oneshot(Request::get("/health/live").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_sweeping_abandoned_upload_leaves_used_storage_unchanged() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let storage_info = |app: axum::Router| {
+        let cookies = cookies.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::get("/api/files/storage/info")
+                        .header(header::COOKIE, cookies)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+            json_body(response).await
+        }
+    };
+
+    let before = storage_info(app.clone()).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/files/upload/init")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(
+                    json!({
+                        "filename": "abandoned.bin",
+                        "file_size": 4096,
+                        "total_chunks": 1
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Sweep only this session, as if the expiry window had already passed.
+    let swept = rocket::handlers::files::sweep_expired_uploads(
+        &state,
+        &format!("upload:*:{}", upload_session_id),
+        chrono::Utc::now().timestamp() + 2 * 86400,
+    )
+    .await
+    .unwrap();
+    assert_eq!(swept, 1);
+
+    let after = storage_info(app).await;
+    assert_eq!(after["storage_used_bytes"], before["storage_used_bytes"]);
+    assert_eq!(after["reserved_bytes"], before["reserved_bytes"]);
+}
+
+#[tokio::test]
+async fn test_recalculate_quota_keeps_only_live_reservations() {
+    let mut config = test_config();
+    config.quota_reservation_enabled = true;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    upload_file(&app, &cookies, &csrf_token, None, "kept.txt", b"ten bytes!").await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/files/upload/init")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(
+                    json!({ "filename": "pending.bin", "file_size": 4096, "total_chunks": 1 }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+
+    // Drift as left behind by a missed sweep and a double debit.
+    let client = state.db.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE users SET storage_used_bytes = 12345, reserved_bytes = 1000000 WHERE id = $1",
+            &[&user_id],
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/files/recalculate-quota")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["actual_storage_used"], 10);
+    assert_eq!(body["reserved_bytes"], 4096);
+
+    let row = client
+        .query_one("SELECT storage_used_bytes, reserved_bytes FROM users WHERE id = $1", &[&user_id])
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i64>("storage_used_bytes"), 10);
+    assert_eq!(row.get::<_, i64>("reserved_bytes"), 4096);
+}
+
+#[tokio::test]
+async fn test_csrf_token_lives_as_long_as_the_session() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+
+    let mut redis = state.redis.clone();
+    let session_ttl: i64 = redis::cmd("TTL")
+        .arg(format!("session:{}", session_id))
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+    let csrf_ttl: i64 = redis::cmd("TTL")
+        .arg(format!("csrf:{}", csrf_token))
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+
+    assert!(session_ttl > 3600, "unexpected session TTL {}", session_ttl);
+    assert!(
+        (session_ttl - csrf_ttl).abs() <= 5,
+        "CSRF TTL {} does not match session TTL {}",
+        csrf_ttl,
+        session_ttl
+    );
+}
+
+#[tokio::test]
+async fn test_upload_status_lists_missing_chunks() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/files/upload/init")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(
+                    json!({
+                        "filename": "resume.bin",
+                        "file_size": 3 * 6 * 1024 * 1024,
+                        "total_chunks": 3
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/files/upload/status?upload_session_id={}", upload_session_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["total_chunks"], 3);
+    assert_eq!(body["chunks_received_count"], 0);
+    assert_eq!(body["received_chunks"], json!([]));
+    assert_eq!(body["missing_chunks"], json!([0, 1, 2]));
+}
+
+const MULTIPART_BOUNDARY: &str = "oneshot-chunk-boundary";
+
+fn chunk_form(upload_session_id: &str, chunk_index: usize, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in [
+        ("upload_session_id", upload_session_id.as_bytes()),
+        ("chunk_index", chunk_index.to_string().as_bytes()),
+        ("chunk", data),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                MULTIPART_BOUNDARY, name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+    body
+}
+
+#[tokio::test]
+async fn test_upload_progress_is_published_until_finalize() {
+    use futures::StreamExt;
+
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let request = |uri: &str, content_type: String, body: Vec<u8>| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let data = b"watched upload";
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/init",
+            "application/json".to_string(),
+            json!({ "filename": "watched.txt", "file_size": data.len(), "total_chunks": 1 })
+                .to_string()
+                .into_bytes(),
+        ))
+        .await
+        .unwrap();
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let user_id = session_user_id(&state, &session_id).await;
+    let messages = rocket::services::upload_progress::subscribe(
+        &state,
+        user_id,
+        session_id.parse().unwrap(),
+        &upload_session_id,
+    )
+    .await
+    .unwrap();
+    let mut messages = std::pin::pin!(messages);
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/chunk",
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+            chunk_form(&upload_session_id, 0, data),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/finalize",
+            "application/json".to_string(),
+            json!({ "upload_session_id": upload_session_id }).to_string().into_bytes(),
+        ))
+        .await
+        .unwrap();
+    let file_id = json_body(response).await["file_id"].as_str().unwrap().to_string();
+
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), messages.next())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(message);
+    }
+    let progress: serde_json::Value = serde_json::from_str(&received[0]).unwrap();
+    assert_eq!(progress["type"], "progress");
+    assert_eq!(progress["chunks_received"], 1);
+    assert_eq!(progress["total_chunks"], 1);
+    assert!(!rocket::services::upload_progress::is_closing(&received[0]));
+
+    let finalized: serde_json::Value = serde_json::from_str(&received[1]).unwrap();
+    assert_eq!(finalized["type"], "finalized");
+    assert_eq!(finalized["file_id"], file_id);
+    assert!(rocket::services::upload_progress::is_closing(&received[1]));
+
+    let response = app
+        .oneshot(
+            Request::get(format!("/ws/uploads/{}", upload_session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_upload_progress_subscriptions_are_capped_and_end_with_the_session() {
+    use futures::StreamExt;
+
+    let mut config = test_config();
+    config.max_progress_sockets_per_user = 1;
+    config.stream_session_check_secs = 1;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let session_uuid: uuid::Uuid = session_id.parse().unwrap();
+
+    let messages = rocket::services::upload_progress::subscribe(&state, user_id, session_uuid, "watched")
+        .await
+        .unwrap();
+    let mut messages = std::pin::pin!(messages);
+    assert_eq!(state.progress_subscriptions.open_for(user_id), 1);
+
+    let second = rocket::services::upload_progress::subscribe(&state, user_id, session_uuid, "other").await;
+    assert!(matches!(second, Err(rocket::error::AppError::RateLimitExceeded(_))));
+
+    let response = app
+        .oneshot(
+            Request::post("/api/auth/logout")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let ended = tokio::time::timeout(std::time::Duration::from_secs(5), messages.next())
+        .await
+        .unwrap();
+    assert!(ended.is_none());
+}
+
+#[tokio::test]
+async fn test_account_events_for_uploads_downloads_and_quota() {
+    use futures::StreamExt;
+
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/events")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+    drop(response);
+
+    state
+        .db
+        .get()
+        .await
+        .unwrap()
+        .execute("UPDATE users SET storage_quota_bytes = 20 WHERE id = $1", &[&user_id])
+        .await
+        .unwrap();
+
+    let events = rocket::services::events::subscribe(&state, user_id, session_id.parse().unwrap())
+        .await
+        .unwrap();
+    let mut events = std::pin::pin!(events);
+
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "big.txt", b"seventeen bytes!!").await;
+    app.clone()
+        .oneshot(
+            Request::get(format!("/api/files/{}", file_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let mut received = std::collections::HashMap::new();
+    while received.len() < 3 {
+        let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        received.insert(event.kind, data);
+    }
+
+    assert_eq!(received["upload_completed"]["file_id"], file_id);
+    assert_eq!(received["upload_completed"]["size_bytes"], 17);
+    assert_eq!(received["download_started"]["file_id"], file_id);
+    assert_eq!(received["quota_warning"]["threshold_percent"], 80);
+    assert_eq!(received["quota_warning"]["storage_quota_bytes"], 20);
+}
+
+#[tokio::test]
+async fn test_event_streams_are_capped_and_end_with_the_session() {
+    use futures::StreamExt;
+
+    let mut config = test_config();
+    config.max_event_streams_per_user = 1;
+    config.stream_session_check_secs = 1;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let user_id = session_user_id(&state, &session_id).await;
+
+    let events = rocket::services::events::subscribe(&state, user_id, session_id.parse().unwrap())
+        .await
+        .unwrap();
+    let mut events = Box::pin(events);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/events")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .oneshot(
+            Request::post("/api/auth/logout")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let ended = tokio::time::timeout(std::time::Duration::from_secs(5), events.next())
+        .await
+        .unwrap();
+    assert!(ended.is_none());
+    drop(events);
+    assert_eq!(state.event_subscriptions.open_for(user_id), 0);
+}
+
+#[tokio::test]
+async fn test_finalize_rejects_upload_with_missing_chunk_file() {
+    let mut config = test_config();
+    config.chunk_size_bytes = 5;
+    let app = test_router(test_state_with(config).await);
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let post_json = |uri: &str, body: serde_json::Value| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/files/upload/init",
+            json!({ "filename": "gone.bin", "file_size": 10, "total_chunks": 2 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    for chunk_index in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/files/upload/chunk")
+                    .header(
+                        header::CONTENT_TYPE,
+                        format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+                    )
+                    .header(header::COOKIE, &cookies)
+                    .header("x-csrf-token", &csrf_token)
+                    .body(Body::from(chunk_form(&upload_session_id, chunk_index, b"hello")))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "chunk {} upload failed", chunk_index);
+    }
+
+    std::fs::remove_file(format!("uploads/files/{}_1.encrypted_chunk", upload_session_id)).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/files/upload/finalize",
+            json!({ "upload_session_id": upload_session_id }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("[1]"));
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["storage_used_bytes"], 0);
+    assert_eq!(body["reserved_bytes"], 0);
+    assert!(!std::path::Path::new(&format!("uploads/files/{}_0.encrypted_chunk", upload_session_id)).exists());
+}
+
+#[tokio::test]
+async fn test_concurrent_finalizes_cannot_exceed_quota() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let post = |uri: &str, content_type: String, body: Vec<u8>| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let mut upload_session_ids = Vec::new();
+    for name in ["first.bin", "second.bin"] {
+        let init = json!({ "filename": name, "file_size": 10, "total_chunks": 1 });
+        let response = app
+            .clone()
+            .oneshot(post("/api/files/upload/init", "application/json".to_string(), init.to_string().into_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "upload init failed");
+        let upload_session_id = json_body(response).await["upload_session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                "/api/files/upload/chunk",
+                format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+                chunk_form(&upload_session_id, 0, b"0123456789"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "chunk upload failed");
+        upload_session_ids.push(upload_session_id);
+    }
+
+    // Room for one of the two files only.
+    let client = state.db.get().await.unwrap();
+    client
+        .execute("UPDATE users SET storage_quota_bytes = 15 WHERE id = $1", &[&user_id])
+        .await
+        .unwrap();
+
+    let finalize = |upload_session_id: &str| {
+        app.clone().oneshot(post(
+            "/api/files/upload/finalize",
+            "application/json".to_string(),
+            json!({ "upload_session_id": upload_session_id }).to_string().into_bytes(),
+        ))
+    };
+    let (first, second) = tokio::join!(finalize(&upload_session_ids[0]), finalize(&upload_session_ids[1]));
+    let mut statuses = [first.unwrap().status().as_u16(), second.unwrap().status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, [200, 507]);
+
+    let row = client
+        .query_one(
+            "SELECT storage_used_bytes, (SELECT COUNT(*) FROM files WHERE user_id = $1) AS files FROM users WHERE id = $1",
+            &[&user_id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i64>("storage_used_bytes"), 10);
+    assert_eq!(row.get::<_, i64>("files"), 1);
+}
+
+#[tokio::test]
+async fn test_upload_enforces_configured_chunk_layout() {
+    let mut config = test_config();
+    config.chunk_size_bytes = 4;
+    let app = test_router(test_state_with(config).await);
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let post_json = |uri: &str, body: serde_json::Value| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    // Ten bytes in chunks of four take three chunks.
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/files/upload/init",
+            json!({ "filename": "layout.bin", "file_size": 10, "total_chunks": 2 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .clone()
+        .oneshot(post_json(
+            "/api/files/upload/init",
+            json!({ "filename": "layout.bin", "file_size": 10, "total_chunks": 3 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let body = json_body(response).await;
+    assert_eq!(body["chunk_size_bytes"], 4);
+    let upload_session_id = body["upload_session_id"].as_str().unwrap().to_string();
+
+    let send_chunk = |chunk_index: usize, data: &'static [u8]| {
+        Request::post("/api/files/upload/chunk")
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+            )
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(chunk_form(&upload_session_id, chunk_index, data)))
+            .unwrap()
+    };
+
+    // Every chunk but the last must be full, and the last holds the remainder.
+    for (chunk_index, data, status) in [
+        (0, &b"abc"[..], 400),
+        (0, &b"abcd"[..], 200),
+        (2, &b"ijkl"[..], 400),
+        (2, &b"ij"[..], 200),
+    ] {
+        let response = app.clone().oneshot(send_chunk(chunk_index, data)).await.unwrap();
+        assert_eq!(
+            response.status().as_u16(),
+            status,
+            "chunk {} of {} bytes",
+            chunk_index,
+            data.len()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_finalize_rejects_checksum_mismatch() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let request = |uri: &str, content_type: String, body: Vec<u8>| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/init",
+            "application/json".to_string(),
+            json!({
+                "filename": "tampered.bin",
+                "file_size": 5,
+                "total_chunks": 1,
+                "expected_hash": format!("sha256:{}", "0".repeat(64))
+            })
+            .to_string()
+            .into_bytes(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/chunk",
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+            chunk_form(&upload_session_id, 0, b"hello"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "chunk upload failed");
+
+    let response = app
+        .oneshot(request(
+            "/api/files/upload/finalize",
+            "application/json".to_string(),
+            json!({ "upload_session_id": upload_session_id }).to_string().into_bytes(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("expected_hash"));
+}
+
+/// Inserts a 10-byte file that was soft-deleted `days_ago` days ago.
+async fn insert_deleted_file(
+    state: &rocket::state::AppState,
+    user_id: uuid::Uuid,
+    days_ago: i32,
+    chunks_metadata: Option<Vec<u8>>,
+) -> uuid::Uuid {
+    let file_id = uuid::Uuid::new_v4();
+    state
+        .db
+        .get()
+        .await
+        .unwrap()
+        .execute(
+            "INSERT INTO files (id, user_id, original_filename, file_size, encrypted_dek, nonce,
+                                chunks_metadata, is_deleted, deleted_at)
+             VALUES ($1, $2, $3, 10, $4, $5, $6, true, NOW() - make_interval(days => $7))",
+            &[
+                &file_id,
+                &user_id,
+                &format!("trash_{}.bin", days_ago),
+                &vec![0u8; 48],
+                &vec![0u8; 12],
+                &chunks_metadata,
+                &days_ago,
+            ],
+        )
+        .await
+        .unwrap();
+    file_id
+}
+
+#[tokio::test]
+async fn test_purge_removes_files_past_the_trash_window() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, _) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+
+    let upload_session = uuid::Uuid::new_v4();
+    let chunk_name = format!("{}_0.encrypted_chunk", upload_session);
+    std::fs::create_dir_all("uploads/files").unwrap();
+    std::fs::write(format!("uploads/files/{}", chunk_name), b"ciphertext").unwrap();
+    let chunks_metadata = rocket::models::file::ChunkInfo::encode_list(&[
+        rocket::models::file::ChunkInfo::new(0, [1; 12], chunk_name.clone(), 10, false, 10),
+    ])
+    .unwrap();
+
+    let file_ids = vec![
+        insert_deleted_file(&state, user_id, 31, Some(chunks_metadata)).await,
+        insert_deleted_file(&state, user_id, 1, None).await,
+    ];
+
+    let purged = rocket::services::files::purge_deleted_files(&state, 30 * 86400).await.unwrap();
+    assert!(purged >= 1);
+
+    let client = state.db.get().await.unwrap();
+    let remaining: Vec<uuid::Uuid> = client
+        .query("SELECT id FROM files WHERE id = ANY($1)", &[&file_ids])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get("id"))
+        .collect();
+    assert_eq!(remaining, vec![file_ids[1]]);
+    assert!(!std::path::Path::new(&format!("uploads/files/{}", chunk_name)).exists());
+}
+
+#[tokio::test]
+async fn test_restore_file_from_trash() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let recent = insert_deleted_file(&state, user_id, 1, None).await;
+    let expired = insert_deleted_file(&state, user_id, 31, None).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/files/trash")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["pagination"]["total"], 2);
+    assert_eq!(body["files"][0]["id"], recent.to_string());
+    assert!(body["files"][0]["deleted_at"].is_string());
+    assert!(body["files"][0]["purge_after"].is_string());
+
+    let restore = |file_id: uuid::Uuid| {
+        Request::post(format!("/api/files/{}/restore", file_id))
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(restore(expired)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let client = state.db.get().await.unwrap();
+    let used_before: i64 = client
+        .query_one("SELECT storage_used_bytes FROM users WHERE id = $1", &[&user_id])
+        .await
+        .unwrap()
+        .get(0);
+
+    let response = app.clone().oneshot(restore(recent)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["quota_charged"], 10);
+
+    let used_after: i64 = client
+        .query_one("SELECT storage_used_bytes FROM users WHERE id = $1", &[&user_id])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(used_after, used_before + 10);
+
+    let response = app.clone().oneshot(restore(recent)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_restore_rejected_when_quota_is_full() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+
+    let file_id = insert_deleted_file(&state, user_id, 1, None).await;
+    let client = state.db.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE users SET storage_used_bytes = storage_quota_bytes WHERE id = $1",
+            &[&user_id],
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .oneshot(
+            Request::post(format!("/api/files/{}/restore", file_id))
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 507);
+
+    let is_deleted: bool = client
+        .query_one("SELECT is_deleted FROM files WHERE id = $1", &[&file_id])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(is_deleted);
+}
+
+#[tokio::test]
+async fn test_reclaimed_file_leaves_trash_and_cannot_be_restored() {
+    let mut config = test_config();
+    config.hard_delete_on_delete = true;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "gone.txt", b"reclaimed on delete").await;
+    let response = app
+        .clone()
+        .oneshot(
+            Request::delete(format!("/api/files/{}", file_id))
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Chunks are reclaimed in the background; poll until the row is flagged.
+    let file_uuid: uuid::Uuid = file_id.parse().unwrap();
+    let client = state.db.get().await.unwrap();
+    let client = &client;
+    let reclaimed = || async move {
+        client
+            .query_one("SELECT chunks_reclaimed FROM files WHERE id = $1", &[&file_uuid])
+            .await
+            .unwrap()
+            .get::<_, bool>(0)
+    };
+    for _ in 0..50 {
+        if reclaimed().await {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(reclaimed().await);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/files/trash")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["pagination"]["total"], 0);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post(format!("/api/files/{}/restore", file_id))
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_rename_file() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let (other_session_id, other_csrf_token) = register_user(&app).await;
+
+    let file_id = uuid::Uuid::new_v4();
+    state
+        .db
+        .get()
+        .await
+        .unwrap()
+        .execute(
+            "INSERT INTO files (id, user_id, original_filename, file_size, encrypted_dek, nonce)
+             VALUES ($1, $2, 'draft.txt', 10, $3, $4)",
+            &[&file_id, &user_id, &vec![0u8; 48], &vec![0u8; 12]],
+        )
+        .await
+        .unwrap();
+
+    let rename = |session_id: &str, csrf_token: &str, filename: &str| {
+        Request::patch(format!("/api/files/{}", file_id))
+            .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+            .header("x-csrf-token", csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "filename": filename }).to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(rename(&session_id, &csrf_token, " report\u{202E}.pdf "))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["filename"], "report.pdf");
+
+    let response = app
+        .clone()
+        .oneshot(rename(&session_id, &csrf_token, "bad\nname"))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .clone()
+        .oneshot(rename(&session_id, &csrf_token, &"a".repeat(256)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .oneshot(rename(&other_session_id, &other_csrf_token, "stolen.pdf"))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_batch_delete_reports_each_file() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let (other_session_id, other_csrf_token) = register_user(&app).await;
+    let other_cookies = format!("session_id={}; csrf_token={}", other_session_id, other_csrf_token);
+
+    let first = upload_file(&app, &cookies, &csrf_token, None, "first.txt", b"first file").await;
+    let second = upload_file(&app, &cookies, &csrf_token, None, "second.txt", b"second").await;
+    let foreign = upload_file(&app, &other_cookies, &other_csrf_token, None, "foreign.txt", b"theirs").await;
+    let missing = uuid::Uuid::new_v4().to_string();
+
+    let batch_delete = |file_ids: serde_json::Value| {
+        Request::post("/api/files/batch-delete")
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "file_ids": file_ids }).to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(batch_delete(json!([first]))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(batch_delete(json!([first, second, foreign, missing])))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["deleted"], 1);
+    assert_eq!(body["quota_released"], 6);
+    let statuses: Vec<_> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["file_id"].as_str().unwrap().to_string(), r["status"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (first.clone(), "already_deleted".to_string()),
+            (second.clone(), "deleted".to_string()),
+            (foreign.clone(), "not_found".to_string()),
+            (missing.clone(), "not_found".to_string()),
+        ]
+    );
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(json_body(response).await["storage_used_bytes"], 0);
+
+    let response = app.clone().oneshot(batch_delete(json!([]))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let too_many: Vec<String> = (0..1001).map(|_| uuid::Uuid::new_v4().to_string()).collect();
+    let response = app.oneshot(batch_delete(json!(too_many))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_move_file_into_folder() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let file_id = uuid::Uuid::new_v4();
+    state
+        .db
+        .get()
+        .await
+        .unwrap()
+        .execute(
+            "INSERT INTO files (id, user_id, original_filename, file_size, encrypted_dek, nonce)
+             VALUES ($1, $2, 'notes.txt', 10, $3, $4)",
+            &[&file_id, &user_id, &vec![0u8; 48], &vec![0u8; 12]],
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/folders")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": "Projects" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let folder_id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    let move_to = |folder_id: serde_json::Value| {
+        Request::patch(format!("/api/files/{}/move", file_id))
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "folder_id": folder_id }).to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(move_to(json!(folder_id))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["folder_id"], folder_id);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/folders/list?folder_id={}", folder_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["files"][0]["id"], file_id.to_string());
+
+    let response = app
+        .clone()
+        .oneshot(move_to(json!(uuid::Uuid::new_v4())))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.oneshot(move_to(serde_json::Value::Null)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(json_body(response).await["folder_id"].is_null());
+}
+
+#[tokio::test]
+async fn test_update_folder() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/folders")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": "Drafts", "description": "wip" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let folder_id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    let update = |body: serde_json::Value| {
+        Request::patch(format!("/api/folders/{}", folder_id))
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(update(json!({ "name": "Final" }))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["name"], "Final");
+    assert_eq!(body["description"], "wip");
+
+    let response = app.clone().oneshot(update(json!({ "name": "" }))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.clone().oneshot(update(json!({}))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .oneshot(
+            Request::patch(format!("/api/folders/{}", uuid::Uuid::new_v4()))
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": "Ghost" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+/// Creates a folder through the API and returns its ID.
+async fn create_folder(app: &axum::Router, cookies: &str, csrf_token: &str, name: &str, parent: Option<&str>) -> String {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/folders")
+                .header(header::COOKIE, cookies)
+                .header("x-csrf-token", csrf_token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "name": name, "parent_folder_id": parent }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    json_body(response).await["id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_move_folder_rejects_cycles() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let parent = create_folder(&app, &cookies, &csrf_token, "Parent", None).await;
+    let child = create_folder(&app, &cookies, &csrf_token, "Child", Some(&parent)).await;
+    let grandchild = create_folder(&app, &cookies, &csrf_token, "Grandchild", Some(&child)).await;
+    let sibling = create_folder(&app, &cookies, &csrf_token, "Sibling", None).await;
+
+    let move_folder = |folder_id: &str, parent_folder_id: serde_json::Value| {
+        Request::patch(format!("/api/folders/{}/move", folder_id))
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "parent_folder_id": parent_folder_id }).to_string()))
+            .unwrap()
+    };
+
+    for target in [&parent, &grandchild] {
+        let response = app.clone().oneshot(move_folder(&parent, json!(target))).await.unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        assert!(json_body(response).await["error"]
+            .as_str()
+            .unwrap()
+            .contains("own subtree"));
+    }
+
+    let response = app.clone().oneshot(move_folder(&child, json!(sibling))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["parent_folder_id"], sibling);
+
+    let response = app
+        .clone()
+        .oneshot(move_folder(&child, json!(uuid::Uuid::new_v4())))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.oneshot(move_folder(&child, serde_json::Value::Null)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(json_body(response).await["parent_folder_id"].is_null());
+}
+
+/// Uploads `data` as a one-chunk file through the API and returns its ID.
+async fn upload_file(
+    app: &axum::Router,
+    cookies: &str,
+    csrf_token: &str,
+    folder_id: Option<&str>,
+    filename: &str,
+    data: &[u8],
+) -> String {
+    let init = json!({ "filename": filename, "file_size": data.len(), "total_chunks": 1 });
+    upload_file_with(app, cookies, csrf_token, folder_id, init, data).await
+}
+
+/// Like [`upload_file`], with a caller-built upload init request body.
+async fn upload_file_with(
+    app: &axum::Router,
+    cookies: &str,
+    csrf_token: &str,
+    folder_id: Option<&str>,
+    init: serde_json::Value,
+    data: &[u8],
+) -> String {
+    let request = |uri: &str, content_type: String, body: Vec<u8>| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::COOKIE, cookies)
+            .header("x-csrf-token", csrf_token)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/init",
+            "application/json".to_string(),
+            init.to_string().into_bytes(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "upload init failed");
+    let upload_session_id = json_body(response).await["upload_session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/chunk",
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+            chunk_form(&upload_session_id, 0, data),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "chunk upload failed");
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/api/files/upload/finalize",
+            "application/json".to_string(),
+            json!({ "upload_session_id": upload_session_id, "folder_id": folder_id })
+                .to_string()
+                .into_bytes(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200, "finalize failed");
+    json_body(response).await["file_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_download_folder_as_zip() {
+    use http_body_util::BodyExt;
+
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let folder_id = create_folder(&app, &cookies, &csrf_token, "Reports", None).await;
+    upload_file(&app, &cookies, &csrf_token, Some(&folder_id), "q1.txt", b"first quarter").await;
+    upload_file(&app, &cookies, &csrf_token, Some(&folder_id), "q2.txt", b"second quarter").await;
+    upload_file(&app, &cookies, &csrf_token, None, "outside.txt", b"not in the folder").await;
+
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/folders/{}/download", folder_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+    assert!(response.headers()[header::CONTENT_DISPOSITION]
+        .to_str()
+        .unwrap()
+        .contains("Reports.zip"));
+
+    let archive = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&archive[..4], b"PK\x03\x04");
+    let end = &archive[archive.len() - 22..];
+    assert_eq!(&end[..4], b"PK\x05\x06");
+    assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+
+    let contains = |needle: &[u8]| archive.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"first quarter"));
+    assert!(contains(b"second quarter"));
+    assert!(!contains(b"not in the folder"));
+}
+
+#[tokio::test]
+async fn test_folder_usage_breaks_down_subfolders() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let top = create_folder(&app, &cookies, &csrf_token, "Top", None).await;
+    let photos = create_folder(&app, &cookies, &csrf_token, "Photos", Some(&top)).await;
+    let trips = create_folder(&app, &cookies, &csrf_token, "Trips", Some(&photos)).await;
+    let empty = create_folder(&app, &cookies, &csrf_token, "Empty", Some(&top)).await;
+
+    upload_file(&app, &cookies, &csrf_token, Some(&top), "notes.txt", b"abc").await;
+    upload_file(&app, &cookies, &csrf_token, Some(&photos), "cat.jpg", b"meow meow").await;
+    upload_file(&app, &cookies, &csrf_token, Some(&trips), "beach.jpg", b"sand").await;
+
+    let usage = |folder_id: &str| {
+        Request::get(format!("/api/folders/{}/usage", folder_id))
+            .header(header::COOKIE, &cookies)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(usage(&top)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["total_size"], 16);
+    assert_eq!(body["direct_size"], 3);
+    assert_eq!(body["subfolders"][0]["id"], photos);
+    assert_eq!(body["subfolders"][0]["name"], "Photos");
+    assert_eq!(body["subfolders"][0]["total_size"], 13);
+    assert_eq!(body["subfolders"][1]["id"], empty);
+    assert_eq!(body["subfolders"][1]["total_size"], 0);
+    assert_eq!(body["subfolders"].as_array().unwrap().len(), 2);
+
+    let (other_session_id, _) = register_user(&app).await;
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/folders/{}/usage", top))
+                .header(header::COOKIE, format!("session_id={}", other_session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_folder_path_lists_ancestors_from_the_root() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let top = create_folder(&app, &cookies, &csrf_token, "Top", None).await;
+    let middle = create_folder(&app, &cookies, &csrf_token, "Middle", Some(&top)).await;
+    let bottom = create_folder(&app, &cookies, &csrf_token, "Bottom", Some(&middle)).await;
+
+    let path = |folder_id: &str| {
+        Request::get(format!("/api/folders/{}/path", folder_id))
+            .header(header::COOKIE, &cookies)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(path(&bottom)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(
+        body["path"],
+        json!([
+            { "id": top, "name": "Top" },
+            { "id": middle, "name": "Middle" },
+            { "id": bottom, "name": "Bottom" },
+        ])
+    );
+
+    let response = app.clone().oneshot(path(&top)).await.unwrap();
+    assert_eq!(json_body(response).await["path"], json!([{ "id": top, "name": "Top" }]));
+
+    let response = app
+        .oneshot(path(&uuid::Uuid::new_v4().to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_restore_folder_tree_from_trash() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let top = create_folder(&app, &cookies, &csrf_token, "Top", None).await;
+    let nested = create_folder(&app, &cookies, &csrf_token, "Nested", Some(&top)).await;
+    upload_file(&app, &cookies, &csrf_token, Some(&top), "kept.txt", b"kept").await;
+    upload_file(&app, &cookies, &csrf_token, Some(&nested), "deep.txt", b"deep data").await;
+    let trashed_before = upload_file(&app, &cookies, &csrf_token, Some(&top), "old.txt", b"old").await;
+
+    let request = |method: &str, uri: String| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let storage_used = |app: axum::Router| {
+        let cookies = cookies.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::get("/api/files/storage/info")
+                        .header(header::COOKIE, cookies)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            json_body(response).await["storage_used_bytes"].as_i64().unwrap()
+        }
+    };
+
+    app.clone()
+        .oneshot(request("DELETE", format!("/api/files/{}", trashed_before)))
+        .await
+        .unwrap();
+    app.clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}", top)))
+        .await
+        .unwrap();
+    assert_eq!(storage_used(app.clone()).await, 0);
+
+    let response = app
+        .clone()
+        .oneshot(request("POST", format!("/api/folders/{}/restore", nested)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .clone()
+        .oneshot(request("POST", format!("/api/folders/{}/restore", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["folders_restored"], 2);
+    assert_eq!(body["files_restored"], 2);
+    assert_eq!(body["quota_charged"], 13);
+    assert_eq!(storage_used(app.clone()).await, 13);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/folders/list?folder_id={}", nested))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(json_body(response).await["files"][0]["original_filename"], "deep.txt");
+
+    let response = app
+        .oneshot(request("POST", format!("/api/folders/{}/restore", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_restore_folder_skips_reclaimed_files() {
+    let mut config = test_config();
+    config.hard_delete_on_delete = true;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let folder = create_folder(&app, &cookies, &csrf_token, "Reclaimed", None).await;
+    let file_id = upload_file(&app, &cookies, &csrf_token, Some(&folder), "gone.txt", b"gone").await;
+
+    let request = |method: &str, uri: String| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}", folder)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Chunks are reclaimed in the background; poll until the row is flagged.
+    let file_uuid: uuid::Uuid = file_id.parse().unwrap();
+    let client = state.db.get().await.unwrap();
+    let client = &client;
+    let reclaimed = || async move {
+        client
+            .query_one("SELECT chunks_reclaimed FROM files WHERE id = $1", &[&file_uuid])
+            .await
+            .unwrap()
+            .get::<_, bool>(0)
+    };
+    for _ in 0..50 {
+        if reclaimed().await {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(reclaimed().await);
+
+    let response = app
+        .clone()
+        .oneshot(request("POST", format!("/api/folders/{}/restore", folder)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["folders_restored"], 1);
+    assert_eq!(body["files_restored"], 0);
+    assert_eq!(body["files_skipped"], 1);
+    assert_eq!(body["quota_charged"], 0);
+
+    let is_deleted: bool = client
+        .query_one("SELECT is_deleted FROM files WHERE id = $1", &[&file_uuid])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(is_deleted);
+}
+
+#[tokio::test]
+async fn test_permanent_folder_delete_and_empty_trash() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let top = create_folder(&app, &cookies, &csrf_token, "Top", None).await;
+    let nested = create_folder(&app, &cookies, &csrf_token, "Nested", Some(&top)).await;
+    let nested_file = upload_file(&app, &cookies, &csrf_token, Some(&nested), "deep.txt", b"deep data").await;
+
+    let request = |method: &str, uri: String| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}/permanent", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}/permanent", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["files_purged"], 1);
+    assert_eq!(body["quota_released"], 0);
+
+    let client = state.db.get().await.unwrap();
+    let top_id: uuid::Uuid = top.parse().unwrap();
+    let nested_id: uuid::Uuid = nested.parse().unwrap();
+    let nested_file_id: uuid::Uuid = nested_file.parse().unwrap();
+    let remaining = client
+        .query_one(
+            "SELECT (SELECT COUNT(*) FROM folders WHERE id = ANY($1)) + (SELECT COUNT(*) FROM files WHERE id = $2)",
+            &[&vec![top_id, nested_id], &nested_file_id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(remaining.get::<_, i64>(0), 0);
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}/permanent", top)))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let old_folder = create_folder(&app, &cookies, &csrf_token, "Old", None).await;
+    app.clone()
+        .oneshot(request("DELETE", format!("/api/folders/{}", old_folder)))
+        .await
+        .unwrap();
+    client
+        .execute(
+            "UPDATE folders SET deleted_at = NOW() - INTERVAL '1 hour' WHERE id = $1",
+            &[&old_folder.parse::<uuid::Uuid>().unwrap()],
+        )
+        .await
+        .unwrap();
+    let old_file = insert_deleted_file(&state, user_id, 1, None).await;
+    let recent_file = insert_deleted_file(&state, user_id, 0, None).await;
+
+    let response = app
+        .oneshot(request("POST", "/api/folders/trash/empty".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert_eq!(body["folders_purged"], 1);
+    assert_eq!(body["files_purged"], 1);
+
+    let remaining: Vec<uuid::Uuid> = client
+        .query("SELECT id FROM files WHERE id = ANY($1)", &[&vec![old_file, recent_file]])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get("id"))
+        .collect();
+    assert_eq!(remaining, vec![recent_file]);
+}
+
+#[tokio::test]
+async fn test_download_increments_access_count() {
+    use http_body_util::BodyExt;
+
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "counted.txt", b"count me").await;
+
+    for _ in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/api/files/{}", file_id))
+                    .header(header::COOKIE, &cookies)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(&response.into_body().collect().await.unwrap().to_bytes()[..], b"count me");
+
+        // The download lock and the counter are both released in the background.
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["files"][0]["id"], file_id);
+    assert_eq!(body["files"][0]["access_count"], 2);
+}
+
+#[tokio::test]
+async fn test_download_holds_a_limiter_slot_until_the_body_is_dropped() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "slot.txt", b"hold a slot").await;
+
+    let available = state.download_limiter.available_permits();
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/files/{}", file_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(state.download_limiter.available_permits(), available - 1);
+
+    drop(response);
+    assert_eq!(state.download_limiter.available_permits(), available);
+}
+
+#[tokio::test]
+async fn test_download_lock_is_exclusive_and_only_released_by_its_holder() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "lock.txt", b"one at a time").await;
+    let download = || {
+        Request::get(format!("/api/files/{}", file_id))
+            .header(header::COOKIE, &cookies)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let first = app.clone().oneshot(download()).await.unwrap();
+    assert_eq!(first.status().as_u16(), 200);
+    let second = app.clone().oneshot(download()).await.unwrap();
+    assert_eq!(second.status().as_u16(), 400);
+
+    // As if the first lock had expired and another download had taken it.
+    let lock_key = format!("user_downloading:{}", user_id);
+    let mut redis = state.redis.clone();
+    let _: () = redis::cmd("SET")
+        .arg(&lock_key)
+        .arg("another-download")
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+
+    drop(first);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let holder: Option<String> = redis::cmd("GET").arg(&lock_key).query_async(&mut redis).await.unwrap();
+    assert_eq!(holder.as_deref(), Some("another-download"));
+
+    let _: () = redis::cmd("DEL").arg(&lock_key).query_async(&mut redis).await.unwrap();
+    let response = app.oneshot(download()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_share_link_download_and_revoke() {
+    use http_body_util::BodyExt;
+
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "shared.txt", b"for your eyes").await;
+
+    let authed = |method: &str, uri: String, body: serde_json::Value| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(authed("POST", format!("/api/files/{}/share", file_id), json!({ "expires_in_secs": 0 })))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .clone()
+        .oneshot(authed("POST", format!("/api/files/{}/share", file_id), json!({})))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let body = json_body(response).await;
+    let token = body["token"].as_str().unwrap().to_string();
+    let url = body["url"].as_str().unwrap().to_string();
+    assert_eq!(url, format!("/api/share/{}", token));
+
+    // No cookies: the link works without an account.
+    let response = app
+        .clone()
+        .oneshot(Request::get(&url).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(&response.into_body().collect().await.unwrap().to_bytes()[..], b"for your eyes");
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(&url)
+                .header(header::RANGE, "bytes=4-7")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 206);
+    assert_eq!(&response.into_body().collect().await.unwrap().to_bytes()[..], b"your");
+
+    let response = app
+        .clone()
+        .oneshot(authed("DELETE", format!("/api/files/{}/share/{}", file_id, token), json!({})))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(Request::get(&url).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .oneshot(Request::get("/api/share/not-a-token").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_share_links_stop_working_when_the_owner_is_cut_off() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "shared.txt", b"for your eyes").await;
+
+    let create_share = || {
+        Request::post(format!("/api/files/{}/share", file_id))
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &csrf_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({}).to_string()))
+            .unwrap()
+    };
+    let download = |url: &str| Request::get(url).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(create_share()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let url = json_body(response).await["url"].as_str().unwrap().to_string();
+
+    let client = state.db.get().await.unwrap();
+    rocket::repositories::user::set_user_active(&client, &user_id, false, &state.stmt_cache)
+        .await
+        .unwrap();
+    let response = app.clone().oneshot(download(&url)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    rocket::repositories::user::set_user_active(&client, &user_id, true, &state.stmt_cache)
+        .await
+        .unwrap();
+    let response = app.clone().oneshot(download(&url)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/logout-all")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.oneshot(download(&url)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_list_and_revoke_sessions() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let credentials = json!({
+        "name": "Sessions User",
+        "username": format!("sessions_{}", nanos),
+        "password": "SecurePass123!@#"
+    });
+
+    let mut session_ids = Vec::new();
+    for (uri, status) in [("/api/auth/register", 201), ("/api/auth/login", 200)] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::USER_AGENT, format!("oneshot-{}", status))
+                    .body(Body::from(credentials.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), status, "{} failed", uri);
+        session_ids.push(cookie_value(&response, "session_id").unwrap());
+    }
+    let (first, second) = (&session_ids[0], &session_ids[1]);
+
+    let list_sessions = |session_id: &str| {
+        Request::get("/api/auth/sessions")
+            .header(header::AUTHORIZATION, format!("Bearer {}", session_id))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(list_sessions(first)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let sessions = json_body(response).await["sessions"].as_array().unwrap().clone();
+    assert_eq!(sessions.len(), 2);
+    // Newest first, with IDs masked.
+    assert_eq!(sessions[0]["id"], second[..8]);
+    assert_eq!(sessions[0]["user_agent"], "oneshot-200");
+    assert_eq!(sessions[0]["current"], false);
+    assert_eq!(sessions[1]["id"], first[..8]);
+    assert_eq!(sessions[1]["current"], true);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::delete(format!("/api/auth/sessions/{}", &second[..8]))
+                .header(header::AUTHORIZATION, format!("Bearer {}", first))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.clone().oneshot(list_sessions(second)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.clone().oneshot(list_sessions(first)).await.unwrap();
+    assert_eq!(json_body(response).await["sessions"].as_array().unwrap().len(), 1);
+
+    // Another user's session is not found, even by its full ID.
+    let (other_session, _) = register_user(&app).await;
+    let response = app
+        .oneshot(
+            Request::delete(format!("/api/auth/sessions/{}", other_session))
+                .header(header::AUTHORIZATION, format!("Bearer {}", first))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_logout_all_revokes_every_session() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let credentials = json!({
+        "name": "Logout All User",
+        "username": format!("logout_all_{}", nanos),
+        "password": "SecurePass123!@#"
+    });
+
+    let mut sessions = Vec::new();
+    for (uri, status) in [("/api/auth/register", 201), ("/api/auth/login", 200)] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(credentials.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), status, "{} failed", uri);
+        sessions.push((
+            cookie_value(&response, "session_id").unwrap(),
+            cookie_value(&response, "csrf_token").unwrap(),
+        ));
+    }
+
+    let (session_id, csrf_token) = &sessions[0];
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/logout-all")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header("x-csrf-token", csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(cookie_value(&response, "session_id").as_deref(), Some(""));
+
+    for (session_id, _) in &sessions {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/files/storage/info")
+                    .header(header::COOKIE, format!("session_id={}", session_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+    }
+}
+
+#[tokio::test]
+async fn test_repeated_failed_logins_lock_the_account() {
+    let mut config = test_config();
+    config.login_lockout_threshold = 3;
+    let app = test_router(test_state_with(config).await);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("lockout_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "name": "Lockout User", "username": username, "password": "SecurePass123!@#" })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201, "Registration failed");
+
+    let login = |password: &str| {
+        Request::post("/api/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "username": username, "password": password }).to_string()))
+            .unwrap()
+    };
+
+    // A successful login resets the count.
+    for _ in 0..2 {
+        let response = app.clone().oneshot(login("WrongPass123!@#")).await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.clone().oneshot(login("SecurePass123!@#")).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    for _ in 0..3 {
+        let response = app.clone().oneshot(login("WrongPass123!@#")).await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.clone().oneshot(login("SecurePass123!@#")).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Account temporarily locked"));
+}
+
+#[tokio::test]
+async fn test_account_locked_in_one_tenant_is_locked_in_every_tenant() {
+    let mut config = test_config();
+    config.login_lockout_threshold = 3;
+    config.login_throttle_attempts = 0;
+    config.tenant_mode = rocket::tenant::TenantMode::Header;
+    config.tenants = vec!["acme".to_string(), "globex".to_string()];
+    let app = test_router(test_state_with(config).await);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("tenant_lockout_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-tenant-id", "acme")
+                .body(Body::from(
+                    json!({ "name": "Lockout User", "username": username, "password": "SecurePass123!@#" })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201, "Registration failed");
+
+    let login = |tenant: &str, password: &str| {
+        Request::post("/api/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-tenant-id", tenant)
+            .body(Body::from(json!({ "username": username, "password": password }).to_string()))
+            .unwrap()
+    };
+
+    for _ in 0..3 {
+        let response = app.clone().oneshot(login("acme", "WrongPass123!@#")).await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.clone().oneshot(login("globex", "SecurePass123!@#")).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Account temporarily locked"));
+}
+
+#[tokio::test]
+async fn test_login_throttle_is_per_ip_and_counts_only_rejected_credentials() {
+    let mut config = test_config();
+    config.login_lockout_threshold = 0;
+    config.login_throttle_attempts = 2;
+    config.trust_proxy_headers = true;
+    let app = test_router(test_state_with(config).await);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("throttle_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "name": "Throttle User", "username": username, "password": "SecurePass123!@#" })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201, "Registration failed");
+
+    let login = |ip: &str, body: String| {
+        Request::post("/api/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-real-ip", ip)
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let credentials = |password: &str| json!({ "username": username, "password": password }).to_string();
+
+    // Malformed requests are not failed logins.
+    for _ in 0..3 {
+        let response = app
+            .clone()
+            .oneshot(login("10.0.0.1", json!({ "username": username }).to_string()))
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+        assert_ne!(response.status().as_u16(), 401);
+    }
+
+    for _ in 0..2 {
+        let response = app.clone().oneshot(login("10.0.0.1", credentials("WrongPass123!@#"))).await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.clone().oneshot(login("10.0.0.1", credentials("SecurePass123!@#"))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Too many failed login attempts"));
+
+    let response = app.clone().oneshot(login("10.0.0.2", credentials("SecurePass123!@#"))).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let oversized = json!({ "username": username, "password": "x".repeat(8 * 1024) }).to_string();
+    let response = app.clone().oneshot(login("10.0.0.3", oversized)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 413);
+}
+
+#[tokio::test]
+async fn test_kek_rotation_requires_admin() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/admin/kek/rotate")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/admin/kek/rotation")
+                .header(header::COOKIE, format!("session_id={}", session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_session_stores_the_dek_encrypted() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, _) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+
+    let client = state.db.get().await.unwrap();
+    let user = rocket::repositories::user::find_by_id(&client, &user_id, &state.stmt_cache)
+        .await
+        .unwrap()
+        .unwrap();
+    let dek_hex = rocket::crypto::dek::decrypt_user_dek(
+        user.encrypted_dek.as_deref().unwrap(),
+        user.dek_salt.as_deref().unwrap(),
+        &rocket::crypto::dek::KdfParams::from_stored(user.dek_kdf_params.as_deref()).unwrap(),
+        "SecurePass123!@#",
+    )
+    .unwrap();
+    let dek = hex::decode(dek_hex.as_str()).unwrap();
+
+    let mut redis = state.redis.clone();
+    let session_json: String = redis::cmd("GET")
+        .arg(format!("session:{}", session_id))
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+    let stored: Vec<u8> = serde_json::from_str::<serde_json::Value>(&session_json).unwrap()["dek"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b.as_u64().unwrap() as u8)
+        .collect();
+
+    assert!(!session_json.contains(dek_hex.as_str()));
+    assert!(!stored.windows(dek.len()).any(|window| window == dek.as_slice()));
+    assert!(!stored.windows(dek_hex.len()).any(|window| window == dek_hex.as_bytes()));
+
+    let session: rocket::models::session::Session = serde_json::from_str(&session_json).unwrap();
+    let opened = rocket::services::sessions::open_session_dek(&state, &session).await.unwrap();
+    assert_eq!(opened.as_slice(), dek.as_slice());
+}
+
+#[tokio::test]
+async fn test_kek_rotation_rewraps_totp_secrets_and_session_deks() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/2fa/enroll")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let otpauth_uri = json_body(response).await["otpauth_uri"].as_str().unwrap().to_string();
+
+    let session_key = rocket::services::sessions::session_key(&state, &session_id.parse().unwrap());
+    let mut redis = state.redis.clone();
+    let read_session = |json: String| serde_json::from_str::<rocket::models::session::Session>(&json).unwrap();
+    let before = read_session(redis::cmd("GET").arg(&session_key).query_async(&mut redis).await.unwrap());
+    let dek = rocket::services::sessions::open_session_dek(&state, &before).await.unwrap();
+
+    let version = rocket::crypto::kek::rotate_kek(&state.db, state.config.master_key.as_ref(), &state.kek_cache)
+        .await
+        .unwrap();
+    rocket::services::kek_rotation::rewrap_keys(&state, version).await.unwrap();
+
+    let client = state.db.get().await.unwrap();
+    let secret = rocket::repositories::two_factor::find_secret(&client, user_id, &state.stmt_cache)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(secret.kek_version, version);
+
+    let after = read_session(redis::cmd("GET").arg(&session_key).query_async(&mut redis).await.unwrap());
+    assert_eq!(after.dek_kek_version, Some(version));
+    let opened = rocket::services::sessions::open_session_dek(&state, &after).await.unwrap();
+    assert_eq!(opened.as_slice(), dek.as_slice());
+    let ttl: i64 = redis::cmd("TTL").arg(&session_key).query_async(&mut redis).await.unwrap();
+    assert!(ttl > 0);
+
+    let code = totp_rs::TOTP::from_url(&otpauth_uri).unwrap().generate_current().unwrap();
+    let response = app
+        .oneshot(
+            Request::post("/api/auth/2fa/verify")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "code": code }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_counts_logins() {
+    rocket::metrics::install_recorder();
+    let app = test_app().await;
+    register_user(&app).await;
+
+    let response = app
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("rocket_registrations_total{outcome=\"success\"}"));
+    assert!(text.contains("rocket_upload_slots_available"));
+}
+
+#[tokio::test]
+async fn test_health_ready_with_backends_up() {
+    let app = test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/health/live").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = json_body(response).await;
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["postgres"], "ok");
+    assert_eq!(body["redis"], "ok");
+}
+
+#[tokio::test]
+async fn test_list_files_filters_by_folder_and_name() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let folder = create_folder(&app, &cookies, &csrf_token, "Reports", None).await;
+    upload_file(&app, &cookies, &csrf_token, Some(&folder), "q1_report.txt", b"first").await;
+    upload_file(&app, &cookies, &csrf_token, Some(&folder), "notes.txt", b"second").await;
+    upload_file(&app, &cookies, &csrf_token, None, "Q2_REPORT.txt", b"third").await;
+
+    let list = |query: String| {
+        let app = app.clone();
+        let cookies = cookies.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::get(format!("/api/files?{}", query))
+                        .header(header::COOKIE, cookies)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 200, "{}", query);
+            let body = json_body(response).await;
+            let mut names: Vec<String> = body["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["filename"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            (names, body["pagination"]["total"].as_i64().unwrap())
+        }
+    };
+
+    let (names, total) = list(format!("folder_id={}", folder)).await;
+    assert_eq!(names, ["notes.txt", "q1_report.txt"]);
+    assert_eq!(total, 2);
+
+    let (names, _) = list("folder_id=root".to_string()).await;
+    assert_eq!(names, ["Q2_REPORT.txt"]);
+
+    let (names, total) = list("search=report".to_string()).await;
+    assert_eq!(names, ["Q2_REPORT.txt", "q1_report.txt"]);
+    assert_eq!(total, 2);
+
+    let (names, _) = list(format!("folder_id={}&search=REPORT", folder)).await;
+    assert_eq!(names, ["q1_report.txt"]);
+
+    // `_` is matched literally, not as a single-character wildcard.
+    let (names, _) = list("search=q_".to_string()).await;
+    assert!(names.is_empty());
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files?folder_id=nope")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_list_files_sorting() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    upload_file(&app, &cookies, &csrf_token, None, "b.txt", b"three").await;
+    upload_file(&app, &cookies, &csrf_token, None, "c.txt", b"a").await;
+    upload_file(&app, &cookies, &csrf_token, None, "a.txt", b"twelve bytes").await;
+
+    for (query, expected) in [
+        ("", ["a.txt", "c.txt", "b.txt"]),
+        ("sort=name", ["a.txt", "b.txt", "c.txt"]),
+        ("sort=name&order=desc", ["c.txt", "b.txt", "a.txt"]),
+        ("sort=size", ["a.txt", "b.txt", "c.txt"]),
+        ("sort=size&order=asc", ["c.txt", "b.txt", "a.txt"]),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/api/files?{}", query))
+                    .header(header::COOKIE, &cookies)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "{}", query);
+        let names: Vec<String> = json_body(response).await["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["filename"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, expected, "{}", query);
+    }
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files?sort=owner_id")
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_list_files_cursor_pagination() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    for name in ["one.txt", "two.txt", "three.txt"] {
+        upload_file(&app, &cookies, &csrf_token, None, name, name.as_bytes()).await;
+    }
+
+    let get = |uri: String| {
+        let app = app.clone();
+        let cookies = cookies.clone();
+        async move {
+            app.oneshot(
+                Request::get(uri)
+                    .header(header::COOKIE, cookies)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let mut names = Vec::new();
+    let mut uri = "/api/files?limit=2".to_string();
+    loop {
+        let response = get(uri.clone()).await;
+        assert_eq!(response.status().as_u16(), 200);
+        let body = json_body(response).await;
+        names.extend(
+            body["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["filename"].as_str().unwrap().to_string()),
+        );
+        match body["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/api/files?limit=2&cursor={}", cursor),
+            None => break,
+        }
+    }
+    assert_eq!(names, ["three.txt", "two.txt", "one.txt"]);
+
+    let response = get("/api/files?cursor=not-a-cursor".to_string()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = get("/api/files?sort=name&cursor=AAAA".to_string()).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_download_serves_the_detected_mime_type() {
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "pixel.bin", png).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/files/{}", file_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+
+    let response = app
+        .oneshot(
+            Request::post("/api/files/upload/init")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(
+                    json!({
+                        "filename": "notes.txt",
+                        "file_size": 5,
+                        "total_chunks": 1,
+                        "mime_type": "nonsense"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_compressed_upload_downloads_unchanged() {
+    use http_body_util::BodyExt;
+
+    let app = test_app().await;
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let data = b"2024-01-01T00:00:00Z INFO GET /api/files 200\n".repeat(200);
+    let init = json!({
+        "filename": "access.log",
+        "file_size": data.len(),
+        "total_chunks": 1,
+        "compress": true
+    });
+    let file_id = upload_file_with(&app, &cookies, &csrf_token, None, init, &data).await;
+
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/files/{}", file_id))
+                .header(header::COOKIE, &cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.as_ref(), data.as_slice());
+}
+
+#[tokio::test]
+async fn test_dedup_shares_identical_chunks_until_last_delete() {
+    let mut config = test_config();
+    config.chunk_dedup_enabled = true;
+    config.hard_delete_on_delete = true;
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let data = b"the same report uploaded twice";
+    let first = upload_file(&app, &cookies, &csrf_token, None, "report.txt", data).await;
+    let second = upload_file(&app, &cookies, &csrf_token, None, "report-copy.txt", data).await;
+
+    let refs = || {
+        let state = state.clone();
+        async move {
+            let client = state.db.get().await.unwrap();
+            client
+                .query("SELECT content_hash, ref_count FROM chunk_refs WHERE user_id = $1", &[&user_id])
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| (r.get::<_, String>("content_hash"), r.get::<_, i32>("ref_count")))
+                .collect::<Vec<_>>()
+        }
+    };
+    let rows = refs().await;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].1, 2);
+    let chunk_path = format!("uploads/files/{}.encrypted_chunk", rows[0].0);
+    assert!(std::path::Path::new(&chunk_path).exists());
+
+    for file_id in [&first, &second] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/api/files/{}", file_id))
+                    .header(header::COOKIE, &cookies)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let delete = |file_id: String| {
+        let app = app.clone();
+        let cookies = cookies.clone();
+        let csrf_token = csrf_token.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::delete(format!("/api/files/{}", file_id))
+                        .header(header::COOKIE, cookies)
+                        .header("x-csrf-token", csrf_token)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+        }
+    };
+
+    // Chunks are reclaimed in the background; poll until the reference drops.
+    delete(first).await;
+    for _ in 0..50 {
+        if refs().await.first().map(|r| r.1) == Some(1) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(refs().await[0].1, 1);
+    assert!(std::path::Path::new(&chunk_path).exists());
+
+    delete(second).await;
+    for _ in 0..50 {
+        if refs().await.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(refs().await.is_empty());
+    assert!(!std::path::Path::new(&chunk_path).exists());
+}
+
+#[tokio::test]
+async fn test_chunk_gc_reclaims_only_orphaned_chunks() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "kept.txt", b"chunk of a valid file").await;
+    let client = state.db.get().await.unwrap();
+    let row = client
+        .query_one(
+            "SELECT chunks_metadata FROM files WHERE id = $1",
+            &[&uuid::Uuid::parse_str(&file_id).unwrap()],
+        )
+        .await
+        .unwrap();
+    let chunks = rocket::models::file::ChunkInfo::decode_list(&row.get::<_, Vec<u8>>("chunks_metadata")).unwrap();
+    let mut kept: Vec<String> = chunks.iter().map(|c| c.get_filename().unwrap()).collect();
+
+    // A chunk of an upload still in progress, whose session lives in Redis.
+    let live_session = uuid::Uuid::new_v4();
+    let mut redis = state.redis.clone();
+    let _: () = redis::cmd("SET")
+        .arg(format!("upload:{}:{}", user_id, live_session))
+        .arg("in progress")
+        .arg("EX")
+        .arg(60)
+        .query_async(&mut redis)
+        .await
+        .unwrap();
+    kept.push(format!("{}_0.encrypted_chunk", live_session));
+
+    let orphan = format!("{}_0.encrypted_chunk", uuid::Uuid::new_v4());
+    std::fs::create_dir_all("uploads/files").unwrap();
+    for name in [&kept[kept.len() - 1], &orphan] {
+        std::fs::write(format!("uploads/files/{}", name), b"encrypted bytes").unwrap();
+    }
+
+    let two_hours_ago = SystemTime::now() - std::time::Duration::from_secs(7200);
+    for name in kept.iter().chain([&orphan]) {
+        std::fs::File::options()
+            .write(true)
+            .open(format!("uploads/files/{}", name))
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+    }
+
+    let report = rocket::services::chunk_gc::collect_orphaned_chunks(&state, std::time::Duration::from_secs(3600))
+        .await
+        .unwrap();
+    assert!(report.reclaimed >= 1);
+    assert!(!std::path::Path::new(&format!("uploads/files/{}", orphan)).exists());
+    for name in &kept {
+        assert!(std::path::Path::new(&format!("uploads/files/{}", name)).exists(), "{} was collected", name);
+    }
+
+    let response = app
+        .oneshot(
+            Request::post("/api/admin/gc/chunks")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_verify_reports_each_chunk() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (session_id, csrf_token) = register_user(&app).await;
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let file_id = upload_file(&app, &cookies, &csrf_token, None, "verified.txt", b"bytes worth verifying").await;
+
+    let verify = |query: &'static str| {
+        let app = app.clone();
+        let uri = format!("/api/files/{}/verify{}", file_id, query);
+        let cookies = cookies.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::get(uri)
+                        .header(header::COOKIE, cookies)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+            json_body(response).await
+        }
+    };
+
+    let report = verify("").await;
+    assert_eq!(report["ok"], true);
+    assert_eq!(report["checksum_checked"], true);
+    assert_eq!(report["chunks"][0]["status"], "ok");
+
+    let report = verify("?checksum=false").await;
+    assert_eq!(report["ok"], true);
+    assert!(report["checksum_computed"].is_null());
+
+    let client = state.db.get().await.unwrap();
+    let row = client
+        .query_one(
+            "SELECT chunks_metadata FROM files WHERE id = $1",
+            &[&uuid::Uuid::parse_str(&file_id).unwrap()],
+        )
+        .await
+        .unwrap();
+    let chunks = rocket::models::file::ChunkInfo::decode_list(&row.get::<_, Vec<u8>>("chunks_metadata")).unwrap();
+    std::fs::remove_file(format!("uploads/files/{}", chunks[0].get_filename().unwrap())).unwrap();
+
+    let report = verify("").await;
+    assert_eq!(report["ok"], false);
+    assert_eq!(report["chunks"][0]["status"], "missing");
+    assert_eq!(report["first_failed_chunk"], 0);
+}
+
+#[tokio::test]
+async fn test_upload_init_rejects_when_disk_reserve_is_not_free() {
+    let mut config = test_config();
+    config.disk_reserve_bytes = u64::MAX;
+    let state = test_state_with(config).await;
+    std::fs::create_dir_all(&state.config.upload_dir).unwrap();
+    let app = test_router(state);
+    let (session_id, csrf_token) = register_user(&app).await;
+
+    let response = app
+        .oneshot(
+            Request::post("/api/files/upload/init")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", session_id, csrf_token))
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(
+                    json!({ "filename": "big.bin", "file_size": 1024, "total_chunks": 1 }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Insufficient server storage"));
+}
+
+#[tokio::test]
+async fn test_register_rejects_a_duplicate_email() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let email = format!("user_{}@example.com", nanos);
+
+    let register = |username: String, email: String| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                Request::post("/api/auth/register")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        json!({
+                            "name": "Test User",
+                            "username": username,
+                            "email": email,
+                            "password": "SecurePass123!@#"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let response = register(format!("first_{}", nanos), email.clone()).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = register(format!("second_{}", nanos), email.to_uppercase()).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Email is already registered"));
+
+    let response = register(format!("third_{}", nanos), "not-an-email".to_string()).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn test_usernames_are_case_insensitive() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+
+    let post = |uri: &'static str, body: serde_json::Value| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let response = post(
+        "/api/auth/register",
+        json!({ "name": "Alice Liddell", "username": format!("Alice_{}", nanos), "password": "SecurePass123!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = post(
+        "/api/auth/login",
+        json!({ "username": format!(" alice_{} ", nanos), "password": "SecurePass123!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post(
+        "/api/auth/register",
+        json!({ "name": "Someone Else", "username": format!("ALICE_{}", nanos), "password": "SecurePass123!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(json_body(response).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("Username is already taken"));
+}
+
+#[tokio::test]
+async fn test_two_factor_enrollment_and_login() {
+    let app = test_app().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("totp_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "name": "TOTP User", "username": username, "password": "SecurePass123!@#" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201, "Registration failed");
+    let session_id = cookie_value(&response, "session_id").unwrap();
+    let csrf_token = cookie_value(&response, "csrf_token").unwrap();
+    let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/2fa/enroll")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let otpauth_uri = json_body(response).await["otpauth_uri"].as_str().unwrap().to_string();
+    let code = totp_rs::TOTP::from_url(&otpauth_uri).unwrap().generate_current().unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/2fa/verify")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, &cookies)
+                .header("x-csrf-token", &csrf_token)
+                .body(Body::from(json!({ "code": code }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    let recovery_codes = body["recovery_codes"].as_array().unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+    let recovery_code = recovery_codes[0].as_str().unwrap().to_string();
+
+    let login = |totp_code: Option<String>| {
+        let app = app.clone();
+        let username = username.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::post("/api/auth/login")
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(
+                            json!({ "username": username, "password": "SecurePass123!@#", "totp_code": totp_code })
+                                .to_string(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status().as_u16();
+            (status, json_body(response).await)
+        }
+    };
+
+    let (status, body) = login(None).await;
+    assert_eq!(status, 401);
+    assert!(body["error"].as_str().unwrap().contains("Two-factor authentication code required"));
+
+    // The code already used to confirm enrollment cannot be replayed.
+    let (status, body) = login(Some(code)).await;
+    assert_eq!(status, 401);
+    assert!(body["error"].as_str().unwrap().contains("Invalid two-factor authentication code"));
+
+    let (status, _) = login(Some(recovery_code.to_lowercase())).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = login(Some(recovery_code)).await;
+    assert_eq!(status, 401);
+}
+
+/// Keeps sent emails so a test can read them.
+#[derive(Default)]
+struct CapturingMailer {
+    sent: std::sync::Mutex<Vec<rocket::services::mailer::Email>>,
+}
+
+impl rocket::services::mailer::Mailer for CapturingMailer {
+    fn send(
+        &self,
+        email: rocket::services::mailer::Email,
+    ) -> futures::future::BoxFuture<'_, rocket::error::Result<()>> {
+        self.sent.lock().unwrap().push(email);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn test_password_reset_with_an_emailed_token() {
+    let mailer = std::sync::Arc::new(CapturingMailer::default());
+    let mut state = test_state().await;
+    state.mailer = mailer.clone();
+    let app = test_router(state);
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let username = format!("reset_{}", nanos);
+    let email = format!("reset_{}@example.com", nanos);
+
+    let post = |uri: &'static str, body: serde_json::Value| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let response = post(
+        "/api/auth/register",
+        json!({ "name": "Test User", "username": username, "email": email, "password": "SecurePass123!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let old_session = cookie_value(&response, "session_id").unwrap();
+
+    // Unknown emails get the same answer and no email.
+    let response = post(
+        "/api/auth/forgot-password",
+        json!({ "email": format!("nobody_{}@example.com", nanos) }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post("/api/auth/forgot-password", json!({ "email": email.to_uppercase() })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut sent = None;
+    for _ in 0..50 {
+        sent = mailer.sent.lock().unwrap().iter().find(|e| e.to == email).cloned();
+        if sent.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let sent = sent.expect("reset email not sent");
+    assert_eq!(mailer.sent.lock().unwrap().len(), 1);
+    let token = sent
+        .body
+        .lines()
+        .find(|line| line.len() == 64 && line.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("token not in email")
+        .to_string();
+
+    let response = post(
+        "/api/auth/reset-password",
+        json!({ "token": "not-a-token", "new_password": "NewSecurePass456!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = post(
+        "/api/auth/reset-password",
+        json!({ "token": token, "new_password": "NewSecurePass456!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token is single-use.
+    let response = post(
+        "/api/auth/reset-password",
+        json!({ "token": token, "new_password": "OtherSecurePass789!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // Sessions opened before the reset are revoked.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/auth/sessions")
+                .header(header::COOKIE, format!("session_id={}", old_session))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = post(
+        "/api/auth/login",
+        json!({ "username": username, "password": "SecurePass123!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = post(
+        "/api/auth/login",
+        json!({ "username": username, "password": "NewSecurePass456!@#" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_admin_user_management() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (admin_session, admin_csrf) = register_user(&app).await;
+    let admin_id = session_user_id(&state, &admin_session).await;
+    let (user_session, _) = register_user(&app).await;
+    let user_id = session_user_id(&state, &user_session).await;
+    let cookies = format!("session_id={}; csrf_token={}", admin_session, admin_csrf);
+
+    let send = |method: &'static str, uri: String, body: Option<serde_json::Value>| {
+        let app = app.clone();
+        let cookies = cookies.clone();
+        let csrf = admin_csrf.clone();
+        async move {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::COOKIE, cookies)
+                .header("x-csrf-token", csrf)
+                .header(header::CONTENT_TYPE, "application/json");
+            let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+            app.oneshot(request.body(body).unwrap()).await.unwrap()
+        }
+    };
+    let quota_uri = format!("/api/admin/users/{}/quota", user_id);
+    let active_uri = format!("/api/admin/users/{}/active", user_id);
+
+    // Without the admin role every route is forbidden.
+    let response = send("GET", "/api/admin/users".to_string(), None).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = send("PATCH", quota_uri.clone(), Some(json!({ "storage_quota_bytes": 1024 }))).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = send("PATCH", active_uri.clone(), Some(json!({ "is_active": false }))).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let client = state.db.get().await.unwrap();
+    client
+        .execute("UPDATE users SET roles = ARRAY['admin'] WHERE id = $1", &[&admin_id])
+        .await
+        .unwrap();
+
+    let response = send("GET", "/api/admin/users?limit=1000".to_string(), None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = json_body(response).await;
+    assert!(body["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|u| u["id"] == user_id.to_string() && u["is_active"] == true));
+
+    let response = send("PATCH", quota_uri.clone(), Some(json!({ "storage_quota_bytes": -1 }))).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = send("PATCH", quota_uri, Some(json!({ "storage_quota_bytes": 1024 }))).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = rocket::repositories::user::find_by_id(&client, &user_id, &state.stmt_cache)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.storage_quota_bytes, 1024);
+
+    let response = send(
+        "PATCH",
+        format!("/api/admin/users/{}/active", admin_id),
+        Some(json!({ "is_active": false })),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = send("PATCH", active_uri, Some(json!({ "is_active": false }))).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["sessions_revoked"], 1);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, format!("session_id={}", user_session))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn test_admin_impersonation_start_action_and_stop() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (admin_session, admin_csrf) = register_user(&app).await;
+    let admin_id = session_user_id(&state, &admin_session).await;
+    let (user_session, user_csrf) = register_user(&app).await;
+    let user_id = session_user_id(&state, &user_session).await;
+    let user_cookies = format!("session_id={}; csrf_token={}", user_session, user_csrf);
+    let file_id = upload_file(&app, &user_cookies, &user_csrf, None, "support.txt", b"ticket").await;
+
+    let client = state.db.get().await.unwrap();
+    client
+        .execute("UPDATE users SET roles = ARRAY['admin'] WHERE id = $1", &[&admin_id])
+        .await
+        .unwrap();
+
+    // Start: the admin is handed a session for the user.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post(format!("/api/admin/users/{}/impersonate", user_id))
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", admin_session, admin_csrf))
+                .header("x-csrf-token", &admin_csrf)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let body = json_body(response).await;
+    assert_eq!(body["user_id"], user_id.to_string());
+    assert_eq!(body["can_decrypt_files"], false);
+    let bearer = format!("Bearer {}", body["session_id"].as_str().unwrap());
+
+    let send = |method: &'static str, uri: String| {
+        let app = app.clone();
+        let bearer = bearer.clone();
+        async move {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, bearer)
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(request).await.unwrap()
+        }
+    };
+
+    // Action: metadata changes work, are audited against the user and name
+    // the admin; file contents stay out of reach.
+    let response = send("GET", format!("/api/files/{}", file_id)).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = send("DELETE", format!("/api/files/{}", file_id)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let file_uuid: uuid::Uuid = file_id.parse().unwrap();
+    let mut entry = None;
+    for _ in 0..50 {
+        entry = client
+            .query_opt(
+                "SELECT user_id, impersonated_by FROM audit_logs WHERE action = 'delete_file' AND resource_id = $1",
+                &[&file_uuid],
+            )
+            .await
+            .unwrap();
+        if entry.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let entry = entry.expect("impersonated delete not audited");
+    assert_eq!(entry.get::<_, Option<uuid::Uuid>>("user_id"), Some(user_id));
+    assert_eq!(entry.get::<_, Option<uuid::Uuid>>("impersonated_by"), Some(admin_id));
+
+    // Stop: logging out ends the impersonation session only.
+    let response = send("POST", "/api/auth/logout".to_string()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = send("GET", "/api/files/storage/info".to_string()).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, format!("session_id={}", user_session))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_impersonated_session_cannot_verify_files() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let (admin_session, admin_csrf) = register_user(&app).await;
+    let admin_id = session_user_id(&state, &admin_session).await;
+    let (user_session, user_csrf) = register_user(&app).await;
+    let user_id = session_user_id(&state, &user_session).await;
+    let user_cookies = format!("session_id={}; csrf_token={}", user_session, user_csrf);
+    let file_id = upload_file(&app, &user_cookies, &user_csrf, None, "support.txt", b"ticket").await;
+
+    let client = state.db.get().await.unwrap();
+    client
+        .execute("UPDATE users SET roles = ARRAY['admin'] WHERE id = $1", &[&admin_id])
+        .await
+        .unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post(format!("/api/admin/users/{}/impersonate", user_id))
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", admin_session, admin_csrf))
+                .header("x-csrf-token", &admin_csrf)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let bearer = format!("Bearer {}", json_body(response).await["session_id"].as_str().unwrap());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/files/{}/verify", file_id))
+                .header(header::AUTHORIZATION, bearer)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .oneshot(
+            Request::get(format!("/api/files/{}/verify", file_id))
+                .header(header::COOKIE, &user_cookies)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_audit_log_records_logins_and_is_append_only() {
+    let state = test_state().await;
+    let app = test_router(state.clone());
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let attempted = format!("ghost_{}", nanos);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "username": attempted, "password": "WrongPass123!@#" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let (session_id, csrf_token) = register_user(&app).await;
+    let user_id = session_user_id(&state, &session_id).await;
+
+    // Entries are written in the background.
+    let client = state.db.get().await.unwrap();
+    let mut failed = None;
+    for _ in 0..50 {
+        failed = client
+            .query_opt(
+                "SELECT id, status, ip_address FROM audit_logs WHERE action = 'login' AND username = $1",
+                &[&attempted],
+            )
+            .await
+            .unwrap();
+        if failed.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let failed = failed.expect("failed login not audited");
+    assert_eq!(failed.get::<_, String>("status"), "failure");
+    assert_eq!(failed.get::<_, Option<String>>("ip_address").as_deref(), Some("127.0.0.1"));
+
+    let id: i64 = failed.get("id");
+    assert!(client
+        .execute("UPDATE audit_logs SET status = 'success' WHERE id = $1", &[&id])
+        .await
+        .is_err());
+    assert!(client.execute("DELETE FROM audit_logs WHERE id = $1", &[&id]).await.is_err());
+
+    let list = |app: axum::Router| {
+        let uri = format!("/api/admin/audit?user_id={}", user_id);
+        let cookies = format!("session_id={}; csrf_token={}", session_id, csrf_token);
+        async move {
+            app.oneshot(Request::get(uri).header(header::COOKIE, cookies).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+        }
+    };
+
+    let response = list(app.clone()).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    client
+        .execute("UPDATE users SET roles = ARRAY['admin'] WHERE id = $1", &[&user_id])
+        .await
+        .unwrap();
+
+    let mut entries = Vec::new();
+    for _ in 0..50 {
+        let response = list(app.clone()).await;
+        assert_eq!(response.status().as_u16(), 200);
+        entries = json_body(response).await["entries"].as_array().unwrap().clone();
+        if !entries.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(entries
+        .iter()
+        .any(|e| e["action"] == "register" && e["status"] == "success" && e["user_id"] == user_id.to_string()));
+}
+
+#[tokio::test]
+async fn test_deactivating_a_user_revokes_their_sessions_in_every_tenant() {
+    let mut config = test_config();
+    config.tenant_mode = rocket::tenant::TenantMode::Header;
+    config.tenants = vec!["acme".to_string(), "globex".to_string()];
+    let state = test_state_with(config).await;
+    let app = test_router(state.clone());
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+
+    let authenticate = |uri: &'static str, tenant: &'static str, username: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::post(uri)
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .header("x-tenant-id", tenant)
+                        .body(Body::from(
+                            json!({ "name": "Tenant User", "username": username, "password": "SecurePass123!@#" })
+                                .to_string(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(response.status().is_success(), "{} via {} failed", uri, tenant);
+            (
+                cookie_value(&response, "session_id").unwrap(),
+                cookie_value(&response, "csrf_token").unwrap(),
+            )
+        }
+    };
+
+    let username = format!("tenant_user_{}", nanos);
+    let (acme_session, _) = authenticate("/api/auth/register", "acme", username.clone()).await;
+    let (globex_session, _) = authenticate("/api/auth/login", "globex", username).await;
+    let user_id = session_user_id(&state.for_tenant("acme"), &acme_session).await;
+    assert_eq!(session_user_id(&state.for_tenant("globex"), &globex_session).await, user_id);
+
+    let (admin_session, admin_csrf) =
+        authenticate("/api/auth/register", "acme", format!("tenant_admin_{}", nanos)).await;
+    let admin_id = session_user_id(&state.for_tenant("acme"), &admin_session).await;
+    state
+        .db
+        .get()
+        .await
+        .unwrap()
+        .execute("UPDATE users SET roles = ARRAY['admin'] WHERE id = $1", &[&admin_id])
+        .await
+        .unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::patch(format!("/api/admin/users/{}/active", user_id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::COOKIE, format!("session_id={}; csrf_token={}", admin_session, admin_csrf))
+                .header("x-csrf-token", &admin_csrf)
+                .header("x-tenant-id", "acme")
+                .body(Body::from(json!({ "is_active": false }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(json_body(response).await["sessions_revoked"], 2);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/files/storage/info")
+                .header(header::COOKIE, format!("session_id={}", globex_session))
+                .header("x-tenant-id", "globex")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use rocket::{
+    config::Config,
+    tenant::{is_valid_tenant_id, resolve_tenant},
+};
+
+const MASTER_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+/// Builds a `Config` from the given variables plus the required ones.
+fn config_with(vars: &[(&str, &str)]) -> Config {
+    let mut env: HashMap<&str, &str> = vars.iter().copied().collect();
+    env.entry("DATABASE_URL").or_insert("postgres://localhost/unused");
+    env.entry("MASTER_KEY").or_insert(MASTER_KEY);
+
+    Config::from_lookup(|key| {
+        env.get(key)
+            .map(|v| v.to_string())
+            .ok_or(std::env::VarError::NotPresent)
+    })
+    .expect("invalid configuration")
+}
+
+fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(name, HeaderValue::from_static(value));
+    headers
+}
+
+#[test]
+fn tenant_ids_are_lowercase_labels() {
+    assert!(is_valid_tenant_id("acme"));
+    assert!(is_valid_tenant_id("acme-2"));
+
+    assert!(!is_valid_tenant_id(""));
+    assert!(!is_valid_tenant_id("Acme"));
+    assert!(!is_valid_tenant_id("acme.corp"));
+    assert!(!is_valid_tenant_id("../acme"));
+    assert!(!is_valid_tenant_id(&"a".repeat(64)));
+}
+
+#[test]
+fn header_mode_accepts_listed_tenants_only() {
+    let config = config_with(&[("TENANT_MODE", "header"), ("TENANTS", "acme,globex")]);
+    let tenant_header = header::HeaderName::from_static("x-tenant-id");
+
+    assert_eq!(resolve_tenant(&headers(tenant_header.clone(), "acme"), &config).as_deref(), Some("acme"));
+    assert_eq!(resolve_tenant(&headers(tenant_header.clone(), " GLOBEX "), &config).as_deref(), Some("globex"));
+    assert_eq!(resolve_tenant(&headers(tenant_header, "initech"), &config), None);
+    assert_eq!(resolve_tenant(&HeaderMap::new(), &config), None);
+}
+
+#[test]
+fn subdomain_mode_reads_the_first_label_under_the_base_domain() {
+    let config = config_with(&[
+        ("TENANT_MODE", "subdomain"),
+        ("TENANTS", "acme"),
+        ("TENANT_BASE_DOMAIN", "example.com"),
+    ]);
+
+    assert_eq!(resolve_tenant(&headers(header::HOST, "acme.example.com"), &config).as_deref(), Some("acme"));
+    assert_eq!(resolve_tenant(&headers(header::HOST, "ACME.example.com:8443"), &config).as_deref(), Some("acme"));
+
+    assert_eq!(resolve_tenant(&headers(header::HOST, "example.com"), &config), None);
+    assert_eq!(resolve_tenant(&headers(header::HOST, "acme.example.org"), &config), None);
+    assert_eq!(resolve_tenant(&headers(header::HOST, "x.acme.example.com"), &config), None);
+    assert_eq!(resolve_tenant(&headers(header::HOST, "acmeexample.com"), &config), None);
+}
+
+#[test]
+fn tenants_off_resolves_nothing() {
+    let config = config_with(&[("TENANTS", "acme")]);
+    let tenant_header = header::HeaderName::from_static("x-tenant-id");
+
+    assert_eq!(resolve_tenant(&headers(tenant_header, "acme"), &config), None);
+}
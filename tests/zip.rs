@@ -0,0 +1,89 @@
+use chrono::{TimeZone, Utc};
+use rocket::handlers::zip::ZipStreamWriter;
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// Builds an archive from `(name, data)` pairs the way the folder download
+/// streams it.
+fn build(entries: &[(&str, &[u8])]) -> (Vec<u8>, Vec<String>) {
+    let modified = Utc.with_ymd_and_hms(2024, 5, 17, 13, 45, 30).unwrap();
+    let mut zip = ZipStreamWriter::new();
+    let mut archive = Vec::new();
+    let mut names = Vec::new();
+
+    for (name, data) in entries {
+        let (header, name) = zip.start_entry(name, data.len() as u64, modified);
+        archive.extend_from_slice(&header);
+        zip.write(data);
+        archive.extend_from_slice(data);
+        archive.extend_from_slice(&zip.finish_entry().unwrap());
+        names.push(name);
+    }
+    assert_eq!(zip.bytes_written(), archive.len() as u64);
+    archive.extend_from_slice(&zip.finish());
+
+    (archive, names)
+}
+
+#[test]
+fn test_central_directory_describes_every_entry() {
+    let entries: [(&str, &[u8]); 2] = [("hello.txt", b"hello"), ("empty.txt", b"")];
+    let (archive, _) = build(&entries);
+
+    let end = &archive[archive.len() - 22..];
+    assert_eq!(u32_at(end, 0), 0x0605_4b50);
+    assert_eq!(u16_at(end, 10), 2);
+    let directory_size = u32_at(end, 12) as usize;
+    let directory_offset = u32_at(end, 16) as usize;
+    assert_eq!(directory_offset + directory_size, archive.len() - 22);
+
+    let mut at = directory_offset;
+    for (name, data) in entries {
+        assert_eq!(u32_at(&archive, at), 0x0201_4b50);
+        assert_eq!(u16_at(&archive, at + 10), 0, "entries are stored");
+        assert_eq!(u32_at(&archive, at + 16), crc32fast::hash(data));
+        assert_eq!(u32_at(&archive, at + 20) as usize, data.len());
+        assert_eq!(u32_at(&archive, at + 24) as usize, data.len());
+
+        let name_len = u16_at(&archive, at + 28) as usize;
+        assert_eq!(&archive[at + 46..at + 46 + name_len], name.as_bytes());
+
+        let local = u32_at(&archive, at + 42) as usize;
+        assert_eq!(u32_at(&archive, local), 0x0403_4b50);
+        let data_start = local + 30 + name_len;
+        assert_eq!(&archive[data_start..data_start + data.len()], data);
+
+        let descriptor = data_start + data.len();
+        assert_eq!(u32_at(&archive, descriptor), 0x0807_4b50);
+        assert_eq!(u32_at(&archive, descriptor + 4), crc32fast::hash(data));
+
+        at += 46 + name_len;
+    }
+}
+
+#[test]
+fn test_entry_names_are_flattened_and_unique() {
+    let (_, names) = build(&[
+        ("report.pdf", b"1"),
+        ("report.pdf", b"2"),
+        ("../etc/passwd", b"3"),
+        ("a\\b.txt", b"4"),
+    ]);
+
+    assert_eq!(names, ["report.pdf", "report (2).pdf", ".._etc_passwd", "a_b.txt"]);
+}
+
+#[test]
+fn test_empty_archive_is_only_the_end_record() {
+    let (archive, _) = build(&[]);
+
+    assert_eq!(archive.len(), 22);
+    assert_eq!(u32_at(&archive, 0), 0x0605_4b50);
+    assert_eq!(u16_at(&archive, 10), 0);
+}